@@ -31,6 +31,22 @@ pub(crate) struct Args {
     #[arg(long)]
     pub(crate) nats_creds_path: Option<String>,
 
+    /// Path to a file where dependency graph state is persisted across restarts
+    #[arg(long)]
+    pub(crate) state_path: Option<String>,
+
+    /// Seconds a job may process a value before council hands it to another job
+    #[arg(long)]
+    pub(crate) processing_lease_timeout_secs: Option<u32>,
+
+    /// Number of shards change sets are split across, when running multiple council instances
+    #[arg(long)]
+    pub(crate) shard_count: Option<u32>,
+
+    /// Shards owned by this instance [default: all of them]
+    #[arg(long, value_delimiter = ',')]
+    pub(crate) shards: Option<Vec<u32>>,
+
     /// Disable OpenTelemetry on startup
     #[arg(long)]
     pub(crate) disable_opentelemetry: bool,
@@ -50,6 +66,21 @@ impl TryFrom<Args> for Config {
             if let Some(creds_file) = args.nats_creds_path {
                 config_map.set("nats.creds_file", creds_file);
             }
+            if let Some(state_path) = args.state_path {
+                config_map.set("state_path", state_path);
+            }
+            if let Some(timeout) = args.processing_lease_timeout_secs {
+                config_map.set("processing_lease_timeout_secs", i64::from(timeout));
+            }
+            if let Some(shard_count) = args.shard_count {
+                config_map.set("shard_count", i64::from(shard_count));
+            }
+            if let Some(shards) = args.shards {
+                config_map.set(
+                    "shards",
+                    shards.into_iter().map(i64::from).collect::<Vec<_>>(),
+                );
+            }
             config_map.set("nats.connection_name", NAME);
         })?
         .try_into()
@@ -96,6 +96,21 @@ pub(crate) struct Args {
     #[arg(long, env = "SI_LANG_SERVER", hide_env = true)]
     pub(crate) lang_server: PathBuf,
 
+    /// Closes the lang server's stdin after the request, for a lang server which reads the request
+    /// up to EOF and so can't answer callback requests
+    #[arg(long)]
+    pub(crate) disable_lang_server_callbacks: bool,
+
+    /// Registers a lang server for a function runtime, used for requests naming that runtime
+    /// [example: python=/usr/local/bin/lang-py]
+    #[arg(long = "runtime-lang-server", value_name = "RUNTIME=PATH", value_parser = parse_runtime_lang_server)]
+    pub(crate) runtime_lang_servers: Vec<(String, PathBuf)>,
+
+    /// Keeps the stdin of a function runtime's lang server open after the request so it can
+    /// answer callback requests, one line of JSON each [example: python]
+    #[arg(long = "runtime-lang-server-callbacks", value_name = "RUNTIME")]
+    pub(crate) runtime_lang_server_callbacks: Vec<String>,
+
     /// Limits execution requests to 1 before shutting down
     #[arg(long, group = "request_limiting")]
     pub(crate) oneshot: bool,
@@ -107,6 +122,11 @@ pub(crate) struct Args {
     /// Cyclone decryption key file location [example: /run/cyclone/cyclone.key]
     #[arg(long)]
     pub(crate) decryption_key: PathBuf,
+
+    /// Delegated cgroup v2 directory used to enforce per-execution memory limits [example:
+    /// /sys/fs/cgroup/cyclone]
+    #[arg(long)]
+    pub(crate) limits_cgroup_root: Option<PathBuf>,
 }
 
 impl TryFrom<Args> for Config {
@@ -135,6 +155,15 @@ impl TryFrom<Args> for Config {
         }
 
         builder.try_lang_server_path(args.lang_server)?;
+        if args.disable_lang_server_callbacks {
+            builder.lang_server_callbacks(false);
+        }
+        for (runtime, path) in args.runtime_lang_servers {
+            builder.try_runtime_lang_server(runtime, path)?;
+        }
+        for runtime in args.runtime_lang_server_callbacks {
+            builder.runtime_lang_server_callbacks(runtime);
+        }
 
         if args.enable_watch {
             builder.watch(Some(Duration::from_secs(args.watch_timeout)));
@@ -160,10 +189,23 @@ impl TryFrom<Args> for Config {
             builder.limit_requests(limit_requests);
         }
 
+        if let Some(limits_cgroup_root) = args.limits_cgroup_root {
+            builder.limits_cgroup_root(limits_cgroup_root);
+        }
+
         builder.build().map_err(Into::into)
     }
 }
 
+fn parse_runtime_lang_server(value: &str) -> Result<(String, PathBuf), String> {
+    match value.split_once('=') {
+        Some((runtime, path)) if !runtime.is_empty() && !path.is_empty() => {
+            Ok((runtime.to_owned(), PathBuf::from(path)))
+        }
+        _ => Err(format!("expected RUNTIME=PATH, found: {value}")),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
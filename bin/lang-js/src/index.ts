@@ -1,8 +1,8 @@
 #!/usr/bin/env node
 
-import fs from "fs";
 import { Command } from "commander";
 import { makeConsole } from "./sandbox/console";
+import { closeStdin, readRequest } from "./sandbox/caller";
 import { Request } from "./request";
 import {
   executeFunction,
@@ -13,7 +13,6 @@ import {
 import { Debug } from "./debug";
 
 const debug = Debug("langJs");
-const STDIN_FD = 0;
 
 function onError(
   errorFn: (...args: unknown[]) => void,
@@ -51,7 +50,7 @@ async function main() {
   let errorFn = makeConsole(executionId).error;
 
   try {
-    const requestJson = fs.readFileSync(STDIN_FD, "utf8");
+    const requestJson = await readRequest();
     debug({ request: requestJson });
     const request: Request = JSON.parse(requestJson);
     if (request.executionId) {
@@ -72,6 +71,7 @@ async function main() {
     }
 
     await executeFunction(kind, request);
+    closeStdin();
   } catch (err) {
     onError(errorFn, err as Error, executionId);
   }
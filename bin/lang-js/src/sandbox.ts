@@ -11,6 +11,7 @@ import Joi from "joi";
 import { FunctionKind } from "./function";
 import { makeConsole } from "./sandbox/console";
 import { makeExec } from "./sandbox/exec";
+import { makeAskCaller } from "./sandbox/caller";
 import * as assetBuilder from "./asset_builder";
 import {
   makeBeforeRequestStorage,
@@ -28,6 +29,7 @@ function commonSandbox(executionId: string): Sandbox {
     zlib,
     fetch,
     siExec: makeExec(executionId),
+    askCaller: makeAskCaller(executionId),
     // Is there any risk leaking this function plainly here? It smells like a risk for RCE outside of the sandbox
     YAML: { stringify: yaml.dump },
     os, // This certainly is bad
@@ -0,0 +1,116 @@
+import readline from "readline";
+import { Debug } from "../debug";
+
+const debug = Debug("langJs:caller");
+
+// Stdin carries the function request followed by the caller's responses, one per line, to requests
+// made with `askCaller`.
+type CallbackResponse = { id: number } & (
+  | { status: "success"; data: unknown }
+  | { status: "failure"; message: string }
+);
+
+interface PendingRequest {
+  resolve: (data: unknown) => void;
+  reject: (err: Error) => void;
+}
+
+const pending = new Map<number, PendingRequest>();
+let nextId = 0;
+let lines: readline.Interface | undefined;
+
+function onResponse(line: string) {
+  let response: CallbackResponse;
+  try {
+    response = JSON.parse(line);
+  } catch (err) {
+    debug({ invalidResponse: line, err });
+    return;
+  }
+
+  const request = pending.get(response.id);
+  if (!request) {
+    debug({ unexpectedResponse: response });
+    return;
+  }
+  pending.delete(response.id);
+
+  if (response.status === "success") {
+    request.resolve(response.data);
+  } else {
+    request.reject(new Error(response.message));
+  }
+}
+
+export function readRequest(): Promise<string> {
+  const input = readline.createInterface({
+    input: process.stdin,
+    terminal: false,
+  });
+  lines = input;
+
+  return new Promise((resolve, reject) => {
+    // Cyclone sends the request on a single line, but requests piped in by hand are often
+    // pretty-printed, so lines are gathered until they make up a whole JSON document.
+    let requestJson = "";
+    let requestRead = false;
+    input.on("line", (line) => {
+      if (requestRead) {
+        onResponse(line);
+        return;
+      }
+      requestJson += `${line}\n`;
+      try {
+        JSON.parse(requestJson);
+      } catch {
+        return;
+      }
+      requestRead = true;
+      resolve(requestJson);
+    });
+    input.on("close", () => {
+      if (!requestRead) {
+        if (requestJson.trim()) {
+          // Let the caller report why the request doesn't parse
+          resolve(requestJson);
+        } else {
+          reject(new Error("stdin was closed before a request was received"));
+        }
+      }
+      for (const [id, request] of pending) {
+        request.reject(
+          new Error(`stdin was closed before request ${id} was answered`),
+        );
+      }
+      pending.clear();
+    });
+  });
+}
+
+// Stops listening for responses so that stdin doesn't keep the process alive
+export function closeStdin() {
+  lines?.close();
+  process.stdin.destroy();
+}
+
+// Lets a function ask whoever is executing it for data it doesn't have, such as a decrypted
+// secret or another component's view, e.g. `await askCaller("componentView", { componentId })`
+export const makeAskCaller =
+  (executionId: string) =>
+  (kind: string, payload?: unknown): Promise<unknown> => {
+    const id = nextId++;
+    debug({ executionId, id, kind });
+
+    return new Promise((resolve, reject) => {
+      pending.set(id, { resolve, reject });
+      console.log(
+        JSON.stringify({
+          protocol: "request",
+          executionId,
+          id,
+          kind,
+          payload: payload ?? null,
+        }),
+      );
+    });
+  };
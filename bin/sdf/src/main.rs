@@ -87,6 +87,8 @@ async fn run(args: args::Args, mut telemetry: ApplicationTelemetryClient) -> Res
     let symmetric_crypto_service =
         Server::create_symmetric_crypto_service(config.symmetric_crypto_service()).await?;
 
+    let secret_backends = Server::create_secret_backends(config.secret_backends())?;
+
     let pkgs_path: PathBuf = config.pkgs_path().try_into()?;
 
     let module_index_url = config.module_index_url().to_string();
@@ -100,6 +102,7 @@ async fn run(args: args::Args, mut telemetry: ApplicationTelemetryClient) -> Res
         Some(pkgs_path),
         Some(module_index_url),
         symmetric_crypto_service,
+        secret_backends,
     );
 
     if let MigrationMode::Run | MigrationMode::RunAndQuit = config.migration_mode() {
@@ -128,6 +131,7 @@ async fn run(args: args::Args, mut telemetry: ApplicationTelemetryClient) -> Res
                 posthog_client,
             )?;
             let second_shutdown_broadcast_rx = initial_shutdown_broadcast_rx.resubscribe();
+            let third_shutdown_broadcast_rx = initial_shutdown_broadcast_rx.resubscribe();
 
             Server::start_resource_refresh_scheduler(
                 services_context.clone(),
@@ -135,6 +139,12 @@ async fn run(args: args::Args, mut telemetry: ApplicationTelemetryClient) -> Res
             )
             .await;
 
+            Server::start_symmetric_key_rotator(
+                services_context.clone(),
+                third_shutdown_broadcast_rx,
+            )
+            .await;
+
             Server::start_status_updater(services_context, second_shutdown_broadcast_rx).await?;
 
             server.run().await?;
@@ -148,6 +158,7 @@ async fn run(args: args::Args, mut telemetry: ApplicationTelemetryClient) -> Res
             )
             .await?;
             let second_shutdown_broadcast_rx = initial_shutdown_broadcast_rx.resubscribe();
+            let third_shutdown_broadcast_rx = initial_shutdown_broadcast_rx.resubscribe();
 
             Server::start_resource_refresh_scheduler(
                 services_context.clone(),
@@ -155,6 +166,12 @@ async fn run(args: args::Args, mut telemetry: ApplicationTelemetryClient) -> Res
             )
             .await;
 
+            Server::start_symmetric_key_rotator(
+                services_context.clone(),
+                third_shutdown_broadcast_rx,
+            )
+            .await;
+
             Server::start_status_updater(services_context, second_shutdown_broadcast_rx).await?;
 
             server.run().await?;
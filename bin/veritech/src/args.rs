@@ -52,6 +52,26 @@ pub(crate) struct Args {
     /// Cyclone pool size
     #[arg(long)]
     pub(crate) cyclone_pool_size: Option<u16>,
+
+    /// Fewest cyclone jails to keep ready [default: a quarter of the pool size]
+    #[arg(long)]
+    pub(crate) cyclone_pool_min_ready: Option<u32>,
+
+    /// Most cyclone jails to keep ready [default: the pool size]
+    #[arg(long)]
+    pub(crate) cyclone_pool_max_ready: Option<u32>,
+
+    /// Cyclone instances to keep booted when not running on firecracker [default: 0]
+    #[arg(long)]
+    pub(crate) cyclone_warm_pool_size: Option<u16>,
+
+    /// Most function executions to run at once [default: the cyclone pool size]
+    #[arg(long)]
+    pub(crate) concurrency_limit: Option<u32>,
+
+    /// Most function executions to run at once for a single workspace [default: no limit]
+    #[arg(long)]
+    pub(crate) workspace_concurrency_limit: Option<u32>,
 }
 
 impl TryFrom<Args> for Config {
@@ -83,6 +103,21 @@ impl TryFrom<Args> for Config {
             if let Some(size) = args.cyclone_pool_size {
                 config_map.set("cyclone.pool_size", size);
             }
+            if let Some(min_ready) = args.cyclone_pool_min_ready {
+                config_map.set("cyclone.pool_min_ready", min_ready);
+            }
+            if let Some(max_ready) = args.cyclone_pool_max_ready {
+                config_map.set("cyclone.pool_max_ready", max_ready);
+            }
+            if let Some(size) = args.cyclone_warm_pool_size {
+                config_map.set("cyclone.warm_pool_size", size);
+            }
+            if let Some(limit) = args.concurrency_limit {
+                config_map.set("scheduler.concurrency_limit", limit);
+            }
+            if let Some(limit) = args.workspace_concurrency_limit {
+                config_map.set("scheduler.workspace_concurrency_limit", limit);
+            }
             config_map.set("nats.connection_name", NAME);
         })?
         .try_into()
@@ -1,9 +1,15 @@
-use futures::StreamExt;
+use futures::{stream::SelectAll, StreamExt};
 use si_data_nats::{NatsClient, Subject, Subscriber};
 use std::time::Duration;
 use telemetry::prelude::*;
 
-use crate::{Graph, Id, Request, Response};
+use crate::{
+    announce_subject, introspection::IntrospectionResponse, Graph, Id, Request, Response, Topology,
+    TOPOLOGY_SUBJECT_SUFFIX,
+};
+
+const TOPOLOGY_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+const TOPOLOGY_FETCH_TIMEOUT: Duration = Duration::from_secs(60);
 
 #[remain::sorted]
 #[derive(Debug)]
@@ -86,8 +92,14 @@ pub struct Client {
     change_set_id: Id,
     pub_channel: Subject,
     reply_channel: Subject,
-    subscriber: Subscriber,
+    /// Our reply channel, merged with the subject council announces itself on when it
+    /// (re)subscribes.
+    subscriber: SelectAll<Subscriber>,
     nats: NatsClient,
+    /// The part of the registered dependency graph that council has not yet reported as
+    /// processed or failed. Re-sent to council when we have been waiting on it for a while, so
+    /// that a restarted council which lost our registration can pick our work back up.
+    pending_graph: Graph,
 }
 
 impl Client {
@@ -97,17 +109,56 @@ impl Client {
         id: Id,
         change_set_id: Id,
     ) -> Result<Self> {
-        let pub_channel = format!("{subject_prefix}.{id}");
-        let reply_channel = format!("{pub_channel}.reply");
+        let topology = Self::fetch_topology(&nats, subject_prefix).await?;
+        // Replies are addressed to the job rather than the shard, so they never overlap with
+        // the subjects council instances subscribe to.
+        let reply_channel = format!("{subject_prefix}.{id}.reply");
+        let (pub_channel, shard) = if topology.shard_count > 1 {
+            let shard = change_set_id.shard(topology.shard_count);
+            (format!("{subject_prefix}.{shard}.{id}"), shard)
+        } else {
+            (format!("{subject_prefix}.{id}"), 0)
+        };
+        let subscriber = futures::stream::select_all([
+            nats.subscribe(reply_channel.clone()).await?,
+            nats.subscribe(announce_subject(subject_prefix, shard))
+                .await?,
+        ]);
         Ok(Self {
             pub_channel: pub_channel.into(),
             change_set_id,
-            subscriber: nats.subscribe(reply_channel.clone()).await?,
+            subscriber,
             reply_channel: reply_channel.into(),
             nats,
+            pending_graph: Graph::new(),
         })
     }
 
+    /// Asks council how it is sharded, waiting up to [`TOPOLOGY_FETCH_TIMEOUT`] for a council
+    /// instance to become available.
+    async fn fetch_topology(nats: &NatsClient, subject_prefix: &str) -> Result<Topology> {
+        let subject = format!("{subject_prefix}.{TOPOLOGY_SUBJECT_SUFFIX}");
+        let message = serde_json::to_vec(&Request::Topology)?;
+        let deadline = tokio::time::Instant::now() + TOPOLOGY_FETCH_TIMEOUT;
+        loop {
+            match nats.request(subject.clone(), message.clone().into()).await {
+                Ok(reply) if !reply.payload().is_empty() => {
+                    return Ok(serde_json::from_slice(reply.payload())?);
+                }
+                Ok(_) => {
+                    warn!(%subject, "No council available to report its topology; retrying");
+                }
+                Err(err) => {
+                    warn!(%subject, error = ?err, "Unable to fetch council topology; retrying");
+                }
+            }
+            if tokio::time::Instant::now() + TOPOLOGY_RETRY_INTERVAL > deadline {
+                return Err(Error::TopologyUnavailable(TOPOLOGY_FETCH_TIMEOUT));
+            }
+            tokio::time::sleep(TOPOLOGY_RETRY_INTERVAL).await;
+        }
+    }
+
     pub fn clone_into_pub(&self) -> PubClient {
         PubClient {
             pub_channel: self.pub_channel.clone(),
@@ -119,36 +170,78 @@ impl Client {
 
     // None means subscriber has been unsubscribed or that the connection has been closed
     pub async fn fetch_response(&mut self) -> Result<Option<Response>> {
-        // TODO: timeout so we don't get stuck here forever if council goes away
         // TODO: handle message.data() empty with Status header as 503: https://github.com/nats-io/nats.go/pull/576
-        let msg = loop {
+        loop {
             let res = tokio::time::timeout(Duration::from_secs(60), self.subscriber.next()).await;
 
-            match res {
-                Ok(msg) => break msg,
+            let msg = match res {
+                Ok(Some(msg)) => msg,
+                Ok(None) => return Ok(None),
                 Err(_) => {
                     warn!(change_set_id = ?self.change_set_id, pub_channel = ?self.pub_channel, reply_channel = ?self.reply_channel, "Council client waiting for response for 60 seconds");
+                    self.reregister_dependency_graph().await?;
+                    continue;
                 }
-            }
-        };
+            };
 
-        match msg {
-            Some(msg) => {
-                if msg.payload().is_empty() {
-                    return Err(Error::NoListenerAvailable);
+            if msg.payload().is_empty() {
+                return Err(Error::NoListenerAvailable);
+            }
+            let response = serde_json::from_slice::<Response>(msg.payload())?;
+            match &response {
+                Response::BeenProcessed { node_id } | Response::Failed { node_id } => {
+                    self.forget_node(*node_id);
                 }
-                Ok(Some(serde_json::from_slice::<Response>(msg.payload())?))
+                Response::Reregister => {
+                    // Council (re)connected and may have missed our registration, so send it
+                    // again rather than waiting for the timeout above.
+                    info!(change_set_id = ?self.change_set_id, reply_channel = ?self.reply_channel, "Council asked jobs to re-register");
+                    self.reregister_dependency_graph().await?;
+                    continue;
+                }
+                Response::LeaseRevoked { .. }
+                | Response::OkToProcess { .. }
+                | Response::Shutdown => {}
             }
-            None => Ok(None),
+            return Ok(Some(response));
         }
     }
 
-    pub async fn register_dependency_graph(&self, dependency_graph: Graph) -> Result<()> {
+    pub async fn register_dependency_graph(&mut self, dependency_graph: Graph) -> Result<()> {
+        for (node_id, dependencies) in &dependency_graph {
+            self.pending_graph
+                .entry(*node_id)
+                .or_default()
+                .extend(dependencies.iter().copied());
+        }
+
         self.clone_into_pub()
             .register_dependency_graph(dependency_graph)
             .await
     }
 
+    /// Sends the still pending part of our dependency graph to council again.
+    ///
+    /// Council merges registrations idempotently, so this is a no-op for a council that still
+    /// knows about our graph, and lets a council that lost its state resume our work.
+    pub async fn reregister_dependency_graph(&self) -> Result<()> {
+        if self.pending_graph.is_empty() {
+            return Ok(());
+        }
+
+        debug!(change_set_id = ?self.change_set_id, reply_channel = ?self.reply_channel, "Re-registering pending dependency graph with council");
+        self.clone_into_pub()
+            .register_dependency_graph(self.pending_graph.clone())
+            .await
+    }
+
+    fn forget_node(&mut self, node_id: Id) {
+        self.pending_graph.remove(&node_id);
+        for dependencies in self.pending_graph.values_mut() {
+            dependencies.retain(|dependency| *dependency != node_id);
+        }
+    }
+
     pub async fn processed_value(&self, node_id: Id) -> Result<()> {
         self.clone_into_pub().processed_value(node_id).await
     }
@@ -169,4 +262,8 @@ pub enum Error {
     NoListenerAvailable,
     #[error(transparent)]
     SerdeJson(#[from] serde_json::Error),
+    #[error("no council instance reported its topology within {0:?}")]
+    TopologyUnavailable(Duration),
+    #[error("unexpected introspection response from council: {0:?}")]
+    UnexpectedIntrospectionResponse(IntrospectionResponse),
 }
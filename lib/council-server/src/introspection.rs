@@ -0,0 +1,141 @@
+//! Types and client for inspecting and repairing the dependency graphs council is coordinating.
+//!
+//! Introspection requests are sent to council over NATS request/reply on the same `council.*`
+//! subjects pinga jobs use, so they can also be issued by hand with the `nats` CLI, e.g.:
+//!
+//! ```text
+//! nats req council.introspection '{"kind":"ListChangeSets"}'
+//! ```
+
+use serde::{Deserialize, Serialize};
+use si_data_nats::NatsClient;
+
+use crate::{
+    client::{Error, Result},
+    Id, Request,
+};
+
+const INTROSPECTION_SUBJECT_SUFFIX: &str = "introspection";
+
+/// A summary of the work council is coordinating for a single change set.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ChangeSetSummary {
+    pub change_set_id: Id,
+    /// The number of nodes (attribute values) still in the graph.
+    pub node_count: usize,
+    /// The number of nodes a job has been told to process.
+    pub processing_count: usize,
+    /// The number of distinct jobs waiting for or processing nodes.
+    pub job_count: usize,
+}
+
+/// The state of a single node (attribute value) in a change set's dependency graph.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NodeDump {
+    pub node_id: Id,
+    /// The nodes that must be processed before this one can be.
+    pub depends_on: Vec<Id>,
+    /// The reply channels of the jobs waiting for this node, in the order they will be offered it.
+    pub wanted_by: Vec<String>,
+    /// The reply channels of the jobs whose processing lease on this node was revoked. They are
+    /// told when the node has been processed, but aren't offered it again.
+    #[serde(default)]
+    pub lease_revoked_from: Vec<String>,
+    /// The reply channel of the job currently processing this node, if any.
+    pub processing_by: Option<String>,
+    /// How long the current job has been processing this node, if known.
+    pub processing_for_secs: Option<u64>,
+}
+
+/// The full dependency graph council holds for a change set.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ChangeSetDump {
+    pub change_set_id: Id,
+    pub nodes: Vec<NodeDump>,
+}
+
+#[remain::sorted]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "kind")]
+pub enum IntrospectionResponse {
+    ChangeSet {
+        dump: ChangeSetDump,
+    },
+    ChangeSets {
+        change_sets: Vec<ChangeSetSummary>,
+    },
+    /// The change set's graph was dropped and every job involved in it was told its nodes
+    /// failed.
+    Drained {
+        change_set_id: Id,
+        node_count: usize,
+        notified_job_count: usize,
+    },
+    UnknownChangeSet {
+        change_set_id: Id,
+    },
+}
+
+/// A client for council's introspection API.
+#[derive(Debug, Clone)]
+pub struct IntrospectionClient {
+    subject: String,
+    nats: NatsClient,
+}
+
+impl IntrospectionClient {
+    /// Creates a client which talks to council on `{subject_prefix}.introspection`, where
+    /// `subject_prefix` is the same council subject prefix pinga jobs use (e.g. `council` or
+    /// `{nats_subject_prefix}.council`).
+    pub fn new(nats: NatsClient, subject_prefix: &str) -> Self {
+        Self {
+            subject: format!("{subject_prefix}.{INTROSPECTION_SUBJECT_SUFFIX}"),
+            nats,
+        }
+    }
+
+    /// Creates a client which talks to the council instance owning `shard` of a sharded council.
+    pub fn for_shard(nats: NatsClient, subject_prefix: &str, shard: u32) -> Self {
+        Self::new(nats, &format!("{subject_prefix}.{shard}"))
+    }
+
+    /// Lists every change set that council currently has dependency graph data for.
+    pub async fn list_change_sets(&self) -> Result<Vec<ChangeSetSummary>> {
+        match self.request(&Request::ListChangeSets).await? {
+            IntrospectionResponse::ChangeSets { change_sets } => Ok(change_sets),
+            other => Err(Error::UnexpectedIntrospectionResponse(other)),
+        }
+    }
+
+    /// Dumps the dependency graph council holds for a change set, returning `None` if council has
+    /// no graph data for it.
+    pub async fn dump_change_set(&self, change_set_id: Id) -> Result<Option<ChangeSetDump>> {
+        match self
+            .request(&Request::DumpChangeSet { change_set_id })
+            .await?
+        {
+            IntrospectionResponse::ChangeSet { dump } => Ok(Some(dump)),
+            IntrospectionResponse::UnknownChangeSet { .. } => Ok(None),
+            other => Err(Error::UnexpectedIntrospectionResponse(other)),
+        }
+    }
+
+    /// Forcibly drops the dependency graph council holds for a change set. Every job waiting for
+    /// or processing a node in it is told that node failed, so stuck jobs can finish.
+    pub async fn drain_change_set(&self, change_set_id: Id) -> Result<IntrospectionResponse> {
+        self.request(&Request::DrainChangeSet { change_set_id })
+            .await
+    }
+
+    async fn request(&self, request: &Request) -> Result<IntrospectionResponse> {
+        let message = serde_json::to_vec(request)?;
+        let reply = self
+            .nats
+            .request(self.subject.clone(), message.into())
+            .await?;
+        if reply.payload().is_empty() {
+            return Err(Error::NoListenerAvailable);
+        }
+        Ok(serde_json::from_slice(reply.payload())?)
+    }
+}
@@ -3,9 +3,11 @@ use std::collections::HashMap;
 use ulid::Ulid;
 
 pub mod client;
+pub mod introspection;
 pub mod server;
 
 pub use client::{Client, PubClient};
+pub use introspection::IntrospectionClient;
 pub use server::Server;
 
 #[derive(Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Hash)]
@@ -24,6 +26,15 @@ impl Id {
             Err(err) => Err(err),
         }
     }
+
+    /// Returns the council shard that owns this (change set) id when council is split into
+    /// `shard_count` shards.
+    ///
+    /// The low bits of a ULID are random, so this spreads change sets evenly across shards while
+    /// being stable across processes and releases.
+    pub fn shard(&self, shard_count: u32) -> u32 {
+        (u128::from(self.0) % u128::from(shard_count.max(1))) as u32
+    }
 }
 
 impl From<Ulid> for Id {
@@ -52,6 +63,15 @@ impl std::fmt::Debug for Id {
 
 pub type Graph = HashMap<Id, Vec<Id>>;
 
+pub(crate) const ANNOUNCE_SUBJECT_SUFFIX: &str = "announce";
+pub(crate) const TOPOLOGY_SUBJECT_SUFFIX: &str = "topology";
+
+/// The subject council broadcasts [`Response::Reregister`] on for a shard. Unsharded council
+/// announces on shard `0`.
+pub(crate) fn announce_subject(subject_prefix: &str, shard: u32) -> String {
+    format!("{subject_prefix}.{ANNOUNCE_SUBJECT_SUFFIX}.{shard}")
+}
+
 #[remain::sorted]
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "kind")]
@@ -59,10 +79,18 @@ pub enum Request {
     Bye {
         change_set_id: Id,
     },
+    DrainChangeSet {
+        change_set_id: Id,
+    },
+    DumpChangeSet {
+        change_set_id: Id,
+    },
+    ListChangeSets,
     ProcessedValue {
         change_set_id: Id,
         node_id: Id,
     },
+    Topology,
     ValueDependencyGraph {
         change_set_id: Id,
         dependency_graph: Graph,
@@ -77,8 +105,30 @@ pub enum Request {
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "kind")]
 pub enum Response {
-    BeenProcessed { node_id: Id },
-    Failed { node_id: Id },
-    OkToProcess { node_ids: Vec<Id> },
+    BeenProcessed {
+        node_id: Id,
+    },
+    Failed {
+        node_id: Id,
+    },
+    LeaseRevoked {
+        node_id: Id,
+    },
+    OkToProcess {
+        node_ids: Vec<Id>,
+    },
+    /// Broadcast by council after it (re)subscribes to NATS, asking every job to re-send its
+    /// pending dependency graph in case council missed or lost it.
+    Reregister,
     Shutdown,
 }
+
+/// How council is split into shards, as reported in response to [`Request::Topology`].
+///
+/// When `shard_count` is greater than one, each change set is owned by the council instance
+/// serving shard [`Id::shard`] of the change set id, and requests about it must be sent on that
+/// shard's subjects.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Topology {
+    pub shard_count: u32,
+}
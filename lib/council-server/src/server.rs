@@ -1,50 +1,185 @@
-use crate::{Graph, Id, Request, Response};
-use std::time::Duration;
+use crate::{
+    announce_subject, introspection::IntrospectionResponse, Graph, Id, Request, Response, Topology,
+    TOPOLOGY_SUBJECT_SUFFIX,
+};
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
-use futures::StreamExt;
-use si_data_nats::{NatsClient, Subject};
+use futures::{stream::SelectAll, StreamExt};
+use si_data_nats::{ConnectOptions, Event, NatsClient, NatsConfig, Subject, Subscriber};
 use telemetry::prelude::*;
-use tokio::{signal, sync::watch};
+use tokio::{
+    signal,
+    sync::{watch, Notify},
+    time::{Instant, MissedTickBehavior},
+};
 
 pub mod config;
 mod graph;
+mod state;
 pub use config::Config;
+pub use state::{StateStore, StateStoreError};
 
 use graph::ChangeSetGraph;
 
+const IDLE_WARNING_INTERVAL: Duration = Duration::from_secs(60);
+const LEASE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+const RESUBSCRIBE_MIN_BACKOFF: Duration = Duration::from_millis(100);
+const RESUBSCRIBE_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
 #[derive(Debug, Clone)]
 pub struct Server {
     nats: NatsClient,
+    nats_config: NatsConfig,
+    /// Notified whenever the NATS client (re)establishes its connection to the server.
+    nats_connected: Arc<Notify>,
+    state_store: Option<StateStore>,
+    processing_lease_timeout: Duration,
+    shard_count: u32,
+    owned_shards: Vec<u32>,
 }
 
 impl Server {
     pub async fn new_with_config(config: config::Config) -> Result<Self> {
+        let nats_connected = Arc::new(Notify::new());
         Ok(Self {
-            nats: NatsClient::new(config.nats()).await?,
+            nats: connect(config.nats(), nats_connected.clone()).await?,
+            nats_config: config.nats().clone(),
+            nats_connected,
+            state_store: config.state_path().map(StateStore::new),
+            processing_lease_timeout: config.processing_lease_timeout(),
+            shard_count: config.shard_count(),
+            owned_shards: config.owned_shards(),
         })
     }
 
+    /// Subscribes to the subjects for the shards this instance owns. An unsharded council owns
+    /// everything published on `council.*`, while shard `n` of a sharded council owns
+    /// `council.n.*`.
+    async fn subscribe(&self, council_subject: &str) -> Result<SelectAll<Subscriber>> {
+        if self.shard_count <= 1 {
+            let subscriber = self.nats.subscribe(format!("{council_subject}.*")).await?;
+            return Ok(futures::stream::select_all([subscriber]));
+        }
+
+        let mut subscribers = Vec::with_capacity(self.owned_shards.len() + 1);
+        for shard in &self.owned_shards {
+            subscribers.push(
+                self.nats
+                    .subscribe(format!("{council_subject}.{shard}.*"))
+                    .await?,
+            );
+        }
+        // Every instance can answer topology requests, so only one of them needs to get each.
+        subscribers.push(
+            self.nats
+                .queue_subscribe(
+                    format!("{council_subject}.{TOPOLOGY_SUBJECT_SUFFIX}"),
+                    "council".to_owned(),
+                )
+                .await?,
+        );
+        Ok(futures::stream::select_all(subscribers))
+    }
+
+    /// Subscribes to council's subjects, retrying with exponential backoff until it succeeds.
+    ///
+    /// The NATS client transparently reconnects and restores subscriptions after short outages, so
+    /// a failure here means the client itself has given up; in that case a new client is created
+    /// from the original config before trying again.
+    async fn resubscribe(&mut self, council_subject: &str) -> SelectAll<Subscriber> {
+        let mut backoff = RESUBSCRIBE_MIN_BACKOFF;
+        loop {
+            match self.subscribe(council_subject).await {
+                Ok(subscriber) => return subscriber,
+                Err(err) => {
+                    error!(error = ?err, ?backoff, "Unable to subscribe to the council request channel on nats; reconnecting");
+                    match connect(&self.nats_config, self.nats_connected.clone()).await {
+                        Ok(nats) => self.nats = nats,
+                        Err(err) => error!(error = ?err, "Unable to reconnect to nats"),
+                    }
+                }
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(RESUBSCRIBE_MAX_BACKOFF);
+        }
+    }
+
+    /// Asks every job to re-send its pending dependency graph, since requests published while we
+    /// were not subscribed never reached us.
+    async fn announce(&self, council_subject: &str) {
+        let shards = if self.shard_count <= 1 {
+            vec![0]
+        } else {
+            self.owned_shards.clone()
+        };
+        let message = serde_json::to_vec(&Response::Reregister).unwrap();
+        for shard in shards {
+            if let Err(err) = self
+                .nats
+                .publish(
+                    announce_subject(council_subject, shard),
+                    message.clone().into(),
+                )
+                .await
+            {
+                error!(error = ?err, shard, "Unable to announce council to jobs");
+            }
+        }
+    }
+
+    /// Rebuilds the graph from the persisted state, if council is configured to persist it.
+    async fn load_graph(&self) -> ChangeSetGraph {
+        let state_store = match &self.state_store {
+            Some(state_store) => state_store,
+            None => return ChangeSetGraph::default(),
+        };
+
+        match state_store.load().await {
+            Ok(graph) => {
+                if !graph.is_empty() {
+                    info!(path = %state_store.path().display(), ?graph, "restored council graph state");
+                }
+                graph
+            }
+            Err(err) => {
+                error!(error = ?err, "unable to restore council graph state; starting with an empty graph");
+                ChangeSetGraph::default()
+            }
+        }
+    }
+
+    async fn persist_graph(&self, graph: &ChangeSetGraph) {
+        if let Some(state_store) = &self.state_store {
+            if let Err(err) = state_store.persist(graph).await {
+                error!(error = ?err, "unable to persist council graph state");
+            }
+        }
+    }
+
     pub async fn run(
-        self,
+        mut self,
         subscriber_started_tx: watch::Sender<()>,
         mut shutdown_request_rx: watch::Receiver<()>,
     ) -> Result<()> {
-        let channel_suffix = "council.*";
-        let subscriber_channel = if let Some(prefix) = self.nats.metadata().subject_prefix() {
-            format!("{}.{}", prefix, channel_suffix)
+        let council_subject = if let Some(prefix) = self.nats.metadata().subject_prefix() {
+            format!("{prefix}.council")
         } else {
-            channel_suffix.to_string()
-        };
-        let mut subscriber = loop {
-            match self.nats.subscribe(subscriber_channel.clone()).await {
-                Ok(sub) => break sub,
-                Err(err) => {
-                    error!("Unable to subscribe to the council request channel on nats: {err}");
-                    tokio::time::sleep(Duration::from_millis(1000)).await;
-                }
-            }
+            "council".to_string()
         };
+        let mut subscriber = self.resubscribe(&council_subject).await;
+        info!(shard_count = self.shard_count, owned_shards = ?self.owned_shards, "Council subscribed to requests");
         let _ = subscriber_started_tx.send(());
+        // Jobs may have registered with a previous council that went away without persisting
+        // their graphs.
+        self.announce(&council_subject).await;
+        let mut subscription_lost = false;
 
         let mut sigterm_watcher = signal::unix::signal(signal::unix::SignalKind::terminate())?;
         let (our_shutdown_request_tx, mut our_shutdown_request_rx) =
@@ -68,11 +203,50 @@ impl Server {
             }
         });
 
-        let mut complete_graph = ChangeSetGraph::default();
+        let mut complete_graph = self.load_graph().await;
+        // Start by persisting whatever we restored so a corrupt state file is replaced.
+        let mut graph_changed = true;
+
+        let idle_warning = tokio::time::sleep(IDLE_WARNING_INTERVAL);
+        tokio::pin!(idle_warning);
+        let mut lease_check = tokio::time::interval(
+            LEASE_CHECK_INTERVAL
+                .min(self.processing_lease_timeout)
+                .max(Duration::from_millis(100)),
+        );
+        lease_check.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
         loop {
-            for (reply_channel, node_ids) in complete_graph.fetch_all_available() {
+            if subscription_lost {
+                warn!("Council lost its subscription to nats; resubscribing");
+                subscriber = tokio::select! {
+                    subscriber = self.resubscribe(&council_subject) => subscriber,
+                    Ok(()) = shutdown_request_rx.changed() => {
+                        info!("Worker task received shutdown notification: stopping");
+                        break;
+                    }
+                    _ = our_shutdown_request_rx.changed() => {
+                        info!("Worker task received our shutdown notification: stopping");
+                        break;
+                    }
+                };
+                subscription_lost = false;
+                info!("Council resubscribed to requests");
+                self.announce(&council_subject).await;
+            }
+
+            let available = complete_graph.fetch_all_available();
+            // Persist before telling jobs they can process, so that a crash in between can never
+            // forget which job was told to process a node.
+            if graph_changed || !available.is_empty() {
+                self.persist_graph(&complete_graph).await;
+                graph_changed = false;
+            }
+
+            for (reply_channel, node_ids) in available {
                 info!(%reply_channel, ?node_ids, "Ok to process AttributeValue");
-                self.nats
+                if let Err(err) = self
+                    .nats
                     .publish(
                         reply_channel,
                         serde_json::to_vec(&Response::OkToProcess { node_ids })
@@ -80,17 +254,48 @@ impl Server {
                             .into(),
                     )
                     .await
-                    .unwrap();
+                {
+                    // The job will re-register its graph once we are reachable again, and its
+                    // lease expires if it never hears from us.
+                    error!(error = ?err, "Unable to tell job it can process AttributeValues");
+                }
             }
 
-            let sleep = tokio::time::sleep(Duration::from_secs(60));
-            tokio::pin!(sleep);
-            // FIXME: handle timeouts
             let (reply_channel, request) = tokio::select! {
-                _ = &mut sleep => {
+                _ = &mut idle_warning => {
                     if !complete_graph.is_empty() {
                         warn!(?complete_graph, "Council has values in graph but has been waiting for messages for 60 seconds");
                     }
+                    idle_warning.as_mut().reset(Instant::now() + IDLE_WARNING_INTERVAL);
+                    continue;
+                }
+                _ = lease_check.tick() => {
+                    let revoked = complete_graph.revoke_expired_leases(self.processing_lease_timeout);
+                    if !revoked.is_empty() {
+                        graph_changed = true;
+                    }
+                    for (reply_channel, node_id) in revoked {
+                        warn!(%reply_channel, %node_id, lease_timeout = ?self.processing_lease_timeout, "Job held processing lease for too long; revoking it");
+                        if let Err(err) = self
+                            .nats
+                            .publish(
+                                reply_channel,
+                                serde_json::to_vec(&Response::LeaseRevoked { node_id })
+                                    .unwrap()
+                                    .into(),
+                            )
+                            .await
+                        {
+                            error!(error = ?err, "Unable to tell job its processing lease was revoked");
+                        }
+                    }
+                    continue;
+                }
+                _ = self.nats_connected.notified() => {
+                    // Anything published while we were disconnected is gone, so have jobs send
+                    // their graphs again.
+                    info!("Council (re)connected to nats; announcing to jobs");
+                    self.announce(&council_subject).await;
                     continue;
                 }
                 req = subscriber.next() => match req {
@@ -105,8 +310,11 @@ impl Server {
                             continue;
                         }
                     }
-                    // FIXME: reconnect
-                    None => break, // Happens if subscriber has been unsubscribed or if connection is closed
+                    // Happens if subscriber has been unsubscribed or if connection is closed
+                    None => {
+                        subscription_lost = true;
+                        continue;
+                    }
                 },
                 Ok(()) = shutdown_request_rx.changed() => {
                     info!("Worker task received shutdown notification: stopping");
@@ -118,8 +326,11 @@ impl Server {
                 }
                 else => unreachable!(),
             };
+            idle_warning
+                .as_mut()
+                .reset(Instant::now() + IDLE_WARNING_INTERVAL);
 
-            match request {
+            let result = match request {
                 Request::ValueDependencyGraph {
                     change_set_id,
                     dependency_graph,
@@ -131,7 +342,6 @@ impl Server {
                         dependency_graph,
                     )
                     .await
-                    .unwrap();
                 }
                 Request::ProcessedValue {
                     change_set_id,
@@ -145,12 +355,9 @@ impl Server {
                         node_id,
                     )
                     .await
-                    .unwrap();
                 }
                 Request::Bye { change_set_id } => {
-                    job_is_going_away(&mut complete_graph, reply_channel, change_set_id)
-                        .await
-                        .unwrap();
+                    job_is_going_away(&mut complete_graph, reply_channel, change_set_id).await
                 }
                 Request::ValueProcessingFailed {
                     change_set_id,
@@ -164,9 +371,31 @@ impl Server {
                         node_id,
                     )
                     .await
-                    .unwrap();
+                }
+                Request::Topology => topology(&self.nats, self.shard_count, reply_channel).await,
+                Request::ListChangeSets => {
+                    list_change_sets(&self.nats, &complete_graph, reply_channel).await
+                }
+                Request::DumpChangeSet { change_set_id } => {
+                    dump_change_set(&self.nats, &complete_graph, reply_channel, change_set_id).await
+                }
+                Request::DrainChangeSet { change_set_id } => {
+                    drain_change_set(
+                        &self.nats,
+                        &mut complete_graph,
+                        reply_channel,
+                        change_set_id,
+                    )
+                    .await
                 }
             };
+            // Jobs whose processing lease was revoked may still report on the node they were
+            // processing, so a request that doesn't match our view of the graph is expected and
+            // must not take council down.
+            if let Err(err) = result {
+                error!(error = ?err, "Unable to handle council request");
+            }
+            graph_changed = true;
         }
 
         Ok(())
@@ -190,9 +419,46 @@ impl Server {
 // |                                                                            | map, and as value in "depends on" for all entries in the hash map).                 |
 // |                                                                            |                                                                                     |
 // | Goto: Wait                                                                 | Goto: Check graph data.                                                             |
+//
+// If a job holds a node for longer than the processing lease timeout, Council assumes the job has
+// gone away: it revokes the lease, informs the job with `LeaseRevoked` and hands the node to the
+// next job that wants it. The revoked job is never offered the node again, but is told when it
+// has been processed. If it reports the node as processed itself before anyone else is handed
+// it, and the node hasn't changed in the meantime, its result is accepted.
+//
+// Whenever Council (re)subscribes or its nats connection is re-established, it broadcasts
+// `Reregister` on `council.announce.<shard>` (shard `0` when unsharded). Requests sent while it
+// was unreachable are lost, so every job re-sends its pending graph, which Council merges
+// idempotently.
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+/// Connects to NATS, notifying `connected` every time the connection is re-established after
+/// being lost. The initial connection isn't notified, as council announces itself once it has
+/// subscribed anyway.
+async fn connect(config: &NatsConfig, connected: Arc<Notify>) -> Result<NatsClient> {
+    let disconnected = Arc::new(AtomicBool::new(false));
+    let options = ConnectOptions::default().event_callback(move |event| {
+        let connected = connected.clone();
+        let disconnected = disconnected.clone();
+        async move {
+            match event {
+                Event::Connected => {
+                    if disconnected.swap(false, Ordering::SeqCst) {
+                        connected.notify_one();
+                    }
+                }
+                Event::Disconnected => {
+                    disconnected.store(true, Ordering::SeqCst);
+                    warn!("Council disconnected from nats");
+                }
+                other => debug!(event = %other, "Council nats connection event"),
+            }
+        }
+    });
+    Ok(NatsClient::new_with_options(config, options).await?)
+}
+
 #[remain::sorted]
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -202,10 +468,14 @@ pub enum Error {
     Io(#[from] std::io::Error),
     #[error(transparent)]
     Nats(#[from] si_data_nats::Error),
+    #[error(transparent)]
+    SerdeJson(#[from] serde_json::Error),
     #[error("Job reported finishing processing, but we expected a different job to be processing")]
     ShouldNotBeProcessingByJob,
     #[error("Unexpected JobId")]
     UnexpectedJobId,
+    #[error("Unknown ChangeSetId")]
+    UnknownChangeSet,
     #[error("Unknown NodeId")]
     UnknownNodeId,
 }
@@ -230,9 +500,16 @@ pub async fn job_processed_a_value(
     node_id: Id,
 ) -> Result<(), Error> {
     info!(%reply_channel, %change_set_id, %node_id, "Job finished processing graph node");
-    for reply_channel in
-        complete_graph.mark_node_as_processed(&reply_channel, change_set_id, node_id)?
-    {
+    let reply_channels =
+        match complete_graph.mark_node_as_processed(&reply_channel, change_set_id, node_id) {
+            Ok(reply_channels) => reply_channels,
+            Err(Error::UnknownChangeSet) => {
+                ignore_late_report(&reply_channel, change_set_id, node_id);
+                return Ok(());
+            }
+            Err(err) => return Err(err),
+        };
+    for reply_channel in reply_channels {
         info!(%reply_channel, ?node_id, "AttributeValue has been processed by a job");
         nats.publish(
             reply_channel,
@@ -240,8 +517,7 @@ pub async fn job_processed_a_value(
                 .unwrap()
                 .into(),
         )
-        .await
-        .unwrap();
+        .await?;
     }
     debug!(?complete_graph);
     Ok(())
@@ -257,9 +533,19 @@ pub async fn job_failed_processing_a_value(
 ) -> Result<(), Error> {
     warn!(%reply_channel, %change_set_id, %node_id, ?complete_graph, "Job failed to process node");
 
-    for (reply_channel, failed_node_id) in
-        complete_graph.remove_node_and_dependents(reply_channel, change_set_id, node_id)?
-    {
+    let failure_notifications = match complete_graph.remove_node_and_dependents(
+        reply_channel.clone(),
+        change_set_id,
+        node_id,
+    ) {
+        Ok(failure_notifications) => failure_notifications,
+        Err(Error::UnknownChangeSet) => {
+            ignore_late_report(&reply_channel, change_set_id, node_id);
+            return Ok(());
+        }
+        Err(err) => return Err(err),
+    };
+    for (reply_channel, failed_node_id) in failure_notifications {
         nats.publish(
             reply_channel,
             serde_json::to_vec(&Response::Failed {
@@ -268,13 +554,18 @@ pub async fn job_failed_processing_a_value(
             .unwrap()
             .into(),
         )
-        .await
-        .unwrap();
+        .await?;
     }
 
     Ok(())
 }
 
+/// A job can still be processing a node of a change set that has been drained since, in which case
+/// every job waiting on the node, including the reporting one, has already been told it failed.
+fn ignore_late_report(reply_channel: &Subject, change_set_id: Id, node_id: Id) {
+    info!(%reply_channel, %change_set_id, %node_id, "Ignoring report for a change set council has no graph for");
+}
+
 #[instrument(level = "info")]
 pub async fn job_is_going_away(
     complete_graph: &mut ChangeSetGraph,
@@ -287,3 +578,84 @@ pub async fn job_is_going_away(
 
     Ok(())
 }
+
+#[instrument(level = "info", skip(nats, complete_graph))]
+pub async fn list_change_sets(
+    nats: &NatsClient,
+    complete_graph: &ChangeSetGraph,
+    reply_channel: Subject,
+) -> Result<(), Error> {
+    let change_sets = complete_graph.change_set_summaries();
+    nats.publish(
+        reply_channel,
+        serde_json::to_vec(&IntrospectionResponse::ChangeSets { change_sets })?.into(),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[instrument(level = "info", skip(nats, complete_graph))]
+pub async fn dump_change_set(
+    nats: &NatsClient,
+    complete_graph: &ChangeSetGraph,
+    reply_channel: Subject,
+    change_set_id: Id,
+) -> Result<(), Error> {
+    let response = match complete_graph.dump_change_set(change_set_id) {
+        Some(dump) => IntrospectionResponse::ChangeSet { dump },
+        None => IntrospectionResponse::UnknownChangeSet { change_set_id },
+    };
+    nats.publish(reply_channel, serde_json::to_vec(&response)?.into())
+        .await?;
+
+    Ok(())
+}
+
+#[instrument(level = "info", skip(nats, complete_graph))]
+pub async fn drain_change_set(
+    nats: &NatsClient,
+    complete_graph: &mut ChangeSetGraph,
+    reply_channel: Subject,
+    change_set_id: Id,
+) -> Result<(), Error> {
+    let response = match complete_graph.drain_change_set(change_set_id) {
+        Some(notifications) => {
+            warn!(%change_set_id, "Draining change set graph on request");
+
+            let mut node_ids = HashSet::new();
+            let mut job_reply_channels = HashSet::new();
+            for (job_reply_channel, node_id) in notifications {
+                node_ids.insert(node_id);
+                job_reply_channels.insert(job_reply_channel.to_string());
+                nats.publish(
+                    job_reply_channel,
+                    serde_json::to_vec(&Response::Failed { node_id })?.into(),
+                )
+                .await?;
+            }
+
+            IntrospectionResponse::Drained {
+                change_set_id,
+                node_count: node_ids.len(),
+                notified_job_count: job_reply_channels.len(),
+            }
+        }
+        None => IntrospectionResponse::UnknownChangeSet { change_set_id },
+    };
+    nats.publish(reply_channel, serde_json::to_vec(&response)?.into())
+        .await?;
+
+    Ok(())
+}
+
+#[instrument(level = "info", skip(nats))]
+pub async fn topology(nats: &NatsClient, shard_count: u32, reply_channel: Subject) -> Result<()> {
+    nats.publish(
+        reply_channel,
+        serde_json::to_vec(&Topology { shard_count })?.into(),
+    )
+    .await?;
+
+    Ok(())
+}
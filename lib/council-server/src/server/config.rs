@@ -1,3 +1,9 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 use si_data_nats::NatsConfig;
@@ -14,19 +20,54 @@ pub enum ConfigError {
 
 pub type Result<T, E = ConfigError> = std::result::Result<T, E>;
 
+const DEFAULT_PROCESSING_LEASE_TIMEOUT_SECS: u64 = 10 * 60;
+
 #[derive(Debug, Builder)]
+#[builder(build_fn(validate = "Self::validate"))]
 pub struct Config {
     #[builder(default = "NatsConfig::default()")]
     nats: NatsConfig,
+
+    #[builder(default, setter(into, strip_option))]
+    state_path: Option<PathBuf>,
+
+    #[builder(default = "default_processing_lease_timeout()")]
+    processing_lease_timeout: Duration,
+
+    #[builder(default = "default_shard_count()")]
+    shard_count: u32,
+
+    #[builder(default)]
+    shards: Vec<u32>,
 }
 
 impl StandardConfig for Config {
     type Builder = ConfigBuilder;
 }
 
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ConfigFile {
     nats: NatsConfig,
+    #[serde(default)]
+    state_path: Option<PathBuf>,
+    #[serde(default = "default_processing_lease_timeout_secs")]
+    processing_lease_timeout_secs: u64,
+    #[serde(default = "default_shard_count")]
+    shard_count: u32,
+    #[serde(default)]
+    shards: Vec<u32>,
+}
+
+impl Default for ConfigFile {
+    fn default() -> Self {
+        Self {
+            nats: Default::default(),
+            state_path: Default::default(),
+            processing_lease_timeout_secs: default_processing_lease_timeout_secs(),
+            shard_count: default_shard_count(),
+            shards: Default::default(),
+        }
+    }
 }
 
 impl StandardConfigFile for ConfigFile {
@@ -39,6 +80,12 @@ impl TryFrom<ConfigFile> for Config {
     fn try_from(value: ConfigFile) -> Result<Self> {
         let mut config = Config::builder();
         config.nats(value.nats);
+        if let Some(state_path) = value.state_path {
+            config.state_path(state_path);
+        }
+        config.processing_lease_timeout(Duration::from_secs(value.processing_lease_timeout_secs));
+        config.shard_count(value.shard_count);
+        config.shards(value.shards);
         config.build().map_err(Into::into)
     }
 }
@@ -54,4 +101,101 @@ impl Config {
     pub fn subject_prefix(&self) -> Option<&str> {
         self.nats.subject_prefix.as_deref()
     }
+
+    /// Gets a reference to the path where the dependency graph state is persisted, if any.
+    ///
+    /// When unset, graph state only lives in memory and is lost on restart.
+    pub fn state_path(&self) -> Option<&Path> {
+        self.state_path.as_deref()
+    }
+
+    /// Gets how long a job may hold a node before its processing lease is revoked and the node
+    /// is handed to the next job that wants it.
+    pub fn processing_lease_timeout(&self) -> Duration {
+        self.processing_lease_timeout
+    }
+
+    /// Gets the total number of shards change sets are split across.
+    pub fn shard_count(&self) -> u32 {
+        self.shard_count
+    }
+
+    /// Gets the shards this council instance owns. When no shards are configured, the instance
+    /// owns every shard.
+    pub fn owned_shards(&self) -> Vec<u32> {
+        if self.shards.is_empty() {
+            (0..self.shard_count()).collect()
+        } else {
+            self.shards.clone()
+        }
+    }
+}
+
+impl ConfigBuilder {
+    /// Checks that the owned shards are distinct and all lie within `0..shard_count`, so that
+    /// every shard this instance subscribes to is one that jobs route change sets to.
+    fn validate(&self) -> Result<(), String> {
+        let shard_count = self.shard_count.unwrap_or_else(default_shard_count);
+        if shard_count == 0 {
+            return Err("shard count must be at least 1".to_string());
+        }
+
+        let shards = self.shards.as_deref().unwrap_or_default();
+        let mut seen = HashSet::with_capacity(shards.len());
+        for &shard in shards {
+            if shard >= shard_count {
+                return Err(format!(
+                    "shard {shard} is out of range for a shard count of {shard_count}"
+                ));
+            }
+            if !seen.insert(shard) {
+                return Err(format!("shard {shard} is listed more than once"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn default_shard_count() -> u32 {
+    1
+}
+
+fn default_processing_lease_timeout_secs() -> u64 {
+    DEFAULT_PROCESSING_LEASE_TIMEOUT_SECS
+}
+
+fn default_processing_lease_timeout() -> Duration {
+    Duration::from_secs(default_processing_lease_timeout_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(shard_count: u32, shards: Vec<u32>) -> Result<Config, ConfigBuilderError> {
+        Config::builder()
+            .shard_count(shard_count)
+            .shards(shards)
+            .build()
+    }
+
+    #[test]
+    fn owned_shards_default_to_every_shard() {
+        let config = build(3, vec![]).expect("failed to build config");
+
+        assert_eq!(vec![0, 1, 2], config.owned_shards());
+    }
+
+    #[test]
+    fn owned_shards_must_lie_within_shard_count() {
+        assert!(build(2, vec![1]).is_ok());
+        assert!(build(2, vec![2]).is_err());
+        assert!(build(0, vec![]).is_err());
+    }
+
+    #[test]
+    fn owned_shards_must_be_distinct() {
+        assert!(build(2, vec![0, 0]).is_err());
+    }
 }
@@ -1,12 +1,20 @@
-use crate::{server::Error, Graph, Id};
-use std::collections::{HashMap, HashSet, VecDeque};
+use crate::{
+    introspection::{ChangeSetDump, ChangeSetSummary, NodeDump},
+    server::Error,
+    Graph, Id,
+};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::Duration,
+};
 
 mod node_metadata;
 
 use node_metadata::NodeMetadata;
+use serde::{Deserialize, Serialize};
 use si_data_nats::Subject;
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Deserialize, Serialize)]
 pub struct ChangeSetGraph {
     dependency_data: HashMap<Id, HashMap<Id, NodeMetadata>>,
 }
@@ -16,6 +24,83 @@ impl ChangeSetGraph {
         self.dependency_data.is_empty()
     }
 
+    pub fn change_set_summaries(&self) -> Vec<ChangeSetSummary> {
+        self.dependency_data
+            .iter()
+            .map(|(change_set_id, graph)| {
+                let mut reply_channels = HashSet::new();
+                let mut processing_count = 0;
+                for metadata in graph.values() {
+                    if let Some(reply_channel) = metadata.processing_reply_channel() {
+                        processing_count += 1;
+                        reply_channels.insert(reply_channel.as_str());
+                    }
+                    reply_channels.extend(
+                        metadata
+                            .waiting_reply_channels_iter()
+                            .map(|reply_channel| reply_channel.as_str()),
+                    );
+                }
+
+                ChangeSetSummary {
+                    change_set_id: *change_set_id,
+                    node_count: graph.len(),
+                    processing_count,
+                    job_count: reply_channels.len(),
+                }
+            })
+            .collect()
+    }
+
+    pub fn dump_change_set(&self, change_set_id: Id) -> Option<ChangeSetDump> {
+        let graph = self.dependency_data.get(&change_set_id)?;
+
+        let mut nodes: Vec<NodeDump> = graph
+            .iter()
+            .map(|(node_id, metadata)| NodeDump {
+                node_id: *node_id,
+                depends_on: metadata.depends_on_node_ids().copied().collect(),
+                wanted_by: metadata
+                    .wanted_by_reply_channels_iter()
+                    .map(ToString::to_string)
+                    .collect(),
+                lease_revoked_from: metadata
+                    .revoked_reply_channels_iter()
+                    .map(ToString::to_string)
+                    .collect(),
+                processing_by: metadata.processing_reply_channel().map(ToString::to_string),
+                processing_for_secs: metadata
+                    .processing_duration()
+                    .map(|duration| duration.as_secs()),
+            })
+            .collect();
+        nodes.sort_by_key(|node| node.node_id.to_string());
+
+        Some(ChangeSetDump {
+            change_set_id,
+            nodes,
+        })
+    }
+
+    /// Remove all of the graph data for `change_set_id`, returning every reply channel that was
+    /// waiting for or processing a node (with the associated `node_id`), or `None` if there is no
+    /// graph data for the change set.
+    pub fn drain_change_set(&mut self, change_set_id: Id) -> Option<Vec<(Subject, Id)>> {
+        let graph = self.dependency_data.remove(&change_set_id)?;
+
+        let mut notifications = Vec::new();
+        for (node_id, metadata) in graph {
+            if let Some(reply_channel) = metadata.processing_reply_channel() {
+                notifications.push((reply_channel.clone(), node_id));
+            }
+            for reply_channel in metadata.waiting_reply_channels_iter() {
+                notifications.push((reply_channel.clone(), node_id));
+            }
+        }
+
+        Some(notifications)
+    }
+
     pub fn fetch_all_available(&mut self) -> HashMap<String, Vec<Id>> {
         let mut result: HashMap<String, Vec<Id>> = HashMap::new();
         for graph in self.dependency_data.values_mut() {
@@ -31,6 +116,21 @@ impl ChangeSetGraph {
         result
     }
 
+    /// Revoke the processing leases that have been held for longer than `lease_timeout`,
+    /// returning the reply channel of the job that held each revoked lease along with the node
+    /// it was processing.
+    pub fn revoke_expired_leases(&mut self, lease_timeout: Duration) -> Vec<(Subject, Id)> {
+        let mut revoked = Vec::new();
+        for graph in self.dependency_data.values_mut() {
+            for (id, metadata) in graph.iter_mut() {
+                if let Some(reply_channel) = metadata.revoke_expired_lease(lease_timeout) {
+                    revoked.push((reply_channel, *id));
+                }
+            }
+        }
+        revoked
+    }
+
     pub fn merge_dependency_graph(
         &mut self,
         reply_channel: Subject,
@@ -76,7 +176,10 @@ impl ChangeSetGraph {
         change_set_id: Id,
         node_id: Id,
     ) -> Result<HashSet<String>, Error> {
-        let change_set_graph_data = self.dependency_data.get_mut(&change_set_id).unwrap();
+        let change_set_graph_data = self
+            .dependency_data
+            .get_mut(&change_set_id)
+            .ok_or(Error::UnknownChangeSet)?;
 
         let (ok_to_remove_node, wanted_by_reply_channels) =
             if let Some(node_metadata) = change_set_graph_data.get_mut(&node_id) {
@@ -127,7 +230,10 @@ impl ChangeSetGraph {
         node_id: Id,
     ) -> Result<Vec<(Subject, Id)>, Error> {
         let mut failure_notifications = Vec::new();
-        let change_set_graph_data = self.dependency_data.get_mut(&change_set_id).unwrap();
+        let change_set_graph_data = self
+            .dependency_data
+            .get_mut(&change_set_id)
+            .ok_or(Error::UnknownChangeSet)?;
 
         let mut node_ids_to_fail = VecDeque::new();
         node_ids_to_fail.push_back(node_id);
@@ -143,7 +249,7 @@ impl ChangeSetGraph {
                     return Err(Error::ShouldNotBeProcessingByJob);
                 }
 
-                for notification_reply_channel in node_metadata.wanted_by_reply_channels_iter() {
+                for notification_reply_channel in node_metadata.waiting_reply_channels_iter() {
                     failure_notifications
                         .push((notification_reply_channel.clone(), node_id_to_fail));
                 }
@@ -165,3 +271,179 @@ impl ChangeSetGraph {
         Ok(failure_notifications)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph_wanted_by(jobs: &[&Subject], change_set_id: Id, node_id: Id) -> ChangeSetGraph {
+        let mut graph = ChangeSetGraph::default();
+        for job in jobs {
+            graph
+                .merge_dependency_graph(
+                    (*job).clone(),
+                    HashMap::from([(node_id, Vec::new())]),
+                    change_set_id,
+                )
+                .expect("failed to merge graph");
+        }
+        graph
+    }
+
+    fn offered(graph: &mut ChangeSetGraph) -> HashMap<String, Vec<Id>> {
+        graph.fetch_all_available()
+    }
+
+    #[test]
+    fn expired_lease_is_offered_to_the_next_job_only() {
+        let (change_set_id, node_id) = (Id::default(), Id::default());
+        let (first, second) = (Subject::from("job.first"), Subject::from("job.second"));
+        let mut graph = graph_wanted_by(&[&first, &second], change_set_id, node_id);
+
+        assert_eq!(
+            HashMap::from([(first.to_string(), vec![node_id])]),
+            offered(&mut graph)
+        );
+        assert!(graph
+            .revoke_expired_leases(Duration::from_secs(60))
+            .is_empty());
+
+        assert_eq!(
+            vec![(first.clone(), node_id)],
+            graph.revoke_expired_leases(Duration::ZERO)
+        );
+        assert_eq!(
+            HashMap::from([(second.to_string(), vec![node_id])]),
+            offered(&mut graph)
+        );
+
+        // The revoked job is never offered the node again, but hears when it has been processed
+        assert_eq!(
+            vec![(second.clone(), node_id)],
+            graph.revoke_expired_leases(Duration::ZERO)
+        );
+        assert!(offered(&mut graph).is_empty());
+
+        let dump = graph
+            .dump_change_set(change_set_id)
+            .expect("change set has no graph");
+        assert_eq!(
+            vec![first.to_string(), second.to_string()],
+            dump.nodes[0].lease_revoked_from
+        );
+    }
+
+    #[test]
+    fn late_report_from_revoked_job_is_accepted_if_nobody_took_over() {
+        let (change_set_id, node_id) = (Id::default(), Id::default());
+        let job = Subject::from("job");
+        let mut graph = graph_wanted_by(&[&job], change_set_id, node_id);
+
+        offered(&mut graph);
+        graph.revoke_expired_leases(Duration::ZERO);
+        assert!(offered(&mut graph).is_empty());
+
+        let notified = graph
+            .mark_node_as_processed(&job, change_set_id, node_id)
+            .expect("failed to mark node as processed");
+        assert_eq!(HashSet::from([job.to_string()]), notified);
+        assert!(graph.is_empty());
+    }
+
+    #[test]
+    fn late_report_from_revoked_job_waits_for_the_job_that_took_over() {
+        let (change_set_id, node_id) = (Id::default(), Id::default());
+        let (first, second) = (Subject::from("job.first"), Subject::from("job.second"));
+        let mut graph = graph_wanted_by(&[&first, &second], change_set_id, node_id);
+
+        offered(&mut graph);
+        graph.revoke_expired_leases(Duration::ZERO);
+        offered(&mut graph);
+
+        let notified = graph
+            .mark_node_as_processed(&first, change_set_id, node_id)
+            .expect("failed to mark node as processed");
+        assert!(notified.is_empty());
+        assert!(!graph.is_empty());
+
+        let notified = graph
+            .mark_node_as_processed(&second, change_set_id, node_id)
+            .expect("failed to mark node as processed");
+        assert_eq!(
+            HashSet::from([first.to_string(), second.to_string()]),
+            notified
+        );
+        assert!(graph.is_empty());
+    }
+
+    #[test]
+    fn stale_late_report_from_revoked_job_is_offered_again() {
+        let (change_set_id, node_id, dependency_id) = (Id::default(), Id::default(), Id::default());
+        let job = Subject::from("job");
+        let mut graph = graph_wanted_by(&[&job], change_set_id, node_id);
+
+        offered(&mut graph);
+        graph.revoke_expired_leases(Duration::ZERO);
+
+        // Another job's graph changes the node's inputs while the revoked job is still going
+        let other = Subject::from("job.other");
+        graph
+            .merge_dependency_graph(
+                other.clone(),
+                HashMap::from([(node_id, vec![dependency_id])]),
+                change_set_id,
+            )
+            .expect("failed to merge graph");
+        assert_eq!(
+            HashMap::from([(other.to_string(), vec![dependency_id])]),
+            offered(&mut graph)
+        );
+        graph
+            .mark_node_as_processed(&other, change_set_id, dependency_id)
+            .expect("failed to mark dependency as processed");
+
+        let notified = graph
+            .mark_node_as_processed(&job, change_set_id, node_id)
+            .expect("failed to mark node as processed");
+        assert!(notified.is_empty());
+
+        // The revoked job wants the node again, behind the job whose graph changed it
+        let dump = graph
+            .dump_change_set(change_set_id)
+            .expect("change set has no graph");
+        let node = dump
+            .nodes
+            .iter()
+            .find(|node| node.node_id == node_id)
+            .expect("node is not in the graph");
+        assert_eq!(vec![other.to_string(), job.to_string()], node.wanted_by);
+        assert!(node.lease_revoked_from.is_empty());
+    }
+    #[test]
+    fn late_report_after_change_set_is_gone_is_an_error() {
+        let (change_set_id, node_id) = (Id::default(), Id::default());
+        let job = Subject::from("job");
+        let mut graph = graph_wanted_by(&[&job], change_set_id, node_id);
+
+        offered(&mut graph);
+        graph
+            .mark_node_as_processed(&job, change_set_id, node_id)
+            .expect("failed to mark node as processed");
+        assert!(graph.is_empty());
+
+        // A job reporting on the node again, or on a change set council never saw
+        assert!(matches!(
+            graph.mark_node_as_processed(&job, change_set_id, node_id),
+            Err(Error::UnknownChangeSet)
+        ));
+        assert!(matches!(
+            graph.remove_node_and_dependents(job.clone(), change_set_id, node_id),
+            Err(Error::UnknownChangeSet)
+        ));
+        assert!(matches!(
+            graph.mark_node_as_processed(&job, Id::default(), node_id),
+            Err(Error::UnknownChangeSet)
+        ));
+        assert!(graph.is_empty());
+    }
+}
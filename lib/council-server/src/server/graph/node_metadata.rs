@@ -1,20 +1,30 @@
 use std::{
     collections::{vec_deque::Iter, HashSet, VecDeque},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
+use serde::{Deserialize, Serialize};
 use si_data_nats::Subject;
 
 use crate::{server::Error, Id};
 
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct NodeMetadata {
     // This should really be an ordered set, to remove duplicates, but we'll deal with
     // that later.
     wanted_by_reply_channels: VecDeque<Subject>,
     processing_reply_channel: Option<Subject>,
+    // Jobs whose processing lease was revoked. They still want the node, so are told when it has
+    // been processed, but are never offered it again as they may still be processing it.
+    #[serde(default)]
+    revoked_leases: Vec<RevokedLease>,
     depends_on_node_ids: HashSet<Id>,
+    // `Instant`s are only meaningful within the process that created them, so they are not
+    // persisted. A restored node is treated as freshly updated, with no stale processing, and
+    // its processing lease (if any) starts over.
+    #[serde(skip)]
     processing_started_at: Option<Instant>,
+    #[serde(skip, default = "Instant::now")]
     last_updated_at: Instant,
 }
 
@@ -23,6 +33,7 @@ impl Default for NodeMetadata {
         Self {
             wanted_by_reply_channels: VecDeque::default(),
             processing_reply_channel: Option::default(),
+            revoked_leases: Vec::default(),
             depends_on_node_ids: HashSet::default(),
             processing_started_at: Option::default(),
             last_updated_at: Instant::now(),
@@ -30,6 +41,15 @@ impl Default for NodeMetadata {
     }
 }
 
+/// A job which was processing a node when its processing lease was revoked.
+#[derive(Debug, Deserialize, Serialize)]
+struct RevokedLease {
+    reply_channel: Subject,
+    // When the job started processing the node, unknown once restored from persisted state.
+    #[serde(skip)]
+    processing_started_at: Option<Instant>,
+}
+
 impl NodeMetadata {
     pub fn add_wanted_by_reply_channel(&mut self, reply_channel: &Subject) {
         self.wanted_by_reply_channels
@@ -41,12 +61,18 @@ impl NodeMetadata {
         self.depends_on_node_ids.is_empty()
     }
 
+    pub fn depends_on_node_ids(&self) -> impl Iterator<Item = &Id> {
+        self.depends_on_node_ids.iter()
+    }
+
     pub fn depends_on(&self, node_id: Id) -> bool {
         self.depends_on_node_ids.contains(&node_id)
     }
 
     pub fn is_empty(&self) -> bool {
-        self.wanted_by_reply_channels.is_empty() && self.processing_reply_channel.is_none()
+        self.wanted_by_reply_channels.is_empty()
+            && self.processing_reply_channel.is_none()
+            && self.revoked_leases.is_empty()
     }
 
     pub fn is_processing_stale(&self) -> bool {
@@ -67,7 +93,7 @@ impl NodeMetadata {
         reply_channel: &Subject,
     ) -> Result<(bool, HashSet<String>), Error> {
         if self.processing_reply_channel().map(|p| &**p) != Some(reply_channel) {
-            return Err(Error::ShouldNotBeProcessingByJob);
+            return self.mark_as_processed_after_revocation(reply_channel);
         }
 
         let processing_reply_channel = self.processing_reply_channel.take();
@@ -90,21 +116,78 @@ impl NodeMetadata {
         }
     }
 
+    /// Handles a job reporting that it processed this node after its lease was revoked.
+    ///
+    /// Its result stands if nobody else has been handed the node since and the node hasn't
+    /// changed since the job started processing it. Otherwise it waits for the job now
+    /// processing the node or, if the result is out of date, is offered the node again.
+    fn mark_as_processed_after_revocation(
+        &mut self,
+        reply_channel: &Subject,
+    ) -> Result<(bool, HashSet<String>), Error> {
+        let revoked_lease = self
+            .revoked_leases
+            .iter()
+            .position(|lease| lease.reply_channel == *reply_channel)
+            .ok_or(Error::ShouldNotBeProcessingByJob)?;
+
+        if self.processing_reply_channel.is_some() {
+            return Ok((false, HashSet::new()));
+        }
+
+        let is_stale = self.revoked_leases[revoked_lease]
+            .processing_started_at
+            .map_or(true, |processing_started_at| {
+                processing_started_at < self.last_updated_at
+            });
+        if is_stale {
+            self.revoked_leases.remove(revoked_lease);
+            self.add_wanted_by_reply_channel(reply_channel);
+
+            return Ok((false, HashSet::new()));
+        }
+
+        if self.dependencies_satisfied() {
+            Ok((true, self.wanted_by_reply_channels()))
+        } else {
+            Ok((false, HashSet::new()))
+        }
+    }
+
     pub fn merge_metadata(&mut self, reply_channel: Subject, dependencies: &Vec<Id>) {
-        self.last_updated_at = Instant::now();
+        let mut changed = false;
 
-        if !self.wanted_by_reply_channels.contains(&reply_channel) {
+        // A job re-registering a graph it already sent (for example after a council restart) is
+        // already either waiting for or processing this node, so it is not added as a waiter
+        // again.
+        if !self.wanted_by_reply_channels.contains(&reply_channel)
+            && self.processing_reply_channel.as_ref() != Some(&reply_channel)
+            && !self
+                .revoked_leases
+                .iter()
+                .any(|lease| lease.reply_channel == reply_channel)
+        {
             self.wanted_by_reply_channels.push_back(reply_channel);
+            changed = true;
+        }
+        for dependency in dependencies {
+            changed |= self.depends_on_node_ids.insert(*dependency);
+        }
+
+        // Only bump the update time when something actually changed, otherwise a re-registered
+        // graph would mark any in-progress processing of this node as stale.
+        if changed {
+            self.last_updated_at = Instant::now();
         }
-        self.depends_on_node_ids.extend(dependencies);
     }
 
     pub fn next_to_process(&mut self) -> Option<Subject> {
         if self.depends_on_node_ids.is_empty() && self.processing_reply_channel.is_none() {
-            self.last_updated_at = Instant::now();
-
             self.processing_reply_channel = self.wanted_by_reply_channels.pop_front();
             if self.processing_reply_channel.is_some() {
+                // Only an actual hand-off counts as an update, otherwise a node nobody wants
+                // right now would make the result of a job whose lease was revoked look stale.
+                self.last_updated_at = Instant::now();
                 self.processing_started_at = Some(Instant::now());
             } else {
                 self.processing_started_at = None;
@@ -114,6 +197,41 @@ impl NodeMetadata {
         None
     }
 
+    /// Revokes the processing lease of the job processing this node if it has held it for longer
+    /// than `lease_timeout`, returning that job's reply channel.
+    ///
+    /// The node will be offered to the next job that wants it. The revoked job is not offered it
+    /// again, as it may still be processing it, but is told once the node has been processed.
+    pub fn revoke_expired_lease(&mut self, lease_timeout: Duration) -> Option<Subject> {
+        self.processing_reply_channel.as_ref()?;
+
+        match self.processing_started_at {
+            Some(processing_started_at) if processing_started_at.elapsed() >= lease_timeout => {
+                let revoked_reply_channel = self.processing_reply_channel.take()?;
+                self.processing_started_at = None;
+                self.revoked_leases.push(RevokedLease {
+                    reply_channel: revoked_reply_channel.clone(),
+                    processing_started_at: Some(processing_started_at),
+                });
+
+                Some(revoked_reply_channel)
+            }
+            Some(_) => None,
+            None => {
+                // Restored from persisted state: we don't know when processing started, so
+                // start the lease now.
+                self.processing_started_at = Some(Instant::now());
+                None
+            }
+        }
+    }
+
+    /// How long the job in `processing_reply_channel` has been processing this node, if known.
+    pub fn processing_duration(&self) -> Option<Duration> {
+        self.processing_started_at
+            .map(|processing_started_at| processing_started_at.elapsed())
+    }
+
     pub fn processing_reply_channel(&self) -> Option<&Subject> {
         self.processing_reply_channel.as_ref()
     }
@@ -123,6 +241,8 @@ impl NodeMetadata {
 
         self.wanted_by_reply_channels
             .retain(|el| el != reply_channel);
+        self.revoked_leases
+            .retain(|lease| lease.reply_channel != *reply_channel);
         self.processing_reply_channel = self
             .processing_reply_channel
             .take()
@@ -135,11 +255,24 @@ impl NodeMetadata {
         };
     }
 
+    /// Every job waiting to hear about this node, including those whose lease was revoked.
     pub fn wanted_by_reply_channels(&self) -> HashSet<String> {
-        HashSet::from_iter(self.wanted_by_reply_channels.iter().map(|s| s.to_string()))
+        HashSet::from_iter(self.waiting_reply_channels_iter().map(|s| s.to_string()))
     }
 
+    /// The jobs this node will be offered to, in order.
     pub fn wanted_by_reply_channels_iter(&self) -> Iter<'_, Subject> {
         self.wanted_by_reply_channels.iter()
     }
+
+    /// The jobs whose processing lease on this node was revoked.
+    pub fn revoked_reply_channels_iter(&self) -> impl Iterator<Item = &Subject> {
+        self.revoked_leases.iter().map(|lease| &lease.reply_channel)
+    }
+
+    /// Every job waiting to hear about this node, other than the one processing it.
+    pub fn waiting_reply_channels_iter(&self) -> impl Iterator<Item = &Subject> {
+        self.wanted_by_reply_channels_iter()
+            .chain(self.revoked_reply_channels_iter())
+    }
 }
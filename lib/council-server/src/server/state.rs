@@ -0,0 +1,90 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use telemetry::prelude::*;
+use thiserror::Error;
+use tokio::fs;
+
+use super::graph::ChangeSetGraph;
+
+#[remain::sorted]
+#[derive(Debug, Error)]
+pub enum StateStoreError {
+    #[error("failed to deserialize council state from {}: {}", .0.display(), .1)]
+    Deserialize(PathBuf, #[source] serde_json::Error),
+    #[error("failed to read council state from {}: {}", .0.display(), .1)]
+    Read(PathBuf, #[source] io::Error),
+    #[error("failed to serialize council state: {0}")]
+    Serialize(#[source] serde_json::Error),
+    #[error("failed to write council state to {}: {}", .0.display(), .1)]
+    Write(PathBuf, #[source] io::Error),
+}
+
+type Result<T> = std::result::Result<T, StateStoreError>;
+
+/// Persists the [`ChangeSetGraph`] to a local file so that council can rebuild its in-flight
+/// dependency graphs after a restart.
+///
+/// The whole graph is written as a single JSON document. Writes go to a sibling temporary file
+/// which is then renamed over the previous state, so a crash mid-write never leaves a truncated
+/// state file behind.
+#[derive(Clone, Debug)]
+pub struct StateStore {
+    path: PathBuf,
+    tmp_path: PathBuf,
+}
+
+impl StateStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let mut tmp_path = path.clone().into_os_string();
+        tmp_path.push(".tmp");
+
+        Self {
+            path,
+            tmp_path: tmp_path.into(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Loads the last persisted graph, returning an empty graph if nothing has been persisted yet.
+    pub async fn load(&self) -> Result<ChangeSetGraph> {
+        let bytes = match fs::read(&self.path).await {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                debug!(path = %self.path.display(), "no persisted council state found");
+                return Ok(ChangeSetGraph::default());
+            }
+            Err(err) => return Err(StateStoreError::Read(self.path.clone(), err)),
+        };
+
+        serde_json::from_slice(&bytes)
+            .map_err(|err| StateStoreError::Deserialize(self.path.clone(), err))
+    }
+
+    /// Atomically replaces the persisted state with the given graph.
+    pub async fn persist(&self, graph: &ChangeSetGraph) -> Result<()> {
+        let bytes = serde_json::to_vec(graph).map_err(StateStoreError::Serialize)?;
+
+        if let Some(parent) = self.path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)
+                    .await
+                    .map_err(|err| StateStoreError::Write(parent.to_path_buf(), err))?;
+            }
+        }
+        fs::write(&self.tmp_path, bytes)
+            .await
+            .map_err(|err| StateStoreError::Write(self.tmp_path.clone(), err))?;
+        fs::rename(&self.tmp_path, &self.path)
+            .await
+            .map_err(|err| StateStoreError::Write(self.path.clone(), err))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,701 @@
+//! Tests council running against a real NATS server: recovering when its connection or council
+//! itself goes away, sharding by change set, persisting its state across restarts and the
+//! introspection requests.
+//!
+//! Each test runs its own `nats-server` on a free local port so that it can be stopped and
+//! restarted underneath council and its clients. The binary is looked up on `$PATH`, or at
+//! `$SI_TEST_NATS_SERVER_PATH` if set.
+
+use std::{
+    collections::HashSet,
+    env,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+use council_server::{
+    introspection::{ChangeSetDump, IntrospectionResponse},
+    server::{config::StandardConfig, Config},
+    Client, Graph, Id, IntrospectionClient, Response, Server,
+};
+use si_data_nats::{NatsClient, NatsConfig};
+use test_log::test;
+use tokio::{
+    sync::watch,
+    task::JoinHandle,
+    time::{sleep, timeout, Instant},
+};
+use tracing::info;
+use ulid::Ulid;
+
+const TEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+struct NatsServer {
+    port: u16,
+    child: Child,
+}
+
+impl NatsServer {
+    async fn start() -> Self {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .expect("failed to find a free port")
+            .local_addr()
+            .expect("failed to read local address")
+            .port();
+        Self {
+            port,
+            child: Self::spawn(port).await,
+        }
+    }
+
+    async fn spawn(port: u16) -> Child {
+        #[allow(clippy::disallowed_methods)] // Used only in tests & so prefixed with `SI_TEST_`
+        let cmd = env::var("SI_TEST_NATS_SERVER_PATH").unwrap_or_else(|_| "nats-server".into());
+        let child = Command::new(&cmd)
+            .args(["--addr", "127.0.0.1", "--port", &port.to_string()])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap_or_else(|err| panic!("failed to spawn nats-server from {cmd:?}: {err}"));
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while tokio::net::TcpStream::connect(("127.0.0.1", port))
+            .await
+            .is_err()
+        {
+            assert!(Instant::now() < deadline, "nats-server never started");
+            sleep(Duration::from_millis(50)).await;
+        }
+        child
+    }
+
+    fn stop(&mut self) {
+        self.child.kill().expect("failed to kill nats-server");
+        self.child.wait().expect("failed to wait for nats-server");
+    }
+
+    async fn restart(&mut self) {
+        info!(port = self.port, "restarting nats-server");
+        self.stop();
+        self.child = Self::spawn(self.port).await;
+    }
+
+    fn config(&self, subject_prefix: &str) -> NatsConfig {
+        NatsConfig {
+            url: format!("nats://127.0.0.1:{}", self.port),
+            subject_prefix: Some(subject_prefix.to_owned()),
+            ..Default::default()
+        }
+    }
+}
+
+impl Drop for NatsServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn nats_prefix() -> String {
+    Ulid::new().to_string().to_lowercase()
+}
+
+struct RunningCouncil {
+    shutdown_tx: watch::Sender<()>,
+    handle: JoinHandle<council_server::server::Result<()>>,
+}
+
+impl RunningCouncil {
+    async fn start(nats_config: NatsConfig) -> Self {
+        Self::start_with_config(
+            Config::builder()
+                .nats(nats_config)
+                .processing_lease_timeout(Duration::from_secs(2))
+                .build()
+                .expect("failed to build config"),
+        )
+        .await
+    }
+
+    /// Starts a council instance owning a single shard of a council split into `shard_count`
+    /// shards.
+    async fn start_shard(nats_config: NatsConfig, shard_count: u32, shard: u32) -> Self {
+        Self::start_with_config(
+            Config::builder()
+                .nats(nats_config)
+                .processing_lease_timeout(Duration::from_secs(2))
+                .shard_count(shard_count)
+                .shards(vec![shard])
+                .build()
+                .expect("failed to build config"),
+        )
+        .await
+    }
+
+    /// Starts a council instance which persists its graph state to `state_path`.
+    async fn start_with_state(nats_config: NatsConfig, state_path: &Path) -> Self {
+        Self::start_with_config(
+            Config::builder()
+                .nats(nats_config)
+                .state_path(state_path)
+                .build()
+                .expect("failed to build config"),
+        )
+        .await
+    }
+
+    async fn start_with_config(config: Config) -> Self {
+        let server = Server::new_with_config(config)
+            .await
+            .expect("failed to create server");
+
+        let (subscriber_started_tx, mut subscriber_started_rx) = watch::channel(());
+        let (shutdown_tx, shutdown_rx) = watch::channel(());
+        let handle = tokio::spawn(server.run(subscriber_started_tx, shutdown_rx));
+        subscriber_started_rx
+            .changed()
+            .await
+            .expect("council never subscribed");
+
+        Self {
+            shutdown_tx,
+            handle,
+        }
+    }
+
+    async fn stop(self) {
+        self.shutdown_tx
+            .send(())
+            .expect("council went away before shutdown");
+        self.handle
+            .await
+            .expect("council task panicked")
+            .expect("council returned an error");
+    }
+}
+
+/// A council state file in the system temp directory, removed when dropped.
+struct StateFile(PathBuf);
+
+impl StateFile {
+    fn new() -> Self {
+        Self(env::temp_dir().join(format!("council-state-{}.json", Ulid::new())))
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for StateFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn council_subject_prefix(nats_config: &NatsConfig) -> String {
+    format!(
+        "{}.council",
+        nats_config.subject_prefix.as_deref().unwrap_or_default()
+    )
+}
+
+async fn nats_client(nats_config: &NatsConfig) -> NatsClient {
+    NatsClient::new(nats_config)
+        .await
+        .expect("failed to connect to NATS")
+}
+
+async fn client(nats_config: &NatsConfig) -> Client {
+    client_for_change_set(nats_config, Id::default()).await
+}
+
+async fn client_for_change_set(nats_config: &NatsConfig, change_set_id: Id) -> Client {
+    Client::new(
+        nats_client(nats_config).await,
+        &council_subject_prefix(nats_config),
+        Id::default(),
+        change_set_id,
+    )
+    .await
+    .expect("failed to create council client")
+}
+
+/// Acts like a pinga job: processes whatever council offers until every node in `graph` has been
+/// reported as processed.
+async fn process_graph(client: &mut Client, graph: &Graph) {
+    let mut remaining: HashSet<Id> = graph.keys().copied().collect();
+    while !remaining.is_empty() {
+        let response = client
+            .fetch_response()
+            .await
+            .expect("failed to fetch response")
+            .expect("council client subscription closed");
+        match response {
+            Response::OkToProcess { node_ids } => {
+                for node_id in node_ids {
+                    client
+                        .processed_value(node_id)
+                        .await
+                        .expect("failed to report processed value");
+                }
+            }
+            Response::BeenProcessed { node_id } => {
+                remaining.remove(&node_id);
+            }
+            Response::LeaseRevoked { .. } => {}
+            other => panic!("unexpected response from council: {other:?}"),
+        }
+    }
+}
+
+#[allow(clippy::disallowed_methods)] // `$RUST_LOG` is checked for in macro
+#[test(tokio::test)]
+async fn council_recovers_from_nats_restart() {
+    let mut nats_server = NatsServer::start().await;
+    let nats_config = nats_server.config(&nats_prefix());
+    let council = RunningCouncil::start(nats_config.clone()).await;
+
+    let (first, second) = (Id::default(), Id::default());
+    let graph = Graph::from([(first, vec![]), (second, vec![first])]);
+    let mut client = client(&nats_config).await;
+    client
+        .register_dependency_graph(graph.clone())
+        .await
+        .expect("failed to register graph");
+    let response = timeout(TEST_TIMEOUT, client.fetch_response())
+        .await
+        .expect("timed out waiting for council")
+        .expect("failed to fetch response");
+    assert!(
+        matches!(response, Some(Response::OkToProcess { ref node_ids }) if node_ids == &[first]),
+        "unexpected response: {response:?}"
+    );
+
+    // Anything published while the server is down is lost, so finishing the graph relies on
+    // council resubscribing and jobs re-sending their state.
+    nats_server.restart().await;
+    client
+        .processed_value(first)
+        .await
+        .expect("failed to report processed value");
+    timeout(TEST_TIMEOUT, process_graph(&mut client, &graph))
+        .await
+        .expect("timed out processing graph after nats restart");
+
+    council.stop().await;
+}
+
+#[allow(clippy::disallowed_methods)] // `$RUST_LOG` is checked for in macro
+#[test(tokio::test)]
+async fn jobs_reregister_when_council_restarts() {
+    let nats_server = NatsServer::start().await;
+    let nats_config = nats_server.config(&nats_prefix());
+    let council = RunningCouncil::start(nats_config.clone()).await;
+
+    let node = Id::default();
+    let graph = Graph::from([(node, vec![])]);
+    let mut client = client(&nats_config).await;
+    client
+        .register_dependency_graph(graph.clone())
+        .await
+        .expect("failed to register graph");
+    let response = timeout(TEST_TIMEOUT, client.fetch_response())
+        .await
+        .expect("timed out waiting for council")
+        .expect("failed to fetch response");
+    assert!(
+        matches!(response, Some(Response::OkToProcess { .. })),
+        "unexpected response: {response:?}"
+    );
+
+    // A council without persisted state forgets our graph entirely; its announcement on startup
+    // is what gets the graph back to it well before the client's own re-registration timeout.
+    council.stop().await;
+    let council = RunningCouncil::start(nats_config.clone()).await;
+
+    timeout(TEST_TIMEOUT, process_graph(&mut client, &graph))
+        .await
+        .expect("timed out processing graph after council restart");
+
+    council.stop().await;
+}
+
+#[allow(clippy::disallowed_methods)] // `$RUST_LOG` is checked for in macro
+#[test(tokio::test)]
+async fn graphs_are_routed_to_the_shard_owning_their_change_set() {
+    const SHARD_COUNT: u32 = 2;
+
+    let nats_server = NatsServer::start().await;
+    let nats_config = nats_server.config(&nats_prefix());
+    let councils = [
+        RunningCouncil::start_shard(nats_config.clone(), SHARD_COUNT, 0).await,
+        RunningCouncil::start_shard(nats_config.clone(), SHARD_COUNT, 1).await,
+    ];
+
+    let change_set_id = Id::default();
+    let owner = change_set_id.shard(SHARD_COUNT);
+    let node = Id::default();
+    let graph = Graph::from([(node, vec![])]);
+    let mut client = client_for_change_set(&nats_config, change_set_id).await;
+    client
+        .register_dependency_graph(graph.clone())
+        .await
+        .expect("failed to register graph");
+    let response = timeout(TEST_TIMEOUT, client.fetch_response())
+        .await
+        .expect("timed out waiting for council")
+        .expect("failed to fetch response");
+    assert!(
+        matches!(response, Some(Response::OkToProcess { ref node_ids }) if node_ids == &[node]),
+        "unexpected response: {response:?}"
+    );
+
+    // The node is now being processed, so only the owning shard holds the graph.
+    let nats = nats_client(&nats_config).await;
+    let subject_prefix = council_subject_prefix(&nats_config);
+    for shard in 0..SHARD_COUNT {
+        let change_sets = IntrospectionClient::for_shard(nats.clone(), &subject_prefix, shard)
+            .list_change_sets()
+            .await
+            .expect("failed to list change sets");
+        let change_set_ids: Vec<Id> = change_sets
+            .iter()
+            .map(|summary| summary.change_set_id)
+            .collect();
+        if shard == owner {
+            assert_eq!(vec![change_set_id], change_set_ids);
+        } else {
+            assert!(
+                change_set_ids.is_empty(),
+                "shard {shard} does not own {change_set_id} but has {change_set_ids:?}"
+            );
+        }
+    }
+
+    client
+        .processed_value(node)
+        .await
+        .expect("failed to report processed value");
+    timeout(TEST_TIMEOUT, process_graph(&mut client, &graph))
+        .await
+        .expect("timed out processing graph");
+
+    for council in councils {
+        council.stop().await;
+    }
+}
+
+/// A node's id, dependencies, waiting jobs and processing job.
+type PersistedNode = (Id, Vec<Id>, Vec<String>, Option<String>);
+
+/// The parts of a change set dump which survive a restart, as how long a node has been processed
+/// for isn't persisted.
+fn persisted_nodes(dump: ChangeSetDump) -> Vec<PersistedNode> {
+    let mut nodes: Vec<_> = dump
+        .nodes
+        .into_iter()
+        .map(|node| {
+            (
+                node.node_id,
+                node.depends_on,
+                node.wanted_by,
+                node.processing_by,
+            )
+        })
+        .collect();
+    nodes.sort_by_key(|(node_id, ..)| node_id.to_string());
+    nodes
+}
+
+#[allow(clippy::disallowed_methods)] // `$RUST_LOG` is checked for in macro
+#[test(tokio::test)]
+async fn council_state_round_trips_through_restart() {
+    let nats_server = NatsServer::start().await;
+    let nats_config = nats_server.config(&nats_prefix());
+    let state_file = StateFile::new();
+    let council = RunningCouncil::start_with_state(nats_config.clone(), state_file.path()).await;
+
+    let change_set_id = Id::default();
+    let (first, second) = (Id::default(), Id::default());
+    let mut client = client_for_change_set(&nats_config, change_set_id).await;
+    client
+        .register_dependency_graph(Graph::from([(first, vec![]), (second, vec![first])]))
+        .await
+        .expect("failed to register graph");
+    let response = timeout(TEST_TIMEOUT, client.fetch_response())
+        .await
+        .expect("timed out waiting for council")
+        .expect("failed to fetch response");
+    assert!(
+        matches!(response, Some(Response::OkToProcess { ref node_ids }) if node_ids == &[first]),
+        "unexpected response: {response:?}"
+    );
+
+    let introspection = IntrospectionClient::new(
+        nats_client(&nats_config).await,
+        &council_subject_prefix(&nats_config),
+    );
+    let before = introspection
+        .dump_change_set(change_set_id)
+        .await
+        .expect("failed to dump change set")
+        .expect("council has no graph for the change set");
+
+    council.stop().await;
+    assert!(
+        state_file.path().exists(),
+        "council never persisted its state"
+    );
+    let council = RunningCouncil::start_with_state(nats_config.clone(), state_file.path()).await;
+
+    let after = introspection
+        .dump_change_set(change_set_id)
+        .await
+        .expect("failed to dump change set")
+        .expect("council did not restore the change set");
+    assert_eq!(persisted_nodes(before), persisted_nodes(after));
+
+    council.stop().await;
+}
+
+#[allow(clippy::disallowed_methods)] // `$RUST_LOG` is checked for in macro
+#[test(tokio::test)]
+async fn council_resumes_restored_graph_after_restart() {
+    let nats_server = NatsServer::start().await;
+    let nats_config = nats_server.config(&nats_prefix());
+    let state_file = StateFile::new();
+    let council = RunningCouncil::start_with_state(nats_config.clone(), state_file.path()).await;
+
+    let (first, second) = (Id::default(), Id::default());
+    let graph = Graph::from([(first, vec![]), (second, vec![first])]);
+    let mut client = client(&nats_config).await;
+    client
+        .register_dependency_graph(graph.clone())
+        .await
+        .expect("failed to register graph");
+    let response = timeout(TEST_TIMEOUT, client.fetch_response())
+        .await
+        .expect("timed out waiting for council")
+        .expect("failed to fetch response");
+    assert!(
+        matches!(response, Some(Response::OkToProcess { ref node_ids }) if node_ids == &[first]),
+        "unexpected response: {response:?}"
+    );
+
+    council.stop().await;
+    let council = RunningCouncil::start_with_state(nats_config.clone(), state_file.path()).await;
+
+    // A council which restored its state still knows this job is processing `first`, so it
+    // accepts the report straight away rather than offering `first` again once the job
+    // re-registers.
+    client
+        .processed_value(first)
+        .await
+        .expect("failed to report processed value");
+    let response = timeout(TEST_TIMEOUT, client.fetch_response())
+        .await
+        .expect("timed out waiting for council")
+        .expect("failed to fetch response");
+    assert!(
+        matches!(response, Some(Response::BeenProcessed { node_id }) if node_id == first),
+        "unexpected response: {response:?}"
+    );
+    timeout(
+        TEST_TIMEOUT,
+        process_graph(&mut client, &Graph::from([(second, vec![])])),
+    )
+    .await
+    .expect("timed out processing graph after council restart");
+
+    council.stop().await;
+}
+
+#[allow(clippy::disallowed_methods)] // `$RUST_LOG` is checked for in macro
+#[test(tokio::test)]
+async fn introspection_lists_dumps_and_drains_change_sets() {
+    let nats_server = NatsServer::start().await;
+    let nats_config = nats_server.config(&nats_prefix());
+    let council = RunningCouncil::start(nats_config.clone()).await;
+    let introspection = IntrospectionClient::new(
+        nats_client(&nats_config).await,
+        &council_subject_prefix(&nats_config),
+    );
+
+    let change_set_id = Id::default();
+    let (first, second) = (Id::default(), Id::default());
+    let mut client = client_for_change_set(&nats_config, change_set_id).await;
+    client
+        .register_dependency_graph(Graph::from([(first, vec![]), (second, vec![first])]))
+        .await
+        .expect("failed to register graph");
+    let response = timeout(TEST_TIMEOUT, client.fetch_response())
+        .await
+        .expect("timed out waiting for council")
+        .expect("failed to fetch response");
+    assert!(
+        matches!(response, Some(Response::OkToProcess { ref node_ids }) if node_ids == &[first]),
+        "unexpected response: {response:?}"
+    );
+
+    let change_sets = introspection
+        .list_change_sets()
+        .await
+        .expect("failed to list change sets");
+    assert_eq!(
+        1,
+        change_sets.len(),
+        "unexpected change sets: {change_sets:?}"
+    );
+    let summary = &change_sets[0];
+    assert_eq!(change_set_id, summary.change_set_id);
+    assert_eq!(2, summary.node_count);
+    assert_eq!(1, summary.processing_count);
+    assert_eq!(1, summary.job_count);
+
+    let dump = introspection
+        .dump_change_set(change_set_id)
+        .await
+        .expect("failed to dump change set")
+        .expect("council has no graph for the change set");
+    assert_eq!(change_set_id, dump.change_set_id);
+    let first_node = dump
+        .nodes
+        .iter()
+        .find(|node| node.node_id == first)
+        .expect("dump is missing the first node");
+    assert!(first_node.depends_on.is_empty());
+    assert!(first_node.processing_by.is_some());
+    let second_node = dump
+        .nodes
+        .iter()
+        .find(|node| node.node_id == second)
+        .expect("dump is missing the second node");
+    assert_eq!(vec![first], second_node.depends_on);
+    assert_eq!(1, second_node.wanted_by.len());
+    assert!(second_node.processing_by.is_none());
+
+    let response = introspection
+        .drain_change_set(change_set_id)
+        .await
+        .expect("failed to drain change set");
+    assert!(
+        matches!(
+            response,
+            IntrospectionResponse::Drained {
+                change_set_id: drained,
+                node_count: 2,
+                notified_job_count: 1,
+            } if drained == change_set_id
+        ),
+        "unexpected drain response: {response:?}"
+    );
+
+    // The job is told every node it was waiting for or processing failed, so it can finish.
+    let mut failed = HashSet::new();
+    while failed.len() < 2 {
+        match timeout(TEST_TIMEOUT, client.fetch_response())
+            .await
+            .expect("timed out waiting for council")
+            .expect("failed to fetch response")
+        {
+            Some(Response::Failed { node_id }) => {
+                failed.insert(node_id);
+            }
+            other => panic!("unexpected response after drain: {other:?}"),
+        }
+    }
+    assert_eq!(HashSet::from([first, second]), failed);
+
+    assert!(introspection
+        .list_change_sets()
+        .await
+        .expect("failed to list change sets")
+        .is_empty());
+    assert!(introspection
+        .dump_change_set(change_set_id)
+        .await
+        .expect("failed to dump change set")
+        .is_none());
+    let response = introspection
+        .drain_change_set(change_set_id)
+        .await
+        .expect("failed to drain change set");
+    assert!(
+        matches!(response, IntrospectionResponse::UnknownChangeSet { change_set_id: unknown } if unknown == change_set_id),
+        "unexpected drain response: {response:?}"
+    );
+
+    council.stop().await;
+}
+
+#[allow(clippy::disallowed_methods)] // `$RUST_LOG` is checked for in macro
+#[test(tokio::test)]
+async fn late_reports_after_a_drain_are_ignored() {
+    let nats_server = NatsServer::start().await;
+    let nats_config = nats_server.config(&nats_prefix());
+    let council = RunningCouncil::start(nats_config.clone()).await;
+    let introspection = IntrospectionClient::new(
+        nats_client(&nats_config).await,
+        &council_subject_prefix(&nats_config),
+    );
+
+    let change_set_id = Id::default();
+    let (first, second) = (Id::default(), Id::default());
+    let mut client = client_for_change_set(&nats_config, change_set_id).await;
+    client
+        .register_dependency_graph(Graph::from([(first, vec![])]))
+        .await
+        .expect("failed to register graph");
+    let response = timeout(TEST_TIMEOUT, client.fetch_response())
+        .await
+        .expect("timed out waiting for council")
+        .expect("failed to fetch response");
+    assert!(
+        matches!(response, Some(Response::OkToProcess { ref node_ids }) if node_ids == &[first]),
+        "unexpected response: {response:?}"
+    );
+
+    introspection
+        .drain_change_set(change_set_id)
+        .await
+        .expect("failed to drain change set");
+    let response = timeout(TEST_TIMEOUT, client.fetch_response())
+        .await
+        .expect("timed out waiting for council")
+        .expect("failed to fetch response");
+    assert!(
+        matches!(response, Some(Response::Failed { node_id }) if node_id == first),
+        "unexpected response after drain: {response:?}"
+    );
+
+    // The job was still processing the node when the change set was drained, and reports on it
+    // once it is done.
+    client
+        .processed_value(first)
+        .await
+        .expect("failed to report processed value");
+    client
+        .clone_into_pub()
+        .failed_processing_value(first)
+        .await
+        .expect("failed to report failed value");
+
+    // Council is still around to process the change set's next graph, and the late reports
+    // didn't leave anything behind.
+    timeout(
+        TEST_TIMEOUT,
+        process_graph(&mut client, &Graph::from([(second, vec![])])),
+    )
+    .await
+    .expect("timed out processing graph after late reports");
+    assert!(introspection
+        .list_change_sets()
+        .await
+        .expect("failed to list change sets")
+        .is_empty());
+
+    council.stop().await;
+}
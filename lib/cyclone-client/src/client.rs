@@ -518,8 +518,9 @@ mod tests {
     use base64::{engine::general_purpose, Engine};
     use buck2_resources::Buck2Resources;
     use cyclone_core::{
-        ComponentKind, ComponentView, CycloneDecryptionKey, FunctionResult, ProgressMessage,
-        ResolverFunctionComponent, ValidationRequest,
+        ComponentKind, ComponentView, CycloneDecryptionKey, ExceededLimit, ExecutionLimits,
+        FunctionResult, ProgressMessage, ResolverFunctionComponent, ResolverFunctionResultSuccess,
+        ValidationRequest,
     };
     use cyclone_server::{Config, ConfigBuilder, Server, UdsIncomingStream};
     use futures::StreamExt;
@@ -531,6 +532,7 @@ mod tests {
     use tracing::warn;
 
     use super::*;
+    use crate::ExecutionError;
 
     fn gen_keys() -> (PublicKey, CycloneDecryptionKey) {
         let (pkey, skey) = sodiumoxide::crypto::box_::gen_keypair();
@@ -856,6 +858,8 @@ mod tests {
                 }"#,
             ),
             before: vec![],
+            limits: None,
+            runtime: None,
         };
 
         // Start the protocol
@@ -891,6 +895,7 @@ mod tests {
                     break;
                 }
                 Some(Ok(ProgressMessage::Heartbeat)) => continue,
+                Some(Ok(unexpected)) => panic!("unexpected msg kind: {unexpected:?}"),
                 Some(Err(err)) => panic!("failed to receive 'second' output: err={err:?}"),
                 None => panic!("output stream ended early"),
             };
@@ -958,6 +963,8 @@ mod tests {
                 }"#,
             ),
             before: vec![],
+            limits: None,
+            runtime: None,
         };
 
         // Start the protocol
@@ -993,6 +1000,7 @@ mod tests {
                     break;
                 }
                 Some(Ok(ProgressMessage::Heartbeat)) => continue,
+                Some(Ok(unexpected)) => panic!("unexpected msg kind: {unexpected:?}"),
                 Some(Err(err)) => panic!("failed to receive 'second' output: err={err:?}"),
                 None => panic!("output stream ended early"),
             };
@@ -1020,6 +1028,252 @@ mod tests {
         }
     }
 
+    #[allow(clippy::disallowed_methods)] // `$RUST_LOG` is checked for in macro
+    #[test(tokio::test)]
+    async fn uds_execute_resolver_cancelled() {
+        let (_, key) = gen_keys();
+        let tmp_socket = rand_uds();
+        let mut builder = Config::builder();
+        let mut client =
+            uds_client_for_running_server(builder.enable_resolver(true), &tmp_socket, key).await;
+
+        let req = ResolverFunctionRequest {
+            execution_id: "5678".to_string(),
+            handler: "doit".to_string(),
+            component: ResolverFunctionComponent {
+                data: ComponentView {
+                    properties: serde_json::json!({}),
+                    kind: ComponentKind::Standard,
+                },
+                parents: vec![],
+            },
+            response_type: cyclone_core::ResolverFunctionResponseType::Object,
+            code_base64: base64_encode(
+                r#"async function doit() {
+                    await new Promise((resolve) => setTimeout(resolve, 60000));
+                    return { a: 'b' };
+                }"#,
+            ),
+            before: vec![],
+            limits: None,
+            runtime: None,
+        };
+
+        let mut progress = client
+            .execute_resolver(req)
+            .await
+            .expect("failed to establish websocket stream")
+            .start()
+            .await
+            .expect("failed to start protocol");
+        progress
+            .cancel("5678")
+            .await
+            .expect("failed to send cancel request");
+
+        loop {
+            match progress.next().await {
+                None => break,
+                Some(Ok(ProgressMessage::Heartbeat | ProgressMessage::OutputStream(_))) => continue,
+                Some(unexpected) => panic!("unexpected progress message: {unexpected:?}"),
+            };
+        }
+        let result = progress.finish().await.expect("failed to return result");
+        match result {
+            FunctionResult::Success(success) => {
+                panic!("result should be failure; success={success:?}")
+            }
+            FunctionResult::Failure(failure) => {
+                assert!(failure.error.is_cancelled());
+                assert_eq!("5678", failure.execution_id);
+            }
+        }
+    }
+
+    /// Writes a lang server which reads the request and then runs `script`, standing in for the
+    /// lang server of another function runtime.
+    fn stub_lang_server(dir: &Path, script: &str) -> PathBuf {
+        let path = dir.join("lang-stub");
+        std::fs::write(&path, format!("#!/bin/sh\n{script}\n"))
+            .expect("failed to write stub lang server");
+        std::fs::set_permissions(&path, std::os::unix::fs::PermissionsExt::from_mode(0o755))
+            .expect("failed to make stub lang server executable");
+        path
+    }
+
+    /// Executes a resolver function for the `stub` runtime, run by a lang server running `script`,
+    /// and returns its result, or `None` if the execution finished without one.
+    async fn execute_resolver_with_stub_runtime(
+        script: &str,
+        limits: Option<ExecutionLimits>,
+    ) -> Option<FunctionResult<ResolverFunctionResultSuccess>> {
+        execute_resolver_with_stub_lang_server(&format!("read -r request\n{script}"), limits).await
+    }
+
+    /// Executes a resolver function for the `stub` runtime, run by a lang server made of `script`
+    /// alone, which must read the request itself.
+    async fn execute_resolver_with_stub_lang_server(
+        script: &str,
+        limits: Option<ExecutionLimits>,
+    ) -> Option<FunctionResult<ResolverFunctionResultSuccess>> {
+        let (_, key) = gen_keys();
+        let tmp_socket = rand_uds();
+        let lang_server_dir = tempfile::tempdir().expect("failed to create tempdir");
+        let mut builder = Config::builder();
+        builder
+            .enable_resolver(true)
+            .try_runtime_lang_server("stub", stub_lang_server(lang_server_dir.path(), script))
+            .expect("failed to resolve stub lang server path");
+        let mut client = uds_client_for_running_server(&mut builder, &tmp_socket, key).await;
+
+        let req = ResolverFunctionRequest {
+            execution_id: "4242".to_string(),
+            handler: "doit".to_string(),
+            component: ResolverFunctionComponent {
+                data: ComponentView {
+                    properties: serde_json::json!({}),
+                    kind: ComponentKind::Standard,
+                },
+                parents: vec![],
+            },
+            response_type: cyclone_core::ResolverFunctionResponseType::Object,
+            code_base64: base64_encode("print('not javascript')"),
+            before: vec![],
+            limits,
+            runtime: Some("stub".to_string()),
+        };
+
+        let mut progress = client
+            .execute_resolver(req)
+            .await
+            .expect("failed to establish websocket stream")
+            .start()
+            .await
+            .expect("failed to start protocol");
+        loop {
+            match progress.next().await {
+                None => break,
+                Some(Ok(ProgressMessage::Heartbeat)) => continue,
+                Some(Err(ExecutionError::FinishBeforeResult)) => return None,
+                Some(unexpected) => panic!("unexpected progress message: {unexpected:?}"),
+            };
+        }
+        Some(progress.finish().await.expect("failed to return result"))
+    }
+
+    fn assert_killed_for_exceeding(
+        result: Option<FunctionResult<ResolverFunctionResultSuccess>>,
+        exceeded: ExceededLimit,
+    ) {
+        match result.expect("execution finished without a result") {
+            FunctionResult::Success(success) => {
+                panic!("result should be failure; success={success:?}")
+            }
+            FunctionResult::Failure(failure) => {
+                assert!(failure.error.is_killed_for_exceeding_limits());
+                assert_eq!(
+                    format!("function was killed for exceeding its {exceeded} limit"),
+                    failure.error.message
+                );
+            }
+        }
+    }
+
+    #[allow(clippy::disallowed_methods)] // `$RUST_LOG` is checked for in macro
+    #[test(tokio::test)]
+    async fn uds_execute_resolver_with_runtime_lang_server() {
+        let result = execute_resolver_with_stub_runtime(
+            r#"echo '{"protocol":"result","status":"success","executionId":"4242","data":{"runtime":"stub"},"unset":false}'"#,
+            None,
+        )
+        .await;
+
+        match result.expect("execution finished without a result") {
+            FunctionResult::Success(success) => {
+                assert_eq!("4242", success.execution_id);
+                assert_eq!(json!({"runtime": "stub"}), success.data);
+            }
+            FunctionResult::Failure(failure) => {
+                panic!("result should be success; failure={failure:?}")
+            }
+        }
+    }
+
+    #[allow(clippy::disallowed_methods)] // `$RUST_LOG` is checked for in macro
+    #[test(tokio::test)]
+    async fn uds_execute_resolver_with_runtime_lang_server_reading_to_eof() {
+        // A lang server which hasn't opted into callbacks gets EOF right after the request
+        let result = execute_resolver_with_stub_lang_server(
+            r#"request=$(cat)
+case "$request" in
+  *'"executionId":"4242"'*) ;;
+  *) exit 1 ;;
+esac
+echo '{"protocol":"result","status":"success","executionId":"4242","data":{"read":"eof"},"unset":false}'"#,
+            Some(ExecutionLimits {
+                timeout_secs: Some(10),
+                ..Default::default()
+            }),
+        )
+        .await;
+
+        match result.expect("execution finished without a result") {
+            FunctionResult::Success(success) => {
+                assert_eq!("4242", success.execution_id);
+                assert_eq!(json!({"read": "eof"}), success.data);
+            }
+            FunctionResult::Failure(failure) => {
+                panic!("result should be success; failure={failure:?}")
+            }
+        }
+    }
+
+    #[allow(clippy::disallowed_methods)] // `$RUST_LOG` is checked for in macro
+    #[test(tokio::test)]
+    async fn uds_execute_resolver_killed_for_exceeding_timeout() {
+        let result = execute_resolver_with_stub_runtime(
+            "sleep 30",
+            Some(ExecutionLimits {
+                timeout_secs: Some(1),
+                ..Default::default()
+            }),
+        )
+        .await;
+
+        assert_killed_for_exceeding(result, ExceededLimit::Timeout);
+    }
+
+    #[allow(clippy::disallowed_methods)] // `$RUST_LOG` is checked for in macro
+    #[test(tokio::test)]
+    async fn uds_execute_resolver_killed_for_exceeding_cpu_time() {
+        // Ignoring `SIGXCPU` leaves the lang server to be killed by the hard limit's `SIGKILL`
+        let result = execute_resolver_with_stub_runtime(
+            "trap '' XCPU\nwhile :; do :; done",
+            Some(ExecutionLimits {
+                cpu_time_secs: Some(1),
+                ..Default::default()
+            }),
+        )
+        .await;
+
+        assert_killed_for_exceeding(result, ExceededLimit::CpuTime);
+    }
+
+    #[allow(clippy::disallowed_methods)] // `$RUST_LOG` is checked for in macro
+    #[test(tokio::test)]
+    async fn uds_execute_resolver_killed_within_cpu_time_is_not_a_limit() {
+        let result = execute_resolver_with_stub_runtime(
+            "kill -KILL $$",
+            Some(ExecutionLimits {
+                cpu_time_secs: Some(60),
+                ..Default::default()
+            }),
+        )
+        .await;
+
+        assert!(result.is_none(), "unexpected result: {result:?}");
+    }
+
     async fn execute_validation<C, Strm>(mut client: C)
     where
         Strm: AsyncRead + AsyncWrite + Connection + Unpin + Send + 'static,
@@ -1041,6 +1295,8 @@ mod tests {
                 }",
             ),
             before: vec![],
+            limits: None,
+            runtime: None,
         };
         let mut progress = client
             .execute_validation(req)
@@ -1060,6 +1316,7 @@ mod tests {
                     break;
                 }
                 Some(Ok(ProgressMessage::Heartbeat)) => continue,
+                Some(Ok(unexpected)) => panic!("unexpected msg kind: {unexpected:?}"),
                 Some(Err(err)) => panic!("failed to receive 'bubblegum' output: err={err:?}"),
                 None => panic!("output stream ended early"),
             };
@@ -1071,6 +1328,7 @@ mod tests {
                     break;
                 }
                 Some(Ok(ProgressMessage::Heartbeat)) => continue,
+                Some(Ok(unexpected)) => panic!("unexpected msg kind: {unexpected:?}"),
                 Some(Err(err)) => {
                     panic!("failed to receive 'all out of gum' output: err={err:?}")
                 }
@@ -1139,6 +1397,8 @@ mod tests {
                 }"#,
             ),
             before: vec![],
+            limits: None,
+            runtime: None,
         };
 
         // Start the protocol
@@ -1158,6 +1418,7 @@ mod tests {
                     break;
                 }
                 Some(Ok(ProgressMessage::Heartbeat)) => continue,
+                Some(Ok(unexpected)) => panic!("unexpected msg kind: {unexpected:?}"),
                 Some(Err(err)) => panic!("failed to receive 'first' output: err={err:?}"),
                 None => panic!("output stream ended early"),
             };
@@ -1169,6 +1430,7 @@ mod tests {
                     break;
                 }
                 Some(Ok(ProgressMessage::Heartbeat)) => continue,
+                Some(Ok(unexpected)) => panic!("unexpected msg kind: {unexpected:?}"),
                 Some(Err(err)) => panic!("failed to receive 'second' output: err={err:?}"),
                 None => panic!("output stream ended early"),
             }
@@ -1180,6 +1442,7 @@ mod tests {
                     break;
                 }
                 Some(Ok(ProgressMessage::Heartbeat)) => continue,
+                Some(Ok(unexpected)) => panic!("unexpected msg kind: {unexpected:?}"),
                 Some(Err(err)) => panic!("failed to receive Output: err={err:?}"),
                 None => panic!("output stream ended early"),
             };
@@ -1226,6 +1489,8 @@ mod tests {
                 }"#,
             ),
             before: vec![],
+            limits: None,
+            runtime: None,
         };
 
         // Start the protocol
@@ -1245,6 +1510,7 @@ mod tests {
                     break;
                 }
                 Some(Ok(ProgressMessage::Heartbeat)) => continue,
+                Some(Ok(unexpected)) => panic!("unexpected msg kind: {unexpected:?}"),
                 Some(Err(err)) => panic!("failed to receive 'first' output: err={err:?}"),
                 None => panic!("output stream ended early"),
             };
@@ -1256,6 +1522,7 @@ mod tests {
                     break;
                 }
                 Some(Ok(ProgressMessage::Heartbeat)) => continue,
+                Some(Ok(unexpected)) => panic!("unexpected msg kind: {unexpected:?}"),
                 Some(Err(err)) => panic!("failed to receive 'second' output: err={err:?}"),
                 None => panic!("output stream ended early"),
             };
@@ -1267,6 +1534,7 @@ mod tests {
                     break;
                 }
                 Some(Ok(ProgressMessage::Heartbeat)) => continue,
+                Some(Ok(unexpected)) => panic!("unexpected msg kind: {unexpected:?}"),
                 Some(Err(err)) => panic!("failed to receive 'second' output: err={err:?}"),
                 None => panic!("output stream ended early"),
             };
@@ -1313,6 +1581,8 @@ mod tests {
                 }"#,
             ),
             before: vec![],
+            limits: None,
+            runtime: None,
         };
 
         // Start the protocol
@@ -1332,6 +1602,7 @@ mod tests {
                     break;
                 }
                 Some(Ok(ProgressMessage::Heartbeat)) => continue,
+                Some(Ok(unexpected)) => panic!("unexpected msg kind: {unexpected:?}"),
                 Some(Err(err)) => panic!("failed to receive 'first' output: err={err:?}"),
                 None => panic!("output stream ended early"),
             };
@@ -1343,6 +1614,7 @@ mod tests {
                     break;
                 }
                 Some(Ok(ProgressMessage::Heartbeat)) => continue,
+                Some(Ok(unexpected)) => panic!("unexpected msg kind: {unexpected:?}"),
                 Some(Err(err)) => panic!("failed to receive 'second' output: err={err:?}"),
                 None => panic!("output stream ended early"),
             }
@@ -1390,6 +1662,8 @@ mod tests {
                 }"#,
             ),
             before: vec![],
+            limits: None,
+            runtime: None,
         };
 
         // Start the protocol
@@ -1409,6 +1683,7 @@ mod tests {
                     break;
                 }
                 Some(Ok(ProgressMessage::Heartbeat)) => continue,
+                Some(Ok(unexpected)) => panic!("unexpected msg kind: {unexpected:?}"),
                 Some(Err(err)) => panic!("failed to receive 'first' output: err={err:?}"),
                 None => panic!("output stream ended early"),
             };
@@ -1420,6 +1695,7 @@ mod tests {
                     break;
                 }
                 Some(Ok(ProgressMessage::Heartbeat)) => continue,
+                Some(Ok(unexpected)) => panic!("unexpected msg kind: {unexpected:?}"),
                 Some(Err(err)) => panic!("failed to receive 'second' output: err={err:?}"),
                 None => panic!("output stream ended early"),
             };
@@ -1469,6 +1745,8 @@ mod tests {
                     return new AssetBuilder().build();
                 }"#,
             ),
+            limits: None,
+            runtime: None,
         };
 
         // Start the protocol
@@ -1488,6 +1766,7 @@ mod tests {
                     break;
                 }
                 Some(Ok(ProgressMessage::Heartbeat)) => continue,
+                Some(Ok(unexpected)) => panic!("unexpected msg kind: {unexpected:?}"),
                 Some(Err(err)) => panic!("failed to receive 'first' output: err={err:?}"),
                 None => panic!("output stream ended early"),
             };
@@ -1499,6 +1778,7 @@ mod tests {
                     break;
                 }
                 Some(Ok(ProgressMessage::Heartbeat)) => continue,
+                Some(Ok(unexpected)) => panic!("unexpected msg kind: {unexpected:?}"),
                 Some(Err(err)) => panic!("failed to receive 'second' output: err={err:?}"),
                 None => panic!("output stream ended early"),
             };
@@ -1548,6 +1828,8 @@ mod tests {
                     return new AssetBuilder().build();
                 }"#,
             ),
+            limits: None,
+            runtime: None,
         };
 
         // Start the protocol
@@ -1567,6 +1849,7 @@ mod tests {
                     break;
                 }
                 Some(Ok(ProgressMessage::Heartbeat)) => continue,
+                Some(Ok(unexpected)) => panic!("unexpected msg kind: {unexpected:?}"),
                 Some(Err(err)) => panic!("failed to receive 'first' output: err={err:?}"),
                 None => panic!("output stream ended early"),
             };
@@ -1578,6 +1861,7 @@ mod tests {
                     break;
                 }
                 Some(Ok(ProgressMessage::Heartbeat)) => continue,
+                Some(Ok(unexpected)) => panic!("unexpected msg kind: {unexpected:?}"),
                 Some(Err(err)) => panic!("failed to receive 'second' output: err={err:?}"),
                 None => panic!("output stream ended early"),
             };
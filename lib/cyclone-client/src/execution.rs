@@ -4,7 +4,10 @@ use std::{
     task::{Context, Poll},
 };
 
-use cyclone_core::{FunctionResult, Message, ProgressMessage};
+use cyclone_core::{
+    CallbackResponse, CallerMessage, CancelExecutionRequest, FunctionResult, Message,
+    ProgressMessage,
+};
 use futures::{Future, SinkExt, Stream, StreamExt};
 use hyper::client::connect::Connection;
 use serde::{de::DeserializeOwned, Serialize};
@@ -117,6 +120,40 @@ where
     pub async fn finish(self) -> Result<FunctionResult<Success>, ExecutionError<Success>> {
         ExecutionClosing::try_from(self)?.finish().await
     }
+
+    /// Answers a [`ProgressMessage::CallbackRequest`] made by the executing function.
+    ///
+    /// The function waits on the response, so every callback request should be answered, with a
+    /// failure if the data can't be provided.
+    pub async fn respond(
+        &mut self,
+        response: &CallbackResponse,
+    ) -> Result<(), ExecutionError<Success>> {
+        let msg = serde_json::to_string(response).map_err(ExecutionError::JSONSerialize)?;
+        self.stream
+            .send(WebSocketMessage::Text(msg))
+            .await
+            .map_err(ExecutionError::WSSendIO)
+    }
+
+    /// Asks cyclone to stop the execution, killing the executing function.
+    ///
+    /// The execution still runs to its finish, with a cancelled
+    /// [`FunctionResultFailure`](cyclone_core::FunctionResultFailure) as its result unless the
+    /// function produced its own result first.
+    pub async fn cancel(
+        &mut self,
+        execution_id: impl Into<String>,
+    ) -> Result<(), ExecutionError<Success>> {
+        let msg = serde_json::to_string(&CallerMessage::Cancel(CancelExecutionRequest {
+            execution_id: execution_id.into(),
+        }))
+        .map_err(ExecutionError::JSONSerialize)?;
+        self.stream
+            .send(WebSocketMessage::Text(msg))
+            .await
+            .map_err(ExecutionError::WSSendIO)
+    }
 }
 
 impl<T, Success> Stream for ExecutionStarted<T, Success>
@@ -133,6 +170,10 @@ where
                 let msg = Message::deserialize_from_str(&json_str)
                     .map_err(ExecutionError::JSONDeserialize)?;
                 match msg {
+                    // The function is asking for data, pass it on to be answered
+                    Message::CallbackRequest(request) => {
+                        Poll::Ready(Some(Ok(ProgressMessage::CallbackRequest(request))))
+                    }
                     // We got a heartbeat message, pass it on
                     Message::Heartbeat => Poll::Ready(Some(Ok(ProgressMessage::Heartbeat))),
                     // We got an output message, pass it on
@@ -5,13 +5,13 @@ mod watch;
 
 pub use client::{Client, ClientConfig, ClientError, CycloneClient, HttpClient, UdsClient};
 pub use cyclone_core::{
-    ActionRunRequest, ActionRunResultSuccess, CycloneEncryptionKey, CycloneEncryptionKeyError,
-    LivenessStatus, LivenessStatusParseError, ReadinessStatus, ReadinessStatusParseError,
-    ReconciliationRequest, ReconciliationResultSuccess, ResolverFunctionRequest,
-    ResolverFunctionResultSuccess, SchemaVariantDefinitionRequest,
+    ActionRunRequest, ActionRunResultSuccess, CallbackRequest, CallbackResponse,
+    CycloneEncryptionKey, CycloneEncryptionKeyError, LivenessStatus, LivenessStatusParseError,
+    ReadinessStatus, ReadinessStatusParseError, ReconciliationRequest, ReconciliationResultSuccess,
+    ResolverFunctionRequest, ResolverFunctionResultSuccess, SchemaVariantDefinitionRequest,
     SchemaVariantDefinitionResultSuccess,
 };
-pub use execution::{Execution, ExecutionError};
+pub use execution::{Execution, ExecutionError, ExecutionStarted};
 pub use hyper::client::connect::Connection;
 pub use hyperlocal::UnixStream;
 pub use ping::{PingExecution, PingExecutionError};
@@ -1,4 +1,4 @@
-use crate::BeforeFunction;
+use crate::{BeforeFunction, ExecutionLimits};
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -9,6 +9,12 @@ pub struct ActionRunRequest {
     pub code_base64: String,
     pub args: serde_json::Value,
     pub before: Vec<BeforeFunction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limits: Option<ExecutionLimits>,
+    /// The runtime the function is written for, selecting the lang server which executes it. The
+    /// default lang server is used when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<String>,
 }
 
 #[remain::sorted]
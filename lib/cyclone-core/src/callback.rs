@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A request, made by a function while it is executing, for data only the caller can provide
+/// (e.g. a decrypted secret or the view of another component).
+///
+/// The caller answers with a [`CallbackResponse`] carrying the same `id` over the same connection
+/// the execution is running on.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallbackRequest {
+    pub execution_id: String,
+    /// Identifies the request within its execution, so that its response can be matched to it.
+    pub id: u64,
+    /// What the function is asking for.
+    ///
+    /// Currently free-form and interpreted by the caller.
+    pub kind: String,
+    pub payload: Value,
+}
+
+/// The caller's answer to a [`CallbackRequest`].
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallbackResponse {
+    /// The `id` of the [`CallbackRequest`] being answered.
+    pub id: u64,
+    #[serde(flatten)]
+    pub result: CallbackResult,
+}
+
+impl CallbackResponse {
+    pub fn success(id: u64, data: Value) -> Self {
+        Self {
+            id,
+            result: CallbackResult::Success { data },
+        }
+    }
+
+    pub fn failure(id: u64, message: impl Into<String>) -> Self {
+        Self {
+            id,
+            result: CallbackResult::Failure {
+                message: message.into(),
+            },
+        }
+    }
+}
+
+#[remain::sorted]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum CallbackResult {
+    Failure {
+        message: String,
+    },
+    /// The requested data, which may contain encrypted values that cyclone decrypts before
+    /// handing it to the function.
+    Success {
+        data: Value,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn response_round_trips_with_flattened_status() {
+        let response = CallbackResponse::success(3, json!({ "name": "starfield" }));
+        let value = serde_json::to_value(&response).expect("failed to serialize");
+        assert_eq!(
+            json!({ "id": 3, "status": "success", "data": { "name": "starfield" } }),
+            value
+        );
+        assert_eq!(
+            response,
+            serde_json::from_value(value).expect("failed to deserialize")
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+use crate::CallbackResponse;
+
+/// Asks cyclone to stop an execution which is still running, killing its function.
+///
+/// The caller sends this over the same connection the execution is running on, and cyclone answers
+/// with a [`cancelled`](crate::FunctionResultFailure::cancelled) result.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelExecutionRequest {
+    pub execution_id: String,
+}
+
+/// A message from the caller of an execution, sent while the execution is running.
+#[remain::sorted]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum CallerMessage {
+    CallbackResponse(CallbackResponse),
+    Cancel(CancelExecutionRequest),
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn tells_cancel_requests_and_callback_responses_apart() {
+        let cancel: CallerMessage = serde_json::from_value(json!({ "executionId": "ex-1" }))
+            .expect("failed to deserialize");
+        assert_eq!(
+            CallerMessage::Cancel(CancelExecutionRequest {
+                execution_id: "ex-1".to_string()
+            }),
+            cancel
+        );
+
+        let response: CallerMessage =
+            serde_json::from_value(json!({ "id": 2, "status": "failure", "message": "nope" }))
+                .expect("failed to deserialize");
+        assert_eq!(
+            CallerMessage::CallbackResponse(CallbackResponse::failure(2, "nope")),
+            response
+        );
+    }
+}
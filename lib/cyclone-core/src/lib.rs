@@ -13,9 +13,12 @@
 
 mod action_run;
 mod before;
+mod callback;
+mod cancel;
 mod canonical_command;
 mod component_view;
 mod crypto;
+mod limits;
 mod liveness;
 pub mod process;
 mod progress;
@@ -33,12 +36,15 @@ pub use si_crypto::{
 
 pub use action_run::{ActionRunRequest, ActionRunResultSuccess, ResourceStatus};
 pub use before::BeforeFunction;
+pub use callback::{CallbackRequest, CallbackResponse, CallbackResult};
+pub use cancel::{CallerMessage, CancelExecutionRequest};
 pub use canonical_command::{CanonicalCommand, CanonicalCommandError};
 pub use component_view::{ComponentKind, ComponentView};
 pub use crypto::{
     decrypt_value_tree, encrypt_value_tree, CycloneSensitiveStrings, CycloneValueDecryptError,
     CycloneValueEncryptError,
 };
+pub use limits::{ExceededLimit, ExecutionLimits};
 pub use liveness::{LivenessStatus, LivenessStatusParseError};
 pub use progress::{
     FunctionResult, FunctionResultFailure, FunctionResultFailureError, Message, OutputStream,
@@ -0,0 +1,52 @@
+use std::{fmt, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+/// Resource ceilings enforced by cyclone on a single function execution.
+///
+/// Every limit is optional; an execution with no limits set runs exactly as it would without an
+/// `ExecutionLimits` at all.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionLimits {
+    /// The CPU time, in seconds, the lang server process may consume.
+    pub cpu_time_secs: Option<u64>,
+    /// The memory, in bytes, the lang server process may use.
+    pub memory_bytes: Option<u64>,
+    /// The wall-clock time, in seconds, the execution may take before it is killed.
+    pub timeout_secs: Option<u64>,
+}
+
+impl ExecutionLimits {
+    /// Returns `true` if no limit is set.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.cpu_time_secs.is_none() && self.memory_bytes.is_none() && self.timeout_secs.is_none()
+    }
+
+    /// Returns the wall-clock timeout, if one is set.
+    #[must_use]
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout_secs.map(Duration::from_secs)
+    }
+}
+
+/// The [`ExecutionLimits`] limit which an execution was killed for exceeding.
+#[remain::sorted]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExceededLimit {
+    CpuTime,
+    Memory,
+    Timeout,
+}
+
+impl fmt::Display for ExceededLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::CpuTime => "cpu time",
+            Self::Memory => "memory",
+            Self::Timeout => "wall-clock timeout",
+        })
+    }
+}
@@ -1,5 +1,7 @@
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
+use crate::{CallbackRequest, ExceededLimit};
+
 /// A line of output, streamed from an executing function.
 ///
 /// An instance of this type typically maps to a single line of output from a process--either on
@@ -42,6 +44,9 @@ pub struct OutputStream {
 #[remain::sorted]
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum ProgressMessage {
+    /// A request from the executing function for data from the caller, which must be answered
+    /// for the function to continue.
+    CallbackRequest(CallbackRequest),
     /// A heartbeat message.
     ///
     /// This message can be used to signal "execution presence" (that is, the producer of such
@@ -54,6 +59,7 @@ pub enum ProgressMessage {
 #[remain::sorted]
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum Message<R> {
+    CallbackRequest(CallbackRequest),
     Fail(Fail),
     Finish,
     Heartbeat,
@@ -104,12 +110,64 @@ pub struct FunctionResultFailure {
     pub timestamp: u64,
 }
 
+impl FunctionResultFailure {
+    /// Builds the failure reported when cyclone kills an execution for exceeding one of its
+    /// [`ExecutionLimits`](crate::ExecutionLimits).
+    pub fn killed_for_exceeding_limits(
+        execution_id: impl Into<String>,
+        exceeded: ExceededLimit,
+        timestamp: u64,
+    ) -> Self {
+        Self {
+            execution_id: execution_id.into(),
+            error: FunctionResultFailureError {
+                kind: FunctionResultFailureError::KILLED_FOR_EXCEEDING_LIMITS_KIND.to_owned(),
+                message: format!("function was killed for exceeding its {exceeded} limit"),
+            },
+            timestamp,
+        }
+    }
+
+    /// Builds the failure reported when an execution is stopped because its caller cancelled it.
+    pub fn cancelled(execution_id: impl Into<String>, timestamp: u64) -> Self {
+        Self {
+            execution_id: execution_id.into(),
+            error: FunctionResultFailureError {
+                kind: FunctionResultFailureError::CANCELLED_KIND.to_owned(),
+                message: "function execution was cancelled".to_owned(),
+            },
+            timestamp,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize, Clone)]
 pub struct FunctionResultFailureError {
     pub kind: String,
     pub message: String,
 }
 
+impl FunctionResultFailureError {
+    /// The `kind` of a failure caused by the caller cancelling the execution.
+    pub const CANCELLED_KIND: &'static str = "cancelled";
+
+    /// The `kind` of a failure caused by cyclone killing the execution for exceeding its limits,
+    /// as opposed to the function itself failing.
+    pub const KILLED_FOR_EXCEEDING_LIMITS_KIND: &'static str = "killedForExceedingLimits";
+
+    /// Returns `true` if the execution was killed for exceeding its limits.
+    #[must_use]
+    pub fn is_killed_for_exceeding_limits(&self) -> bool {
+        self.kind == Self::KILLED_FOR_EXCEEDING_LIMITS_KIND
+    }
+
+    /// Returns `true` if the execution was cancelled by its caller.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.kind == Self::CANCELLED_KIND
+    }
+}
+
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Fail {
     pub message: String,
@@ -1,4 +1,4 @@
-use crate::BeforeFunction;
+use crate::{BeforeFunction, ExecutionLimits};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -10,6 +10,12 @@ pub struct ReconciliationRequest {
     pub code_base64: String,
     pub args: serde_json::Value,
     pub before: Vec<BeforeFunction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limits: Option<ExecutionLimits>,
+    /// The runtime the function is written for, selecting the lang server which executes it. The
+    /// default lang server is used when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -1,4 +1,5 @@
 use std::{
+    collections::{HashMap, HashSet},
     net::{SocketAddr, ToSocketAddrs},
     path::{Path, PathBuf},
     time::Duration,
@@ -52,8 +53,20 @@ pub struct Config {
     #[builder(try_setter, setter(into))]
     lang_server_path: CanonicalFile,
 
+    #[builder(default = "true")]
+    lang_server_callbacks: bool,
+
+    #[builder(setter(custom), default)]
+    runtime_lang_servers: HashMap<String, CanonicalFile>,
+
+    #[builder(setter(custom), default)]
+    runtime_lang_server_callbacks: HashSet<String>,
+
     #[builder(setter(into), default)]
     limit_requests: Option<u32>,
+
+    #[builder(setter(into), default)]
+    limits_cgroup_root: Option<PathBuf>,
 }
 
 impl Config {
@@ -117,14 +130,73 @@ impl Config {
         self.lang_server_path.as_path()
     }
 
+    /// Gets whether the default lang server answers callback requests.
+    #[must_use]
+    pub fn lang_server_callbacks(&self) -> bool {
+        self.lang_server_callbacks
+    }
+
+    /// Gets the lang servers registered for specific function runtimes, which execute requests
+    /// naming that runtime in place of the default lang server, along with whether each answers
+    /// callback requests.
+    pub fn runtime_lang_servers(&self) -> impl Iterator<Item = (&str, &Path, bool)> {
+        self.runtime_lang_servers.iter().map(|(runtime, path)| {
+            (
+                runtime.as_str(),
+                path.as_path(),
+                self.runtime_lang_server_callbacks.contains(runtime),
+            )
+        })
+    }
+
     /// Gets a reference to the config's limit requests.
     #[must_use]
     pub fn limit_requests(&self) -> Option<u32> {
         self.limit_requests
     }
+
+    /// Gets a reference to the config's limits cgroup root, a delegated cgroup v2 directory under
+    /// which a cgroup is created per execution to enforce memory limits.
+    #[must_use]
+    pub fn limits_cgroup_root(&self) -> Option<&Path> {
+        self.limits_cgroup_root.as_deref()
+    }
 }
 
 impl ConfigBuilder {
+    /// Registers the lang server which executes functions written for `runtime`.
+    ///
+    /// The lang server is invoked and spoken to just like the default lang server: it is run with
+    /// the kind of function as its only argument, reads the request as a line of JSON on stdin and
+    /// writes its output and result as lines of JSON on stdout. Its stdin is closed after the
+    /// request, so it may also read the request up to EOF, unless it opts into callbacks with
+    /// [`runtime_lang_server_callbacks`](Self::runtime_lang_server_callbacks).
+    pub fn try_runtime_lang_server<P>(
+        &mut self,
+        runtime: impl Into<String>,
+        path: P,
+    ) -> Result<&mut Self>
+    where
+        P: TryInto<CanonicalFile, Error = CanonicalFileError>,
+    {
+        self.runtime_lang_servers
+            .get_or_insert_with(HashMap::new)
+            .insert(runtime.into(), path.try_into()?);
+        Ok(self)
+    }
+
+    /// Marks the lang server of `runtime` as answering callback requests.
+    ///
+    /// Such a lang server writes callback requests as lines of JSON on stdout and reads each
+    /// response as a line of JSON on stdin after the request, so its stdin stays open until it has
+    /// written its result. It must not wait for EOF before running the function.
+    pub fn runtime_lang_server_callbacks(&mut self, runtime: impl Into<String>) -> &mut Self {
+        self.runtime_lang_server_callbacks
+            .get_or_insert_with(HashSet::new)
+            .insert(runtime.into());
+        self
+    }
+
     pub fn http_socket(&mut self, socket_addrs: impl ToSocketAddrs) -> Result<&mut Self> {
         Ok(self.incoming_stream(IncomingStream::http_socket(socket_addrs)?))
     }
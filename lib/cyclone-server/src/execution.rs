@@ -4,17 +4,22 @@ use std::{
     path::PathBuf,
     process::Stdio,
     string::FromUtf8Error,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, PoisonError, RwLock, RwLockReadGuard,
+    },
     time::Duration,
 };
 
 use axum::extract::ws::WebSocket;
 use bytes_lines_codec::BytesLinesCodec;
 use cyclone_core::{
+    decrypt_value_tree,
     process::{self, ShutdownError},
-    CycloneDecryptionKey, CycloneDecryptionKeyError, CycloneSensitiveStrings,
-    CycloneValueDecryptError, FunctionResult, FunctionResultFailure, FunctionResultFailureError,
-    Message, OutputStream,
+    CallbackRequest, CallbackResponse, CallbackResult, CallerMessage, CycloneDecryptionKey,
+    CycloneDecryptionKeyError, CycloneSensitiveStrings, CycloneValueDecryptError, ExceededLimit,
+    ExecutionLimits, FunctionResult, FunctionResultFailure, FunctionResultFailureError, Message,
+    OutputStream,
 };
 use futures::{SinkExt, StreamExt, TryStreamExt};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
@@ -28,21 +33,29 @@ use tokio::{
 use tokio_serde::{formats::SymmetricalJson, Deserializer, Framed, SymmetricallyFramed};
 use tokio_util::codec::{Decoder, FramedRead, FramedWrite};
 
-use crate::{request::DecryptRequest, WebSocketMessage};
+use crate::{
+    limits::{self, ExecutionCgroup},
+    request::{DecryptRequest, ExecutionRequest},
+    state::LangServers,
+    WebSocketMessage,
+};
 
 const TX_TIMEOUT_SECS: Duration = Duration::from_secs(5);
+const CHILD_EXIT_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub fn new<Request, LangServerSuccess, Success>(
-    lang_server_path: impl Into<PathBuf>,
+    lang_servers: LangServers,
     lang_server_debugging: bool,
     key: Arc<CycloneDecryptionKey>,
     command: String,
+    limits_cgroup_root: Option<PathBuf>,
 ) -> Execution<Request, LangServerSuccess, Success> {
     Execution {
-        lang_server_path: lang_server_path.into(),
+        lang_servers,
         lang_server_debugging,
         key,
         command,
+        limits_cgroup_root,
         request_marker: PhantomData,
         lang_server_success_marker: PhantomData,
         success_marker: PhantomData,
@@ -52,6 +65,8 @@ pub fn new<Request, LangServerSuccess, Success>(
 #[remain::sorted]
 #[derive(Debug, Error)]
 pub enum ExecutionError {
+    #[error("failed to set up execution cgroup")]
+    Cgroup(#[source] io::Error),
     #[error("failed to consume the {0} stream for the child process")]
     ChildIO(&'static str),
     #[error("failed to receive child process message")]
@@ -76,6 +91,8 @@ pub enum ExecutionError {
     SendTimeout(#[source] tokio::time::error::Elapsed),
     #[error("unexpected websocket message type: {0:?}")]
     UnexpectedMessageType(WebSocketMessage),
+    #[error("no lang server is registered for function runtime: {0}")]
+    UnsupportedRuntime(String),
     #[error("failed to close websocket")]
     WSClose(#[source] axum::Error),
     #[error("failed to receive websocket message--stream is closed")]
@@ -90,10 +107,11 @@ type Result<T> = std::result::Result<T, ExecutionError>;
 
 #[derive(Debug)]
 pub struct Execution<Request, LangServerSuccess, Success> {
-    lang_server_path: PathBuf,
+    lang_servers: LangServers,
     lang_server_debugging: bool,
     key: Arc<CycloneDecryptionKey>,
     command: String,
+    limits_cgroup_root: Option<PathBuf>,
     request_marker: PhantomData<Request>,
     lang_server_success_marker: PhantomData<LangServerSuccess>,
     success_marker: PhantomData<Success>,
@@ -101,7 +119,8 @@ pub struct Execution<Request, LangServerSuccess, Success> {
 
 impl<Request, LangServerSuccess, Success> Execution<Request, LangServerSuccess, Success>
 where
-    Request: DecryptRequest + Serialize + DeserializeOwned + Unpin + core::fmt::Debug,
+    Request:
+        DecryptRequest + ExecutionRequest + Serialize + DeserializeOwned + Unpin + core::fmt::Debug,
     LangServerSuccess: DeserializeOwned,
     Success: Serialize,
 {
@@ -117,9 +136,26 @@ where
         // Decrypt the relevant contents of the request and track any resulting sensitive strings
         // to be redacted
         request.decrypt(&mut sensitive_strings, &self.key)?;
+        let execution_id = request.execution_id().to_owned();
+        let lang_server = self
+            .lang_servers
+            .for_runtime(request.runtime())
+            .ok_or_else(|| {
+                ExecutionError::UnsupportedRuntime(request.runtime().unwrap_or_default().to_owned())
+            })?;
+        let lang_server_path = lang_server.path().to_path_buf();
+        let lang_server_callbacks = lang_server.callbacks();
+        let limits = request.limits().filter(|limits| !limits.is_empty());
+        let limits_cgroup_root = match limits {
+            Some(ExecutionLimits {
+                memory_bytes: Some(_),
+                ..
+            }) => self.limits_cgroup_root.as_deref(),
+            _ => None,
+        };
 
         // Spawn lang server as a child process with handles on all i/o descriptors
-        let mut command = Command::new(&self.lang_server_path);
+        let mut command = Command::new(&lang_server_path);
         command
             .arg(&self.command)
             .stdin(Stdio::piped())
@@ -128,13 +164,32 @@ where
         if self.lang_server_debugging {
             command.env("SI_LANG_JS_LOG", "*");
         }
-        debug!(cmd = ?command, "spawning child process");
+        if let Some(limits) = limits {
+            limits::apply_rlimits(&mut command, limits, limits_cgroup_root.is_some());
+        }
+        debug!(cmd = ?command, ?limits, runtime = ?request.runtime(), "spawning child process");
         let mut child = command
             .spawn()
-            .map_err(|err| ExecutionError::ChildSpawn(err, self.lang_server_path.clone()))?;
+            .map_err(|err| ExecutionError::ChildSpawn(err, lang_server_path))?;
+
+        // The lang server doesn't run the function until it reads the request below, so moving
+        // it into its cgroup here still applies the limits to all of the function's work.
+        let cgroup = match (limits, limits_cgroup_root, child.id()) {
+            (Some(limits), Some(root), Some(pid)) => {
+                match ExecutionCgroup::create(root, pid, limits).await {
+                    Ok(cgroup) => Some(cgroup),
+                    Err(err) => {
+                        let _ = child.start_kill();
+                        return Err(ExecutionError::Cgroup(err));
+                    }
+                }
+            }
+            _ => None,
+        };
 
         let stdin = child.stdin.take().ok_or(ExecutionError::ChildIO("stdin"))?;
-        Self::child_send_function_request(stdin, request).await?;
+        let stdin =
+            Self::child_send_function_request(stdin, request, lang_server_callbacks).await?;
 
         let stderr = {
             let stderr = child
@@ -155,9 +210,14 @@ where
 
         Ok(ExecutionStarted {
             child,
+            stdin,
             stdout,
             stderr,
-            sensitive_strings: Arc::new(sensitive_strings),
+            key: self.key,
+            sensitive_strings: Arc::new(RwLock::new(sensitive_strings)),
+            execution_id,
+            limits,
+            cgroup,
             success_marker: self.success_marker,
         })
     }
@@ -186,7 +246,16 @@ where
         Ok(())
     }
 
-    async fn child_send_function_request(stdin: ChildStdin, request: Request) -> Result<()> {
+    /// Sends the request to the lang server as a single line of JSON.
+    ///
+    /// For a lang server answering callbacks, its stdin is returned so that responses to any
+    /// [`CallbackRequest`]s the function makes can follow the request, a line each. Otherwise its
+    /// stdin is closed, as it may be reading the request up to EOF.
+    async fn child_send_function_request(
+        stdin: ChildStdin,
+        request: Request,
+        callbacks: bool,
+    ) -> Result<Option<SiFramedWrite>> {
         let value = serde_json::to_value(&request).map_err(ExecutionError::JSONSerialize)?;
 
         let codec = FramedWrite::new(stdin, BytesLinesCodec::new());
@@ -196,15 +265,14 @@ where
             .await
             .map_err(ExecutionError::SendTimeout)?
             .map_err(ExecutionError::ChildSendIO)?;
-        time::timeout(TX_TIMEOUT_SECS, stdin.close())
-            .await
-            .map_err(ExecutionError::SendTimeout)?
-            .map_err(ExecutionError::ChildSendIO)?;
-        Ok(())
+
+        Ok(callbacks.then_some(stdin))
     }
 }
 
 type SiFramedRead = FramedRead<ChildStdout, BytesLinesCodec>;
+type SiFramedWrite =
+    Framed<FramedWrite<ChildStdin, BytesLinesCodec>, Value, Value, SymmetricalJson<Value>>;
 type SiFramed<S> = Framed<SiFramedRead, S, S, SymmetricalJson<S>>;
 type SiMessage<S> = LangServerMessage<S>;
 type SiDecoderError = <BytesLinesCodec as Decoder>::Error;
@@ -213,25 +281,30 @@ type SiJsonError<S> = <SymmetricalJson<SiMessage<S>> as Deserializer<SiMessage<S
 #[derive(Debug)]
 pub struct ExecutionStarted<LangServerSuccess, Success> {
     child: Child,
+    stdin: Option<SiFramedWrite>,
     stdout: SiFramed<SiMessage<LangServerSuccess>>,
     stderr: FramedRead<ChildStderr, BytesLinesCodec>,
-    sensitive_strings: Arc<CycloneSensitiveStrings>,
+    key: Arc<CycloneDecryptionKey>,
+    sensitive_strings: Arc<RwLock<CycloneSensitiveStrings>>,
+    execution_id: String,
+    limits: Option<ExecutionLimits>,
+    cgroup: Option<ExecutionCgroup>,
     success_marker: PhantomData<Success>,
 }
 
 // TODO: implement shutdown oneshot
 async fn handle_stderr(
     stderr: FramedRead<ChildStderr, BytesLinesCodec>,
-    sensitive_strings: Arc<CycloneSensitiveStrings>,
+    sensitive_strings: Arc<RwLock<CycloneSensitiveStrings>>,
 ) {
     async fn handle_stderr_fallible(
         mut stderr: FramedRead<ChildStderr, BytesLinesCodec>,
-        sensitive_strings: Arc<CycloneSensitiveStrings>,
+        sensitive_strings: Arc<RwLock<CycloneSensitiveStrings>>,
     ) -> Result<()> {
         while let Some(line) = stderr.next().await {
             let line = line.map_err(ExecutionError::ChildRecvIO)?;
             let line = String::from_utf8(line.to_vec())?;
-            let line = sensitive_strings.redact(line.as_ref());
+            let line = read_sensitive_strings(&sensitive_strings).redact(line.as_ref());
 
             eprintln!("{line}");
         }
@@ -252,16 +325,31 @@ where
     pub async fn process(self, ws: &mut WebSocket) -> Result<ExecutionClosing<Success>> {
         tokio::spawn(handle_stderr(self.stderr, self.sensitive_strings.clone()));
 
+        let sent_result = AtomicBool::new(false);
         let mut stream = self
             .stdout
             .map(|ls_result| match ls_result {
                 Ok(ls_msg) => match ls_msg {
                     LangServerMessage::Output(mut output) => {
-                        Self::filter_output(&mut output, &self.sensitive_strings)?;
+                        Self::filter_output(
+                            &mut output,
+                            &read_sensitive_strings(&self.sensitive_strings),
+                        )?;
                         Ok(Message::OutputStream(output.into()))
                     }
+                    LangServerMessage::Request(mut request) => {
+                        redact_value_tree(
+                            &mut request.payload,
+                            &read_sensitive_strings(&self.sensitive_strings),
+                        );
+                        Ok(Message::CallbackRequest(request))
+                    }
                     LangServerMessage::Result(mut result) => {
-                        Self::filter_result(&mut result, &self.sensitive_strings)?;
+                        Self::filter_result(
+                            &mut result,
+                            &read_sensitive_strings(&self.sensitive_strings),
+                        )?;
+                        sent_result.store(true, Ordering::Relaxed);
                         Ok(Message::Result(result.into()))
                     }
                 },
@@ -278,16 +366,191 @@ where
                 Err(err) => Err(err),
             });
 
-        while let Some(msg) = stream.try_next().await? {
-            ws.send(msg).await.map_err(ExecutionError::WSSendIO)?;
+        // Callback responses from the caller are relayed to the lang server until it has
+        // produced its result or the caller stops sending, after which its stdin is closed. The
+        // caller may also cancel the execution up until then, which stops the streaming early.
+        let mut stdin = self.stdin;
+        let mut listening = true;
+        let key = self.key;
+        let execution_id = &self.execution_id;
+        let streamed = async {
+            loop {
+                tokio::select! {
+                    msg = stream.try_next() => match msg? {
+                        Some(msg) => {
+                            ws.send(msg).await.map_err(ExecutionError::WSSendIO)?;
+                            if sent_result.load(Ordering::Relaxed) {
+                                stdin = None;
+                                listening = false;
+                            }
+                        }
+                        None => break,
+                    },
+                    ws_msg = ws.next(), if listening => match ws_msg {
+                        Some(Ok(WebSocketMessage::Text(json_str))) => {
+                            match serde_json::from_str(&json_str)
+                                .map_err(ExecutionError::JSONDeserialize)?
+                            {
+                                CallerMessage::CallbackResponse(response) => match stdin.as_mut() {
+                                    Some(stdin) => {
+                                        Self::child_send_callback_response(
+                                            stdin,
+                                            response,
+                                            &key,
+                                            &self.sensitive_strings,
+                                        )
+                                        .await?;
+                                    }
+                                    None => {
+                                        warn!(
+                                            execution_id = %execution_id,
+                                            id = response.id,
+                                            "dropping callback response for a lang server which doesn't answer callbacks",
+                                        );
+                                    }
+                                },
+                                CallerMessage::Cancel(request)
+                                    if &request.execution_id == execution_id =>
+                                {
+                                    return Ok(true);
+                                }
+                                CallerMessage::Cancel(request) => {
+                                    warn!(
+                                        execution_id = %execution_id,
+                                        requested_execution_id = %request.execution_id,
+                                        "ignoring cancel request for another execution",
+                                    );
+                                }
+                            }
+                        }
+                        Some(Ok(WebSocketMessage::Ping(_) | WebSocketMessage::Pong(_))) => {}
+                        Some(Ok(WebSocketMessage::Close(_))) | None => {
+                            stdin = None;
+                            listening = false;
+                        }
+                        Some(Ok(unexpected)) => {
+                            return Err(ExecutionError::UnexpectedMessageType(unexpected))
+                        }
+                        Some(Err(err)) => return Err(ExecutionError::WSRecvIO(err)),
+                    },
+                }
+            }
+            Ok::<_, ExecutionError>(false)
+        };
+
+        let mut child = self.child;
+        let (exceeded, cancelled) = match self.limits.and_then(|limits| limits.timeout()) {
+            Some(timeout) => match time::timeout(timeout, streamed).await {
+                Ok(streamed) => (None, streamed?),
+                Err(_elapsed) => {
+                    if let Err(err) = child.start_kill() {
+                        warn!(error = ?err, "failed to kill timed out child process");
+                    }
+                    (Some(ExceededLimit::Timeout), false)
+                }
+            },
+            None => (None, streamed.await?),
+        };
+
+        if cancelled {
+            info!(execution_id = %self.execution_id, "cancelling execution at the caller's request");
+            process::child_shutdown(
+                &mut child,
+                Some(process::Signal::SIGTERM),
+                Some(CHILD_EXIT_TIMEOUT),
+            )
+            .await?;
+            Self::ws_send_failure(
+                ws,
+                FunctionResultFailure::cancelled(self.execution_id, crate::timestamp()),
+            )
+            .await?;
+
+            return Ok(ExecutionClosing {
+                child,
+                cgroup: self.cgroup,
+                success_marker: PhantomData,
+            });
+        }
+
+        let exceeded = match (exceeded, self.limits) {
+            // The lang server stopped before producing a result, so find out whether it was
+            // killed for exceeding one of its limits.
+            (None, Some(limits)) if !sent_result.load(Ordering::Relaxed) => {
+                match time::timeout(CHILD_EXIT_TIMEOUT, limits::wait_for_exit(&mut child)).await {
+                    Ok(Ok((status, cpu_time))) => {
+                        limits::exceeded_limit(status, cpu_time, limits, self.cgroup.as_ref()).await
+                    }
+                    Ok(Err(err)) => {
+                        warn!(error = ?err, "failed to wait on child process");
+                        None
+                    }
+                    Err(_elapsed) => None,
+                }
+            }
+            (exceeded, _) => exceeded,
+        };
+
+        if let Some(exceeded) = exceeded {
+            warn!(execution_id = %self.execution_id, %exceeded, "killed execution for exceeding its limits");
+            Self::ws_send_failure(
+                ws,
+                FunctionResultFailure::killed_for_exceeding_limits(
+                    self.execution_id,
+                    exceeded,
+                    crate::timestamp(),
+                ),
+            )
+            .await?;
         }
 
         Ok(ExecutionClosing {
-            child: self.child,
+            child,
+            cgroup: self.cgroup,
             success_marker: PhantomData,
         })
     }
 
+    /// Decrypts the data in a callback response, tracking anything decrypted for redaction, and
+    /// hands the response to the lang server.
+    async fn child_send_callback_response(
+        stdin: &mut SiFramedWrite,
+        mut response: CallbackResponse,
+        key: &CycloneDecryptionKey,
+        sensitive_strings: &RwLock<CycloneSensitiveStrings>,
+    ) -> Result<()> {
+        if let CallbackResult::Success { data } = &mut response.result {
+            decrypt_value_tree(
+                data,
+                &mut sensitive_strings
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner),
+                key,
+            )?;
+        }
+        let value = serde_json::to_value(&response).map_err(ExecutionError::JSONSerialize)?;
+
+        time::timeout(TX_TIMEOUT_SECS, stdin.send(value))
+            .await
+            .map_err(ExecutionError::SendTimeout)?
+            .map_err(ExecutionError::ChildSendIO)?;
+        Ok(())
+    }
+
+    /// Sends a failure result for an execution which cyclone stopped before the function could
+    /// produce its own result.
+    async fn ws_send_failure(ws: &mut WebSocket, failure: FunctionResultFailure) -> Result<()> {
+        let msg = Message::<Success>::Result(FunctionResult::Failure(failure))
+            .serialize_to_string()
+            .map_err(ExecutionError::JSONSerialize)?;
+
+        time::timeout(TX_TIMEOUT_SECS, ws.send(WebSocketMessage::Text(msg)))
+            .await
+            .map_err(ExecutionError::SendTimeout)?
+            .map_err(ExecutionError::WSSendIO)?;
+        Ok(())
+    }
+
     fn filter_output(
         output: &mut LangServerOutput,
         sensitive_strings: &CycloneSensitiveStrings,
@@ -304,18 +567,7 @@ where
         sensitive_strings: &CycloneSensitiveStrings,
     ) -> Result<()> {
         let mut value = serde_json::to_value(&result).map_err(ExecutionError::JSONSerialize)?;
-
-        let mut work_queue = vec![&mut value];
-        while let Some(work) = work_queue.pop() {
-            match work {
-                Value::Array(values) => work_queue.extend(values),
-                Value::Object(object) => object.values_mut().for_each(|v| work_queue.push(v)),
-                Value::String(string) if sensitive_strings.has_sensitive(string) => {
-                    *string = sensitive_strings.redact(string);
-                }
-                Value::String(_) | Value::Null | Value::Number(_) | Value::Bool(_) => {}
-            }
-        }
+        redact_value_tree(&mut value, sensitive_strings);
 
         let mut filtered_result: LangServerResult<LangServerSuccess> =
             serde_json::from_value(value).map_err(ExecutionError::JSONDeserialize)?;
@@ -324,9 +576,32 @@ where
     }
 }
 
+fn read_sensitive_strings(
+    sensitive_strings: &RwLock<CycloneSensitiveStrings>,
+) -> RwLockReadGuard<'_, CycloneSensitiveStrings> {
+    sensitive_strings
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+fn redact_value_tree(value: &mut Value, sensitive_strings: &CycloneSensitiveStrings) {
+    let mut work_queue = vec![value];
+    while let Some(work) = work_queue.pop() {
+        match work {
+            Value::Array(values) => work_queue.extend(values),
+            Value::Object(object) => object.values_mut().for_each(|v| work_queue.push(v)),
+            Value::String(string) if sensitive_strings.has_sensitive(string) => {
+                *string = sensitive_strings.redact(string);
+            }
+            Value::String(_) | Value::Null | Value::Number(_) | Value::Bool(_) => {}
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ExecutionClosing<Success> {
     child: Child,
+    cgroup: Option<ExecutionCgroup>,
     success_marker: PhantomData<Success>,
 }
 
@@ -342,6 +617,8 @@ where
                 .await
                 .map_err(Into::into);
         drop(self.child);
+        // Only removable once the child has exited
+        drop(self.cgroup);
 
         match (finished, closed, shutdown) {
             // Everything succeeds, great!
@@ -398,6 +675,7 @@ where
 #[serde(tag = "protocol", rename_all = "camelCase")]
 pub enum LangServerMessage<Success> {
     Output(LangServerOutput),
+    Request(CallbackRequest),
     Result(LangServerResult<Success>),
 }
 
@@ -25,12 +25,12 @@ use telemetry::prelude::*;
 use super::extract::LimitRequestGuard;
 use crate::{
     execution::{self, Execution},
-    request::DecryptRequest,
+    request::{DecryptRequest, ExecutionRequest},
     result::{
         LangServerActionRunResultSuccess, LangServerReconciliationResultSuccess,
         LangServerResolverFunctionResultSuccess, LangServerValidationResultSuccess,
     },
-    state::{DecryptionKey, LangServerPath, TelemetryLevel, WatchKeepalive},
+    state::{DecryptionKey, LangServers, LimitsCgroupRoot, TelemetryLevel, WatchKeepalive},
     watch,
 };
 
@@ -83,21 +83,23 @@ pub async fn ws_execute_ping(
 #[allow(clippy::unused_async)]
 pub async fn ws_execute_resolver(
     wsu: WebSocketUpgrade,
-    State(lang_server_path): State<LangServerPath>,
+    State(lang_servers): State<LangServers>,
     State(key): State<DecryptionKey>,
     State(telemetry_level): State<TelemetryLevel>,
+    State(limits_cgroup_root): State<LimitsCgroupRoot>,
     limit_request_guard: LimitRequestGuard,
 ) -> impl IntoResponse {
-    let lang_server_path = lang_server_path.as_path().to_path_buf();
+    let limits_cgroup_root = limits_cgroup_root.to_path_buf();
     wsu.on_upgrade(move |socket| {
         let request: PhantomData<ResolverFunctionRequest> = PhantomData;
         let lang_server_success: PhantomData<LangServerResolverFunctionResultSuccess> = PhantomData;
         let success: PhantomData<ResolverFunctionResultSuccess> = PhantomData;
         handle_socket(
             socket,
-            lang_server_path,
+            lang_servers,
             telemetry_level.is_debug_or_lower(),
             key.into(),
+            limits_cgroup_root,
             limit_request_guard,
             "resolverfunction".to_owned(),
             request,
@@ -110,21 +112,23 @@ pub async fn ws_execute_resolver(
 #[allow(clippy::unused_async)]
 pub async fn ws_execute_validation(
     wsu: WebSocketUpgrade,
-    State(lang_server_path): State<LangServerPath>,
+    State(lang_servers): State<LangServers>,
     State(key): State<DecryptionKey>,
     State(telemetry_level): State<TelemetryLevel>,
+    State(limits_cgroup_root): State<LimitsCgroupRoot>,
     limit_request_guard: LimitRequestGuard,
 ) -> impl IntoResponse {
-    let lang_server_path = lang_server_path.as_path().to_path_buf();
+    let limits_cgroup_root = limits_cgroup_root.to_path_buf();
     wsu.on_upgrade(move |socket| {
         let request: PhantomData<ValidationRequest> = PhantomData;
         let lang_server_success: PhantomData<LangServerValidationResultSuccess> = PhantomData;
         let success: PhantomData<ValidationResultSuccess> = PhantomData;
         handle_socket(
             socket,
-            lang_server_path,
+            lang_servers,
             telemetry_level.is_debug_or_lower(),
             key.into(),
+            limits_cgroup_root,
             limit_request_guard,
             "validation".to_owned(),
             request,
@@ -137,21 +141,23 @@ pub async fn ws_execute_validation(
 #[allow(clippy::unused_async)]
 pub async fn ws_execute_action_run(
     wsu: WebSocketUpgrade,
-    State(lang_server_path): State<LangServerPath>,
+    State(lang_servers): State<LangServers>,
     State(key): State<DecryptionKey>,
     State(telemetry_level): State<TelemetryLevel>,
+    State(limits_cgroup_root): State<LimitsCgroupRoot>,
     limit_request_guard: LimitRequestGuard,
 ) -> impl IntoResponse {
-    let lang_server_path = lang_server_path.as_path().to_path_buf();
+    let limits_cgroup_root = limits_cgroup_root.to_path_buf();
     wsu.on_upgrade(move |socket| {
         let request: PhantomData<ActionRunRequest> = PhantomData;
         let lang_server_success: PhantomData<LangServerActionRunResultSuccess> = PhantomData;
         let success: PhantomData<ActionRunResultSuccess> = PhantomData;
         handle_socket(
             socket,
-            lang_server_path,
+            lang_servers,
             telemetry_level.is_debug_or_lower(),
             key.into(),
+            limits_cgroup_root,
             limit_request_guard,
             "actionRun".to_owned(),
             request,
@@ -164,21 +170,23 @@ pub async fn ws_execute_action_run(
 #[allow(clippy::unused_async)]
 pub async fn ws_execute_reconciliation(
     wsu: WebSocketUpgrade,
-    State(lang_server_path): State<LangServerPath>,
+    State(lang_servers): State<LangServers>,
     State(key): State<DecryptionKey>,
     State(telemetry_level): State<TelemetryLevel>,
+    State(limits_cgroup_root): State<LimitsCgroupRoot>,
     limit_request_guard: LimitRequestGuard,
 ) -> impl IntoResponse {
-    let lang_server_path = lang_server_path.as_path().to_path_buf();
+    let limits_cgroup_root = limits_cgroup_root.to_path_buf();
     wsu.on_upgrade(move |socket| {
         let request: PhantomData<ReconciliationRequest> = PhantomData;
         let lang_server_success: PhantomData<LangServerReconciliationResultSuccess> = PhantomData;
         let success: PhantomData<ReconciliationResultSuccess> = PhantomData;
         handle_socket(
             socket,
-            lang_server_path,
+            lang_servers,
             telemetry_level.is_debug_or_lower(),
             key.into(),
+            limits_cgroup_root,
             limit_request_guard,
             "reconciliation".to_owned(),
             request,
@@ -191,21 +199,23 @@ pub async fn ws_execute_reconciliation(
 #[allow(clippy::unused_async)]
 pub async fn ws_execute_schema_variant_definition(
     wsu: WebSocketUpgrade,
-    State(lang_server_path): State<LangServerPath>,
+    State(lang_servers): State<LangServers>,
     State(key): State<DecryptionKey>,
     State(telemetry_level): State<TelemetryLevel>,
+    State(limits_cgroup_root): State<LimitsCgroupRoot>,
     limit_request_guard: LimitRequestGuard,
 ) -> impl IntoResponse {
-    let lang_server_path = lang_server_path.as_path().to_path_buf();
+    let limits_cgroup_root = limits_cgroup_root.to_path_buf();
     wsu.on_upgrade(move |socket| {
         let request: PhantomData<SchemaVariantDefinitionRequest> = PhantomData;
         let lang_server_success: PhantomData<SchemaVariantDefinitionResultSuccess> = PhantomData;
         let success: PhantomData<SchemaVariantDefinitionResultSuccess> = PhantomData;
         handle_socket(
             socket,
-            lang_server_path,
+            lang_servers,
             telemetry_level.is_debug_or_lower(),
             key.into(),
+            limits_cgroup_root,
             limit_request_guard,
             "schemaVariantDefinition".to_owned(),
             request,
@@ -218,28 +228,38 @@ pub async fn ws_execute_schema_variant_definition(
 #[allow(clippy::too_many_arguments)]
 async fn handle_socket<Request, LangServerSuccess, Success>(
     mut socket: WebSocket,
-    lang_server_path: PathBuf,
+    lang_servers: LangServers,
     lang_server_debugging: bool,
     key: Arc<cyclone_core::CycloneDecryptionKey>,
+    limits_cgroup_root: Option<PathBuf>,
     _limit_request_guard: LimitRequestGuard,
     sub_command: String,
     _request_marker: PhantomData<Request>,
     _lang_server_success_marker: PhantomData<LangServerSuccess>,
     success_marker: PhantomData<Success>,
 ) where
-    Request: DecryptRequest + Serialize + DeserializeOwned + Unpin + fmt::Debug,
+    Request: DecryptRequest + ExecutionRequest + Serialize + DeserializeOwned + Unpin + fmt::Debug,
     Success: Serialize + Unpin + fmt::Debug,
     LangServerSuccess: Serialize + DeserializeOwned + Unpin + fmt::Debug + Into<Success>,
 {
     let proto = {
-        let execution: Execution<Request, LangServerSuccess, Success> =
-            execution::new(lang_server_path, lang_server_debugging, key, sub_command);
+        let execution: Execution<Request, LangServerSuccess, Success> = execution::new(
+            lang_servers,
+            lang_server_debugging,
+            key,
+            sub_command,
+            limits_cgroup_root,
+        );
         match execution.start(&mut socket).await {
             Ok(started) => started,
             Err(err) => {
                 warn!(error = ?err, "failed to start protocol");
-                if let Err(err) =
-                    fail_to_process(socket, "failed to start protocol", success_marker).await
+                if let Err(err) = fail_to_process(
+                    socket,
+                    format!("failed to start protocol: {err}"),
+                    success_marker,
+                )
+                .await
                 {
                     warn!(error = ?err, kind = std::any::type_name::<Request>(), "failed to fail execute function");
                 };
@@ -2,6 +2,7 @@ mod config;
 mod execution;
 mod extract;
 mod handlers;
+mod limits;
 mod request;
 mod result;
 mod routes;
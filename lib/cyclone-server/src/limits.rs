@@ -0,0 +1,296 @@
+//! Enforcement of per-execution [`ExecutionLimits`] on lang server child processes.
+//!
+//! CPU time is capped with `RLIMIT_CPU`: the child receives `SIGXCPU` when it reaches the limit and
+//! `SIGKILL` a second later. As a child can be sent `SIGKILL` for other reasons too, such as by the
+//! kernel's OOM killer, a `SIGKILL` is only put down to the CPU time limit when the child used up
+//! its CPU time. Memory is capped with a cgroup v2 `memory.max` when cyclone is given a
+//! delegated cgroup to create execution cgroups under, and with `RLIMIT_DATA` otherwise. Only the
+//! cgroup's `oom_kill` event is reported as exceeding the memory limit: running into
+//! `RLIMIT_DATA` surfaces as failed allocations, and the crash that follows can't be told apart
+//! from any other, so it is reported as an ordinary failure. The wall-clock timeout is enforced
+//! by the execution itself.
+
+use std::{
+    io,
+    os::unix::process::ExitStatusExt,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    time::Duration,
+};
+
+use cyclone_core::{ExceededLimit, ExecutionLimits};
+use nix::{
+    sys::{
+        resource::{setrlimit, Resource},
+        signal::Signal,
+        wait::{waitid, Id, WaitPidFlag, WaitStatus},
+    },
+    unistd::{sysconf, Pid, SysconfVar},
+};
+use telemetry::prelude::*;
+use tokio::{
+    fs,
+    process::{Child, Command},
+    time,
+};
+
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Applies the rlimits for `limits` to the process `command` spawns.
+pub(crate) fn apply_rlimits(
+    command: &mut Command,
+    limits: ExecutionLimits,
+    memory_in_cgroup: bool,
+) {
+    let cpu_time_secs = limits.cpu_time_secs;
+    let memory_bytes = if memory_in_cgroup {
+        None
+    } else {
+        limits.memory_bytes
+    };
+    if cpu_time_secs.is_none() && memory_bytes.is_none() {
+        return;
+    }
+
+    // Safety: the closure runs in the forked child before `exec` and only calls `setrlimit`,
+    // which is async-signal-safe.
+    unsafe {
+        command.pre_exec(move || {
+            if let Some(secs) = cpu_time_secs {
+                // The soft limit sends SIGXCPU; the hard limit a second later sends SIGKILL in
+                // case the process handles or ignores SIGXCPU.
+                setrlimit(Resource::RLIMIT_CPU, secs, secs.saturating_add(1))?;
+            }
+            if let Some(bytes) = memory_bytes {
+                setrlimit(Resource::RLIMIT_DATA, bytes, bytes)?;
+            }
+            Ok(())
+        });
+    }
+}
+
+/// A cgroup v2 cgroup holding a single lang server child process.
+///
+/// The cgroup directory is removed when this is dropped, which only succeeds once the child has
+/// exited.
+#[derive(Debug)]
+pub(crate) struct ExecutionCgroup {
+    path: PathBuf,
+}
+
+impl ExecutionCgroup {
+    /// Creates a cgroup for the child process `pid` under `root`, which must be a cgroup v2
+    /// directory delegated to cyclone with the `memory` controller enabled for its children.
+    pub(crate) async fn create(root: &Path, pid: u32, limits: ExecutionLimits) -> io::Result<Self> {
+        let cgroup = Self {
+            path: root.join(format!("execution-{pid}")),
+        };
+        fs::create_dir(&cgroup.path).await?;
+
+        if let Some(bytes) = limits.memory_bytes {
+            fs::write(cgroup.path.join("memory.max"), bytes.to_string()).await?;
+            // Without this the kernel would swap the process out rather than kill it.
+            fs::write(cgroup.path.join("memory.swap.max"), "0").await?;
+        }
+        fs::write(cgroup.path.join("cgroup.procs"), pid.to_string()).await?;
+
+        Ok(cgroup)
+    }
+
+    /// Returns `true` if the kernel OOM-killed a process in this cgroup.
+    async fn oom_killed(&self) -> bool {
+        match fs::read_to_string(self.path.join("memory.events")).await {
+            Ok(events) => events.lines().any(|line| {
+                line.strip_prefix("oom_kill ")
+                    .and_then(|count| count.trim().parse::<u64>().ok())
+                    .map_or(false, |count| count > 0)
+            }),
+            Err(err) => {
+                warn!(error = ?err, path = %self.path.display(), "failed to read cgroup memory events");
+                false
+            }
+        }
+    }
+}
+
+impl Drop for ExecutionCgroup {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_dir(&self.path) {
+            warn!(error = ?err, path = %self.path.display(), "failed to remove execution cgroup");
+        }
+    }
+}
+
+/// Waits for `child` to exit, returning its exit status along with the CPU time it used, if that
+/// could be read.
+///
+/// The CPU time is read from `/proc` after the child has exited but before it is reaped, which is
+/// the only point at which it covers all of the child's work.
+pub(crate) async fn wait_for_exit(child: &mut Child) -> io::Result<(ExitStatus, Option<Duration>)> {
+    let cpu_time = match child.id() {
+        Some(pid) => {
+            let pid = Pid::from_raw(pid as i32);
+            let flags = WaitPidFlag::WEXITED | WaitPidFlag::WNOHANG | WaitPidFlag::WNOWAIT;
+            loop {
+                match waitid(Id::Pid(pid), flags) {
+                    Ok(WaitStatus::StillAlive) => time::sleep(EXIT_POLL_INTERVAL).await,
+                    Ok(_) => break cpu_time_of(pid).await,
+                    Err(err) => {
+                        debug!(error = ?err, %pid, "failed to wait on child process without reaping it");
+                        break None;
+                    }
+                }
+            }
+        }
+        None => None,
+    };
+
+    Ok((child.wait().await?, cpu_time))
+}
+
+/// Reads the user and system CPU time used by the process `pid` from `/proc/<pid>/stat`.
+async fn cpu_time_of(pid: Pid) -> Option<Duration> {
+    let stat = match fs::read_to_string(format!("/proc/{pid}/stat")).await {
+        Ok(stat) => stat,
+        Err(err) => {
+            debug!(error = ?err, %pid, "failed to read child process stat");
+            return None;
+        }
+    };
+    let ticks_per_sec = sysconf(SysconfVar::CLK_TCK).ok().flatten()?;
+
+    parse_cpu_time(&stat, u64::try_from(ticks_per_sec).ok()?)
+}
+
+/// Parses the CPU time out of the contents of a `/proc/<pid>/stat` file. The command name in the
+/// second field may contain spaces and parentheses, so fields are counted from the last `)`.
+fn parse_cpu_time(stat: &str, ticks_per_sec: u64) -> Option<Duration> {
+    let mut fields = stat
+        .get(stat.rfind(')')?.checked_add(1)?..)?
+        .split_whitespace();
+    // `utime` and `stime` are the 14th and 15th fields, the 12th and 13th after the command name.
+    let utime: u64 = fields.nth(11)?.parse().ok()?;
+    let stime: u64 = fields.next()?.parse().ok()?;
+    let ticks = utime.checked_add(stime)?;
+
+    Some(Duration::from_millis(
+        ticks.checked_mul(1000)?.checked_div(ticks_per_sec)?,
+    ))
+}
+
+/// Determines whether a child process which exited with `status`, having used `cpu_time`, was
+/// killed for exceeding one of its `limits`.
+pub(crate) async fn exceeded_limit(
+    status: ExitStatus,
+    cpu_time: Option<Duration>,
+    limits: ExecutionLimits,
+    cgroup: Option<&ExecutionCgroup>,
+) -> Option<ExceededLimit> {
+    if limits.memory_bytes.is_some() {
+        if let Some(cgroup) = cgroup {
+            if cgroup.oom_killed().await {
+                return Some(ExceededLimit::Memory);
+            }
+        }
+    }
+
+    let signal = Signal::try_from(status.signal()?).ok()?;
+    match signal {
+        Signal::SIGXCPU if limits.cpu_time_secs.is_some() => Some(ExceededLimit::CpuTime),
+        // The hard limit's `SIGKILL` only follows once the child has used its CPU time
+        Signal::SIGKILL => match (limits.cpu_time_secs, cpu_time) {
+            (Some(secs), Some(cpu_time)) if cpu_time >= Duration::from_secs(secs) => {
+                Some(ExceededLimit::CpuTime)
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `/proc/<pid>/stat` line with a utime of 250 and a stime of 50 ticks.
+    const STAT: &str = "4242 (lang-js) S 1 4242 4242 0 -1 4194560 1337 0 0 0 250 50 0 0 20 0 11 0 \
+        123456 1234567890 12345 18446744073709551615 1 1 0 0 0 0 0 4096 134234626 0 0 0 17 3 0 0 \
+        0 0 0";
+
+    #[test]
+    fn parse_cpu_time_adds_user_and_system_time() {
+        assert_eq!(Some(Duration::from_secs(3)), parse_cpu_time(STAT, 100));
+        assert_eq!(Some(Duration::from_millis(1200)), parse_cpu_time(STAT, 250));
+    }
+
+    #[test]
+    fn parse_cpu_time_counts_fields_from_the_end_of_the_command_name() {
+        let stat = STAT.replace("(lang-js)", "(lang js) (1 2 3))");
+        assert_eq!(Some(Duration::from_secs(3)), parse_cpu_time(&stat, 100));
+    }
+
+    #[test]
+    fn parse_cpu_time_rejects_malformed_stats() {
+        assert_eq!(None, parse_cpu_time("", 100));
+        assert_eq!(None, parse_cpu_time("4242 lang-js S 1", 100));
+        assert_eq!(None, parse_cpu_time("4242 (lang-js) S 1 4242", 100));
+        assert_eq!(
+            None,
+            parse_cpu_time(&STAT.replace(" 250 50 ", " 250 nope "), 100)
+        );
+        assert_eq!(None, parse_cpu_time(STAT, 0));
+    }
+
+    fn killed_by(signal: Signal) -> ExitStatus {
+        ExitStatus::from_raw(signal as i32)
+    }
+
+    #[tokio::test]
+    async fn crashes_without_a_cgroup_are_not_a_memory_limit() {
+        let limits = ExecutionLimits {
+            memory_bytes: Some(64 * 1024 * 1024),
+            ..Default::default()
+        };
+
+        for signal in [Signal::SIGABRT, Signal::SIGSEGV, Signal::SIGTRAP] {
+            assert_eq!(
+                None,
+                exceeded_limit(killed_by(signal), None, limits, None).await,
+                "{signal} was reported as exceeding a limit"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn cpu_time_limit_is_reported_once_used_up() {
+        let limits = ExecutionLimits {
+            cpu_time_secs: Some(1),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            Some(ExceededLimit::CpuTime),
+            exceeded_limit(killed_by(Signal::SIGXCPU), None, limits, None).await
+        );
+        assert_eq!(
+            Some(ExceededLimit::CpuTime),
+            exceeded_limit(
+                killed_by(Signal::SIGKILL),
+                Some(Duration::from_millis(1010)),
+                limits,
+                None
+            )
+            .await
+        );
+        assert_eq!(
+            None,
+            exceeded_limit(
+                killed_by(Signal::SIGKILL),
+                Some(Duration::from_millis(10)),
+                limits,
+                None
+            )
+            .await
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use cyclone_core::{
     decrypt_value_tree, ActionRunRequest, BeforeFunction, CycloneDecryptionKey,
-    CycloneSensitiveStrings, CycloneValueDecryptError, ReconciliationRequest,
+    CycloneSensitiveStrings, CycloneValueDecryptError, ExecutionLimits, ReconciliationRequest,
     ResolverFunctionRequest, SchemaVariantDefinitionRequest, ValidationRequest,
 };
 
@@ -63,6 +63,42 @@ impl DecryptRequest for SchemaVariantDefinitionRequest {
     }
 }
 
+pub trait ExecutionRequest {
+    fn execution_id(&self) -> &str;
+
+    fn limits(&self) -> Option<ExecutionLimits>;
+
+    fn runtime(&self) -> Option<&str>;
+}
+
+macro_rules! impl_execution_request {
+    ($($request:ty),+ $(,)?) => {
+        $(
+            impl ExecutionRequest for $request {
+                fn execution_id(&self) -> &str {
+                    &self.execution_id
+                }
+
+                fn limits(&self) -> Option<ExecutionLimits> {
+                    self.limits
+                }
+
+                fn runtime(&self) -> Option<&str> {
+                    self.runtime.as_deref()
+                }
+            }
+        )+
+    };
+}
+
+impl_execution_request!(
+    ResolverFunctionRequest,
+    ActionRunRequest,
+    ReconciliationRequest,
+    ValidationRequest,
+    SchemaVariantDefinitionRequest,
+);
+
 fn decrypt_before_func_args(
     before: &mut Vec<BeforeFunction>,
     sensitive_strings: &mut CycloneSensitiveStrings,
@@ -18,8 +18,9 @@ use tokio::{
 use tower_http::trace::{DefaultMakeSpan, TraceLayer};
 
 use crate::{
-    routes::routes, state::AppState, Config, IncomingStream, UdsIncomingStream,
-    UdsIncomingStreamError,
+    routes::routes,
+    state::{AppState, LangServer},
+    Config, IncomingStream, UdsIncomingStream, UdsIncomingStreamError,
 };
 
 #[cfg(target_os = "linux")]
@@ -198,7 +199,18 @@ fn build_service(
 ) -> Result<(IntoMakeService<Router>, oneshot::Receiver<()>)> {
     let (shutdown_tx, shutdown_rx) = mpsc::channel(4);
 
-    let state = AppState::new(config.lang_server_path(), decryption_key, telemetry_level);
+    let state = AppState::new(
+        LangServer::new(config.lang_server_path(), config.lang_server_callbacks()),
+        config
+            .runtime_lang_servers()
+            .map(|(runtime, path, callbacks)| {
+                (runtime.to_owned(), LangServer::new(path, callbacks))
+            })
+            .collect(),
+        decryption_key,
+        telemetry_level,
+        config.limits_cgroup_root().map(Path::to_path_buf),
+    );
 
     let routes = routes(config, state, shutdown_tx)
         // TODO(fnichol): customize http tracing further, using:
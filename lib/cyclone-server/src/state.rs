@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     ops::Deref,
     path::{Path, PathBuf},
     sync::Arc,
@@ -10,31 +11,82 @@ use tokio::sync::mpsc;
 
 #[derive(Clone, FromRef)]
 pub struct AppState {
-    lang_server_path: LangServerPath,
+    lang_servers: LangServers,
     decryption_key: DecryptionKey,
     telemetry_level: TelemetryLevel,
+    limits_cgroup_root: LimitsCgroupRoot,
 }
 
 impl AppState {
     pub fn new(
-        lang_server_path: impl Into<PathBuf>,
+        lang_server: LangServer,
+        runtime_lang_servers: HashMap<String, LangServer>,
         decryption_key: cyclone_core::CycloneDecryptionKey,
         telemetry_level: Box<dyn telemetry::TelemetryLevel>,
+        limits_cgroup_root: Option<PathBuf>,
     ) -> Self {
         Self {
-            lang_server_path: LangServerPath(Arc::new(lang_server_path.into())),
+            lang_servers: LangServers {
+                default: Arc::new(lang_server),
+                by_runtime: Arc::new(runtime_lang_servers),
+            },
             decryption_key: DecryptionKey(Arc::new(decryption_key)),
             telemetry_level: TelemetryLevel(Arc::new(telemetry_level)),
+            limits_cgroup_root: LimitsCgroupRoot(limits_cgroup_root.map(Arc::new)),
         }
     }
 }
 
+/// The lang servers available to execute functions, keyed by the function runtime they serve.
 #[derive(Clone, Debug, FromRef)]
-pub struct LangServerPath(Arc<PathBuf>);
+pub struct LangServers {
+    default: Arc<LangServer>,
+    by_runtime: Arc<HashMap<String, LangServer>>,
+}
+
+impl LangServers {
+    /// Returns the lang server for functions written for `runtime`, or the default lang server
+    /// when no runtime is given.
+    pub fn for_runtime(&self, runtime: Option<&str>) -> Option<&LangServer> {
+        match runtime {
+            Some(runtime) => self.by_runtime.get(runtime),
+            None => Some(self.default.as_ref()),
+        }
+    }
+}
+
+/// A lang server program.
+#[derive(Clone, Debug)]
+pub struct LangServer {
+    path: PathBuf,
+    callbacks: bool,
+}
+
+impl LangServer {
+    pub fn new(path: impl Into<PathBuf>, callbacks: bool) -> Self {
+        Self {
+            path: path.into(),
+            callbacks,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    /// Whether the lang server answers callback requests, in which case its stdin stays open after
+    /// the request for the responses. Otherwise stdin is closed once the request is written.
+    pub fn callbacks(&self) -> bool {
+        self.callbacks
+    }
+}
+
+#[derive(Clone, Debug, FromRef)]
+pub struct LimitsCgroupRoot(Option<Arc<PathBuf>>);
 
-impl LangServerPath {
-    pub fn as_path(&self) -> &Path {
-        self.0.as_path()
+impl LimitsCgroupRoot {
+    pub fn to_path_buf(&self) -> Option<PathBuf> {
+        self.0.as_deref().cloned()
     }
 }
 
@@ -12,7 +12,7 @@ use buck2_resources::Buck2Resources;
 use dal::{
     builtins::SelectedTestBuiltinSchemas,
     job::processor::{JobQueueProcessor, NatsProcessor},
-    DalContext, JwtPublicSigningKey, ServicesContext,
+    DalContext, JwtPublicSigningKey, SecretBackends, ServicesContext,
 };
 use derive_builder::Builder;
 use jwt_simple::prelude::RS256KeyPair;
@@ -45,6 +45,7 @@ const ENV_VAR_MODULE_INDEX_URL: &str = "SI_TEST_MODULE_INDEX_URL";
 const ENV_VAR_PG_HOSTNAME: &str = "SI_TEST_PG_HOSTNAME";
 const ENV_VAR_PG_DBNAME: &str = "SI_TEST_PG_DBNAME";
 const ENV_VAR_BUILTIN_SCHEMAS: &str = "SI_TEST_BUILTIN_SCHEMAS";
+const ENV_VAR_EMBEDDED_VERITECH: &str = "SI_TEST_EMBEDDED_VERITECH";
 
 pub static COLOR_EYRE_INIT: Once = Once::new();
 
@@ -93,6 +94,10 @@ pub struct Config {
     #[builder(default)]
     pkgs_path: Option<PathBuf>,
     symmetric_crypto_service_config: SymmetricCryptoServiceConfig,
+    /// Whether functions execute on a cyclone pool inside the test process rather than through a
+    /// Veritech server.
+    #[builder(default)]
+    embedded_veritech: bool,
 }
 
 impl Config {
@@ -120,6 +125,12 @@ impl Config {
             config.module_index_url = value;
         }
 
+        if let Ok(value) = env::var(ENV_VAR_EMBEDDED_VERITECH) {
+            config.embedded_veritech = value.parse().wrap_err_with(|| {
+                format!("{ENV_VAR_EMBEDDED_VERITECH} must be either true or false")
+            })?;
+        }
+
         Ok(config)
     }
 }
@@ -165,6 +176,8 @@ pub struct TestContext {
     pg_pool: PgPool,
     /// A connected NATS client
     nats_conn: NatsClient,
+    /// A client for the function execution system
+    veritech: veritech_client::Client,
     /// A [`JobQueueProcessor`] impl
     job_processor: Box<dyn JobQueueProcessor + Send + Sync>,
     /// A key for re-recrypting messages to the function execution system.
@@ -234,17 +247,16 @@ impl TestContext {
 
     /// Creates a new [`ServicesContext`].
     pub async fn create_services_context(&self) -> ServicesContext {
-        let veritech = veritech_client::Client::new(self.nats_conn.clone());
-
         ServicesContext::new(
             self.pg_pool.clone(),
             self.nats_conn.clone(),
             self.job_processor.clone(),
-            veritech,
+            self.veritech.clone(),
             self.encryption_key.clone(),
             self.config.pkgs_path.to_owned(),
             None,
             self.symmetric_crypto_service.clone(),
+            SecretBackends::default(),
         )
     }
 
@@ -313,6 +325,13 @@ impl TestContextBuilder {
             .wrap_err("failed to create NatsClient")?;
         let job_processor = Box::new(NatsProcessor::new(nats_conn.clone()))
             as Box<dyn JobQueueProcessor + Send + Sync>;
+        // Each test runs on its own runtime, so an embedded cyclone pool can't be shared between
+        // tests and is created alongside the other pools
+        let veritech = if self.config.embedded_veritech {
+            veritech_client::Client::embedded(veritech_embedded_executor().await?)
+        } else {
+            veritech_client::Client::new(nats_conn.clone())
+        };
 
         let symmetric_crypto_service =
             SymmetricCryptoService::from_config(&self.config.symmetric_crypto_service_config)
@@ -322,6 +341,7 @@ impl TestContextBuilder {
             config,
             pg_pool,
             nats_conn,
+            veritech,
             job_processor,
             encryption_key: self.encryption_key.clone(),
             symmetric_crypto_service,
@@ -474,6 +494,30 @@ pub async fn veritech_server_for_uds_cyclone(
     Ok(server)
 }
 
+/// Configures and builds a [`veritech_client::EmbeddedExecutor`] which runs functions on a cyclone
+/// pool in the test process, for tests which shouldn't depend on a Veritech server.
+pub async fn veritech_embedded_executor() -> Result<veritech_client::EmbeddedExecutor> {
+    let config: veritech_server::Config = {
+        let mut config_file = veritech_server::ConfigFile::default();
+        veritech_server::detect_and_configure_development(&mut config_file)
+            .wrap_err("failed to detect and configure Veritech ConfigFile")?;
+        config_file
+            .try_into()
+            .wrap_err("failed to build Veritech server config")?
+    };
+
+    match config.into_cyclone_spec() {
+        veritech_server::CycloneSpec::LocalUds(spec) => {
+            veritech_client::EmbeddedExecutor::for_cyclone_uds(spec)
+                .await
+                .wrap_err("failed to create embedded Veritech executor")
+        }
+        veritech_server::CycloneSpec::LocalHttp(_) => Err(eyre!(
+            "embedded Veritech executor requires a LocalUds cyclone spec"
+        )),
+    }
+}
+
 async fn global_setup(test_context_builer: TestContextBuilder) -> Result<()> {
     info!("running global test setup");
     let test_context = test_context_builer.build_for_global().await?;
@@ -4,7 +4,7 @@ use tokio::fs;
 
 use dal::{
     pkg::PkgExporter, ChangeSet, ChangeSetPk, DalContext, JobQueueProcessor, NatsProcessor, Schema,
-    ServicesContext, StandardModel, Tenancy, Workspace,
+    SecretBackends, ServicesContext, StandardModel, Tenancy, Workspace,
 };
 use si_crypto::{SymmetricCryptoService, SymmetricCryptoServiceConfigFile};
 use si_data_nats::{NatsClient, NatsConfig};
@@ -82,6 +82,7 @@ async fn ctx() -> Result<DalContext> {
         None,
         None,
         symmetric_crypto_service,
+        SecretBackends::default(),
     );
 
     Ok(DalContext::builder(services_context, false)
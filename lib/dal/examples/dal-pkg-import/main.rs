@@ -4,7 +4,7 @@ use buck2_resources::Buck2Resources;
 use dal::generate_unique_id;
 use dal::{
     pkg::import_pkg_from_pkg, ChangeSet, DalContext, JobQueueProcessor, NatsProcessor,
-    ServicesContext, Tenancy, Workspace,
+    SecretBackends, ServicesContext, Tenancy, Workspace,
 };
 use si_crypto::{SymmetricCryptoService, SymmetricCryptoServiceConfigFile};
 use si_data_nats::{NatsClient, NatsConfig};
@@ -71,6 +71,7 @@ async fn ctx() -> Result<DalContext> {
         None,
         None,
         symmetric_crypto_service,
+        SecretBackends::default(),
     );
 
     Ok(DalContext::builder(services_context, false)
@@ -16,6 +16,7 @@ use crate::{
         producer::{BlockingJobError, BlockingJobResult, JobProducer},
         queue::JobQueue,
     },
+    secret::SecretBackends,
     HistoryActor, StandardModel, Tenancy, TenancyError, Visibility,
 };
 
@@ -41,6 +42,8 @@ pub struct ServicesContext {
     module_index_url: Option<String>,
     /// A service that can encrypt and decrypt values with a set of symmetric keys
     symmetric_crypto_service: SymmetricCryptoService,
+    /// The backends secrets stored outside of the database are fetched from
+    secret_backends: SecretBackends,
 }
 
 impl ServicesContext {
@@ -55,6 +58,7 @@ impl ServicesContext {
         pkgs_path: Option<PathBuf>,
         module_index_url: Option<String>,
         symmetric_crypto_service: SymmetricCryptoService,
+        secret_backends: SecretBackends,
     ) -> Self {
         Self {
             pg_pool,
@@ -65,6 +69,7 @@ impl ServicesContext {
             pkgs_path,
             module_index_url,
             symmetric_crypto_service,
+            secret_backends,
         }
     }
 
@@ -111,6 +116,11 @@ impl ServicesContext {
         &self.symmetric_crypto_service
     }
 
+    /// Get a reference to the external secret backends
+    pub fn secret_backends(&self) -> &SecretBackends {
+        &self.secret_backends
+    }
+
     /// Builds and returns a new [`Connections`].
     pub async fn connections(&self) -> PgPoolResult<Connections> {
         let pg_conn = self.pg_pool.get().await?;
@@ -272,6 +282,10 @@ impl DalContext {
         self.services_context.symmetric_crypto_service()
     }
 
+    pub fn secret_backends(&self) -> &SecretBackends {
+        self.services_context.secret_backends()
+    }
+
     /// Consumes all inner transactions, committing all changes made within them, and
     /// blocks until all queued jobs have reported as finishing.
     pub async fn blocking_commit(&self) -> Result<(), TransactionsError> {
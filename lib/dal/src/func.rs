@@ -7,14 +7,14 @@ use si_data_pg::PgError;
 use strum::IntoEnumIterator;
 use telemetry::prelude::*;
 use thiserror::Error;
-use veritech_client::CycloneValueEncryptError;
+use veritech_client::{CycloneValueEncryptError, ExecutionLimits};
 
 use crate::func::argument::FuncArgumentError;
 use crate::{
     generate_unique_id, impl_standard_model, pk, standard_model, standard_model_accessor,
-    standard_model_accessor_ro, DalContext, FuncBinding, HistoryEventError, SecretError,
-    StandardModel, StandardModelError, Tenancy, Timestamp, TransactionsError, Visibility,
-    WorkspacePk,
+    standard_model_accessor_ro, DalContext, FuncBinding, HistoryEvent, HistoryEventError,
+    SecretError, StandardModel, StandardModelError, Tenancy, Timestamp, TransactionsError,
+    Visibility, WorkspacePk,
 };
 
 use self::backend::{FuncBackendKind, FuncBackendResponseType};
@@ -27,6 +27,7 @@ pub mod binding_return_value;
 pub mod execution;
 pub mod identity;
 pub mod intrinsics;
+pub mod result_cache;
 
 pub fn is_intrinsic(name: &str) -> bool {
     intrinsics::IntrinsicFunc::iter().any(|intrinsic| intrinsic.name() == name)
@@ -111,8 +112,17 @@ pub struct Func {
     link: Option<String>,
     hidden: bool,
     builtin: bool,
+    /// Whether this func always returns the same result for the same code and arguments, which
+    /// allows its results to be cached (see [`FuncResultCache`](crate::func::result_cache::FuncResultCache)).
+    pure: bool,
     backend_kind: FuncBackendKind,
     backend_response_type: FuncBackendResponseType,
+    /// The runtime this func's code is written for, which selects the lang server that executes
+    /// it. When unset, the code is JavaScript run by the default lang server.
+    runtime: Option<String>,
+    /// The resource ceilings cyclone enforces when executing this func. When unset, executions
+    /// are unlimited.
+    limits: Option<ExecutionLimits>,
     handler: Option<String>,
     code_base64: Option<String>,
     code_sha256: String,
@@ -230,6 +240,45 @@ impl Func {
         .await
     }
 
+    /// Returns the resource ceilings cyclone enforces when executing this func, if any.
+    pub fn limits(&self) -> Option<&ExecutionLimits> {
+        self.limits.as_ref()
+    }
+
+    /// Sets the resource ceilings cyclone enforces when executing this func. Limits with nothing
+    /// set are stored as no limits at all.
+    pub async fn set_limits(
+        &mut self,
+        ctx: &DalContext,
+        limits: Option<ExecutionLimits>,
+    ) -> FuncResult<()> {
+        let limits = limits.filter(|limits| !limits.is_empty());
+        let value = limits.map(serde_json::to_value).transpose()?;
+        let updated_at = standard_model::update(
+            ctx,
+            Self::table_name(),
+            "limits",
+            self.id(),
+            &value,
+            standard_model::TypeHint::JsonB,
+        )
+        .await?;
+        let _history_event = HistoryEvent::new(
+            ctx,
+            &Self::history_event_label(vec!["updated"]),
+            &Self::history_event_message("updated"),
+            &serde_json::json![{
+                "pk": self.pk,
+                "field": "limits",
+                "value": &value,
+            }],
+        )
+        .await?;
+        self.timestamp.updated_at = updated_at;
+        self.limits = limits;
+        Ok(())
+    }
+
     pub fn metadata_view(&self) -> FuncMetadataView {
         FuncMetadataView {
             display_name: self.display_name().unwrap_or_else(|| self.name()).into(),
@@ -272,12 +321,14 @@ impl Func {
     standard_model_accessor!(link, Option<String>, FuncResult);
     standard_model_accessor!(hidden, bool, FuncResult);
     standard_model_accessor!(builtin, bool, FuncResult);
+    standard_model_accessor!(pure, bool, FuncResult);
     standard_model_accessor!(backend_kind, Enum(FuncBackendKind), FuncResult);
     standard_model_accessor!(
         backend_response_type,
         Enum(FuncBackendResponseType),
         FuncResult
     );
+    standard_model_accessor!(runtime, Option<String>, FuncResult);
     standard_model_accessor!(handler, Option<String>, FuncResult);
     standard_model_accessor!(code_base64, Option<String>, FuncResult);
     standard_model_accessor_ro!(code_sha256, String);
@@ -5,14 +5,17 @@ use telemetry::prelude::*;
 use thiserror::Error;
 use tokio::sync::mpsc;
 use veritech_client::{
-    ActionRunResultSuccess, BeforeFunction, Client as VeritechClient, FunctionResult, OutputStream,
-    ResolverFunctionResponseType,
+    ActionRunResultSuccess, BeforeFunction, Client as VeritechClient, ExecutionLimits,
+    FunctionResult, OutputStream, ResolverFunctionResponseType,
 };
 
 use crate::{label_list::ToLabelList, DalContext, Func, FuncId, PropKind, StandardModel};
 
+use self::callback::DalCallbackHandler;
+
 pub mod array;
 pub mod boolean;
+pub mod callback;
 pub mod diff;
 pub mod identity;
 pub mod integer;
@@ -23,12 +26,15 @@ pub mod js_schema_variant_definition;
 pub mod map;
 pub mod object;
 pub mod string;
+pub mod wasm;
 
 #[remain::sorted]
 #[derive(Error, Debug)]
 pub enum FuncBackendError {
     #[error("expected same array entry prop kinds - expected {0}, found: {1}")]
     DifferingArrayEntryPropKinds(PropKind, PropKind),
+    #[error("base64 decode error: {0}")]
+    Base64Decode(#[from] base64::DecodeError),
     #[error("dispatch func missing code_base64 {0}")]
     DispatchMissingBase64(FuncId),
     #[error("dispatch func missing handler {0}")]
@@ -51,6 +57,12 @@ pub enum FuncBackendError {
     Ulid(#[from] ulid::DecodeError),
     #[error("veritech client error: {0}")]
     VeritechClient(#[from] veritech_client::ClientError),
+    #[error("wasm error: {0}")]
+    Wasm(#[from] wasmi::Error),
+    #[error("wasm module does not follow the func abi: {0}")]
+    WasmAbi(String),
+    #[error("wasm execution task failed: {0}")]
+    WasmJoin(#[from] tokio::task::JoinError),
 }
 
 pub type FuncBackendResult<T> = Result<T, FuncBackendError>;
@@ -88,6 +100,8 @@ pub enum FuncBackendKind {
     String,
     Unset,
     Validation,
+    /// A WebAssembly module executed in-process rather than through veritech.
+    Wasm,
 }
 
 #[remain::sorted]
@@ -191,9 +205,14 @@ pub struct FuncDispatchContext {
 impl FuncDispatchContext {
     pub fn new(ctx: &DalContext) -> (Self, mpsc::Receiver<OutputStream>) {
         let (output_tx, rx) = mpsc::channel(64);
+        let veritech = match ctx.tenancy().workspace_pk() {
+            Some(workspace_pk) => ctx.veritech().for_workspace(workspace_pk.to_string()),
+            None => ctx.veritech().clone(),
+        }
+        .with_callback_handler(DalCallbackHandler::new(ctx));
         (
             Self {
-                veritech: ctx.veritech().clone(),
+                veritech,
                 output_tx,
             },
             rx,
@@ -238,7 +257,9 @@ pub trait FuncDispatch: std::fmt::Debug {
         let handler = func
             .handler()
             .ok_or_else(|| FuncBackendError::DispatchMissingHandler(*func.id()))?;
-        let value = Self::new(context, code_base64, handler, args, before);
+        let runtime = func.runtime().map(ToOwned::to_owned);
+        let limits = func.limits().copied();
+        let value = Self::new(context, code_base64, handler, args, before, runtime, limits);
         Ok(value)
     }
 
@@ -288,6 +309,8 @@ pub trait FuncDispatch: std::fmt::Debug {
         handler: &str,
         args: Self::Args,
         before: Vec<BeforeFunction>,
+        runtime: Option<String>,
+        limits: Option<ExecutionLimits>,
     ) -> Box<Self>;
     async fn dispatch(self: Box<Self>) -> FuncBackendResult<FunctionResult<Self::Output>>;
 }
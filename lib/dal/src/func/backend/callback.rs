@@ -0,0 +1,63 @@
+//! Answers the data functions ask for while they execute, using the [`DalContext`] they were
+//! dispatched from.
+
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use telemetry::prelude::*;
+use veritech_client::{CallbackHandler, CallbackRequest, CallbackResponse};
+
+use crate::{ComponentId, ComponentView, DalContext};
+
+/// The view of another component, as the function's change set sees it.
+const COMPONENT_VIEW_KIND: &str = "componentView";
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ComponentViewPayload {
+    component_id: ComponentId,
+}
+
+/// Answers callback requests from functions dispatched with a given [`DalContext`].
+#[derive(Clone, Debug)]
+pub struct DalCallbackHandler {
+    ctx: DalContext,
+}
+
+impl DalCallbackHandler {
+    pub fn new(ctx: &DalContext) -> Self {
+        Self { ctx: ctx.clone() }
+    }
+
+    async fn component_view(
+        ctx: &DalContext,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, String> {
+        let payload: ComponentViewPayload =
+            serde_json::from_value(payload).map_err(|err| err.to_string())?;
+        let view = ComponentView::new(ctx, payload.component_id)
+            .await
+            .map_err(|err| err.to_string())?;
+        serde_json::to_value(view).map_err(|err| err.to_string())
+    }
+}
+
+impl CallbackHandler for DalCallbackHandler {
+    fn handle(&self, request: CallbackRequest) -> BoxFuture<'static, CallbackResponse> {
+        let ctx = self.ctx.clone();
+        Box::pin(async move {
+            debug!(
+                execution_id = %request.execution_id,
+                kind = %request.kind,
+                "answering callback request"
+            );
+            let result = match request.kind.as_str() {
+                COMPONENT_VIEW_KIND => Self::component_view(&ctx, request.payload).await,
+                kind => Err(format!("unknown callback request kind: {kind}")),
+            };
+            match result {
+                Ok(data) => CallbackResponse::success(request.id, data),
+                Err(message) => CallbackResponse::failure(request.id, message),
+            }
+        })
+    }
+}
@@ -3,8 +3,8 @@ use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use telemetry::tracing::trace;
 use veritech_client::{
-    ActionRunRequest, ActionRunResultSuccess, BeforeFunction, FunctionResult, OutputStream,
-    ResourceStatus,
+    ActionRunRequest, ActionRunResultSuccess, BeforeFunction, ExecutionLimits, FunctionResult,
+    OutputStream, ResourceStatus,
 };
 
 use crate::func::backend::{
@@ -31,6 +31,8 @@ impl FuncDispatch for FuncBackendJsAction {
         handler: &str,
         args: Self::Args,
         before: Vec<BeforeFunction>,
+        runtime: Option<String>,
+        limits: Option<ExecutionLimits>,
     ) -> Box<Self> {
         let request = ActionRunRequest {
             // Once we start tracking the state of these executions, then this id will be useful,
@@ -40,6 +42,8 @@ impl FuncDispatch for FuncBackendJsAction {
             code_base64: code_base64.into(),
             args: serde_json::to_value(args).unwrap(),
             before,
+            limits,
+            runtime,
         };
 
         Box::new(Self { context, request })
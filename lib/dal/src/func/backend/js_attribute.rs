@@ -2,8 +2,8 @@ use async_trait::async_trait;
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use veritech_client::{
-    BeforeFunction, FunctionResult, ResolverFunctionComponent, ResolverFunctionRequest,
-    ResolverFunctionResponseType, ResolverFunctionResultSuccess,
+    BeforeFunction, ExecutionLimits, FunctionResult, ResolverFunctionComponent,
+    ResolverFunctionRequest, ResolverFunctionResponseType, ResolverFunctionResultSuccess,
 };
 
 use crate::func::backend::{ExtractPayload, FuncBackendResult, FuncDispatch, FuncDispatchContext};
@@ -31,6 +31,8 @@ impl FuncDispatch for FuncBackendJsAttribute {
         handler: &str,
         args: Self::Args,
         before: Vec<BeforeFunction>,
+        runtime: Option<String>,
+        limits: Option<ExecutionLimits>,
     ) -> Box<Self> {
         let request = ResolverFunctionRequest {
             // Once we start tracking the state of these executions, then this id will be useful,
@@ -41,6 +43,8 @@ impl FuncDispatch for FuncBackendJsAttribute {
             response_type: args.response_type,
             code_base64: code_base64.into(),
             before,
+            limits,
+            runtime,
         };
 
         Box::new(Self { context, request })
@@ -3,7 +3,8 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::str::FromStr;
 use veritech_client::{
-    BeforeFunction, FunctionResult, ReconciliationRequest, ReconciliationResultSuccess,
+    BeforeFunction, ExecutionLimits, FunctionResult, ReconciliationRequest,
+    ReconciliationResultSuccess,
 };
 
 use crate::func::backend::{ExtractPayload, FuncBackendResult, FuncDispatch, FuncDispatchContext};
@@ -44,6 +45,8 @@ impl FuncDispatch for FuncBackendJsReconciliation {
         handler: &str,
         args: Self::Args,
         before: Vec<BeforeFunction>,
+        runtime: Option<String>,
+        limits: Option<ExecutionLimits>,
     ) -> Box<Self> {
         let request = ReconciliationRequest {
             // Once we start tracking the state of these executions, then this id will be useful,
@@ -53,6 +56,8 @@ impl FuncDispatch for FuncBackendJsReconciliation {
             code_base64: code_base64.into(),
             args: serde_json::to_value(args).unwrap(),
             before,
+            limits,
+            runtime,
         };
 
         Box::new(Self { context, request })
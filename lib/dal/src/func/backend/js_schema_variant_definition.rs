@@ -2,7 +2,7 @@ use crate::func::backend::{ExtractPayload, FuncBackendResult, FuncDispatch, Func
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use veritech_client::{
-    BeforeFunction, FunctionResult, SchemaVariantDefinitionRequest,
+    BeforeFunction, ExecutionLimits, FunctionResult, SchemaVariantDefinitionRequest,
     SchemaVariantDefinitionResultSuccess,
 };
 #[derive(Debug, Clone)]
@@ -22,11 +22,15 @@ impl FuncDispatch for FuncBackendJsSchemaVariantDefinition {
         handler: &str,
         _args: Self::Args,
         _before: Vec<BeforeFunction>,
+        runtime: Option<String>,
+        limits: Option<ExecutionLimits>,
     ) -> Box<Self> {
         let request = SchemaVariantDefinitionRequest {
             execution_id: "villanelle".to_string(),
             handler: handler.into(),
             code_base64: code_base64.to_owned(),
+            limits,
+            runtime,
         };
 
         Box::new(Self { context, request })
@@ -0,0 +1,345 @@
+//! Executes [`Funcs`](crate::Func) compiled to WebAssembly in-process, rather than dispatching
+//! them through veritech to a cyclone instance.
+//!
+//! The module is decoded from the func's `code_base64` and the export named by its `handler` is
+//! called with the binding's arguments. Modules are given no imports, so they can't reach the
+//! clock, randomness or the outside world: the same module and arguments always produce the same
+//! result. Executions are bounded by [`DEFAULT_FUEL`] and [`DEFAULT_MEMORY_BYTES`].
+//!
+//! A module must export:
+//!
+//! - `memory`: its linear memory
+//! - `alloc(len: i32) -> i32`: reserves `len` bytes and returns a pointer to them
+//! - the handler, `(ptr: i32, len: i32) -> i64`: reads the arguments as JSON from the `len` bytes
+//!   at `ptr` and returns the location of its JSON output as `(ptr << 32) | len`
+//!
+//! The output is either `{ "status": "success", "data": ... }` or
+//! `{ "status": "failure", "error": { "kind": ..., "message": ... } }`.
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose, Engine as _};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use veritech_client::{
+    BeforeFunction, ExceededLimit, ExecutionLimits, FunctionResult, FunctionResultFailure,
+    FunctionResultFailureError,
+};
+use wasmi::{
+    core::TrapCode, Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder,
+};
+
+use crate::func::backend::{
+    ExtractPayload, FuncBackendError, FuncBackendResult, FuncDispatch, FuncDispatchContext,
+};
+
+/// The fuel, roughly one unit per instruction executed, a single execution may consume.
+pub const DEFAULT_FUEL: u64 = 100_000_000;
+/// The most linear memory a single execution may grow to.
+pub const DEFAULT_MEMORY_BYTES: usize = 64 * 1024 * 1024;
+
+const EXECUTION_ID: &str = "wasm";
+const TRAP_KIND: &str = "wasmTrap";
+
+#[derive(Debug)]
+pub struct FuncBackendWasm {
+    code_base64: String,
+    handler: String,
+    args: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FuncBackendWasmResultSuccess {
+    pub execution_id: String,
+    pub data: serde_json::Value,
+    pub timestamp: u64,
+}
+
+impl ExtractPayload for FuncBackendWasmResultSuccess {
+    type Payload = serde_json::Value;
+
+    fn extract(self) -> FuncBackendResult<Self::Payload> {
+        Ok(self.data)
+    }
+}
+
+#[remain::sorted]
+#[derive(Deserialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+enum WasmOutput {
+    Failure { error: FunctionResultFailureError },
+    Success { data: serde_json::Value },
+}
+
+#[async_trait]
+impl FuncDispatch for FuncBackendWasm {
+    type Args = serde_json::Value;
+    type Output = FuncBackendWasmResultSuccess;
+
+    fn new(
+        _context: FuncDispatchContext,
+        code_base64: &str,
+        handler: &str,
+        args: Self::Args,
+        // Before funcs are JavaScript run by lang-js, which this backend doesn't go through
+        _before: Vec<BeforeFunction>,
+        // The module is run in process, so there is no lang server to select
+        _runtime: Option<String>,
+        // Modules are bounded by wasmi's fuel and memory limits instead
+        _limits: Option<ExecutionLimits>,
+    ) -> Box<Self> {
+        Box::new(Self {
+            code_base64: code_base64.into(),
+            handler: handler.into(),
+            args,
+        })
+    }
+
+    async fn dispatch(self: Box<Self>) -> FuncBackendResult<FunctionResult<Self::Output>> {
+        let code = general_purpose::STANDARD_NO_PAD.decode(&self.code_base64)?;
+        let input = serde_json::to_vec(&self.args)?;
+
+        // Executions are CPU-bound and, though bounded by fuel, can run for a while
+        let output = tokio::task::spawn_blocking(move || {
+            execute(
+                &code,
+                &self.handler,
+                &input,
+                DEFAULT_FUEL,
+                DEFAULT_MEMORY_BYTES,
+            )
+        })
+        .await??;
+
+        let timestamp = timestamp();
+        Ok(match output {
+            Execution::Completed(output) => match serde_json::from_slice(&output)? {
+                WasmOutput::Success { data } => FunctionResult::Success(Self::Output {
+                    execution_id: EXECUTION_ID.to_owned(),
+                    data,
+                    timestamp,
+                }),
+                WasmOutput::Failure { error } => FunctionResult::Failure(FunctionResultFailure {
+                    execution_id: EXECUTION_ID.to_owned(),
+                    error,
+                    timestamp,
+                }),
+            },
+            Execution::OutOfFuel => {
+                FunctionResult::Failure(FunctionResultFailure::killed_for_exceeding_limits(
+                    EXECUTION_ID,
+                    ExceededLimit::CpuTime,
+                    timestamp,
+                ))
+            }
+            Execution::Trapped(message) => FunctionResult::Failure(FunctionResultFailure {
+                execution_id: EXECUTION_ID.to_owned(),
+                error: FunctionResultFailureError {
+                    kind: TRAP_KIND.to_owned(),
+                    message,
+                },
+                timestamp,
+            }),
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Execution {
+    Completed(Vec<u8>),
+    OutOfFuel,
+    Trapped(String),
+}
+
+/// Instantiates `code` and calls its `handler` export with `input`, following the ABI described
+/// in the module documentation.
+///
+/// Traps raised by the module are the func failing, so are reported as an [`Execution`] rather
+/// than an error; errors are reserved for modules which can't be run at all.
+fn execute(
+    code: &[u8],
+    handler: &str,
+    input: &[u8],
+    fuel: u64,
+    memory_bytes: usize,
+) -> FuncBackendResult<Execution> {
+    let mut config = Config::default();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config);
+    let module = Module::new(&engine, code)?;
+
+    let mut store = Store::new(
+        &engine,
+        StoreLimitsBuilder::new().memory_size(memory_bytes).build(),
+    );
+    store.limiter(|limits: &mut StoreLimits| limits);
+    store.add_fuel(fuel).map_err(wasmi::Error::from)?;
+
+    let instance = match Linker::new(&engine)
+        .instantiate(&mut store, &module)
+        .and_then(|instance| instance.start(&mut store))
+    {
+        Ok(instance) => instance,
+        Err(wasmi::Error::Trap(trap)) => return Ok(trapped(trap)),
+        Err(err) => return Err(err.into()),
+    };
+
+    let memory = instance
+        .get_memory(&store, "memory")
+        .ok_or_else(|| FuncBackendError::WasmAbi("missing export `memory`".to_owned()))?;
+    let alloc = instance
+        .get_typed_func::<i32, i32>(&store, "alloc")
+        .map_err(|err| FuncBackendError::WasmAbi(format!("export `alloc`: {err}")))?;
+    let handler = instance
+        .get_typed_func::<(i32, i32), i64>(&store, handler)
+        .map_err(|err| FuncBackendError::WasmAbi(format!("export `{handler}`: {err}")))?;
+
+    let input_len = i32::try_from(input.len())
+        .map_err(|_| FuncBackendError::WasmAbi("arguments exceed 2GiB".to_owned()))?;
+    let input_ptr = match alloc.call(&mut store, input_len) {
+        Ok(ptr) => ptr,
+        Err(trap) => return Ok(trapped(trap)),
+    };
+    memory
+        .write(&mut store, input_ptr as u32 as usize, input)
+        .map_err(|err| FuncBackendError::WasmAbi(format!("writing arguments: {err}")))?;
+
+    let packed = match handler.call(&mut store, (input_ptr, input_len)) {
+        Ok(packed) => packed as u64,
+        Err(trap) => return Ok(trapped(trap)),
+    };
+    let (output_ptr, output_len) = ((packed >> 32) as usize, (packed as u32) as usize);
+
+    // The location comes from the module, so it's checked against its memory before anything is
+    // allocated for the output
+    let data = memory.data(&store);
+    let output = output_ptr
+        .checked_add(output_len)
+        .and_then(|output_end| data.get(output_ptr..output_end))
+        .ok_or_else(|| {
+            FuncBackendError::WasmAbi(format!(
+                "output of {output_len} bytes at {output_ptr} is outside the module's {} bytes of memory",
+                data.len()
+            ))
+        })?;
+
+    Ok(Execution::Completed(output.to_vec()))
+}
+
+fn trapped(trap: wasmi::core::Trap) -> Execution {
+    match trap.trap_code() {
+        Some(TrapCode::OutOfFuel) => Execution::OutOfFuel,
+        _ => Execution::Trapped(trap.to_string()),
+    }
+}
+
+fn timestamp() -> u64 {
+    u64::try_from(std::cmp::max(Utc::now().timestamp(), 0)).expect("timestamp not be negative")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compiled from:
+    ///
+    /// ```wat
+    /// (module
+    ///   (memory (export "memory") 1)
+    ///   (global $next (mut i32) (i32.const 27))
+    ///   (data (i32.const 0) "{\"status\":\"success\",\"data\":")
+    ///   (func (export "alloc") (param $len i32) (result i32)
+    ///     (global.get $next)
+    ///     (global.set $next (i32.add (global.get $next) (local.get $len))))
+    ///   ;; Appends `}` to the arguments, which `alloc` placed right after the data segment
+    ///   (func (export "echo") (param $ptr i32) (param $len i32) (result i64)
+    ///     (i32.store8 (i32.add (local.get $ptr) (local.get $len)) (i32.const 125))
+    ///     (i64.extend_i32_u (i32.add (i32.add (local.get $ptr) (local.get $len)) (i32.const 1))))
+    ///   (func (export "spin") (param i32 i32) (result i64)
+    ///     (loop $forever (br $forever))
+    ///     (unreachable)))
+    /// ```
+    const MODULE: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x0c, 0x02, 0x60, 0x01, 0x7f, 0x01,
+        0x7f, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7e, 0x03, 0x04, 0x03, 0x00, 0x01, 0x01, 0x05, 0x03,
+        0x01, 0x00, 0x01, 0x06, 0x06, 0x01, 0x7f, 0x01, 0x41, 0x1b, 0x0b, 0x07, 0x20, 0x04, 0x06,
+        0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, 0x02, 0x00, 0x05, 0x61, 0x6c, 0x6c, 0x6f, 0x63, 0x00,
+        0x00, 0x04, 0x65, 0x63, 0x68, 0x6f, 0x00, 0x01, 0x04, 0x73, 0x70, 0x69, 0x6e, 0x00, 0x02,
+        0x0a, 0x2d, 0x03, 0x0b, 0x00, 0x23, 0x00, 0x23, 0x00, 0x20, 0x00, 0x6a, 0x24, 0x00, 0x0b,
+        0x16, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6a, 0x41, 0xfd, 0x00, 0x3a, 0x00, 0x00, 0x20, 0x00,
+        0x20, 0x01, 0x6a, 0x41, 0x01, 0x6a, 0xad, 0x0b, 0x08, 0x00, 0x03, 0x40, 0x0c, 0x00, 0x0b,
+        0x00, 0x0b, 0x0b, 0x21, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x1b, 0x7b, 0x22, 0x73, 0x74, 0x61,
+        0x74, 0x75, 0x73, 0x22, 0x3a, 0x22, 0x73, 0x75, 0x63, 0x63, 0x65, 0x73, 0x73, 0x22, 0x2c,
+        0x22, 0x64, 0x61, 0x74, 0x61, 0x22, 0x3a,
+    ];
+
+    /// Compiled from:
+    ///
+    /// ```wat
+    /// (module
+    ///   (memory (export "memory") 1)
+    ///   (func (export "alloc") (param i32) (result i32)
+    ///     (i32.const 0))
+    ///   (func (export "huge") (param i32 i32) (result i64)
+    ///     (i64.const -1))
+    ///   (func (export "pastEnd") (param i32 i32) (result i64)
+    ///     (i64.const 0xffff00000002)))
+    /// ```
+    const OUT_OF_BOUNDS_MODULE: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x0c, 0x02, 0x60, 0x01, 0x7f, 0x01,
+        0x7f, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7e, 0x03, 0x04, 0x03, 0x00, 0x01, 0x01, 0x05, 0x03,
+        0x01, 0x00, 0x01, 0x07, 0x23, 0x04, 0x06, 0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, 0x02, 0x00,
+        0x05, 0x61, 0x6c, 0x6c, 0x6f, 0x63, 0x00, 0x00, 0x04, 0x68, 0x75, 0x67, 0x65, 0x00, 0x01,
+        0x07, 0x70, 0x61, 0x73, 0x74, 0x45, 0x6e, 0x64, 0x00, 0x02, 0x0a, 0x16, 0x03, 0x04, 0x00,
+        0x41, 0x00, 0x0b, 0x04, 0x00, 0x42, 0x7f, 0x0b, 0x0a, 0x00, 0x42, 0x82, 0x80, 0x80, 0x80,
+        0xf0, 0xff, 0x3f, 0x0b,
+    ];
+
+    const FUEL: u64 = 1_000_000;
+    const MEMORY_BYTES: usize = 1024 * 1024;
+
+    #[test]
+    fn passes_arguments_and_returns_output() {
+        let output = execute(
+            MODULE,
+            "echo",
+            br#"{"name":"starfield"}"#,
+            FUEL,
+            MEMORY_BYTES,
+        )
+        .expect("failed to execute");
+
+        assert_eq!(
+            Execution::Completed(br#"{"status":"success","data":{"name":"starfield"}}"#.to_vec()),
+            output
+        );
+    }
+
+    #[test]
+    fn runs_out_of_fuel() {
+        let output = execute(MODULE, "spin", b"{}", FUEL, MEMORY_BYTES).expect("failed to execute");
+
+        assert_eq!(Execution::OutOfFuel, output);
+    }
+
+    #[test]
+    fn missing_handler_is_an_error() {
+        let err = execute(MODULE, "missing", b"{}", FUEL, MEMORY_BYTES)
+            .expect_err("executed a missing handler");
+
+        assert!(matches!(err, FuncBackendError::WasmAbi(_)));
+    }
+
+    #[test]
+    fn output_outside_memory_is_an_error() {
+        for handler in ["huge", "pastEnd"] {
+            let err = execute(OUT_OF_BOUNDS_MODULE, handler, b"{}", FUEL, MEMORY_BYTES)
+                .expect_err("read output from outside the module's memory");
+
+            assert!(
+                matches!(err, FuncBackendError::WasmAbi(_)),
+                "{handler}: {err}"
+            );
+        }
+    }
+}
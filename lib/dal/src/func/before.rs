@@ -35,8 +35,12 @@ pub async fn before_funcs_for_component(
         func,
     } in standard_model::objects_from_rows(rows)?
     {
-        // Decrypt message from EncryptedSecret
-        let mut arg = encrypted_secret.decrypt(ctx).await?.message().into_inner();
+        // Decrypt message from EncryptedSecret, recording that this func used it
+        let mut arg = encrypted_secret
+            .decrypt_for_func(ctx, func.id, *component_id)
+            .await?
+            .message()
+            .into_inner();
         // Re-encrypt raw Value for transmission to Cyclone via Veritech
         encrypt_value_tree(&mut arg, ctx.encryption_key())?;
 
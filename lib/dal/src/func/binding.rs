@@ -23,6 +23,7 @@ use crate::{
         map::FuncBackendMap,
         object::FuncBackendObject,
         string::FuncBackendString,
+        wasm::FuncBackendWasm,
         FuncBackend, FuncDispatch, FuncDispatchContext, InvalidResolverFunctionTypeError,
     },
     TransactionsError, WsEvent, WsEventError, WsEventResult, WsPayload,
@@ -37,6 +38,7 @@ use crate::{DalContext, Tenancy};
 use super::{
     binding_return_value::{FuncBindingReturnValue, FuncBindingReturnValueError},
     execution::{FuncExecution, FuncExecutionError},
+    result_cache::{FuncResultCache, FuncResultCacheError},
     FuncId,
 };
 
@@ -59,6 +61,8 @@ pub enum FuncBindingError {
     FuncBindingReturnValue(#[from] FuncBindingReturnValueError),
     #[error("func execution tracking error: {0}")]
     FuncExecutionError(#[from] FuncExecutionError),
+    #[error("func result cache error: {0}")]
+    FuncResultCache(#[from] FuncResultCacheError),
     #[error("unable to retrieve func for func binding: {0:?}")]
     FuncNotFound(FuncBindingPk),
     #[error("history event error: {0}")]
@@ -209,15 +213,55 @@ impl FuncBinding {
     );
 
     // For a given [`FuncBinding`](Self), execute using veritech.
+    //
+    // Results of executing pure funcs are looked up in, and stored to, the
+    // [`FuncResultCache`](crate::func::result_cache::FuncResultCache).
+    #[instrument(
+        name = "func_binding.execute",
+        skip_all,
+        level = "debug",
+        fields(si.func.cache = Empty)
+    )]
     async fn execute(
         &self,
         ctx: &DalContext,
         before: Vec<BeforeFunction>,
     ) -> FuncBindingResult<FuncBindingReturnValue> {
+        let span = Span::current();
         let (func, execution, context, mut rx) = self.prepare_execution(ctx).await?;
-        let value = self
-            .execute_critical_section(func.clone(), context, before)
-            .await?;
+
+        // Before funcs carry freshly encrypted secrets, so executions using them never share a
+        // key and aren't worth caching
+        let cache_key = if *func.pure() && before.is_empty() {
+            Some(FuncResultCache::key(&func, &self.args)?)
+        } else {
+            None
+        };
+        let cached = match cache_key {
+            Some(key) => FuncResultCache::get(ctx, key).await?,
+            None => None,
+        };
+
+        let value = match cached {
+            Some(value) => {
+                span.record("si.func.cache", "hit");
+                debug!(func_id = %func.id(), "func result cache hit");
+                // Nothing was dispatched, so nothing will be sent down the output stream
+                drop(context);
+                value
+            }
+            None => {
+                let value = self
+                    .execute_critical_section(func.clone(), context, before)
+                    .await?;
+                if let Some(key) = cache_key {
+                    span.record("si.func.cache", "miss");
+                    debug!(func_id = %func.id(), "func result cache miss");
+                    FuncResultCache::insert(ctx, key, &func, &value).await?;
+                }
+                value
+            }
+        };
 
         let mut output = Vec::new();
         while let Some(output_stream) = rx.recv().await {
@@ -281,6 +325,9 @@ impl FuncBinding {
             FuncBackendKind::Object => FuncBackendObject::create_and_execute(&self.args).await,
             FuncBackendKind::String => FuncBackendString::create_and_execute(&self.args).await,
             FuncBackendKind::Unset => Ok((None, None)),
+            FuncBackendKind::Wasm => {
+                FuncBackendWasm::create_and_execute(context, &func, &self.args, before).await
+            }
             FuncBackendKind::Validation => {
                 unimplemented!("direct Validation function execution is deprecated")
             }
@@ -366,7 +413,8 @@ impl FuncBinding {
             | FuncBackendKind::Object
             | FuncBackendKind::String
             | FuncBackendKind::Unset
-            | FuncBackendKind::Validation => {}
+            | FuncBackendKind::Validation
+            | FuncBackendKind::Wasm => {}
 
             FuncBackendKind::JsAction
             | FuncBackendKind::JsAttribute
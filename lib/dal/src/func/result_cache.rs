@@ -0,0 +1,116 @@
+//! Caches the results of executing [`pure`](crate::Func::pure) [`Funcs`](crate::Func), so that
+//! executing one again with the same code and arguments doesn't need a round-trip through veritech.
+//!
+//! Entries are keyed by a [`Hash`] of everything that determines a pure func's result and scoped
+//! to the workspace that executed them. Entries for a func are removed when its code changes.
+
+use serde::Serialize;
+use si_data_pg::PgError;
+use si_hash::Hash;
+use telemetry::prelude::*;
+use thiserror::Error;
+
+use crate::{DalContext, Func, FuncBackendKind, FuncBackendResponseType, TransactionsError};
+use crate::{StandardModel, WorkspacePk};
+
+#[remain::sorted]
+#[derive(Error, Debug)]
+pub enum FuncResultCacheError {
+    #[error("pg error: {0}")]
+    Pg(#[from] PgError),
+    #[error("error serializing/deserializing json: {0}")]
+    SerdeJson(#[from] serde_json::Error),
+    #[error("transactions error: {0}")]
+    Transactions(#[from] TransactionsError),
+}
+
+pub type FuncResultCacheResult<T> = Result<T, FuncResultCacheError>;
+
+/// The unprocessed and processed values returned by executing a [`Func`](crate::Func).
+pub type FuncResultCacheValue = (Option<serde_json::Value>, Option<serde_json::Value>);
+
+/// Everything that determines the result of executing a pure [`Func`](crate::Func).
+#[derive(Serialize)]
+struct FuncResultCacheKeyInput<'a> {
+    backend_kind: &'a FuncBackendKind,
+    backend_response_type: &'a FuncBackendResponseType,
+    // Left out when unset, so keys for funcs run by the default lang server are unchanged
+    #[serde(skip_serializing_if = "Option::is_none")]
+    runtime: Option<&'a str>,
+    handler: Option<&'a str>,
+    code_base64: Option<&'a str>,
+    args: &'a serde_json::Value,
+}
+
+/// A cache of the results of executing [`pure`](crate::Func::pure) [`Funcs`](crate::Func).
+pub struct FuncResultCache;
+
+impl FuncResultCache {
+    /// Computes the cache key for executing `func` with `args`.
+    pub fn key(func: &Func, args: &serde_json::Value) -> FuncResultCacheResult<Hash> {
+        let input = serde_json::to_vec(&FuncResultCacheKeyInput {
+            backend_kind: func.backend_kind(),
+            backend_response_type: func.backend_response_type(),
+            runtime: func.runtime(),
+            handler: func.handler(),
+            code_base64: func.code_base64(),
+            args,
+        })?;
+        Ok(Hash::new(&input))
+    }
+
+    #[instrument(skip_all, level = "debug", fields(si.func.cache.key = %key))]
+    pub async fn get(
+        ctx: &DalContext,
+        key: Hash,
+    ) -> FuncResultCacheResult<Option<FuncResultCacheValue>> {
+        let row = ctx
+            .txns()
+            .await?
+            .pg()
+            .query_opt(
+                "SELECT unprocessed_value, value
+                FROM func_result_cache
+                WHERE key = $1 AND tenancy_workspace_pk = $2",
+                &[&key.to_string(), &workspace_pk(ctx)],
+            )
+            .await?;
+
+        Ok(match row {
+            Some(row) => Some((row.try_get("unprocessed_value")?, row.try_get("value")?)),
+            None => None,
+        })
+    }
+
+    #[instrument(skip_all, level = "debug", fields(si.func.cache.key = %key))]
+    pub async fn insert(
+        ctx: &DalContext,
+        key: Hash,
+        func: &Func,
+        (unprocessed_value, value): &FuncResultCacheValue,
+    ) -> FuncResultCacheResult<()> {
+        ctx.txns()
+            .await?
+            .pg()
+            .execute(
+                "INSERT INTO func_result_cache
+                    (key, tenancy_workspace_pk, func_id, code_sha256, unprocessed_value, value)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (key, tenancy_workspace_pk) DO NOTHING",
+                &[
+                    &key.to_string(),
+                    &workspace_pk(ctx),
+                    func.id(),
+                    &func.code_sha256(),
+                    unprocessed_value,
+                    value,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+fn workspace_pk(ctx: &DalContext) -> WorkspacePk {
+    ctx.tenancy().workspace_pk().unwrap_or(WorkspacePk::NONE)
+}
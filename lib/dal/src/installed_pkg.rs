@@ -54,6 +54,8 @@ pub struct InstalledPkg {
     id: InstalledPkgId,
     name: String,
     root_hash: String,
+    /// The version the package was built with, if it was installed after versions were recorded.
+    version: Option<String>,
     #[serde(flatten)]
     tenancy: Tenancy,
     #[serde(flatten)]
@@ -77,6 +79,7 @@ impl InstalledPkg {
         ctx: &DalContext,
         name: impl AsRef<str>,
         root_hash: impl AsRef<str>,
+        version: Option<&str>,
     ) -> InstalledPkgResult<Self> {
         let name = name.as_ref();
         let root_hash = root_hash.as_ref();
@@ -85,8 +88,8 @@ impl InstalledPkg {
             .await?
             .pg()
             .query_one(
-                "SELECT object FROM installed_pkg_create_v1($1, $2, $3, $4)",
-                &[ctx.tenancy(), ctx.visibility(), &name, &root_hash],
+                "SELECT object FROM installed_pkg_create_v2($1, $2, $3, $4, $5)",
+                &[ctx.tenancy(), ctx.visibility(), &name, &root_hash, &version],
             )
             .await?;
         let object = standard_model::finish_create_from_row(ctx, row).await?;
@@ -95,8 +98,14 @@ impl InstalledPkg {
 
     standard_model_accessor!(name, String, InstalledPkgResult);
     standard_model_accessor!(root_hash, String, InstalledPkgResult);
+    standard_model_accessor!(version, Option<String>, InstalledPkgResult);
 
     pub async fn find_by_hash(ctx: &DalContext, hash: &str) -> InstalledPkgResult<Option<Self>> {
         Ok(Self::find_by_attr(ctx, "root_hash", &hash).await?.pop())
     }
+
+    /// Finds every installed version of the package with the given name.
+    pub async fn find_by_name(ctx: &DalContext, name: &str) -> InstalledPkgResult<Vec<Self>> {
+        Ok(Self::find_by_attr(ctx, "name", &name).await?)
+    }
 }
@@ -245,6 +245,14 @@ impl DependentValuesUpdate {
                         // the pg_pool to do writes
                         ctx.rollback().await?;
                     }
+                    council_server::Response::LeaseRevoked { node_id } => {
+                        // Council tells us when the node has been processed, either by another
+                        // job or by us reporting it late, and only offers it to us again if our
+                        // inputs turn out to be out of date, so there is nothing to do but wait.
+                        warn!(?node_id, job_id = ?self.job_id(), "Council revoked our processing lease for node");
+                    }
+                    // Handled by the council client itself, which re-sends our graph.
+                    council_server::Response::Reregister => {}
                     council_server::Response::Shutdown => break,
                 },
                 // FIXME: reconnect
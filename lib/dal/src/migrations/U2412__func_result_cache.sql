@@ -0,0 +1,40 @@
+-- Funcs which always return the same result for the same code and arguments can opt in to having
+-- their results cached.
+ALTER TABLE funcs
+    ADD COLUMN pure bool NOT NULL DEFAULT FALSE;
+
+-- The results of executing pure funcs, keyed by a hash of everything that determines the result.
+-- Entries aren't subject to visibility, as the same code and arguments give the same result in
+-- every change set.
+CREATE TABLE func_result_cache
+(
+    key                  text                     NOT NULL,
+    tenancy_workspace_pk ident                    NOT NULL,
+    func_id              ident                    NOT NULL,
+    code_sha256          text                     NOT NULL,
+    unprocessed_value    jsonb,
+    value                jsonb,
+    created_at           timestamp with time zone NOT NULL DEFAULT CLOCK_TIMESTAMP(),
+    PRIMARY KEY (key, tenancy_workspace_pk)
+);
+CREATE INDEX func_result_cache_func_id_code_sha256 ON func_result_cache (func_id, code_sha256);
+
+-- The cache key covers the func's code, so stale entries are never returned, but there's no point
+-- keeping entries around for code that has been replaced.
+CREATE OR REPLACE FUNCTION func_result_cache_invalidate_trigger_v1() RETURNS trigger AS
+$$
+BEGIN
+    DELETE
+    FROM func_result_cache
+    WHERE func_id = OLD.id
+      AND code_sha256 = OLD.code_sha256;
+    RETURN NULL;
+END;
+$$ LANGUAGE PLPGSQL;
+
+CREATE TRIGGER func_result_cache_invalidate
+    AFTER UPDATE OF code_base64
+    ON funcs
+    FOR EACH ROW
+    WHEN (OLD.code_base64 IS DISTINCT FROM NEW.code_base64)
+EXECUTE FUNCTION func_result_cache_invalidate_trigger_v1();
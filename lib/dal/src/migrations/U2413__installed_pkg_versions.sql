@@ -0,0 +1,36 @@
+-- Record the version of each installed package so that dependencies on it can be resolved. Packages
+-- installed before this have no recorded version.
+ALTER TABLE installed_pkgs
+    ADD COLUMN version text;
+
+CREATE INDEX ON installed_pkgs (name);
+
+CREATE OR REPLACE FUNCTION installed_pkg_create_v2(
+    this_tenancy jsonb,
+    this_visibility jsonb,
+    this_name text,
+    this_root_hash text,
+    this_version text,
+    OUT object json) AS
+$$
+DECLARE
+    this_tenancy_record    tenancy_record_v1;
+    this_visibility_record visibility_record_v1;
+    this_new_row           installed_pkgs%ROWTYPE;
+BEGIN
+    this_tenancy_record := tenancy_json_to_columns_v1(this_tenancy);
+    this_visibility_record := visibility_json_to_columns_v1(this_visibility);
+
+    INSERT INTO installed_pkgs (
+        tenancy_workspace_pk, visibility_change_set_pk,
+        name, root_hash, version
+    ) VALUES (
+        this_tenancy_record.tenancy_workspace_pk,
+        this_visibility_record.visibility_change_set_pk,
+        this_name, this_root_hash, this_version
+    )
+    RETURNING * INTO this_new_row;
+
+    object := row_to_json(this_new_row);
+END;
+$$ LANGUAGE PLPGSQL VOLATILE;
@@ -0,0 +1,47 @@
+-- Every decryption of a secret, so that operators can tell which functions use a secret and when it
+-- was last used. Rows are only ever inserted.
+CREATE TABLE secret_usages
+(
+    pk                   ident primary key        default ident_create_v1(),
+    tenancy_workspace_pk ident,
+    secret_id            ident                    NOT NULL,
+    func_id              ident,
+    component_id         ident,
+    change_set_pk        ident                    NOT NULL,
+    actor                jsonb                    NOT NULL,
+    used_at              timestamp with time zone NOT NULL DEFAULT CLOCK_TIMESTAMP()
+);
+CREATE INDEX ON secret_usages (secret_id, used_at DESC);
+
+CREATE OR REPLACE FUNCTION secret_usage_create_v1(
+    this_tenancy jsonb,
+    this_secret_id ident,
+    this_func_id ident,
+    this_component_id ident,
+    this_change_set_pk ident,
+    this_actor jsonb,
+    OUT object json) AS
+$$
+DECLARE
+    this_tenancy_record tenancy_record_v1;
+    this_new_row        secret_usages%ROWTYPE;
+BEGIN
+    this_tenancy_record := tenancy_json_to_columns_v1(this_tenancy);
+
+    INSERT INTO secret_usages (tenancy_workspace_pk,
+                               secret_id,
+                               func_id,
+                               component_id,
+                               change_set_pk,
+                               actor)
+    VALUES (this_tenancy_record.tenancy_workspace_pk,
+            this_secret_id,
+            this_func_id,
+            this_component_id,
+            this_change_set_pk,
+            this_actor)
+    RETURNING * INTO this_new_row;
+
+    object := row_to_json(this_new_row);
+END;
+$$ LANGUAGE PLPGSQL VOLATILE;
@@ -0,0 +1,4 @@
+-- The runtime a func's code is written for, selecting the lang server cyclone executes it with.
+-- Funcs without one are JavaScript run by the default lang server.
+ALTER TABLE funcs
+    ADD COLUMN runtime text;
@@ -0,0 +1,4 @@
+-- The resource ceilings cyclone enforces when executing a func, as serialized `ExecutionLimits`.
+-- Funcs without any run unlimited.
+ALTER TABLE funcs
+    ADD COLUMN limits jsonb;
@@ -10,6 +10,7 @@ pub use import::{
     attach_resource_payload_to_value, import_pkg, import_pkg_from_pkg, ImportAttributeSkip,
     ImportEdgeSkip, ImportOptions, ImportSkips,
 };
+use module_index_client::IndexClientError;
 use si_pkg::{FuncSpecBackendKind, FuncSpecBackendResponseType, SiPkgError, SpecError};
 
 use crate::authentication_prototype::AuthenticationPrototypeError;
@@ -33,6 +34,7 @@ use crate::{
     WsEvent, WsEventResult, WsPayload,
 };
 
+mod dependency;
 mod export;
 mod import;
 
@@ -101,6 +103,14 @@ pub enum PkgError {
     ConflictingMapKeyPrototypes(PropId),
     #[error("expected data on an SiPkg node, but none found: {0}")]
     DataNotFound(String),
+    #[error("dependency {0} {1} conflicts with installed version(s): {2}")]
+    DependencyConflict(String, String, String),
+    #[error("dependency cycle detected: {0}")]
+    DependencyCycle(String),
+    #[error("dependency {0} {1} is not installed and no module index is configured to fetch it")]
+    DependencyModuleIndexNotConfigured(String, String),
+    #[error("no published module satisfies dependency {0} {1}")]
+    DependencyNotFound(String, String),
     #[error(transparent)]
     Edge(#[from] EdgeError),
     #[error("edge refers to component not in export: {0}")]
@@ -181,6 +191,8 @@ pub enum PkgError {
     MissingSocketName(String, SocketEdgeKind),
     #[error("Unique id missing for node in workspace backup: {0}")]
     MissingUniqueIdForNode(String),
+    #[error("Module index error: {0}")]
+    ModuleIndexClient(#[from] IndexClientError),
     #[error(transparent)]
     Node(#[from] NodeError),
     #[error("Package with that hash already installed: {0}")]
@@ -263,6 +275,7 @@ impl From<FuncBackendKind> for FuncSpecBackendKind {
             FuncBackendKind::String => Self::String,
             FuncBackendKind::Unset => Self::Unset,
             FuncBackendKind::Validation => Self::Validation,
+            FuncBackendKind::Wasm => Self::Wasm,
             FuncBackendKind::JsAuthentication => Self::JsAuthentication,
         }
     }
@@ -286,6 +299,7 @@ impl From<FuncSpecBackendKind> for FuncBackendKind {
             FuncSpecBackendKind::String => Self::String,
             FuncSpecBackendKind::Unset => Self::Unset,
             FuncSpecBackendKind::Validation => Self::Validation,
+            FuncSpecBackendKind::Wasm => Self::Wasm,
             FuncSpecBackendKind::JsAuthentication => Self::JsAuthentication,
         }
     }
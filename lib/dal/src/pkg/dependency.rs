@@ -0,0 +1,178 @@
+use async_recursion::async_recursion;
+use module_index_client::{IndexClient, ModuleDetailsResponse, ModuleKind, ModuleSearchRequest};
+use semver::Version;
+use si_pkg::{SiPkg, SiPkgDependency};
+use telemetry::prelude::*;
+use ulid::Ulid;
+
+use crate::{installed_pkg::InstalledPkg, DalContext};
+
+use super::{import::import_dependency, ImportOptions, PkgError, PkgResult};
+
+/// Makes sure every dependency declared by `pkg` is installed, installing missing ones from the
+/// module index, and returns the dependency packages so their funcs can be referenced while
+/// importing `pkg`.
+///
+/// A dependency is satisfied by an installed module with the same name whose version matches the
+/// requirement. If a module with that name is installed but no installed version matches, the
+/// dependency conflicts and the install is refused rather than installing a second copy.
+/// `chain` holds the names of the modules currently being installed and is used to refuse
+/// dependency cycles.
+#[async_recursion]
+pub(super) async fn resolve_dependencies(
+    ctx: &DalContext,
+    pkg: &SiPkg,
+    options: &ImportOptions,
+    chain: &[String],
+) -> PkgResult<Vec<SiPkg>> {
+    let mut dependency_pkgs = vec![];
+
+    for dependency in pkg.dependencies()? {
+        if chain.iter().any(|name| name == dependency.name()) {
+            let mut cycle = chain.to_vec();
+            cycle.push(dependency.name().to_owned());
+            return Err(PkgError::DependencyCycle(cycle.join(" -> ")));
+        }
+
+        let installed = InstalledPkg::find_by_name(ctx, dependency.name()).await?;
+        if !installed.is_empty() {
+            let satisfying = installed.iter().find(|installed_pkg| {
+                installed_pkg
+                    .version()
+                    .and_then(|version| Version::parse(version).ok())
+                    .map_or(false, |version| dependency.matches(&version))
+            });
+
+            match satisfying {
+                Some(installed_pkg) => {
+                    debug!(
+                        name = dependency.name(),
+                        version_req = %dependency.version_req(),
+                        version = installed_pkg.version(),
+                        "dependency already installed"
+                    );
+                    if let Some(dependency_pkg) =
+                        fetch_installed(options, installed_pkg.name(), installed_pkg.root_hash())
+                            .await?
+                    {
+                        dependency_pkgs.push(dependency_pkg);
+                    }
+                    continue;
+                }
+                None => {
+                    let installed_versions: Vec<&str> = installed
+                        .iter()
+                        .map(|installed_pkg| installed_pkg.version().unwrap_or("unversioned"))
+                        .collect();
+                    return Err(PkgError::DependencyConflict(
+                        dependency.name().to_owned(),
+                        dependency.version_req().to_string(),
+                        installed_versions.join(", "),
+                    ));
+                }
+            }
+        }
+
+        let client = options.module_index_client.as_ref().ok_or_else(|| {
+            PkgError::DependencyModuleIndexNotConfigured(
+                dependency.name().to_owned(),
+                dependency.version_req().to_string(),
+            )
+        })?;
+
+        let module = find_matching_module(client, &dependency)
+            .await?
+            .ok_or_else(|| {
+                PkgError::DependencyNotFound(
+                    dependency.name().to_owned(),
+                    dependency.version_req().to_string(),
+                )
+            })?;
+
+        let pkg_data = client
+            .download_module(Ulid::from_string(&module.id)?)
+            .await?;
+        let dependency_pkg = SiPkg::load_from_bytes(pkg_data)?;
+
+        info!(
+            name = dependency.name(),
+            version = module.version(),
+            "installing dependency from module index"
+        );
+        import_dependency(ctx, &dependency_pkg, options, chain).await?;
+
+        dependency_pkgs.push(dependency_pkg);
+    }
+
+    Ok(dependency_pkgs)
+}
+
+/// Finds the highest published version of a module satisfying `dependency`.
+async fn find_matching_module(
+    client: &IndexClient,
+    dependency: &SiPkgDependency<'_>,
+) -> PkgResult<Option<ModuleDetailsResponse>> {
+    let modules = modules_named(client, dependency.name()).await?;
+
+    Ok(modules
+        .into_iter()
+        .filter(|module| module.name == dependency.name())
+        .filter_map(|module| {
+            let version = Version::parse(module.version()?).ok()?;
+            dependency.matches(&version).then_some((version, module))
+        })
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, module)| module))
+}
+
+/// Fetches the package for an already installed module from the module index so that its funcs
+/// can be referenced. Returns `None` when there is no module index or it no longer has the
+/// package, in which case the dependent module has to carry the funcs it uses itself.
+async fn fetch_installed(
+    options: &ImportOptions,
+    name: &str,
+    root_hash: &str,
+) -> PkgResult<Option<SiPkg>> {
+    let client = match &options.module_index_client {
+        Some(client) => client,
+        None => return Ok(None),
+    };
+
+    let module = match modules_named(client, name)
+        .await?
+        .into_iter()
+        .find(|module| module.latest_hash == root_hash)
+    {
+        Some(module) => module,
+        None => return Ok(None),
+    };
+
+    let pkg_data = client
+        .download_module(Ulid::from_string(&module.id)?)
+        .await?;
+
+    Ok(Some(SiPkg::load_from_bytes(pkg_data)?))
+}
+
+/// Lists every published module visible to the client with exactly the given name, builtins
+/// included.
+async fn modules_named(client: &IndexClient, name: &str) -> PkgResult<Vec<ModuleDetailsResponse>> {
+    let mut search = ModuleSearchRequest {
+        name: Some(name.to_owned()),
+        kind: Some(ModuleKind::Module),
+        ..Default::default()
+    };
+    let mut modules = vec![];
+
+    loop {
+        let page = client.search_modules(&search).await?;
+        modules.extend(page.modules);
+
+        match page.next_cursor {
+            Some(cursor) => search.cursor = Some(cursor),
+            None => break,
+        }
+    }
+
+    Ok(modules)
+}
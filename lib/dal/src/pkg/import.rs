@@ -5,15 +5,17 @@ use std::{
 };
 
 use chrono::Utc;
+use module_index_client::IndexClient;
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 
 use si_pkg::{
     AttrFuncInputSpec, AttributeValuePath, AttributeValueSpec, ComponentSpec, ComponentSpecVariant,
-    EdgeSpec, EdgeSpecKind, FuncArgumentSpec, FuncSpec, FuncSpecData, SchemaVariantSpecPropRoot,
-    SiPkg, SiPkgActionFunc, SiPkgAttrFuncInputView, SiPkgAuthFunc, SiPkgComponent, SiPkgEdge,
-    SiPkgError, SiPkgFunc, SiPkgKind, SiPkgLeafFunction, SiPkgMetadata, SiPkgProp, SiPkgPropData,
-    SiPkgSchema, SiPkgSchemaData, SiPkgSchemaVariant, SiPkgSocket, SiPkgSocketData, SocketSpecKind,
+    EdgeSpec, EdgeSpecKind, FuncArgumentSpec, FuncSpec, FuncSpecData, PkgVerifyingKey,
+    SchemaVariantSpecPropRoot, SiPkg, SiPkgActionFunc, SiPkgAttrFuncInputView, SiPkgAuthFunc,
+    SiPkgComponent, SiPkgEdge, SiPkgError, SiPkgFunc, SiPkgKind, SiPkgLeafFunction, SiPkgMetadata,
+    SiPkgProp, SiPkgPropData, SiPkgSchema, SiPkgSchemaData, SiPkgSchemaVariant, SiPkgSocket,
+    SiPkgSocketData, SocketSpecKind,
 };
 use telemetry::prelude::*;
 
@@ -32,7 +34,7 @@ use crate::{
         InstalledPkg, InstalledPkgAsset, InstalledPkgAssetKind, InstalledPkgAssetTyped,
         InstalledPkgId,
     },
-    pkg::PkgExporter,
+    pkg::{dependency::resolve_dependencies, PkgExporter},
     prop::PropPath,
     schema::{
         variant::{
@@ -80,6 +82,12 @@ pub struct ImportOptions {
     /// If set to `true` then we will set the functions to a builtin
     /// in the UI. They will be marked as such.
     pub is_builtin: bool,
+    /// If set, the package must be signed by one of these keys or it will be refused. Packages
+    /// carrying a signature which doesn't match their contents are refused regardless.
+    pub trusted_signing_keys: Option<Vec<PkgVerifyingKey>>,
+    /// If set, dependencies which are not already installed are fetched from this module index
+    /// and installed before the package itself.
+    pub module_index_client: Option<IndexClient>,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -1283,12 +1291,67 @@ pub async fn import_pkg_from_pkg(
     Option<InstalledPkgId>,
     Vec<SchemaVariantId>,
     Option<Vec<ImportSkips>>,
+)> {
+    let options = options.unwrap_or_default();
+
+    import_pkg_from_pkg_inner(
+        ctx,
+        pkg,
+        &options,
+        override_builtin_schema_feature_flag,
+        &[],
+    )
+    .await
+}
+
+/// Installs a module required by another module being installed. Dependencies are always
+/// recorded as installed modules so that later installs can find them.
+pub(super) async fn import_dependency(
+    ctx: &DalContext,
+    pkg: &SiPkg,
+    options: &ImportOptions,
+    chain: &[String],
+) -> PkgResult<()> {
+    let options = ImportOptions {
+        schemas: None,
+        skip_import_funcs: None,
+        no_record: false,
+        is_builtin: options.is_builtin,
+        trusted_signing_keys: options.trusted_signing_keys.clone(),
+        module_index_client: options.module_index_client.clone(),
+    };
+
+    import_pkg_from_pkg_inner(ctx, pkg, &options, false, chain).await?;
+
+    Ok(())
+}
+
+async fn import_pkg_from_pkg_inner(
+    ctx: &DalContext,
+    pkg: &SiPkg,
+    options: &ImportOptions,
+    override_builtin_schema_feature_flag: bool,
+    chain: &[String],
+) -> PkgResult<(
+    Option<InstalledPkgId>,
+    Vec<SchemaVariantId>,
+    Option<Vec<ImportSkips>>,
 )> {
     // We have to write the installed_pkg row first, so that we have an id, and rely on transaction
     // semantics to remove the row if anything in the installation process fails
     let root_hash = pkg.hash()?.to_string();
 
-    let options = options.unwrap_or_default();
+    match &options.trusted_signing_keys {
+        Some(trusted_signing_keys) => {
+            let signature = pkg.verify_trusted(trusted_signing_keys)?;
+            debug!(
+                signer = signature.signer(),
+                key_id = %signature.key_id(),
+                "package signature trusted"
+            );
+        }
+        None => pkg.verify()?,
+    }
 
     if InstalledPkg::find_by_hash(ctx, &root_hash).await?.is_some() {
         return Err(PkgError::PackageAlreadyInstalled(root_hash));
@@ -1296,13 +1359,22 @@ pub async fn import_pkg_from_pkg(
 
     let metadata = pkg.metadata()?;
 
+    let mut dependency_chain = chain.to_vec();
+    dependency_chain.push(metadata.name().to_owned());
+    let dependency_pkgs = resolve_dependencies(ctx, pkg, options, &dependency_chain).await?;
+
     let installed_pkg_id = if options.no_record {
         None
     } else {
         Some(
-            *InstalledPkg::new(ctx, metadata.name(), pkg.hash()?.to_string())
-                .await?
-                .id(),
+            *InstalledPkg::new(
+                ctx,
+                metadata.name(),
+                pkg.hash()?.to_string(),
+                Some(metadata.version()),
+            )
+            .await?
+            .id(),
         )
     };
 
@@ -1310,6 +1382,16 @@ pub async fn import_pkg_from_pkg(
 
     match metadata.kind() {
         SiPkgKind::Module => {
+            for dependency_pkg in &dependency_pkgs {
+                seed_dependency_funcs(
+                    ctx,
+                    ctx.visibility().change_set_pk,
+                    dependency_pkg,
+                    &mut change_set_things,
+                )
+                .await?;
+            }
+
             let (installed_schema_variant_ids, _, _) = import_change_set(
                 ctx,
                 ctx.visibility().change_set_pk,
@@ -1320,7 +1402,7 @@ pub async fn import_pkg_from_pkg(
                 &[],
                 installed_pkg_id,
                 &mut change_set_things,
-                &options,
+                options,
                 override_builtin_schema_feature_flag,
             )
             .await?;
@@ -1364,7 +1446,7 @@ pub async fn import_pkg_from_pkg(
                 &default_change_set.edges()?,
                 installed_pkg_id,
                 &mut change_set_things,
-                &options,
+                options,
                 override_builtin_schema_feature_flag,
             )
             .await?;
@@ -1396,7 +1478,7 @@ pub async fn import_pkg_from_pkg(
                     &change_set.edges()?,
                     installed_pkg_id,
                     &mut change_set_things,
-                    &options,
+                    options,
                     override_builtin_schema_feature_flag,
                 )
                 .await?;
@@ -1424,12 +1506,13 @@ pub async fn import_pkg_from_pkg(
 pub async fn import_pkg(
     ctx: &DalContext,
     pkg_file_path: impl AsRef<Path>,
+    options: Option<ImportOptions>,
     override_builtin_schema_feature_flag: bool,
 ) -> PkgResult<SiPkg> {
     println!("Importing package from {:?}", pkg_file_path.as_ref());
     let pkg = SiPkg::load_from_file(&pkg_file_path).await?;
 
-    import_pkg_from_pkg(ctx, &pkg, None, override_builtin_schema_feature_flag).await?;
+    import_pkg_from_pkg(ctx, &pkg, options, override_builtin_schema_feature_flag).await?;
 
     Ok(pkg)
 }
@@ -1490,6 +1573,45 @@ async fn update_func(
     Ok(())
 }
 
+/// Makes the funcs installed by a dependency available to the package being imported under the
+/// unique ids the dependency gave them, so that the package can refer to them without carrying
+/// its own copies.
+async fn seed_dependency_funcs(
+    ctx: &DalContext,
+    change_set_pk: ChangeSetPk,
+    dependency_pkg: &SiPkg,
+    thing_map: &mut ThingMap,
+) -> PkgResult<()> {
+    for func_spec in dependency_pkg.funcs()? {
+        let installed_func_record = match InstalledPkgAsset::list_for_kind_and_hash(
+            ctx,
+            InstalledPkgAssetKind::Func,
+            &func_spec.hash().to_string(),
+        )
+        .await?
+        .pop()
+        {
+            Some(record) => record,
+            None => continue,
+        };
+
+        if let InstalledPkgAssetTyped::Func { id, .. } =
+            installed_func_record.as_installed_func()?
+        {
+            let func = Func::get_by_id(ctx, &id)
+                .await?
+                .ok_or(PkgError::InstalledFuncMissing(id))?;
+            thing_map.insert(
+                change_set_pk,
+                func_spec.unique_id().to_owned(),
+                Thing::Func(func),
+            );
+        }
+    }
+
+    Ok(())
+}
+
 async fn import_func(
     ctx: &DalContext,
     change_set_pk: ChangeSetPk,
@@ -1,6 +1,7 @@
 use std::fmt;
 
 use base64::{engine::general_purpose, Engine};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use si_crypto::{SymmetricCryptoError, SymmetricCryptoService, SymmetricNonce};
@@ -12,7 +13,7 @@ use sodiumoxide::crypto::{
 use strum::{AsRefStr, Display, EnumString};
 use thiserror::Error;
 
-use si_data_pg::PgError;
+use si_data_pg::{PgError, PgPoolError};
 use telemetry::prelude::*;
 use veritech_client::SensitiveContainer;
 
@@ -24,11 +25,20 @@ use crate::{
     property_editor::schema::PropertyEditorPropWidgetKind,
     serde_impls::{base64_bytes_serde, nonce_serde},
     standard_model::{self, objects_from_rows, TypeHint},
-    standard_model_accessor, standard_model_accessor_ro, ActorView, DalContext, HistoryActor,
-    HistoryEvent, HistoryEventError, KeyPair, KeyPairError, StandardModel, StandardModelError,
-    Tenancy, Timestamp, TransactionsError, UserPk, Visibility,
+    standard_model_accessor, standard_model_accessor_ro, ActorView, ComponentId, DalContext,
+    FuncId, HistoryActor, HistoryEvent, HistoryEventError, KeyPair, KeyPairError, StandardModel,
+    StandardModelError, Tenancy, Timestamp, TransactionsError, UserPk, Visibility,
 };
 
+pub mod backend;
+pub mod usage;
+
+pub use backend::{
+    SecretBackend, SecretBackendError, SecretBackendKind, SecretBackends, SecretBackendsConfig,
+    SecretReference,
+};
+pub use usage::{SecretUsage, SecretUsagePk, SecretUsageView};
+
 const LIST_SECRET_DEFINITIONS: &str = include_str!("queries/secrets/list_secret_definitions.sql");
 
 /// Error type for Secrets.
@@ -41,14 +51,28 @@ pub enum SecretError {
     DeserializeMessage(#[source] serde_json::Error),
     #[error("history event error: {0}")]
     HistoryEvent(#[from] HistoryEventError),
+    #[error("secret references must point to an external backend, not {0}")]
+    InvalidReferenceBackend(SecretBackendKind),
     #[error("key pair error: {0}")]
     KeyPair(#[from] KeyPairError),
     #[error("key pair not found for secret")]
     KeyPairNotFound,
     #[error("pg error: {0}")]
     Pg(#[from] PgError),
+    #[error("pg pool error: {0}")]
+    PgPool(#[from] PgPoolError),
+    #[error("secret references can only be used within a workspace")]
+    ReferenceWithoutWorkspace,
+    #[error("secret backend error: {0}")]
+    SecretBackend(#[from] SecretBackendError),
+    #[error("secret backend not configured: {0}")]
+    SecretBackendNotConfigured(SecretBackendKind),
     #[error("secret not found: {0}")]
     SecretNotFound(SecretId),
+    #[error("secret reference serde error: {0}")]
+    SecretReferenceSerde(#[source] serde_json::Error),
+    #[error("error serializing/deserializing json: {0}")]
+    SerdeJson(#[from] serde_json::Error),
     #[error("standard model error: {0}")]
     StandardModelError(#[from] StandardModelError),
     #[error("symmetric crypto error: {0}")]
@@ -75,6 +99,8 @@ pub struct Secret {
     key_pair_pk: KeyPairPk,
     definition: String,
     description: Option<String>,
+    #[serde(default)]
+    backend_kind: SecretBackendKind,
     #[serde(flatten)]
     tenancy: Tenancy,
     #[serde(flatten)]
@@ -130,6 +156,7 @@ impl Secret {
     // Once created, these object fields are to be considered immutable
     standard_model_accessor_ro!(definition, String);
     standard_model_accessor_ro!(description, Option<String>);
+    standard_model_accessor_ro!(backend_kind, SecretBackendKind);
 
     pub async fn key_pair(&self, ctx: &DalContext) -> SecretResult<KeyPair> {
         Ok(KeyPair::get_by_pk(ctx, self.key_pair_pk).await?)
@@ -143,12 +170,27 @@ pub struct SecretView {
     pub name: String,
     pub definition: String,
     pub description: Option<String>,
+    pub backend_kind: SecretBackendKind,
     pub created_info: HistoryEventMetadata,
     pub updated_info: Option<HistoryEventMetadata>,
+    /// When the secret was last decrypted for use by a function
+    pub last_used_at: Option<DateTime<Utc>>,
 }
 
 impl SecretView {
     pub async fn from_secret(ctx: &DalContext, secret: Secret) -> SecretResult<Self> {
+        let last_used_at = SecretUsage::last_used_at(ctx, secret.id).await?;
+        Self::from_secret_last_used_at(ctx, secret, last_used_at).await
+    }
+
+    /// Creates a view of the secret like [`from_secret`](Self::from_secret), with when it was
+    /// last used already looked up (e.g. with [`SecretUsage::last_used_at_by_secret`] when listing
+    /// secrets).
+    pub async fn from_secret_last_used_at(
+        ctx: &DalContext,
+        secret: Secret,
+        last_used_at: Option<DateTime<Utc>>,
+    ) -> SecretResult<Self> {
         let created_info = {
             let actor = match secret.created_by {
                 None => HistoryActor::SystemInit,
@@ -186,8 +228,10 @@ impl SecretView {
             name: secret.name,
             definition: secret.definition,
             description: secret.description,
+            backend_kind: secret.backend_kind,
             created_info,
             updated_info,
+            last_used_at,
         })
     }
 }
@@ -201,6 +245,7 @@ impl From<EncryptedSecret> for Secret {
             key_pair_pk: value.key_pair_pk,
             definition: value.definition,
             description: value.description,
+            backend_kind: value.backend_kind,
             tenancy: value.tenancy,
             timestamp: value.timestamp,
             created_by: value.created_by,
@@ -215,6 +260,9 @@ impl From<EncryptedSecret> for Secret {
 /// This type contains the raw encrypted payload as well as the necessary encryption metadata and
 /// should therefore should *only* be used internally when decrypting secrets for use by Cyclone.
 ///
+/// For a secret stored in an external [`SecretBackend`], the payload is its [`SecretReference`]
+/// rather than the secret itself.
+///
 /// NOTE: Other than creating a new encrypted secret, any external API will likely want to use
 /// the [`Secret`] type which does not expose extra encryption information.
 #[derive(Clone, Deserialize, Serialize)]
@@ -232,6 +280,8 @@ pub struct EncryptedSecret {
     crypted: Vec<u8>,
     version: SecretVersion,
     algorithm: SecretAlgorithm,
+    #[serde(default)]
+    backend_kind: SecretBackendKind,
     #[serde(flatten)]
     tenancy: Tenancy,
     #[serde(flatten)]
@@ -252,6 +302,7 @@ impl fmt::Debug for EncryptedSecret {
             .field("description", &self.description)
             .field("version", &self.version)
             .field("algorithm", &self.algorithm)
+            .field("backend_kind", &self.backend_kind)
             .field("key_hash", &self.key_hash)
             .field("tenancy", &self.tenancy)
             .field("timestamp", &self.timestamp)
@@ -282,8 +333,62 @@ impl EncryptedSecret {
         version: SecretVersion,
         algorithm: SecretAlgorithm,
     ) -> SecretResult<Secret> {
-        let name = name.as_ref();
+        Self::insert(
+            ctx,
+            name.as_ref(),
+            definition,
+            description,
+            crypted,
+            key_pair_pk,
+            version,
+            algorithm,
+            SecretBackendKind::Database,
+        )
+        .await
+    }
+
+    /// Creates a new secret whose message is stored in an external [`SecretBackend`] and returns
+    /// a corresponding [`Secret`] representation. Only the reference is stored, the message is
+    /// fetched when the secret is decrypted.
+    pub async fn new_external(
+        ctx: &DalContext,
+        name: impl AsRef<str>,
+        definition: String,
+        description: Option<String>,
+        reference: &SecretReference,
+        key_pair_pk: KeyPairPk,
+    ) -> SecretResult<Secret> {
+        if reference.backend_kind == SecretBackendKind::Database {
+            return Err(SecretError::InvalidReferenceBackend(reference.backend_kind));
+        }
+        scoped_location(reference, ctx.tenancy())?;
 
+        Self::insert(
+            ctx,
+            name.as_ref(),
+            definition,
+            description,
+            &serde_json::to_vec(reference).map_err(SecretError::SecretReferenceSerde)?,
+            key_pair_pk,
+            SecretVersion::default(),
+            SecretAlgorithm::default(),
+            reference.backend_kind,
+        )
+        .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn insert(
+        ctx: &DalContext,
+        name: &str,
+        definition: String,
+        description: Option<String>,
+        crypted: &[u8],
+        key_pair_pk: KeyPairPk,
+        version: SecretVersion,
+        algorithm: SecretAlgorithm,
+        backend_kind: SecretBackendKind,
+    ) -> SecretResult<Secret> {
         let maybe_actor = match ctx.history_actor() {
             HistoryActor::SystemInit => None,
             HistoryActor::User(user_pk) => Some(user_pk),
@@ -296,7 +401,7 @@ impl EncryptedSecret {
             .await?
             .pg()
             .query_one(
-                "SELECT object FROM encrypted_secret_create_v1($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)",
+                "SELECT object FROM encrypted_secret_create_v2($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)",
                 &[
                     ctx.tenancy(),
                     ctx.visibility(),
@@ -310,6 +415,7 @@ impl EncryptedSecret {
                     &base64_encode_bytes(nonce.as_ref()),
                     &key_hash.to_string(),
                     &maybe_actor,
+                    &backend_kind.as_ref(),
                 ],
             )
             .await?;
@@ -324,11 +430,43 @@ impl EncryptedSecret {
     standard_model_accessor!(algorithm, Enum(SecretAlgorithm), SecretResult);
     standard_model_accessor!(updated_by, Option<Pk(UserPk)>, SecretResult);
     standard_model_accessor!(key_pair_pk, Pk(KeyPairPk), SecretResult);
+    standard_model_accessor_ro!(backend_kind, SecretBackendKind);
 
     // Once created, this object field is immutable
     standard_model_accessor_ro!(definition, String);
 
+    /// Replaces the message of the secret, storing it in the database from now on.
     pub async fn set_crypted(&mut self, ctx: &DalContext, value: Vec<u8>) -> SecretResult<()> {
+        self.set_payload(ctx, value, SecretBackendKind::Database)
+            .await
+    }
+
+    /// Points the secret at a message stored in an external [`SecretBackend`], replacing its
+    /// message.
+    pub async fn set_reference(
+        &mut self,
+        ctx: &DalContext,
+        reference: &SecretReference,
+    ) -> SecretResult<()> {
+        if reference.backend_kind == SecretBackendKind::Database {
+            return Err(SecretError::InvalidReferenceBackend(reference.backend_kind));
+        }
+        scoped_location(reference, ctx.tenancy())?;
+
+        self.set_payload(
+            ctx,
+            serde_json::to_vec(reference).map_err(SecretError::SecretReferenceSerde)?,
+            reference.backend_kind,
+        )
+        .await
+    }
+
+    async fn set_payload(
+        &mut self,
+        ctx: &DalContext,
+        value: Vec<u8>,
+        backend_kind: SecretBackendKind,
+    ) -> SecretResult<()> {
         let (double_crypted, nonce, key_hash) = ctx.symmetric_crypto_service().encrypt(&value);
         let updated_at = standard_model::update(
             ctx,
@@ -357,6 +495,15 @@ impl EncryptedSecret {
             TypeHint::Text,
         )
         .await?;
+        standard_model::update(
+            ctx,
+            "encrypted_secrets",
+            "backend_kind",
+            self.id(),
+            &backend_kind.as_ref(),
+            TypeHint::Text,
+        )
+        .await?;
 
         let _history_event = HistoryEvent::new(
             ctx,
@@ -367,13 +514,49 @@ impl EncryptedSecret {
         .await?;
         self.timestamp.updated_at = updated_at;
         self.crypted = value;
+        self.backend_kind = backend_kind;
 
         Ok(())
     }
 
     /// Decrypts the encrypted secret with its associated [`KeyPair`] and returns a
     /// [`DecryptedSecret`].
+    ///
+    /// For a secret stored in an external [`SecretBackend`], its message is fetched from the
+    /// backend instead.
+    ///
+    /// Every successful decryption is recorded as a [`SecretUsage`]; use
+    /// [`decrypt_for_func`](Self::decrypt_for_func) when decrypting for a function.
     pub async fn decrypt(self, ctx: &DalContext) -> SecretResult<DecryptedSecret> {
+        let id = self.id;
+        let decrypted = self.decrypt_inner(ctx).await?;
+        SecretUsage::record(ctx, id, None, None).await?;
+
+        Ok(decrypted)
+    }
+
+    /// Decrypts the encrypted secret like [`decrypt`](Self::decrypt), recording that it was used
+    /// by the given function of the given component.
+    pub async fn decrypt_for_func(
+        self,
+        ctx: &DalContext,
+        func_id: FuncId,
+        component_id: ComponentId,
+    ) -> SecretResult<DecryptedSecret> {
+        let id = self.id;
+        let decrypted = self.decrypt_inner(ctx).await?;
+        SecretUsage::record(ctx, id, Some(func_id), Some(component_id)).await?;
+
+        Ok(decrypted)
+    }
+
+    async fn decrypt_inner(self, ctx: &DalContext) -> SecretResult<DecryptedSecret> {
+        if self.backend_kind != SecretBackendKind::Database {
+            return self
+                .resolve(ctx.secret_backends(), ctx.symmetric_crypto_service())
+                .await;
+        }
+
         let key_pair = self.key_pair(ctx).await?;
 
         self.into_decrypted(
@@ -411,11 +594,45 @@ impl EncryptedSecret {
         }
     }
 
+    async fn resolve(
+        self,
+        secret_backends: &SecretBackends,
+        symmetric_crypto_service: &SymmetricCryptoService,
+    ) -> SecretResult<DecryptedSecret> {
+        let reference: SecretReference = serde_json::from_slice(
+            &symmetric_crypto_service.decrypt(&self.crypted, &self.nonce, &self.key_hash)?,
+        )
+        .map_err(SecretError::SecretReferenceSerde)?;
+        let backend = secret_backends.get(reference.backend_kind).ok_or(
+            SecretError::SecretBackendNotConfigured(reference.backend_kind),
+        )?;
+
+        let message = backend
+            .fetch(&scoped_location(&reference, &self.tenancy)?)
+            .await?;
+
+        Ok(DecryptedSecret {
+            name: self.name,
+            definition: self.definition,
+            message,
+        })
+    }
+
     pub async fn key_pair(&self, ctx: &DalContext) -> SecretResult<KeyPair> {
         Ok(KeyPair::get_by_pk(ctx, self.key_pair_pk).await?)
     }
 }
 
+/// Scopes the location of a reference to the workspace of the given tenancy, so a secret can only
+/// reach messages stored under its own workspace in a shared backend.
+fn scoped_location(reference: &SecretReference, tenancy: &Tenancy) -> SecretResult<String> {
+    let workspace_pk = tenancy
+        .workspace_pk()
+        .ok_or(SecretError::ReferenceWithoutWorkspace)?;
+
+    Ok(reference.scoped_location(workspace_pk)?)
+}
+
 /// A secret that has been decrypted.
 ///
 /// This type is returned by calling `EncryptedSecret.decrypt(&txn).await?` which contains the raw
@@ -561,6 +778,7 @@ mod tests {
                 crypted: double_crypted,
                 version: Default::default(),
                 algorithm: Default::default(),
+                backend_kind: Default::default(),
                 tenancy: Tenancy::new(wid),
                 timestamp: Timestamp::now(),
                 created_by: None,
@@ -607,6 +825,94 @@ mod tests {
             assert_eq!("dockerHub", decrypted.definition);
             assert_eq!(message, decrypted.message);
         }
+
+        #[tokio::test]
+        async fn resolve_external() {
+            let dir = tempfile::tempdir().expect("failed to create tempdir");
+            let workspace_pk = WorkspacePk::generate();
+            std::fs::create_dir(dir.path().join(workspace_pk.to_string()))
+                .expect("failed to create workspace dir");
+            let message =
+                serde_json::json!({"username": "The Cadillac Three", "password": "Slow Rollin"});
+            std::fs::write(
+                dir.path()
+                    .join(workspace_pk.to_string())
+                    .join("docker-hub.json"),
+                serde_json::to_vec(&message).expect("failed to serialize message"),
+            )
+            .expect("failed to write secret");
+
+            let service =
+                SymmetricCryptoService::new(SymmetricCryptoService::generate_key(), vec![]);
+            let reference = SecretReference {
+                backend_kind: SecretBackendKind::File,
+                location: "docker-hub.json".to_owned(),
+            };
+
+            let mut encrypted = encrypted_secret(
+                "the-cadillac-three",
+                "dockerHub".to_owned(),
+                None,
+                serde_json::to_vec(&reference).expect("failed to serialize reference"),
+                &service,
+                workspace_pk,
+            );
+            encrypted.backend_kind = SecretBackendKind::File;
+
+            assert!(matches!(
+                encrypted
+                    .clone()
+                    .resolve(&SecretBackends::default(), &service)
+                    .await,
+                Err(SecretError::SecretBackendNotConfigured(
+                    SecretBackendKind::File
+                ))
+            ));
+
+            let backends =
+                SecretBackends::default().with_backend(backend::FileSecretBackend::new(dir.path()));
+            let decrypted = encrypted
+                .clone()
+                .resolve(&backends, &service)
+                .await
+                .expect("could not resolve secret");
+
+            assert_eq!("the-cadillac-three", decrypted.name);
+            assert_eq!("dockerHub", decrypted.definition);
+            assert_eq!(message, decrypted.message);
+
+            // The same location in another workspace is resolved under that workspace's prefix
+            let mut other_workspace = encrypted_secret(
+                "the-cadillac-three",
+                "dockerHub".to_owned(),
+                None,
+                serde_json::to_vec(&reference).expect("failed to serialize reference"),
+                &service,
+                WorkspacePk::generate(),
+            );
+            other_workspace.backend_kind = SecretBackendKind::File;
+            assert!(matches!(
+                other_workspace.clone().resolve(&backends, &service).await,
+                Err(SecretError::SecretBackend(SecretBackendError::NotFound(_)))
+            ));
+
+            // A reference outside of the workspace's prefix is refused
+            let escaping = SecretReference {
+                backend_kind: SecretBackendKind::File,
+                location: format!("../{workspace_pk}/docker-hub.json"),
+            };
+            let (crypted, nonce, key_hash) = service
+                .encrypt(&serde_json::to_vec(&escaping).expect("failed to serialize reference"));
+            other_workspace.crypted = crypted;
+            other_workspace.nonce = nonce;
+            other_workspace.key_hash = *key_hash;
+            assert!(matches!(
+                other_workspace.resolve(&backends, &service).await,
+                Err(SecretError::SecretBackend(
+                    SecretBackendError::InvalidLocation(_)
+                ))
+            ));
+        }
     }
 
     mod secret_version {
@@ -0,0 +1,467 @@
+//! Backends holding the contents of secrets outside of our database.
+//!
+//! A secret stored in a [`SecretBackendKind::Database`] backend carries its (encrypted) message in
+//! the `encrypted_secrets` table. A secret stored in any other backend only carries a
+//! [`SecretReference`] to where its message lives, which is resolved by the matching
+//! [`SecretBackend`] when a function needing the secret is executed. The message is then encrypted
+//! for Cyclone like any other secret, so it is never written to our database.
+//!
+//! Every backend is shared by all workspaces, so a [`SecretReference`] is always resolved under
+//! the pk of the workspace owning the secret: the location `docker/hub.json` of a secret in
+//! workspace `01H...` is read from `<root>/01H.../docker/hub.json` by a [`FileSecretBackend`].
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use strum::{AsRefStr, Display, EnumString};
+use thiserror::Error;
+use tokio::fs;
+use url::Url;
+
+use crate::WorkspacePk;
+
+#[remain::sorted]
+#[derive(Error, Debug)]
+pub enum SecretBackendError {
+    #[error("secret backend base url can't have a location appended: {0}")]
+    InvalidBaseUrl(Url),
+    #[error("invalid header for secret backend: {0}")]
+    InvalidHeader(String),
+    #[error("invalid secret location: {0}")]
+    InvalidLocation(String),
+    #[error("io error reading secret at {0}: {1}")]
+    Io(String, #[source] std::io::Error),
+    #[error("secret not found at {0}")]
+    NotFound(String),
+    #[error("http error fetching secret: {0}")]
+    Reqwest(#[from] reqwest::Error),
+    #[error("secret at {0} is not valid json: {1}")]
+    SerdeJson(String, #[source] serde_json::Error),
+    #[error("nothing at {1} in the secret at {0}")]
+    ValuePointerNotFound(String, String),
+}
+
+pub type SecretBackendResult<T> = Result<T, SecretBackendError>;
+
+/// Where the message of a secret is stored.
+#[remain::sorted]
+#[derive(
+    AsRefStr,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Deserialize,
+    Display,
+    EnumString,
+    Eq,
+    Hash,
+    PartialEq,
+    Serialize,
+)]
+#[serde(rename_all = "camelCase")]
+#[strum(serialize_all = "camelCase")]
+pub enum SecretBackendKind {
+    /// Encrypted in the `encrypted_secrets` table
+    #[default]
+    Database,
+    /// A JSON file under a configured directory
+    File,
+    /// A JSON document fetched from an HTTP key/value store, such as Vault
+    HttpKv,
+}
+
+/// Where to find the message of a secret stored outside of our database.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretReference {
+    pub backend_kind: SecretBackendKind,
+    /// A relative, `/` separated path within the workspace's prefix of the backend
+    pub location: String,
+}
+
+impl SecretReference {
+    /// Returns the location to fetch from the backend for a secret of the given workspace, which
+    /// is the reference's location under the workspace's pk. Locations that could leave that
+    /// prefix are refused.
+    pub fn scoped_location(&self, workspace_pk: WorkspacePk) -> SecretBackendResult<String> {
+        if self
+            .location
+            .split('/')
+            .any(|segment| segment.is_empty() || segment == "." || segment == "..")
+        {
+            return Err(SecretBackendError::InvalidLocation(self.location.clone()));
+        }
+
+        Ok(format!("{workspace_pk}/{}", self.location))
+    }
+}
+
+/// A place secret messages can be fetched from.
+#[async_trait]
+pub trait SecretBackend: fmt::Debug + Send + Sync {
+    /// The kind of [`SecretReference`] this backend resolves.
+    fn kind(&self) -> SecretBackendKind;
+
+    /// Fetches the message stored at `location`, which has already been scoped to a workspace
+    /// with [`SecretReference::scoped_location`].
+    async fn fetch(&self, location: &str) -> SecretBackendResult<Value>;
+}
+
+/// The configuration of the external secret backends. Backends which aren't configured can't be
+/// used to resolve secrets.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct SecretBackendsConfig {
+    pub file: Option<FileSecretBackendConfig>,
+    pub http_kv: Option<HttpKvSecretBackendConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FileSecretBackendConfig {
+    /// The directory holding a directory of secrets per workspace pk.
+    pub root: PathBuf,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct HttpKvSecretBackendConfig {
+    /// The URL the workspace pk and secret location are appended to.
+    pub base_url: Url,
+    /// Headers sent with every request, typically holding a token.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// A JSON pointer to the message within the fetched document, e.g. `/data/data` for a Vault
+    /// KV version 2 engine. The whole document is the message when not set.
+    pub value_pointer: Option<String>,
+}
+
+impl fmt::Debug for HttpKvSecretBackendConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpKvSecretBackendConfig")
+            .field("base_url", &self.base_url)
+            .field("headers", &self.headers.keys().collect::<Vec<_>>())
+            .field("value_pointer", &self.value_pointer)
+            .finish()
+    }
+}
+
+/// The configured secret backends, by the kind of reference they resolve.
+#[derive(Clone, Debug, Default)]
+pub struct SecretBackends {
+    backends: Arc<HashMap<SecretBackendKind, Arc<dyn SecretBackend>>>,
+}
+
+impl SecretBackends {
+    /// Creates the backends enabled in the given [`SecretBackendsConfig`].
+    pub fn from_config(config: &SecretBackendsConfig) -> SecretBackendResult<Self> {
+        let mut backends = Self::default();
+
+        if let Some(file) = &config.file {
+            backends = backends.with_backend(FileSecretBackend::new(file.root.clone()));
+        }
+        if let Some(http_kv) = &config.http_kv {
+            backends = backends.with_backend(HttpKvSecretBackend::new(http_kv)?);
+        }
+
+        Ok(backends)
+    }
+
+    /// Adds a backend, replacing any backend of the same kind.
+    pub fn with_backend(self, backend: impl SecretBackend + 'static) -> Self {
+        let mut backends = (*self.backends).clone();
+        backends.insert(backend.kind(), Arc::new(backend));
+
+        Self {
+            backends: Arc::new(backends),
+        }
+    }
+
+    /// Gets the backend resolving references of the given kind, if it is configured.
+    pub fn get(&self, kind: SecretBackendKind) -> Option<&dyn SecretBackend> {
+        self.backends.get(&kind).map(|backend| backend.as_ref())
+    }
+}
+
+/// Reads secrets from JSON files under a root directory.
+#[derive(Clone, Debug)]
+pub struct FileSecretBackend {
+    root: PathBuf,
+}
+
+impl FileSecretBackend {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+#[async_trait]
+impl SecretBackend for FileSecretBackend {
+    fn kind(&self) -> SecretBackendKind {
+        SecretBackendKind::File
+    }
+
+    async fn fetch(&self, location: &str) -> SecretBackendResult<Value> {
+        // Keep secret files inside the root directory
+        if location.is_empty()
+            || !Path::new(location)
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(SecretBackendError::InvalidLocation(location.to_owned()));
+        }
+
+        let buf = match fs::read(self.root.join(location)).await {
+            Ok(buf) => buf,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Err(SecretBackendError::NotFound(location.to_owned()))
+            }
+            Err(err) => return Err(SecretBackendError::Io(location.to_owned(), err)),
+        };
+
+        serde_json::from_slice(&buf)
+            .map_err(|err| SecretBackendError::SerdeJson(location.to_owned(), err))
+    }
+}
+
+/// Fetches secrets from an HTTP key/value store with a `GET` of the location appended to a base
+/// URL.
+#[derive(Clone)]
+pub struct HttpKvSecretBackend {
+    client: reqwest::Client,
+    base_url: Url,
+    headers: HeaderMap,
+    value_pointer: Option<String>,
+}
+
+impl HttpKvSecretBackend {
+    pub fn new(config: &HttpKvSecretBackendConfig) -> SecretBackendResult<Self> {
+        if config.base_url.cannot_be_a_base() {
+            return Err(SecretBackendError::InvalidBaseUrl(config.base_url.clone()));
+        }
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.headers {
+            let name = HeaderName::try_from(name.as_str())
+                .map_err(|_| SecretBackendError::InvalidHeader(name.to_owned()))?;
+            let mut value = HeaderValue::try_from(value.as_str())
+                .map_err(|_| SecretBackendError::InvalidHeader(name.to_string()))?;
+            value.set_sensitive(true);
+            headers.insert(name, value);
+        }
+
+        Ok(Self {
+            client: reqwest::Client::new(),
+            base_url: config.base_url.clone(),
+            headers,
+            value_pointer: config.value_pointer.clone(),
+        })
+    }
+
+    fn url(&self, location: &str) -> SecretBackendResult<Url> {
+        let segments: Vec<&str> = location.split('/').collect();
+        if segments
+            .iter()
+            .any(|segment| segment.is_empty() || *segment == "." || *segment == "..")
+        {
+            return Err(SecretBackendError::InvalidLocation(location.to_owned()));
+        }
+
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .map_err(|_| SecretBackendError::InvalidBaseUrl(self.base_url.clone()))?
+            .pop_if_empty()
+            .extend(segments);
+
+        Ok(url)
+    }
+}
+
+impl fmt::Debug for HttpKvSecretBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpKvSecretBackend")
+            .field("base_url", &self.base_url)
+            .field("value_pointer", &self.value_pointer)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl SecretBackend for HttpKvSecretBackend {
+    fn kind(&self) -> SecretBackendKind {
+        SecretBackendKind::HttpKv
+    }
+
+    async fn fetch(&self, location: &str) -> SecretBackendResult<Value> {
+        let response = self
+            .client
+            .get(self.url(location)?)
+            .headers(self.headers.clone())
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(SecretBackendError::NotFound(location.to_owned()));
+        }
+
+        let bytes = response.error_for_status()?.bytes().await?;
+        let mut document: Value = serde_json::from_slice(&bytes)
+            .map_err(|err| SecretBackendError::SerdeJson(location.to_owned(), err))?;
+
+        match &self.value_pointer {
+            Some(pointer) => document
+                .pointer_mut(pointer)
+                .map(Value::take)
+                .ok_or_else(|| {
+                    SecretBackendError::ValuePointerNotFound(
+                        location.to_owned(),
+                        pointer.to_owned(),
+                    )
+                }),
+            None => Ok(document),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    #[test]
+    fn scoped_location() {
+        let workspace_pk = WorkspacePk::generate();
+        let reference = |location: &str| SecretReference {
+            backend_kind: SecretBackendKind::File,
+            location: location.to_owned(),
+        };
+
+        assert_eq!(
+            format!("{workspace_pk}/docker/hub.json"),
+            reference("docker/hub.json")
+                .scoped_location(workspace_pk)
+                .expect("failed to scope location")
+        );
+
+        // Nothing may reach the prefix of another workspace
+        let other_workspace_pk = WorkspacePk::generate();
+        for location in [
+            format!("../{other_workspace_pk}/docker/hub.json"),
+            format!("docker/../../{other_workspace_pk}/docker/hub.json"),
+            format!("/{other_workspace_pk}/docker/hub.json"),
+            "./docker/hub.json".to_owned(),
+            "docker//hub.json".to_owned(),
+            String::new(),
+        ] {
+            assert!(
+                matches!(
+                    reference(&location).scoped_location(workspace_pk),
+                    Err(SecretBackendError::InvalidLocation(_))
+                ),
+                "{location} was not refused"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn file_backend() {
+        let dir = tempfile::tempdir().expect("failed to create tempdir");
+        std::fs::create_dir(dir.path().join("docker")).expect("failed to create dir");
+        std::fs::write(
+            dir.path().join("docker/hub.json"),
+            r#"{"username": "The Cadillac Three", "password": "Slow Rollin"}"#,
+        )
+        .expect("failed to write secret");
+        let backend = FileSecretBackend::new(dir.path());
+
+        let message = backend
+            .fetch("docker/hub.json")
+            .await
+            .expect("failed to fetch secret");
+        assert_eq!(
+            serde_json::json!({"username": "The Cadillac Three", "password": "Slow Rollin"}),
+            message
+        );
+
+        assert!(matches!(
+            backend.fetch("docker/nope.json").await,
+            Err(SecretBackendError::NotFound(_))
+        ));
+        assert!(matches!(
+            backend.fetch("../hub.json").await,
+            Err(SecretBackendError::InvalidLocation(_))
+        ));
+        assert!(matches!(
+            backend.fetch("/etc/passwd").await,
+            Err(SecretBackendError::InvalidLocation(_))
+        ));
+    }
+
+    /// Serves a single request with the given JSON body, returning the request it received.
+    async fn serve_once(body: &'static str) -> (Url, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("failed to bind");
+        let url = Url::parse(&format!(
+            "http://{}/v1/secret/data/",
+            listener.local_addr().expect("failed to get address")
+        ))
+        .expect("failed to parse url");
+
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.expect("failed to accept");
+            let mut request = vec![0; 4096];
+            let len = stream.read(&mut request).await.expect("failed to read");
+            stream
+                .write_all(
+                    format!(
+                        "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                    .as_bytes(),
+                )
+                .await
+                .expect("failed to write");
+            String::from_utf8_lossy(&request[..len]).into_owned()
+        });
+
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn http_kv_backend() {
+        let (base_url, request) =
+            serve_once(r#"{"data": {"data": {"token": "hunter2"}, "metadata": {"version": 3}}}"#)
+                .await;
+        let backend = HttpKvSecretBackend::new(&HttpKvSecretBackendConfig {
+            base_url,
+            headers: BTreeMap::from([("X-Vault-Token".to_owned(), "s.root".to_owned())]),
+            value_pointer: Some("/data/data".to_owned()),
+        })
+        .expect("failed to create backend");
+
+        let message = backend
+            .fetch("aws/prod")
+            .await
+            .expect("failed to fetch secret");
+        assert_eq!(serde_json::json!({"token": "hunter2"}), message);
+
+        let request = request.await.expect("failed to serve request");
+        assert!(request.starts_with("GET /v1/secret/data/aws/prod HTTP/1.1\r\n"));
+        assert!(request.to_lowercase().contains("x-vault-token: s.root\r\n"));
+
+        assert!(matches!(
+            backend.fetch("aws/../root").await,
+            Err(SecretBackendError::InvalidLocation(_))
+        ));
+    }
+}
@@ -0,0 +1,202 @@
+//! The audit trail of secret decryptions.
+//!
+//! A [`SecretUsage`] is recorded every time an [`EncryptedSecret`](super::EncryptedSecret) is
+//! decrypted, with the function and component it was decrypted for when known. Usages are written
+//! outside of the transaction of the [`DalContext`], so that a decryption stays on record even if
+//! the work it was done for is rolled back.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    pk, ActorView, ChangeSetPk, ComponentId, DalContext, Func, FuncId, HistoryActor, StandardModel,
+    Tenancy,
+};
+
+use super::{SecretId, SecretResult};
+
+pk!(SecretUsagePk);
+
+/// A single decryption of a secret.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SecretUsage {
+    pk: SecretUsagePk,
+    secret_id: SecretId,
+    /// The function the secret was decrypted for
+    func_id: Option<FuncId>,
+    /// The component whose function needed the secret
+    component_id: Option<ComponentId>,
+    change_set_pk: ChangeSetPk,
+    actor: HistoryActor,
+    used_at: DateTime<Utc>,
+    #[serde(flatten)]
+    tenancy: Tenancy,
+}
+
+impl SecretUsage {
+    /// Records that the secret was decrypted for the given function and component, by the actor
+    /// and in the change set of the context.
+    pub async fn record(
+        ctx: &DalContext,
+        secret_id: SecretId,
+        func_id: Option<FuncId>,
+        component_id: Option<ComponentId>,
+    ) -> SecretResult<Self> {
+        let actor = serde_json::to_value(ctx.history_actor())?;
+
+        let conn = ctx.pg_pool().get().await?;
+        let row = conn
+            .query_one(
+                "SELECT object FROM secret_usage_create_v1($1, $2, $3, $4, $5, $6)",
+                &[
+                    ctx.tenancy(),
+                    &secret_id,
+                    &func_id,
+                    &component_id,
+                    &ctx.visibility().change_set_pk,
+                    &actor,
+                ],
+            )
+            .await?;
+        let json: serde_json::Value = row.try_get("object")?;
+
+        Ok(serde_json::from_value(json)?)
+    }
+
+    /// Lists the decryptions of a secret, most recent first, optionally only those since the
+    /// given time.
+    pub async fn list_for_secret(
+        ctx: &DalContext,
+        secret_id: SecretId,
+        since: Option<DateTime<Utc>>,
+    ) -> SecretResult<Vec<Self>> {
+        let rows = ctx
+            .txns()
+            .await?
+            .pg()
+            .query(
+                "SELECT row_to_json(secret_usages.*) AS object
+                 FROM secret_usages
+                 WHERE secret_id = $2
+                   AND in_tenancy_v1($1, tenancy_workspace_pk)
+                   AND ($3::timestamptz IS NULL OR used_at >= $3)
+                 ORDER BY used_at DESC",
+                &[ctx.tenancy(), &secret_id, &since],
+            )
+            .await?;
+
+        let mut usages = Vec::with_capacity(rows.len());
+        for row in rows {
+            let json: serde_json::Value = row.try_get("object")?;
+            usages.push(serde_json::from_value(json)?);
+        }
+
+        Ok(usages)
+    }
+
+    /// Gets when the secret was last decrypted, if ever.
+    pub async fn last_used_at(
+        ctx: &DalContext,
+        secret_id: SecretId,
+    ) -> SecretResult<Option<DateTime<Utc>>> {
+        let row = ctx
+            .txns()
+            .await?
+            .pg()
+            .query_one(
+                "SELECT max(used_at) AS last_used_at
+                 FROM secret_usages
+                 WHERE secret_id = $2
+                   AND in_tenancy_v1($1, tenancy_workspace_pk)",
+                &[ctx.tenancy(), &secret_id],
+            )
+            .await?;
+
+        Ok(row.try_get("last_used_at")?)
+    }
+
+    /// Gets when each secret in the tenancy was last decrypted, leaving out secrets that never
+    /// were.
+    pub async fn last_used_at_by_secret(
+        ctx: &DalContext,
+    ) -> SecretResult<HashMap<SecretId, DateTime<Utc>>> {
+        let rows = ctx
+            .txns()
+            .await?
+            .pg()
+            .query(
+                "SELECT secret_id, max(used_at) AS last_used_at
+                 FROM secret_usages
+                 WHERE in_tenancy_v1($1, tenancy_workspace_pk)
+                 GROUP BY secret_id",
+                &[ctx.tenancy()],
+            )
+            .await?;
+
+        let mut last_used_at = HashMap::with_capacity(rows.len());
+        for row in rows {
+            last_used_at.insert(row.try_get("secret_id")?, row.try_get("last_used_at")?);
+        }
+
+        Ok(last_used_at)
+    }
+
+    pub fn secret_id(&self) -> SecretId {
+        self.secret_id
+    }
+
+    pub fn func_id(&self) -> Option<FuncId> {
+        self.func_id
+    }
+
+    pub fn component_id(&self) -> Option<ComponentId> {
+        self.component_id
+    }
+
+    pub fn change_set_pk(&self) -> ChangeSetPk {
+        self.change_set_pk
+    }
+
+    pub fn actor(&self) -> HistoryActor {
+        self.actor
+    }
+
+    pub fn used_at(&self) -> DateTime<Utc> {
+        self.used_at
+    }
+}
+
+/// A [`SecretUsage`] ready to be displayed, with the names of its function and actor.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretUsageView {
+    pub func_id: Option<FuncId>,
+    /// `None` when the function can't be seen from the current change set
+    pub func_name: Option<String>,
+    pub component_id: Option<ComponentId>,
+    pub change_set_pk: ChangeSetPk,
+    pub actor: ActorView,
+    pub used_at: DateTime<Utc>,
+}
+
+impl SecretUsageView {
+    pub async fn from_usage(ctx: &DalContext, usage: SecretUsage) -> SecretResult<Self> {
+        let func_name = match usage.func_id {
+            Some(func_id) => Func::get_by_id(ctx, &func_id)
+                .await?
+                .map(|func| func.name().to_owned()),
+            None => None,
+        };
+
+        Ok(Self {
+            func_id: usage.func_id,
+            func_name,
+            component_id: usage.component_id,
+            change_set_pk: usage.change_set_pk,
+            actor: ActorView::from_history_actor(ctx, usage.actor).await?,
+            used_at: usage.used_at,
+        })
+    }
+}
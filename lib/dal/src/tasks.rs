@@ -4,7 +4,12 @@
 // This modules should remain private! Add "pub use" statements to use their contents.
 mod resource_scheduler;
 mod status_receiver;
+mod symmetric_key_rotator;
 
 pub use resource_scheduler::{ResourceScheduler, ResourceSchedulerError};
 pub use status_receiver::client::StatusReceiverClient;
 pub use status_receiver::{StatusReceiver, StatusReceiverError, StatusReceiverRequest};
+pub use symmetric_key_rotator::{
+    KeyHashUsage, SymmetricKeyRotationError, SymmetricKeyRotationProgress,
+    SymmetricKeyRotationReport, SymmetricKeyRotationResult, SymmetricKeyRotator,
+};
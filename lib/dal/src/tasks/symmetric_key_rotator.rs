@@ -0,0 +1,401 @@
+//! This module contains [`SymmetricKeyRotator`], which is a "long-running" task that re-encrypts
+//! everything stored with a retired symmetric key under the active key of the
+//! [`SymmetricCryptoService`], so that retired keys can eventually be removed.
+
+use std::{collections::BTreeMap, str::FromStr, time::Duration};
+
+use base64::{engine::general_purpose, Engine};
+use si_crypto::{SymmetricCryptoError, SymmetricCryptoService, SymmetricNonce};
+use si_data_pg::{PgError, PgPoolError, PgRow};
+use si_hash::Hash;
+use telemetry::prelude::*;
+use thiserror::Error;
+use tokio::{sync::broadcast, time};
+
+use crate::ServicesContext;
+
+/// How many rows are re-encrypted in a single transaction by default.
+const BATCH_SIZE: i64 = 100;
+
+#[remain::sorted]
+#[derive(Error, Debug)]
+pub enum SymmetricKeyRotationError {
+    #[error("error decoding base64 column: {0}")]
+    Base64Decode(#[from] base64::DecodeError),
+    #[error("invalid key hash: {0}")]
+    InvalidKeyHash(String),
+    #[error("invalid nonce length")]
+    InvalidNonce,
+    #[error(transparent)]
+    Pg(#[from] PgError),
+    #[error(transparent)]
+    PgPool(#[from] PgPoolError),
+    #[error(transparent)]
+    SymmetricCrypto(#[from] SymmetricCryptoError),
+}
+
+pub type SymmetricKeyRotationResult<T> = Result<T, SymmetricKeyRotationError>;
+
+/// A table column set holding data encrypted with the [`SymmetricCryptoService`].
+struct EncryptedColumns {
+    table: &'static str,
+    crypted: &'static str,
+    nonce: &'static str,
+    key_hash: &'static str,
+}
+
+const ENCRYPTED_COLUMNS: &[EncryptedColumns] = &[
+    EncryptedColumns {
+        table: "encrypted_secrets",
+        crypted: "crypted",
+        nonce: "nonce",
+        key_hash: "key_hash",
+    },
+    EncryptedColumns {
+        table: "key_pairs",
+        crypted: "secret_key_crypted",
+        nonce: "secret_key_nonce",
+        key_hash: "secret_key_key_hash",
+    },
+];
+
+/// How many rows are still encrypted with a key other than the active key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyHashUsage {
+    /// Whether the key is loaded in the [`SymmetricCryptoService`].
+    pub loaded: bool,
+    /// The number of rows, across all encrypted tables, encrypted with the key.
+    pub rows: i64,
+}
+
+/// Where a key rotation stands: what is left to re-encrypt and which keys are no longer needed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymmetricKeyRotationReport {
+    active_key_hash: String,
+    retired_keys: BTreeMap<String, KeyHashUsage>,
+}
+
+impl SymmetricKeyRotationReport {
+    /// The hash of the key everything is being re-encrypted with.
+    pub fn active_key_hash(&self) -> &str {
+        &self.active_key_hash
+    }
+
+    /// Every key other than the active key which is either loaded or still used by some rows.
+    pub fn retired_keys(&self) -> impl Iterator<Item = (&str, &KeyHashUsage)> {
+        self.retired_keys
+            .iter()
+            .map(|(key_hash, usage)| (key_hash.as_str(), usage))
+    }
+
+    /// The number of rows not yet encrypted with the active key.
+    pub fn remaining(&self) -> i64 {
+        self.retired_keys.values().map(|usage| usage.rows).sum()
+    }
+
+    /// Loaded keys which no row is encrypted with anymore. These can be dropped from the
+    /// configuration.
+    pub fn safe_to_remove(&self) -> impl Iterator<Item = &str> {
+        self.retired_keys()
+            .filter(|(_, usage)| usage.loaded && usage.rows == 0)
+            .map(|(key_hash, _)| key_hash)
+    }
+
+    /// Keys which rows are encrypted with but which aren't loaded. Those rows can't be decrypted,
+    /// nor re-encrypted, until the key is added back to the configuration.
+    pub fn missing(&self) -> impl Iterator<Item = (&str, &KeyHashUsage)> {
+        self.retired_keys().filter(|(_, usage)| !usage.loaded)
+    }
+}
+
+/// What a single pass of [`SymmetricKeyRotator::rotate`] did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SymmetricKeyRotationProgress {
+    /// Rows now encrypted with the active key.
+    pub reencrypted: u64,
+    /// Rows which could not be re-encrypted and were left alone.
+    pub failed: u64,
+}
+
+/// The symmetric key rotator moves rows encrypted with any loaded key other than the active key
+/// over to the active key. Rotating a key is then a matter of making the new key active, keeping
+/// the old key as an extra key until the rotator reports it is safe to remove, and then dropping
+/// it from the configuration.
+#[derive(Debug, Clone)]
+pub struct SymmetricKeyRotator {
+    services_context: ServicesContext,
+    batch_size: i64,
+}
+
+impl SymmetricKeyRotator {
+    pub fn new(services_context: ServicesContext) -> SymmetricKeyRotator {
+        SymmetricKeyRotator {
+            services_context,
+            batch_size: BATCH_SIZE,
+        }
+    }
+
+    /// Sets how many rows are re-encrypted in a single transaction.
+    pub fn with_batch_size(mut self, batch_size: i64) -> SymmetricKeyRotator {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Starts the rotator, consuming itself. It runs until a shutdown is requested, logging its
+    /// progress whenever it changes.
+    pub fn start(self, mut shutdown_broadcast_rx: broadcast::Receiver<()>) {
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = shutdown_broadcast_rx.recv() => {
+                    info!("Symmetric Key Rotator received shutdown request, bailing out");
+                },
+                _ = self.start_task() => {}
+            }
+            info!("Symmetric Key Rotator stopped");
+        });
+    }
+
+    /// The internal task spawned by `start`. Every minute it re-encrypts whatever is left and
+    /// reports where the rotation stands.
+    #[instrument(name = "symmetric_key_rotator.start_task", skip_all, level = "debug")]
+    async fn start_task(&self) {
+        let mut interval = time::interval(Duration::from_secs(60));
+        let mut last_report = None;
+        loop {
+            interval.tick().await;
+            match self.run().await {
+                Ok(report) => {
+                    if last_report.as_ref() != Some(&report) {
+                        log_report(&report);
+                        last_report = Some(report);
+                    }
+                }
+                Err(err) => error!("{err}"),
+            }
+        }
+    }
+
+    #[instrument(name = "symmetric_key_rotator.run", skip_all, level = "debug")]
+    async fn run(&self) -> SymmetricKeyRotationResult<SymmetricKeyRotationReport> {
+        let progress = self.rotate().await?;
+        if progress.reencrypted > 0 || progress.failed > 0 {
+            info!(
+                reencrypted = progress.reencrypted,
+                failed = progress.failed,
+                "re-encrypted rows with the active symmetric key"
+            );
+        }
+
+        self.report().await
+    }
+
+    /// Re-encrypts every row encrypted with a loaded key other than the active key, in batches
+    /// which are each committed in their own transaction. Rows which fail to re-encrypt are
+    /// logged and skipped.
+    #[instrument(name = "symmetric_key_rotator.rotate", skip_all, level = "debug")]
+    pub async fn rotate(&self) -> SymmetricKeyRotationResult<SymmetricKeyRotationProgress> {
+        let crypto = self.services_context.symmetric_crypto_service();
+        let retired_key_hashes: Vec<String> = crypto
+            .key_hashes()
+            .filter(|key_hash| *key_hash != crypto.active_key_hash())
+            .map(ToString::to_string)
+            .collect();
+
+        let mut progress = SymmetricKeyRotationProgress::default();
+        if retired_key_hashes.is_empty() {
+            return Ok(progress);
+        }
+
+        for columns in ENCRYPTED_COLUMNS {
+            // Rows are walked in pk order so that rows which fail to re-encrypt aren't retried
+            // forever within a pass
+            let mut after_pk = String::new();
+            loop {
+                let last_pk = self
+                    .rotate_batch(columns, &retired_key_hashes, &after_pk, &mut progress)
+                    .await?;
+                match last_pk {
+                    Some(last_pk) => after_pk = last_pk,
+                    None => break,
+                }
+            }
+        }
+
+        Ok(progress)
+    }
+
+    /// Re-encrypts the next batch of rows after `after_pk`, returning the pk of the last row of
+    /// the batch or `None` when there were no rows left.
+    async fn rotate_batch(
+        &self,
+        columns: &EncryptedColumns,
+        retired_key_hashes: &[String],
+        after_pk: &str,
+        progress: &mut SymmetricKeyRotationProgress,
+    ) -> SymmetricKeyRotationResult<Option<String>> {
+        let EncryptedColumns {
+            table,
+            crypted,
+            nonce,
+            key_hash,
+        } = columns;
+        let crypto = self.services_context.symmetric_crypto_service();
+
+        let mut conn = self.services_context.pg_pool().get().await?;
+        let txn = conn.transaction().await?;
+
+        let rows = txn
+            .query(
+                &format!(
+                    "SELECT pk, {crypted} AS crypted, {nonce} AS nonce, {key_hash} AS key_hash
+                     FROM {table}
+                     WHERE {key_hash} = ANY($1) AND pk > $2
+                     ORDER BY pk
+                     LIMIT $3
+                     FOR UPDATE"
+                ),
+                &[&retired_key_hashes, &after_pk, &self.batch_size],
+            )
+            .await?;
+
+        let mut last_pk = None;
+        for row in rows {
+            let pk: String = row.try_get("pk")?;
+
+            match reencrypt_row(crypto, &row) {
+                Ok((new_crypted, new_nonce, new_key_hash)) => {
+                    txn.execute(
+                        &format!(
+                            "UPDATE {table}
+                             SET {crypted} = $2, {nonce} = $3, {key_hash} = $4
+                             WHERE pk = $1"
+                        ),
+                        &[
+                            &pk,
+                            &base64_encode_bytes(&new_crypted),
+                            &base64_encode_bytes(new_nonce.as_ref()),
+                            &new_key_hash.to_string(),
+                        ],
+                    )
+                    .await?;
+                    progress.reencrypted += 1;
+                }
+                Err(err) => {
+                    error!(table, pk = %pk, error = %err, "failed to re-encrypt row, skipping");
+                    progress.failed += 1;
+                }
+            }
+
+            last_pk = Some(pk);
+        }
+
+        txn.commit().await?;
+
+        Ok(last_pk)
+    }
+
+    /// Counts the rows encrypted with each key other than the active key.
+    #[instrument(name = "symmetric_key_rotator.report", skip_all, level = "debug")]
+    pub async fn report(&self) -> SymmetricKeyRotationResult<SymmetricKeyRotationReport> {
+        let crypto = self.services_context.symmetric_crypto_service();
+        let active_key_hash = crypto.active_key_hash().to_string();
+
+        let mut retired_keys: BTreeMap<String, KeyHashUsage> = crypto
+            .key_hashes()
+            .map(ToString::to_string)
+            .filter(|key_hash| *key_hash != active_key_hash)
+            .map(|key_hash| {
+                (
+                    key_hash,
+                    KeyHashUsage {
+                        loaded: true,
+                        rows: 0,
+                    },
+                )
+            })
+            .collect();
+
+        let conn = self.services_context.pg_pool().get().await?;
+        for EncryptedColumns {
+            table, key_hash, ..
+        } in ENCRYPTED_COLUMNS
+        {
+            let rows = conn
+                .query(
+                    &format!(
+                        "SELECT {key_hash} AS key_hash, count(*) AS row_count
+                         FROM {table}
+                         WHERE {key_hash} != $1
+                         GROUP BY {key_hash}"
+                    ),
+                    &[&active_key_hash],
+                )
+                .await?;
+
+            for row in rows {
+                let key_hash: String = row.try_get("key_hash")?;
+                let count: i64 = row.try_get("row_count")?;
+                retired_keys.entry(key_hash).or_default().rows += count;
+            }
+        }
+
+        Ok(SymmetricKeyRotationReport {
+            active_key_hash,
+            retired_keys,
+        })
+    }
+}
+
+fn reencrypt_row(
+    crypto: &SymmetricCryptoService,
+    row: &PgRow,
+) -> SymmetricKeyRotationResult<(Vec<u8>, SymmetricNonce, Hash)> {
+    let crypted = general_purpose::STANDARD_NO_PAD.decode(row.try_get::<_, String>("crypted")?)?;
+    let nonce = SymmetricNonce::from_slice(
+        &general_purpose::STANDARD_NO_PAD.decode(row.try_get::<_, String>("nonce")?)?,
+    )
+    .ok_or(SymmetricKeyRotationError::InvalidNonce)?;
+    let key_hash: String = row.try_get("key_hash")?;
+    let key_hash = Hash::from_str(&key_hash)
+        .map_err(|_| SymmetricKeyRotationError::InvalidKeyHash(key_hash))?;
+
+    let (crypted, nonce, key_hash) = crypto.reencrypt(&crypted, &nonce, &key_hash)?;
+
+    Ok((crypted, nonce, *key_hash))
+}
+
+fn log_report(report: &SymmetricKeyRotationReport) {
+    if report.remaining() > 0 {
+        info!(
+            active_key_hash = report.active_key_hash(),
+            remaining = report.remaining(),
+            "rows remain encrypted with retired symmetric keys"
+        );
+    }
+    for (key_hash, usage) in report.retired_keys() {
+        if usage.loaded && usage.rows > 0 {
+            info!(
+                key_hash,
+                rows = usage.rows,
+                "retired symmetric key still in use"
+            );
+        }
+    }
+    for key_hash in report.safe_to_remove() {
+        info!(
+            key_hash,
+            "retired symmetric key is no longer used by any row and is safe to remove"
+        );
+    }
+    for (key_hash, usage) in report.missing() {
+        warn!(
+            key_hash,
+            rows = usage.rows,
+            "rows are encrypted with a symmetric key that is not loaded and can't be re-encrypted"
+        );
+    }
+}
+
+fn base64_encode_bytes(bytes: &[u8]) -> String {
+    general_purpose::STANDARD_NO_PAD.encode(bytes)
+}
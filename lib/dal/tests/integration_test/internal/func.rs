@@ -7,6 +7,7 @@ use dal::{
         binding::FuncBinding,
         binding_return_value::FuncBindingReturnValue,
         execution::FuncExecution,
+        result_cache::FuncResultCache,
     },
     generate_name, ChangeSetPk, DalContext, Func, FuncBackendKind, FuncBackendResponseType, FuncId,
     StandardModel, Visibility,
@@ -15,6 +16,7 @@ use dal_test::{
     test,
     test_harness::{create_func, create_func_binding},
 };
+use veritech_client::ExecutionLimits;
 
 mod reconciliation;
 mod schema_variant_definition;
@@ -31,6 +33,42 @@ async fn new(ctx: &DalContext) {
     .expect("cannot create func");
 }
 
+#[test]
+async fn runtime_and_limits_are_stored(ctx: &DalContext) {
+    let mut func = create_func(ctx).await;
+    assert_eq!(None, func.runtime());
+    assert_eq!(None, func.limits());
+
+    let limits = ExecutionLimits {
+        cpu_time_secs: Some(5),
+        memory_bytes: Some(64 * 1024 * 1024),
+        timeout_secs: None,
+    };
+    func.set_runtime(ctx, Some("python".to_string()))
+        .await
+        .expect("could not set runtime");
+    func.set_limits(ctx, Some(limits))
+        .await
+        .expect("could not set limits");
+
+    let func = Func::get_by_id(ctx, func.id())
+        .await
+        .expect("could not get func")
+        .expect("func not found");
+    assert_eq!(Some("python"), func.runtime());
+    assert_eq!(Some(&limits), func.limits());
+
+    let mut func = func;
+    func.set_limits(ctx, Some(ExecutionLimits::default()))
+        .await
+        .expect("could not clear limits");
+    let func = Func::get_by_id(ctx, func.id())
+        .await
+        .expect("could not get func")
+        .expect("func not found");
+    assert_eq!(None, func.limits());
+}
+
 #[test]
 async fn func_binding_new(ctx: &DalContext) {
     let func = create_func(ctx).await;
@@ -89,6 +127,41 @@ async fn func_binding_execute(ctx: &DalContext) {
     );
 }
 
+#[test]
+async fn func_binding_execute_caches_pure_func_results(ctx: &DalContext) {
+    let mut func = create_func(ctx).await;
+    func.set_pure(ctx, true).await.expect("cannot set pure");
+    func.set_code_plaintext(ctx, Some("// funky"))
+        .await
+        .expect("cannot set code");
+    let args = serde_json::to_value(FuncBackendStringArgs::new("funky".to_string()))
+        .expect("cannot serialize args to json");
+    let key = FuncResultCache::key(&func, &args).expect("cannot compute cache key");
+
+    FuncBinding::create_and_execute(ctx, args, *func.id(), vec![])
+        .await
+        .expect("failed to execute func binding");
+    assert_eq!(
+        FuncResultCache::get(ctx, key)
+            .await
+            .expect("cannot get cached result"),
+        Some((
+            Some(serde_json::json!["funky"]),
+            Some(serde_json::json!["funky"])
+        ))
+    );
+
+    func.set_code_plaintext(ctx, Some("// funkier"))
+        .await
+        .expect("cannot set code");
+    assert_eq!(
+        FuncResultCache::get(ctx, key)
+            .await
+            .expect("cannot get cached result"),
+        None
+    );
+}
+
 #[test]
 async fn func_binding_execute_unset(ctx: &DalContext) {
     let name = dal_test::test_harness::generate_fake_name();
@@ -21,6 +21,7 @@ mod secret;
 mod socket;
 mod standard_model;
 mod status_update;
+mod symmetric_key_rotator;
 mod tenancy;
 mod user;
 mod visibility;
@@ -10,13 +10,20 @@ use dal::{
 };
 use dal::{BuiltinsResult, ComponentType};
 use dal_test::{connection_annotation_string, test, DalContextHeadRef};
+use module_index_client::{IndexClient, ModuleDetailsResponse};
 use si_pkg::{
-    ActionFuncSpec, AttrFuncInputSpec, AttrFuncInputSpecKind, FuncArgumentSpec, FuncSpec,
-    FuncSpecBackendKind, FuncSpecBackendResponseType, FuncSpecData, LeafFunctionSpec,
+    ActionFuncSpec, AttrFuncInputSpec, AttrFuncInputSpecKind, DependencySpec, FuncArgumentSpec,
+    FuncSpec, FuncSpecBackendKind, FuncSpecBackendResponseType, FuncSpecData, LeafFunctionSpec,
     LeafInputLocation as PkgLeafInputLocation, LeafKind as PkgLeafKind, PkgSpec, PropSpec,
     PropSpecKind, SchemaSpec, SchemaSpecData, SchemaVariantSpec, SchemaVariantSpecData, SiPkg,
     SocketSpec, SocketSpecArity, SocketSpecData, SocketSpecKind,
 };
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+use ulid::Ulid;
+use url::Url;
 
 async fn make_stellarfield(ctx: &DalContext) -> BuiltinsResult<()> {
     let mut stellarfield_builder = PkgSpec::builder();
@@ -809,3 +816,193 @@ async fn test_install_pkg(ctx: &DalContext) {
     .expect("able to search for ac input")
     .expect("able to find ac input");
 }
+
+fn dependency_pkg(name: &str, version: &str, dependencies: &[(&str, &str)]) -> SiPkg {
+    let mut builder = PkgSpec::builder();
+    builder
+        .name(name)
+        .version(version)
+        .created_by("System Initiative");
+    for (dependency_name, version_req) in dependencies {
+        builder.dependency(
+            DependencySpec::builder()
+                .name(*dependency_name)
+                .version_req(*version_req)
+                .build()
+                .expect("build dependency spec"),
+        );
+    }
+
+    SiPkg::load_from_spec(builder.build().expect("build pkg spec")).expect("load pkg from spec")
+}
+
+/// Serves just enough of the module index API for dependency resolution: searching by name and
+/// downloading a module.
+async fn stub_module_index(pkgs: Vec<SiPkg>) -> IndexClient {
+    let mut modules = Vec::new();
+    for pkg in pkgs {
+        let metadata = pkg.metadata().expect("get metadata");
+        let details = ModuleDetailsResponse {
+            id: Ulid::new().to_string(),
+            name: metadata.name().to_owned(),
+            description: None,
+            owner_user_id: "owner".to_owned(),
+            owner_display_name: None,
+            metadata: serde_json::json!({ "version": metadata.version() }),
+            latest_hash: metadata.hash().to_string(),
+            latest_hash_created_at: chrono::Utc::now(),
+            created_at: chrono::Utc::now(),
+            download_count: 0,
+        };
+        modules.push((details, pkg.write_to_bytes().expect("write pkg to bytes")));
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind stub module index");
+    let addr = listener.local_addr().expect("get stub module index addr");
+
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut request = Vec::new();
+            let mut buf = [0_u8; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                match stream.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => request.extend_from_slice(&buf[..read]),
+                }
+            }
+            let request = String::from_utf8_lossy(&request);
+            let target = request.split(' ').nth(1).unwrap_or_default().to_owned();
+
+            let (content_type, body) = if target.starts_with("/modules/search") {
+                let found: Vec<_> = modules
+                    .iter()
+                    .filter(|(details, _)| target.contains(&format!("name={}&", details.name)))
+                    .map(|(details, _)| details.clone())
+                    .collect();
+                let response = serde_json::json!({
+                    "modules": found,
+                    "nextCursor": null,
+                    "facets": { "owners": [], "kinds": [], "builtin": [] },
+                });
+                ("application/json", response.to_string().into_bytes())
+            } else {
+                let bytes = modules
+                    .iter()
+                    .find(|(details, _)| target == format!("/modules/{}/download", details.id))
+                    .map(|(_, bytes)| bytes.clone())
+                    .unwrap_or_default();
+                ("application/octet-stream", bytes)
+            };
+
+            let head = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: {content_type}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(head.as_bytes()).await;
+            let _ = stream.write_all(&body).await;
+        }
+    });
+
+    IndexClient::unauthenticated_client(
+        Url::parse(&format!("http://{addr}/")).expect("parse stub module index url"),
+    )
+}
+
+#[test]
+async fn installs_dependencies_from_module_index(ctx: &DalContext) {
+    let base = dependency_pkg("base", "1.2.0", &[]);
+    let middle = dependency_pkg("middle", "0.3.1", &[("base", "^1.1")]);
+    let app = dependency_pkg("app", "2.0.0", &[("middle", "~0.3")]);
+    let client = stub_module_index(vec![base, middle]).await;
+
+    import_pkg_from_pkg(
+        ctx,
+        &app,
+        Some(ImportOptions {
+            module_index_client: Some(client),
+            ..Default::default()
+        }),
+        true,
+    )
+    .await
+    .expect("install pkg with dependencies");
+
+    let base_at = installed_at(ctx, "base").await;
+    let middle_at = installed_at(ctx, "middle").await;
+    let app_at = installed_at(ctx, "app").await;
+
+    // Dependencies are installed before the modules needing them
+    assert!(base_at < middle_at);
+    assert!(middle_at < app_at);
+}
+
+async fn installed_at(ctx: &DalContext, name: &str) -> chrono::DateTime<chrono::Utc> {
+    let mut installed = InstalledPkg::find_by_name(ctx, name)
+        .await
+        .expect("find installed pkg");
+    assert_eq!(1, installed.len(), "{name} is installed once");
+    installed
+        .pop()
+        .expect("installed pkg")
+        .timestamp()
+        .created_at
+}
+
+#[test]
+async fn skips_dependencies_already_satisfied(ctx: &DalContext) {
+    let base = dependency_pkg("base", "1.4.2", &[]);
+    import_pkg_from_pkg(ctx, &base, None, true)
+        .await
+        .expect("install base pkg");
+
+    // Without a module index, installing would fail if the dependency were not already satisfied
+    let app = dependency_pkg("app", "1.0.0", &[("base", ">=1.2, <2")]);
+    import_pkg_from_pkg(ctx, &app, None, true)
+        .await
+        .expect("install pkg with satisfied dependency");
+
+    assert_eq!(
+        1,
+        InstalledPkg::find_by_name(ctx, "base")
+            .await
+            .expect("find installed base")
+            .len()
+    );
+}
+
+#[test]
+async fn refuses_conflicting_dependencies(ctx: &DalContext) {
+    let base = dependency_pkg("base", "1.4.2", &[]);
+    import_pkg_from_pkg(ctx, &base, None, true)
+        .await
+        .expect("install base pkg");
+
+    let newer_base = dependency_pkg("base", "2.0.0", &[]);
+    let client = stub_module_index(vec![newer_base]).await;
+    let app = dependency_pkg("app", "1.0.0", &[("base", "^2")]);
+    let result = import_pkg_from_pkg(
+        ctx,
+        &app,
+        Some(ImportOptions {
+            module_index_client: Some(client),
+            ..Default::default()
+        }),
+        true,
+    )
+    .await;
+
+    match result {
+        Err(PkgError::DependencyConflict(name, version_req, installed)) => {
+            assert_eq!("base", name);
+            assert_eq!("^2", version_req);
+            assert_eq!("1.4.2", installed);
+        }
+        other => panic!("expected a dependency conflict, got {other:?}"),
+    }
+    assert!(InstalledPkg::find_by_name(ctx, "app")
+        .await
+        .expect("find installed app")
+        .is_empty());
+}
@@ -1,5 +1,6 @@
 use dal::{
-    DalContext, EncryptedSecret, Secret, SecretAlgorithm, SecretVersion, StandardModel,
+    secret::SecretView, ComponentId, DalContext, EncryptedSecret, Func, FuncBackendKind,
+    FuncBackendResponseType, Secret, SecretAlgorithm, SecretUsage, SecretVersion, StandardModel,
     WorkspaceSignup,
 };
 use dal_test::{
@@ -119,3 +120,106 @@ async fn encrypt_decrypt_round_trip(ctx: &DalContext, nw: &WorkspaceSignup) {
         serde_json::to_value(&decrypted).expect("failed to serial decrypted into Value");
     assert_eq!(decrypted_value["message"], message);
 }
+
+#[test]
+async fn decrypt_records_usage(ctx: &DalContext, nw: &WorkspaceSignup) {
+    let secret = create_secret(ctx, nw.key_pair.pk()).await;
+    let secret_id = *secret.id();
+
+    assert_eq!(
+        SecretUsage::last_used_at(ctx, secret_id)
+            .await
+            .expect("failed to get when secret was last used"),
+        None
+    );
+
+    EncryptedSecret::get_by_id(ctx, &secret_id)
+        .await
+        .expect("failed to fetch encrypted secret")
+        .expect("failed to find encrypted secret for tenancy and/or visibility")
+        .decrypt(ctx)
+        .await
+        .expect("failed to decrypt encrypted secret");
+
+    let func = Func::new(
+        ctx,
+        generate_fake_name(),
+        FuncBackendKind::Unset,
+        FuncBackendResponseType::Unset,
+    )
+    .await
+    .expect("cannot create func");
+    let component_id = ComponentId::generate();
+    EncryptedSecret::get_by_id(ctx, &secret_id)
+        .await
+        .expect("failed to fetch encrypted secret")
+        .expect("failed to find encrypted secret for tenancy and/or visibility")
+        .decrypt_for_func(ctx, *func.id(), component_id)
+        .await
+        .expect("failed to decrypt encrypted secret for func");
+
+    // Most recent first
+    let usages = SecretUsage::list_for_secret(ctx, secret_id, None)
+        .await
+        .expect("failed to list secret usages");
+    assert_eq!(usages.len(), 2);
+    assert_eq!(usages[0].func_id(), Some(*func.id()));
+    assert_eq!(usages[0].component_id(), Some(component_id));
+    assert_eq!(usages[1].func_id(), None);
+    assert_eq!(usages[1].component_id(), None);
+    assert!(usages[0].used_at() > usages[1].used_at());
+
+    let since = SecretUsage::list_for_secret(ctx, secret_id, Some(usages[0].used_at()))
+        .await
+        .expect("failed to list secret usages since last use");
+    assert_eq!(since, usages[..1]);
+
+    let last_used_at = Some(usages[0].used_at());
+    assert_eq!(
+        SecretUsage::last_used_at(ctx, secret_id)
+            .await
+            .expect("failed to get when secret was last used"),
+        last_used_at
+    );
+    assert_eq!(
+        SecretUsage::last_used_at_by_secret(ctx)
+            .await
+            .expect("failed to get when secrets were last used")
+            .get(&secret_id)
+            .copied(),
+        last_used_at
+    );
+    let view = SecretView::from_secret(ctx, secret)
+        .await
+        .expect("failed to create secret view");
+    assert_eq!(view.last_used_at, last_used_at);
+}
+
+#[test]
+async fn failed_decrypt_records_no_usage(ctx: &DalContext, nw: &WorkspaceSignup) {
+    let secret = EncryptedSecret::new(
+        ctx,
+        generate_fake_name(),
+        "Mock".to_owned(),
+        None,
+        "im-crypted-bytes-maybe".as_bytes(),
+        nw.key_pair.pk(),
+        SecretVersion::V1,
+        SecretAlgorithm::Sealedbox,
+    )
+    .await
+    .expect("failed to create secret");
+
+    EncryptedSecret::get_by_id(ctx, secret.id())
+        .await
+        .expect("failed to fetch encrypted secret")
+        .expect("failed to find encrypted secret for tenancy and/or visibility")
+        .decrypt(ctx)
+        .await
+        .expect_err("decrypted a secret which isn't sealed with the key pair");
+
+    let usages = SecretUsage::list_for_secret(ctx, *secret.id(), None)
+        .await
+        .expect("failed to list secret usages");
+    assert!(usages.is_empty());
+}
@@ -0,0 +1,115 @@
+use base64::{engine::general_purpose, Engine};
+use dal::{
+    tasks::{KeyHashUsage, SymmetricKeyRotator},
+    EncryptedSecret, ServicesContext, StandardModel, Tenancy,
+};
+use dal_test::{helpers::workspace_signup, test, test_harness::create_secret_with_message};
+use si_crypto::SymmetricCryptoService;
+
+/// Returns a copy of `services_ctx` which uses `symmetric_crypto_service` instead.
+fn with_symmetric_crypto_service(
+    services_ctx: &ServicesContext,
+    symmetric_crypto_service: SymmetricCryptoService,
+) -> ServicesContext {
+    ServicesContext::new(
+        services_ctx.pg_pool().clone(),
+        services_ctx.nats_conn().clone(),
+        services_ctx.job_processor(),
+        services_ctx.veritech().clone(),
+        services_ctx.encryption_key(),
+        None,
+        None,
+        symmetric_crypto_service,
+        services_ctx.secret_backends().clone(),
+    )
+}
+
+#[test]
+async fn rotation_reencrypts_secrets_in_batches(services_ctx: ServicesContext) {
+    let old_key = SymmetricCryptoService::generate_key();
+    let new_key = SymmetricCryptoService::generate_key();
+    let old_crypto = SymmetricCryptoService::new(old_key.clone(), vec![]);
+    let old_key_hash = old_crypto.active_key_hash().to_string();
+
+    // Seed a workspace, whose key pair is encrypted with the old key, and its secrets
+    let mut ctx = with_symmetric_crypto_service(&services_ctx, old_crypto)
+        .into_builder(true)
+        .build_default()
+        .await
+        .expect("failed to build dal context");
+    let (nw, _) = workspace_signup(&ctx)
+        .await
+        .expect("failed to sign up workspace");
+    ctx.update_tenancy(Tenancy::new(*nw.workspace.pk()));
+    let mut secrets = Vec::new();
+    for index in 0..5 {
+        let message = serde_json::json!({ "index": index });
+        let secret = create_secret_with_message(&ctx, nw.key_pair.pk(), &message).await;
+        secrets.push((*secret.id(), message));
+    }
+
+    // A row which can't be decrypted must be skipped without stopping the batches after it
+    let (corrupt_secret_id, _) = secrets.remove(2);
+    ctx.txns()
+        .await
+        .expect("failed to get transactions")
+        .pg()
+        .execute(
+            "UPDATE encrypted_secrets SET crypted = $2 WHERE id = $1",
+            &[
+                &corrupt_secret_id,
+                &general_purpose::STANDARD_NO_PAD.encode([0u8; 64]),
+            ],
+        )
+        .await
+        .expect("failed to corrupt secret");
+    ctx.blocking_commit().await.expect("failed to commit");
+
+    // Batches of two rows mean the secrets are re-encrypted over several transactions
+    let rotator = SymmetricKeyRotator::new(with_symmetric_crypto_service(
+        &services_ctx,
+        SymmetricCryptoService::new(new_key.clone(), vec![old_key]),
+    ))
+    .with_batch_size(2);
+    let progress = rotator.rotate().await.expect("failed to rotate");
+    assert_eq!(5, progress.reencrypted, "four secrets and the key pair");
+    assert_eq!(1, progress.failed);
+
+    // Rows encrypted with the harness' own key are reported too, so only look at the old key
+    let report = rotator.report().await.expect("failed to report");
+    let old_key_usage = report
+        .retired_keys()
+        .find(|(key_hash, _)| *key_hash == old_key_hash)
+        .map(|(_, usage)| *usage);
+    assert_eq!(
+        Some(KeyHashUsage {
+            loaded: true,
+            rows: 1,
+        }),
+        old_key_usage
+    );
+
+    // A second pass leaves the row it can't re-encrypt alone and finds nothing else to do
+    let progress = rotator.rotate().await.expect("failed to rotate");
+    assert_eq!(0, progress.reencrypted);
+    assert_eq!(1, progress.failed);
+
+    // Everything else decrypts without the old key loaded at all
+    let mut ctx =
+        with_symmetric_crypto_service(&services_ctx, SymmetricCryptoService::new(new_key, vec![]))
+            .into_builder(true)
+            .build_default()
+            .await
+            .expect("failed to build dal context");
+    ctx.update_tenancy(Tenancy::new(*nw.workspace.pk()));
+    for (secret_id, message) in secrets {
+        let decrypted = EncryptedSecret::get_by_id(&ctx, &secret_id)
+            .await
+            .expect("failed to get encrypted secret")
+            .expect("failed to find encrypted secret")
+            .decrypt(&ctx)
+            .await
+            .expect("failed to decrypt re-encrypted secret");
+        assert_eq!(message, *decrypted.message());
+    }
+}
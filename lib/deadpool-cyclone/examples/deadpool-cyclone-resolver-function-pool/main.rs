@@ -11,7 +11,8 @@ use deadpool_cyclone::{
         cyclone::{LocalUdsInstance, LocalUdsInstanceSpec},
         Instance,
     },
-    CycloneClient, FunctionResult, Manager, Pool, ProgressMessage, ResolverFunctionRequest,
+    CallbackResponse, CycloneClient, FunctionResult, Manager, Pool, ProgressMessage,
+    ResolverFunctionRequest,
 };
 use futures::{stream, StreamExt, TryStreamExt};
 use tokio::signal;
@@ -101,6 +102,14 @@ async fn execute(
     let mut progress = instance.execute_resolver(request).await?.start().await?;
     while let Some(message) = progress.try_next().await? {
         match message {
+            ProgressMessage::CallbackRequest(request) => {
+                progress
+                    .respond(&CallbackResponse::failure(
+                        request.id,
+                        "this example can't answer callback requests",
+                    ))
+                    .await?;
+            }
             ProgressMessage::Heartbeat => info!("heartbeat"),
             ProgressMessage::OutputStream(output) => {
                 info!(
@@ -6,7 +6,7 @@ pub use local_http::{
 };
 pub use local_uds::{
     LocalUdsInstance, LocalUdsInstanceError, LocalUdsInstanceSpec, LocalUdsInstanceSpecBuilder,
-    LocalUdsRuntimeStrategy, LocalUdsSocketStrategy,
+    LocalUdsRuntimeStrategy, LocalUdsSocketStrategy, LocalUdsWarmPool,
 };
 
 mod local_http;
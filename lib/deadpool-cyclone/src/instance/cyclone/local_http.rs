@@ -1,4 +1,6 @@
 use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsString,
     net::{Ipv4Addr, SocketAddr, SocketAddrV4},
     result,
     time::Duration,
@@ -11,10 +13,10 @@ use cyclone_client::{
 };
 use cyclone_core::{
     process::{self, ShutdownError},
-    ActionRunRequest, ActionRunResultSuccess, CanonicalCommand, ReconciliationRequest,
-    ReconciliationResultSuccess, ResolverFunctionRequest, ResolverFunctionResultSuccess,
-    SchemaVariantDefinitionRequest, SchemaVariantDefinitionResultSuccess, ValidationRequest,
-    ValidationResultSuccess,
+    ActionRunRequest, ActionRunResultSuccess, CanonicalCommand, CanonicalCommandError,
+    ReconciliationRequest, ReconciliationResultSuccess, ResolverFunctionRequest,
+    ResolverFunctionResultSuccess, SchemaVariantDefinitionRequest,
+    SchemaVariantDefinitionResultSuccess, ValidationRequest, ValidationResultSuccess,
 };
 use derive_builder::Builder;
 use futures::StreamExt;
@@ -266,6 +268,16 @@ pub struct LocalHttpInstanceSpec {
     #[builder(try_setter, setter(into))]
     lang_server_cmd_path: CanonicalCommand,
 
+    /// Canonical paths to the language server programs for function runtimes, keyed by runtime.
+    #[builder(setter(custom), default)]
+    runtime_lang_server_cmd_paths: HashMap<String, CanonicalCommand>,
+
+    /// Function runtimes whose language servers answer callback requests, so their stdin is kept
+    /// open after the request. A language server which isn't listed may read its request up to
+    /// EOF.
+    #[builder(setter(custom), default)]
+    runtime_lang_server_callbacks: HashSet<String>,
+
     /// Socket strategy for a spawned Cyclone server.
     #[builder(default)]
     socket_strategy: LocalHttpSocketStrategy,
@@ -357,6 +369,15 @@ impl LocalHttpInstanceSpec {
             .arg("--lang-server")
             .arg(&self.lang_server_cmd_path)
             .arg("--enable-watch");
+        for (runtime, path) in &self.runtime_lang_server_cmd_paths {
+            let mut arg = OsString::from(runtime);
+            arg.push("=");
+            arg.push(path);
+            cmd.arg("--runtime-lang-server").arg(arg);
+        }
+        for runtime in &self.runtime_lang_server_callbacks {
+            cmd.arg("--runtime-lang-server-callbacks").arg(runtime);
+        }
         if let Some(limit_requests) = self.limit_requests {
             cmd.arg("--limit-requests").arg(limit_requests.to_string());
         }
@@ -388,6 +409,30 @@ impl SpecBuilder for LocalHttpInstanceSpecBuilder {
 }
 
 impl LocalHttpInstanceSpecBuilder {
+    /// Adds the language server program a spawned Cyclone server uses to execute functions written
+    /// for `runtime`.
+    pub fn try_runtime_lang_server_cmd_path<P>(
+        &mut self,
+        runtime: impl Into<String>,
+        path: P,
+    ) -> result::Result<&mut Self, CanonicalCommandError>
+    where
+        P: TryInto<CanonicalCommand, Error = CanonicalCommandError>,
+    {
+        self.runtime_lang_server_cmd_paths
+            .get_or_insert_with(HashMap::new)
+            .insert(runtime.into(), path.try_into()?);
+        Ok(self)
+    }
+
+    /// Marks the language server of `runtime` as answering callback requests.
+    pub fn runtime_lang_server_callbacks(&mut self, runtime: impl Into<String>) -> &mut Self {
+        self.runtime_lang_server_callbacks
+            .get_or_insert_with(HashSet::new)
+            .insert(runtime.into());
+        self
+    }
+
     /// Sets the limit requests strategy to `1` for a spawned Cyclone server.
     pub fn oneshot(&mut self) -> &mut Self {
         self.limit_requests(Some(1))
@@ -6,7 +6,14 @@ use ::std::path::Path;
 use rand::distributions::Alphanumeric;
 use rand::thread_rng;
 use rand::Rng;
-use std::{io, path::PathBuf, result, time::Duration};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    ffi::OsString,
+    fmt, io,
+    path::PathBuf,
+    result,
+    time::Duration,
+};
 use tokio::time::Instant;
 
 use bollard::container::{
@@ -22,26 +29,26 @@ use cyclone_client::{
 };
 use cyclone_core::{
     process::{self, ShutdownError},
-    ActionRunRequest, ActionRunResultSuccess, CanonicalCommand, ReconciliationRequest,
-    ReconciliationResultSuccess, ResolverFunctionRequest, ResolverFunctionResultSuccess,
-    SchemaVariantDefinitionRequest, SchemaVariantDefinitionResultSuccess, ValidationRequest,
-    ValidationResultSuccess,
+    ActionRunRequest, ActionRunResultSuccess, CanonicalCommand, CanonicalCommandError,
+    ReconciliationRequest, ReconciliationResultSuccess, ResolverFunctionRequest,
+    ResolverFunctionResultSuccess, SchemaVariantDefinitionRequest,
+    SchemaVariantDefinitionResultSuccess, ValidationRequest, ValidationResultSuccess,
 };
 use derive_builder::Builder;
-use futures::StreamExt;
+use futures::{future, StreamExt};
 use serde::{Deserialize, Serialize};
 use tempfile::{NamedTempFile, TempPath};
 use thiserror::Error;
 use tokio::{
     io::{AsyncRead, AsyncWrite},
     process::{Child, Command},
-    sync::oneshot,
+    sync::{oneshot, watch, Mutex},
     time,
 };
-use tracing::{trace, warn};
+use tracing::{debug, trace, warn};
 
 use crate::instance::{Instance, Spec, SpecBuilder};
-use crate::PoolNoodle;
+use crate::{PoolNoodle, PoolNoodleError};
 
 /// Error type for [`LocalUdsInstance`].
 #[remain::sorted]
@@ -92,6 +99,9 @@ pub enum LocalUdsInstanceError {
     /// Instance has exhausted its predefined request count.
     #[error("no remaining requests, cyclone server is considered unhealthy")]
     NoRemainingRequests,
+    /// Failed to get a jail from the execution pool.
+    #[error(transparent)]
+    PoolNoodle(#[from] PoolNoodleError),
     /// Failed to setup the host correctly.
     #[error("failed to setup host")]
     SetupFailed,
@@ -293,8 +303,171 @@ impl LocalUdsInstance {
     }
 }
 
+/// How often a [`LocalUdsWarmPool`] is topped back up after instances are checked out.
+const WARM_POOL_REFILL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often the idle instances of a [`LocalUdsWarmPool`] are health-checked.
+const WARM_POOL_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A pool of booted [`LocalUdsInstance`]s, ready to be checked out.
+///
+/// Spawning a Cyclone process or container and waiting for its watch session to come up dominates
+/// the latency of an execution on the [`LocalProcess`](LocalUdsRuntimeStrategy::LocalProcess) and
+/// [`LocalDocker`](LocalUdsRuntimeStrategy::LocalDocker) runtimes. Once
+/// [started](Self::start), the pool boots instances in the background using whichever
+/// [`LocalInstanceRuntime`] the spec selects, keeps their watch sessions open while they sit idle
+/// and replaces any that stop answering liveness checks, until it is [shut down](Self::shutdown).
+#[derive(Clone)]
+pub struct LocalUdsWarmPool {
+    instances: Arc<Mutex<VecDeque<LocalUdsInstance>>>,
+    shutdown_tx: Arc<watch::Sender<bool>>,
+}
+
+impl Default for LocalUdsWarmPool {
+    fn default() -> Self {
+        let (shutdown_tx, _) = watch::channel(false);
+        Self {
+            instances: Default::default(),
+            shutdown_tx: Arc::new(shutdown_tx),
+        }
+    }
+}
+
+impl fmt::Debug for LocalUdsWarmPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalUdsWarmPool").finish_non_exhaustive()
+    }
+}
+
+impl LocalUdsWarmPool {
+    /// Returns the number of booted instances waiting to be checked out.
+    pub async fn len(&self) -> usize {
+        self.instances.lock().await.len()
+    }
+
+    /// Returns `true` if no booted instances are waiting to be checked out.
+    pub async fn is_empty(&self) -> bool {
+        self.instances.lock().await.is_empty()
+    }
+
+    /// Starts a background task which keeps `size` booted instances of `spec` in the pool until
+    /// the pool is [shut down](Self::shutdown).
+    pub fn start(&self, spec: LocalUdsInstanceSpec, size: usize) {
+        let pool = self.clone();
+        let mut shutdown_rx = self.shutdown_tx.subscribe();
+        tokio::spawn(async move {
+            let mut last_health_check = Instant::now();
+            while !*shutdown_rx.borrow() {
+                if last_health_check.elapsed() >= WARM_POOL_HEALTH_CHECK_INTERVAL {
+                    pool.check_health().await;
+                    last_health_check = Instant::now();
+                }
+                pool.refill(size, || spec.spawn_cold()).await;
+                tokio::select! {
+                    _ = time::sleep(WARM_POOL_REFILL_INTERVAL) => {}
+                    _ = shutdown_rx.changed() => {}
+                }
+            }
+            trace!("warm cyclone pool shut down");
+        });
+    }
+
+    /// Stops booting instances and terminates those waiting to be checked out.
+    ///
+    /// Instances which were already checked out are left to their pool.
+    pub async fn shutdown(&self) {
+        self.shutdown_tx.send_replace(true);
+        let idle: Vec<_> = self.instances.lock().await.drain(..).collect();
+        terminate_all(idle).await;
+    }
+
+    fn is_shut_down(&self) -> bool {
+        *self.shutdown_tx.borrow()
+    }
+
+    /// Takes a booted instance out of the pool, if one is ready.
+    async fn take(&self) -> Option<LocalUdsInstance> {
+        if self.is_shut_down() {
+            return None;
+        }
+
+        loop {
+            let mut instance = self.instances.lock().await.pop_front()?;
+            if instance.is_watch_shutdown_open() {
+                return Some(instance);
+            }
+            // The watch session ended while the instance sat idle, so the server has likely gone
+            // away or is about to
+            if let Err(err) = instance.terminate().await {
+                warn!(error = ?err, "failed to terminate dead warm cyclone instance");
+            }
+        }
+    }
+
+    async fn refill<F, Fut>(&self, size: usize, spawn: F)
+    where
+        F: Fn() -> Fut,
+        Fut: future::Future<Output = Result<LocalUdsInstance>>,
+    {
+        let missing = size.saturating_sub(self.len().await);
+        if missing == 0 {
+            return;
+        }
+
+        debug!(missing, "booting warm cyclone instances");
+        let spawned = future::join_all((0..missing).map(|_| spawn())).await;
+        let mut booted = Vec::with_capacity(spawned.len());
+        for result in spawned {
+            match result {
+                Ok(instance) => booted.push(instance),
+                Err(err) => warn!(error = ?err, "failed to boot warm cyclone instance"),
+            }
+        }
+
+        let mut instances = self.instances.lock().await;
+        // The pool may have been shut down while the instances were booting
+        if self.is_shut_down() {
+            drop(instances);
+            terminate_all(booted).await;
+        } else {
+            instances.extend(booted);
+        }
+    }
+
+    async fn check_health(&self) {
+        // Check each idle instance in turn rather than draining the pool, so instances remain
+        // available for checkout while the check is running
+        let idle = self.len().await;
+        for _ in 0..idle {
+            let mut instance = match self.instances.lock().await.pop_front() {
+                Some(instance) => instance,
+                None => break,
+            };
+            let healthy =
+                instance.is_watch_shutdown_open() && instance.client.liveness().await.is_ok();
+            if healthy {
+                self.instances.lock().await.push_back(instance);
+            } else {
+                warn!("warm cyclone instance failed its health check, replacing it");
+                if let Err(err) = instance.terminate().await {
+                    warn!(error = ?err, "failed to terminate unhealthy warm cyclone instance");
+                }
+            }
+        }
+    }
+}
+
+async fn terminate_all(instances: Vec<LocalUdsInstance>) {
+    for mut instance in instances {
+        if let Err(err) = instance.terminate().await {
+            warn!(error = ?err, "failed to terminate warm cyclone instance");
+        }
+    }
+}
+
 /// The [`Spec`] for [`LocalUdsInstance`]
 #[derive(Builder, Clone, Debug)]
+#[builder(build_fn(validate = "Self::validate"))]
 pub struct LocalUdsInstanceSpec {
     /// Canonical path to the `cyclone` program.
     #[builder(try_setter, setter(into), default)]
@@ -308,6 +481,21 @@ pub struct LocalUdsInstanceSpec {
     #[builder(try_setter, setter(into), default)]
     lang_server_cmd_path: CanonicalCommand,
 
+    /// Canonical paths to the language server programs for function runtimes, keyed by runtime.
+    ///
+    /// The [`LocalDocker`](LocalUdsRuntimeStrategy::LocalDocker) runtime mounts each program into
+    /// the container. They can't be used with the
+    /// [`LocalFirecracker`](LocalUdsRuntimeStrategy::LocalFirecracker) runtime, whose VM image
+    /// provides its own language server.
+    #[builder(setter(custom), default)]
+    runtime_lang_server_cmd_paths: HashMap<String, CanonicalCommand>,
+
+    /// Function runtimes whose language servers answer callback requests, so their stdin is kept
+    /// open after the request. A language server which isn't listed may read its request up to
+    /// EOF.
+    #[builder(setter(custom), default)]
+    runtime_lang_server_callbacks: HashSet<String>,
+
     /// Socket strategy for a spawned Cyclone server.
     #[builder(default)]
     socket_strategy: LocalUdsSocketStrategy,
@@ -347,6 +535,19 @@ pub struct LocalUdsInstanceSpec {
     /// pool noodle
     #[builder(default)]
     pool_noodle: PoolNoodle,
+
+    /// Number of booted instances to keep ready for checkout when running on the
+    /// [`LocalProcess`](LocalUdsRuntimeStrategy::LocalProcess) or
+    /// [`LocalDocker`](LocalUdsRuntimeStrategy::LocalDocker) runtimes. A size of `0` spawns every
+    /// instance on checkout.
+    ///
+    /// Every warm instance needs a socket of its own, so a warm pool can't be used with a
+    /// [`Custom`](LocalUdsSocketStrategy::Custom) socket.
+    #[builder(setter(into), default)]
+    warm_pool_size: u16,
+
+    #[builder(setter(skip))]
+    warm_pool: LocalUdsWarmPool,
 }
 
 #[async_trait]
@@ -356,13 +557,36 @@ impl Spec for LocalUdsInstanceSpec {
 
     async fn setup(&mut self) -> result::Result<(), Self::Error> {
         match self.runtime_strategy {
-            LocalUdsRuntimeStrategy::LocalDocker => Ok(()),
-            LocalUdsRuntimeStrategy::LocalProcess => Ok(()),
+            LocalUdsRuntimeStrategy::LocalDocker | LocalUdsRuntimeStrategy::LocalProcess => {
+                if self.warm_pool_size > 0 {
+                    self.warm_pool
+                        .start(self.clone(), self.warm_pool_size.into());
+                }
+                Ok(())
+            }
             LocalUdsRuntimeStrategy::LocalFirecracker => setup_firecracker(self).await,
         }
     }
 
     async fn spawn(&self) -> result::Result<Self::Instance, Self::Error> {
+        if let Some(instance) = self.warm_pool.take().await {
+            trace!("cyclone-execution: checked out warm instance");
+            return Ok(instance);
+        }
+
+        self.spawn_cold().await
+    }
+}
+
+impl LocalUdsInstanceSpec {
+    /// Shuts down the pool of booted instances kept for this spec, if there is one, terminating
+    /// the instances waiting to be checked out.
+    pub async fn shutdown_warm_pool(&self) {
+        self.warm_pool.shutdown().await;
+    }
+
+    /// Boots a new [`LocalUdsInstance`] and waits for its watch session to come up.
+    async fn spawn_cold(&self) -> Result<LocalUdsInstance> {
         let (temp_path, socket) = temp_path_and_socket_from(&self.socket_strategy)?;
         let mut runtime = runtime_instance_from_spec(self, &socket).await?;
 
@@ -408,7 +632,7 @@ impl Spec for LocalUdsInstanceSpec {
                     Err(err) => err,
                 };
                 if retries < 1 {
-                    return Err(Self::Error::WatchInitTimeout);
+                    return Err(LocalUdsInstanceError::WatchInitTimeout);
                 }
                 retries -= 1;
                 time::sleep(Duration::from_millis(64)).await;
@@ -434,7 +658,7 @@ impl Spec for LocalUdsInstanceSpec {
         watch_progress
             .next()
             .await
-            .ok_or(Self::Error::WatchClosed)??;
+            .ok_or(LocalUdsInstanceError::WatchClosed)??;
         trace!(
             "cyclone-execution: watch first-ping {:?}",
             SystemTime::now()
@@ -452,7 +676,7 @@ impl Spec for LocalUdsInstanceSpec {
                 .expect("time has gone backwards")
         );
 
-        Ok(Self::Instance {
+        Ok(LocalUdsInstance {
             _temp_path: temp_path,
             client,
             limit_requests: self.limit_requests,
@@ -472,6 +696,59 @@ impl SpecBuilder for LocalUdsInstanceSpecBuilder {
 }
 
 impl LocalUdsInstanceSpecBuilder {
+    fn validate(&self) -> result::Result<(), String> {
+        if let (Some(LocalUdsSocketStrategy::Custom(socket)), Some(warm_pool_size)) =
+            (&self.socket_strategy, self.warm_pool_size)
+        {
+            if warm_pool_size > 0 {
+                return Err(format!(
+                    "a warm pool of {warm_pool_size} instances can't share the custom socket {}",
+                    socket.display()
+                ));
+            }
+        }
+        if let (
+            Some(LocalUdsRuntimeStrategy::LocalFirecracker),
+            Some(runtime_lang_server_cmd_paths),
+        ) = (&self.runtime_strategy, &self.runtime_lang_server_cmd_paths)
+        {
+            if !runtime_lang_server_cmd_paths.is_empty() {
+                let mut runtimes: Vec<_> = runtime_lang_server_cmd_paths.keys().collect();
+                runtimes.sort();
+                return Err(format!(
+                    "the firecracker runtime can't use language servers for the {runtimes:?} \
+                    function runtimes"
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds the language server program a spawned Cyclone server uses to execute functions written
+    /// for `runtime`.
+    pub fn try_runtime_lang_server_cmd_path<P>(
+        &mut self,
+        runtime: impl Into<String>,
+        path: P,
+    ) -> result::Result<&mut Self, CanonicalCommandError>
+    where
+        P: TryInto<CanonicalCommand, Error = CanonicalCommandError>,
+    {
+        self.runtime_lang_server_cmd_paths
+            .get_or_insert_with(HashMap::new)
+            .insert(runtime.into(), path.try_into()?);
+        Ok(self)
+    }
+
+    /// Marks the language server of `runtime` as answering callback requests.
+    pub fn runtime_lang_server_callbacks(&mut self, runtime: impl Into<String>) -> &mut Self {
+        self.runtime_lang_server_callbacks
+            .get_or_insert_with(HashSet::new)
+            .insert(runtime.into());
+        self
+    }
+
     /// Sets the limit requests strategy to `1` for a spawned Cyclone server.
     pub fn oneshot(&mut self) -> &mut Self {
         self.limit_requests(Some(1))
@@ -606,6 +883,15 @@ impl LocalProcessRuntime {
             .arg("--lang-server")
             .arg(&spec.lang_server_cmd_path)
             .arg("--enable-watch");
+        for (runtime, path) in &spec.runtime_lang_server_cmd_paths {
+            let mut arg = OsString::from(runtime);
+            arg.push("=");
+            arg.push(path);
+            cmd.arg("--runtime-lang-server").arg(arg);
+        }
+        for runtime in &spec.runtime_lang_server_callbacks {
+            cmd.arg("--runtime-lang-server-callbacks").arg(runtime);
+        }
         if let Some(limit_requests) = spec.limit_requests {
             cmd.arg("--limit-requests").arg(limit_requests.to_string());
         }
@@ -704,6 +990,22 @@ impl LocalDockerRuntime {
             String::from("/usr/local/bin/lang-js"),
             String::from("--enable-watch"),
         ];
+        let mut runtime_lang_server_mounts = Vec::new();
+        for (runtime, path) in &spec.runtime_lang_server_cmd_paths {
+            let target = format!("/usr/local/bin/lang-server-{runtime}");
+            cmd.push(String::from("--runtime-lang-server"));
+            cmd.push(format!("{runtime}={target}"));
+            runtime_lang_server_mounts.push(Mount {
+                source: Some(path.as_path().to_string_lossy().to_string()),
+                target: Some(target),
+                typ: Some(MountTypeEnum::BIND),
+                ..Default::default()
+            });
+        }
+        for runtime in &spec.runtime_lang_server_callbacks {
+            cmd.push(String::from("--runtime-lang-server-callbacks"));
+            cmd.push(runtime.to_owned());
+        }
         if let Some(limit_requests) = spec.limit_requests {
             cmd.push(String::from("--limit-requests"));
             cmd.push(limit_requests.to_string())
@@ -735,7 +1037,7 @@ impl LocalDockerRuntime {
             .expect("socket path not available")
             .to_str()
             .expect("unable to unpack path");
-        let mounts = vec![
+        let mut mounts = vec![
             Mount {
                 source: Some(String::from(socket_dir)),
                 target: Some(String::from(socket_dir)),
@@ -749,6 +1051,7 @@ impl LocalDockerRuntime {
                 ..Default::default()
             },
         ];
+        mounts.extend(runtime_lang_server_mounts);
 
         let container_id = docker
             .create_container(
@@ -821,16 +1124,7 @@ struct LocalFirecrackerRuntime {
 
 impl LocalFirecrackerRuntime {
     async fn build(spec: LocalUdsInstanceSpec) -> Result<Box<dyn LocalInstanceRuntime>> {
-        let vm_id = spec
-            .pool_noodle
-            .0
-            .lock()
-            .await
-            .get_ready_jail()
-            .await
-            .expect(
-            "Function execution is impossible as the execution pool is starved and not recovering.",
-        );
+        let vm_id = spec.pool_noodle.get_ready_jail().await?;
 
         let _start_time = spec
             .pool_noodle
@@ -1019,3 +1313,153 @@ async fn watch_task<Strm>(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// Counts terminations rather than running anything.
+    struct FakeRuntime {
+        socket: PathBuf,
+        terminated: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl LocalInstanceRuntime for FakeRuntime {
+        fn id(&self) -> u32 {
+            0
+        }
+
+        fn socket(&mut self) -> PathBuf {
+            self.socket.clone()
+        }
+
+        async fn spawn(&mut self) -> result::Result<(), LocalUdsInstanceError> {
+            Ok(())
+        }
+
+        async fn terminate(&mut self) -> result::Result<(), LocalUdsInstanceError> {
+            self.terminated.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    /// Boots fake instances, keeping their watch sessions open until dropped.
+    #[derive(Default)]
+    struct FakeBooter {
+        booted: AtomicUsize,
+        terminated: Arc<AtomicUsize>,
+        watch_shutdown_rxs: std::sync::Mutex<Vec<oneshot::Receiver<()>>>,
+    }
+
+    impl FakeBooter {
+        fn boot(&self) -> future::Ready<Result<LocalUdsInstance>> {
+            self.booted.fetch_add(1, Ordering::SeqCst);
+            let socket = PathBuf::from("/nonexistent/cyclone.sock");
+            let (watch_shutdown_tx, watch_shutdown_rx) = oneshot::channel();
+            self.watch_shutdown_rxs
+                .lock()
+                .expect("lock poisoned")
+                .push(watch_shutdown_rx);
+
+            future::ready(Ok(LocalUdsInstance {
+                _temp_path: None,
+                client: Client::uds(&socket, Default::default()).expect("failed to create client"),
+                limit_requests: Some(1),
+                runtime: Box::new(FakeRuntime {
+                    socket,
+                    terminated: self.terminated.clone(),
+                }),
+                watch_shutdown_tx,
+            }))
+        }
+
+        fn booted(&self) -> usize {
+            self.booted.load(Ordering::SeqCst)
+        }
+
+        fn terminated(&self) -> usize {
+            self.terminated.load(Ordering::SeqCst)
+        }
+
+        /// Ends the watch session of the first instance booted.
+        fn end_first_watch(&self) {
+            self.watch_shutdown_rxs
+                .lock()
+                .expect("lock poisoned")
+                .remove(0);
+        }
+    }
+
+    #[tokio::test]
+    async fn refill_tops_up_to_size_after_take() {
+        let booter = FakeBooter::default();
+        let pool = LocalUdsWarmPool::default();
+
+        pool.refill(3, || booter.boot()).await;
+        assert_eq!(3, pool.len().await);
+
+        assert!(pool.take().await.is_some());
+        assert_eq!(2, pool.len().await);
+
+        pool.refill(3, || booter.boot()).await;
+        assert_eq!(3, pool.len().await);
+        assert_eq!(4, booter.booted());
+    }
+
+    #[tokio::test]
+    async fn take_skips_instances_whose_watch_ended() {
+        let booter = FakeBooter::default();
+        let pool = LocalUdsWarmPool::default();
+        pool.refill(2, || booter.boot()).await;
+
+        booter.end_first_watch();
+
+        assert!(pool.take().await.is_some());
+        assert_eq!(1, booter.terminated());
+        assert!(pool.is_empty().await);
+    }
+
+    #[tokio::test]
+    async fn shutdown_terminates_idle_instances() {
+        let booter = FakeBooter::default();
+        let pool = LocalUdsWarmPool::default();
+        pool.refill(2, || booter.boot()).await;
+
+        pool.shutdown().await;
+        assert!(pool.is_empty().await);
+        assert_eq!(2, booter.terminated());
+        assert!(pool.take().await.is_none());
+
+        // Instances booted after shutdown are terminated rather than pooled
+        pool.refill(2, || booter.boot()).await;
+        assert!(pool.is_empty().await);
+        assert_eq!(4, booter.terminated());
+    }
+
+    #[test]
+    fn warm_pool_is_rejected_with_custom_socket() {
+        let mut builder = LocalUdsInstanceSpecBuilder::default();
+        builder.socket_strategy(LocalUdsSocketStrategy::custom("/tmp/cyclone.sock"));
+
+        assert!(builder.build().is_ok());
+        assert!(builder.warm_pool_size(2_u16).build().is_err());
+    }
+
+    #[test]
+    fn runtime_lang_servers_are_rejected_on_firecracker() {
+        let lang_server = std::env::current_exe().expect("failed to find test executable");
+        let mut builder = LocalUdsInstanceSpecBuilder::default();
+        builder
+            .try_runtime_lang_server_cmd_path("python", lang_server)
+            .expect("failed to canonicalize lang server path");
+
+        assert!(builder.build().is_ok());
+        assert!(builder
+            .runtime_strategy(LocalUdsRuntimeStrategy::LocalFirecracker)
+            .build()
+            .is_err());
+    }
+}
@@ -21,17 +21,20 @@ use async_trait::async_trait;
 use thiserror::Error;
 
 pub use self::instance::{Instance, Spec};
-pub use crate::pool_noodle::pool_noodle::PoolNoodle;
+pub use crate::pool_noodle::pool_noodle::{
+    PoolNoodle, PoolNoodleConfig, PoolNoodleError, PoolNoodleStats,
+};
 
 pub use cyclone_client::{
     ClientError, CycloneClient, CycloneEncryptionKey, CycloneEncryptionKeyError, ExecutionError,
+    ExecutionStarted, UnixStream,
 };
 pub use cyclone_core::{
-    ActionRunRequest, ActionRunResultSuccess, ComponentView, FunctionResult, FunctionResultFailure,
-    FunctionResultFailureError, OutputStream, ProgressMessage, ReconciliationRequest,
-    ReconciliationResultSuccess, ResolverFunctionRequest, ResolverFunctionResultSuccess,
-    ResourceStatus, SchemaVariantDefinitionRequest, SchemaVariantDefinitionResultSuccess,
-    ValidationRequest, ValidationResultSuccess,
+    ActionRunRequest, ActionRunResultSuccess, CallbackRequest, CallbackResponse, ComponentView,
+    FunctionResult, FunctionResultFailure, FunctionResultFailureError, OutputStream,
+    ProgressMessage, ReconciliationRequest, ReconciliationResultSuccess, ResolverFunctionRequest,
+    ResolverFunctionResultSuccess, ResourceStatus, SchemaVariantDefinitionRequest,
+    SchemaVariantDefinitionResultSuccess, ValidationRequest, ValidationResultSuccess,
 };
 
 /// [`Instance`] implementations.
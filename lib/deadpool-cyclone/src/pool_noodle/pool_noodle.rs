@@ -1,17 +1,29 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::Arc;
 
+use futures::future::join_all;
 use tokio::sync::Mutex;
 use tokio::time::Duration;
 use tokio::{process::Command, time::Instant};
-use tracing::{debug, trace, warn};
+use tracing::{debug, info, trace, warn};
 
 use std::{collections::VecDeque, result};
 use thiserror::Error;
 use tokio::time;
 
-const MINUMUM_JAIL_PERCENTAGE: f32 = 0.25;
 const CYCLONE_EXECUTION_TIMEOUT: u64 = 3600;
+/// How far back checkouts are counted when estimating the demand for ready jails.
+const CHECKOUT_RATE_WINDOW: Duration = Duration::from_secs(30);
+/// The weight given to the newest sample when updating a latency average.
+const LATENCY_AVERAGE_WEIGHT: f64 = 0.2;
+/// The most jails prepared, or cleaned, at the same time.
+const MAX_CONCURRENT_JAIL_OPERATIONS: usize = 8;
+/// How often [`PoolNoodleStats`] are reported.
+const STATS_INTERVAL: Duration = Duration::from_secs(10);
+/// How often a caller waiting on a starved pool checks for a ready jail.
+const GET_READY_JAIL_INTERVAL: Duration = Duration::from_millis(100);
+/// How long a caller waits on a starved pool before giving up.
+const GET_READY_JAIL_TIMEOUT: Duration = Duration::from_secs(30);
 
 type Result<T> = result::Result<T, PoolNoodleError>;
 ///---------------------------------------------------------------------
@@ -56,12 +68,23 @@ type Result<T> = result::Result<T, PoolNoodleError>;
 #[remain::sorted]
 #[derive(Debug, Error)]
 pub enum PoolNoodleError {
+    /// Every jail has been taken out of rotation after failing to be prepared or cleaned.
+    #[error("Every jail in the execution pool has been taken out of rotation!")]
+    AllJailsQuarantined,
     /// Failed to clean a jail.
     #[error("Failed to clean the jail")]
     CleanJail,
     /// Failed to get a new jail ID.
     #[error("Failed to get a new jail from the execution pool!")]
     ExecutionPoolStarved,
+    /// The config asks for more jails to always be kept ready than may ever be kept ready.
+    #[error("min_ready ({min_ready}) must not be greater than max_ready ({max_ready})")]
+    MinReadyAboveMaxReady {
+        /// The configured fewest jails to keep ready.
+        min_ready: u32,
+        /// The configured most jails to keep ready.
+        max_ready: u32,
+    },
     /// Failed to prepare a new jail.
     #[error("Failed to prepare the jail")]
     PrepareJail,
@@ -70,155 +93,276 @@ pub enum PoolNoodleError {
     SetClean,
 }
 
-/// Pool Noodle is a tool for ensuring that we maintain a bare minimum number of Firecracker Jails
-/// for function execution. We wrap it in an Arc Mutex so we can update the queues it manages
-/// across threads.
+/// Bounds and thresholds for a [`PoolNoodle`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PoolNoodleConfig {
+    /// The total number of jails to manage.
+    pub pool_size: u32,
+    /// The fewest jails to keep ready, however quiet things are.
+    pub min_ready: u32,
+    /// The most jails to keep ready, however busy things are.
+    pub max_ready: u32,
+    /// How many times in a row a jail may fail to be prepared before it's taken out of rotation.
+    pub max_prepare_failures: u32,
+}
+
+impl PoolNoodleConfig {
+    /// The share of the pool kept ready by default, however quiet things are.
+    pub const DEFAULT_MIN_READY_PERCENTAGE: f32 = 0.25;
+    /// How many times in a row a jail may fail to be prepared by default.
+    pub const DEFAULT_MAX_PREPARE_FAILURES: u32 = 3;
+
+    /// Creates a config for a pool of `pool_size` jails which keeps between a quarter and all of
+    /// them ready.
+    pub fn new(pool_size: u32) -> Self {
+        Self {
+            pool_size,
+            min_ready: (pool_size as f32 * Self::DEFAULT_MIN_READY_PERCENTAGE).ceil() as u32,
+            max_ready: pool_size,
+            max_prepare_failures: Self::DEFAULT_MAX_PREPARE_FAILURES,
+        }
+    }
+}
+
+/// A snapshot of the jails managed by a [`PoolNoodle`], along with counters of what has happened
+/// to them since it was started.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PoolNoodleStats {
+    /// The number of jails currently wanted ready.
+    pub target_ready: u32,
+    /// Jails that can currently be used to run functions.
+    pub ready: usize,
+    /// Jails currently running functions.
+    pub active: usize,
+    /// Jails that have been used and must be cleaned up.
+    pub to_be_cleaned: usize,
+    /// Jails that are available to be prepared.
+    pub unprepared: usize,
+    /// Jails taken out of rotation after failing to be prepared or cleaned.
+    pub quarantined: usize,
+    /// Jails handed out to run functions.
+    pub checkouts: u64,
+    /// Requests for a jail which found none ready.
+    pub starved_checkouts: u64,
+    /// Failed attempts to prepare a jail.
+    pub prepare_failures: u64,
+    /// Failed attempts to clean a jail.
+    pub clean_failures: u64,
+}
+
+/// Pool Noodle is a tool for ensuring that we maintain enough ready Firecracker Jails for function
+/// execution. We wrap it in an Arc Mutex so we can update the queues it manages across threads.
+///
+/// The number of jails kept ready adapts to demand: enough to cover the checkouts expected, at the
+/// recent checkout rate, while a checked out jail runs, is cleaned and is prepared again, within
+/// the bounds of its [`PoolNoodleConfig`].
 #[derive(Debug, Clone)]
 pub struct PoolNoodle(pub Arc<Mutex<PoolNoodleInner>>);
 
 /// Inner struct to excpsulate the queues of jails in different states.
 ///
-/// pool_size: the total number of jails we want to manage
+/// active: jails that are currently running functions, along with when they started
 /// ready: jails that can currently be used to run functions
 /// to_be_cleaned: jails that have been used and must be cleaned up
 /// unprepared: jails that are available to be prepared and moved into a ready state
+/// quarantined: jails that repeatedly failed to be prepared, or failed to be cleaned
 #[derive(Debug)]
 pub struct PoolNoodleInner {
-    pool_size: u32,
+    config: PoolNoodleConfig,
     active: BTreeMap<u32, Instant>,
     ready: Vec<u32>,
     to_be_cleaned: VecDeque<u32>,
     unprepared: VecDeque<u32>,
+    quarantined: BTreeSet<u32>,
+    consecutive_prepare_failures: HashMap<u32, u32>,
+    recent_checkouts: VecDeque<Instant>,
+    execution_latency: Option<Duration>,
+    clean_latency: Option<Duration>,
+    prepare_latency: Option<Duration>,
+    target_ready: u32,
+    checkouts: u64,
+    starved_checkouts: u64,
+    prepare_failures: u64,
+    clean_failures: u64,
 }
 
 impl Default for PoolNoodle {
     fn default() -> Self {
-        Self::new(0)
+        Self::with_config(PoolNoodleConfig::new(0))
     }
 }
 
 impl PoolNoodle {
-    /// Creates a new instance of PoolNoodle
-    pub fn new(pool_size: u32) -> Self {
+    /// Creates a new instance of PoolNoodle, rejecting a config whose `min_ready` is greater than
+    /// its `max_ready`.
+    pub fn new(config: PoolNoodleConfig) -> Result<Self> {
+        if config.min_ready > config.max_ready {
+            return Err(PoolNoodleError::MinReadyAboveMaxReady {
+                min_ready: config.min_ready,
+                max_ready: config.max_ready,
+            });
+        }
+
+        Ok(Self::with_config(config))
+    }
+
+    fn with_config(config: PoolNoodleConfig) -> Self {
         PoolNoodle(Arc::new(
             PoolNoodleInner {
-                pool_size,
+                config,
                 active: BTreeMap::new(),
                 ready: Vec::new(),
                 to_be_cleaned: VecDeque::new(),
-                unprepared: VecDeque::from_iter(0..pool_size),
+                unprepared: VecDeque::from_iter(0..config.pool_size),
+                quarantined: BTreeSet::new(),
+                consecutive_prepare_failures: HashMap::new(),
+                recent_checkouts: VecDeque::new(),
+                execution_latency: None,
+                clean_latency: None,
+                prepare_latency: None,
+                target_ready: config.min_ready,
+                checkouts: 0,
+                starved_checkouts: 0,
+                prepare_failures: 0,
+                clean_failures: 0,
             }
             .into(),
         ))
     }
 
-    /// Starts the loop responsible for jail lifetimes. The loop works by:
-    /// 1. Check if we have fewer ready jails than the `[MINUMUM_JAIL_PERCENTAGE]` of `[pool_size]`
-    /// 2. If so, go get an unprepared jail and prepare it!
-    /// 3. If not, check if there are any jails that need to be cleaned.
-    /// 4. If so, clean them and move them to `[unprepared]` so they can be made ready.
-    /// 5. If not, let's go check if our oldest active jail is older than the timeout
-    /// 6. If so, push it to to_be_cleaned
-    /// 7. If not, do nothing!
+    /// Starts the loop responsible for jail lifetimes. On every tick, the loop:
+    /// 1. Moves any active jails older than the timeout to `[to_be_cleaned]`
+    /// 2. Works out how many ready jails we want, based on recent demand
+    /// 3. Prepares enough unprepared jails to make up any shortfall
+    /// 4. Cleans jails that need cleaning and moves them to `[unprepared]`
+    ///
+    /// Jails are prepared and cleaned concurrently and without holding the lock, so that checkouts
+    /// aren't held up by the scripts doing the work.
     ///
     /// todo(scott): this is a brute force approach. I deally moving this to be event driving and
     /// talking over channels will lets us simplify the cross-thread vec fun and the forver-looping
     /// future that we just let run rampant.
     #[allow(clippy::let_underscore_future)] // This needs to just run in the background forever.
     pub fn start(&self) {
-        let me = Arc::clone(&self.0);
+        let me = self.clone();
         let _ = tokio::spawn(async move {
             let mut interval = time::interval(Duration::from_millis(100));
+            let mut stats_reported_at = Instant::now();
 
             loop {
                 interval.tick().await;
 
-                let mut me = me.lock().await;
-                let active_len = me.active.len();
-                let ready_len = me.ready.len() as f32;
-                let to_be_cleaned_len = me.to_be_cleaned.len();
-                let unprepared_len = me.unprepared.len();
-                let target = me.pool_size as f32 * MINUMUM_JAIL_PERCENTAGE;
-
-                trace!(
-                    "PoolNoodle Stats -- desired ready: {}, ready: {}, active: {}, to be cleaned: {}, unprepared: {}",
-                    target,
-                    ready_len,
-                    active_len,
-                    to_be_cleaned_len,
-                    unprepared_len,
-                );
+                let (to_prepare, to_clean) = me.0.lock().await.plan(Instant::now());
 
-                // we're at fewer than 25% of the total, let's make more jails
-                if ready_len < target && unprepared_len != 0 {
-                    if let Some(id) = me.unprepared.pop_back() {
-                        match PoolNoodle::prepare_jail(id).await {
-                            Ok(_) => {
-                                debug!("PoolNoodle: jail readied: {}", id);
-                                me.ready.push(id);
-                            }
-                            Err(_) => {
-                                warn!("PoolNoodle: failed to ready jail: {}", id);
-                                me.unprepared.push_front(id);
-                            }
-                        }
-                    };
-
-                // let's go clean some dead jails!
-                } else if to_be_cleaned_len != 0 {
-                    // go get a jail that needs to be cleaned
-                    if let Some(id) = me.to_be_cleaned.pop_back() {
-                        // attempt to clean it
-                        match PoolNoodle::clean_jail(id).await {
-                            // it worked!
-                            Ok(_) => {
-                                debug!("PoolNoodle: jail cleaned: {}", id);
-                                me.unprepared.push_back(id);
-                                // this jail should no longer be active, so let's make sure we
-                                // remove it
-                                me.active.remove(&id);
-                            }
-                            // it did not work. We should move on to a different jail. This one will be
-                            // abandoned.
-                            Err(_) => {
-                                warn!("PoolNoodle: failed to clean jail: {}", id);
-                            }
-                        };
-                    };
-
-                // let's go see if any of our active jails have timed out
-                } else if active_len != 0 {
-                    // peak at the top jail to see if it needs to be cleaned
-                    if let Some((id, start_time)) = me.active.last_key_value() {
-                        let elapsed = start_time.elapsed();
-                        if elapsed >= Duration::from_secs(CYCLONE_EXECUTION_TIMEOUT) {
-                            debug!(
-                                "PoolNoodle: jail active for {:?}, more than timeout of {}s: {}",
-                                elapsed, CYCLONE_EXECUTION_TIMEOUT, id
-                            );
-                            if let Some((id, _)) = me.active.pop_last() {
-                                me.set_as_to_be_cleaned(id).await;
-                            }
-                        }
-                    };
-                } else {
-                    continue;
-                };
+                if !to_prepare.is_empty() {
+                    let prepared = join_all(to_prepare.into_iter().map(|id| async move {
+                        let started_at = Instant::now();
+                        (id, PoolNoodle::prepare_jail(id).await, started_at.elapsed())
+                    }))
+                    .await;
+
+                    let mut me = me.0.lock().await;
+                    for (id, result, elapsed) in prepared {
+                        me.prepared(id, result, elapsed);
+                    }
+                }
+
+                if !to_clean.is_empty() {
+                    let cleaned = join_all(to_clean.into_iter().map(|id| async move {
+                        let started_at = Instant::now();
+                        (id, PoolNoodle::clean_jail(id).await, started_at.elapsed())
+                    }))
+                    .await;
+
+                    let mut me = me.0.lock().await;
+                    for (id, result, elapsed) in cleaned {
+                        me.cleaned(id, result, elapsed);
+                    }
+                }
+
+                if stats_reported_at.elapsed() >= STATS_INTERVAL {
+                    let stats = me.stats().await;
+                    info!(
+                        pool_noodle.target_ready = stats.target_ready,
+                        pool_noodle.ready = stats.ready,
+                        pool_noodle.active = stats.active,
+                        pool_noodle.to_be_cleaned = stats.to_be_cleaned,
+                        pool_noodle.unprepared = stats.unprepared,
+                        pool_noodle.quarantined = stats.quarantined,
+                        pool_noodle.checkouts = stats.checkouts,
+                        pool_noodle.starved_checkouts = stats.starved_checkouts,
+                        pool_noodle.prepare_failures = stats.prepare_failures,
+                        pool_noodle.clean_failures = stats.clean_failures,
+                        "PoolNoodle stats",
+                    );
+                    stats_reported_at = Instant::now();
+                }
             }
         });
     }
 
+    /// This pops a ready jail from the stack and returns its Id so it can be executed.
+    /// If there are no ready jails, it will retry until it either gets one or times out, unless
+    /// every jail has been taken out of rotation, in which case it fails straight away.
+    pub async fn get_ready_jail(&self) -> Result<u32> {
+        let deadline = Instant::now() + GET_READY_JAIL_TIMEOUT;
+        let mut starved = false;
+
+        loop {
+            trace!("PoolNoodle: getting a ready jail.");
+            {
+                let mut me = self.0.lock().await;
+                if let Some(id) = me.ready.pop() {
+                    debug!("PoolNoodle: got ready jail: {}", id);
+                    if !starved {
+                        me.record_checkout(Instant::now());
+                    }
+                    return Ok(id);
+                }
+
+                if !starved {
+                    // Count the demand as soon as it arrives, so the ready target rises during a
+                    // burst rather than after it
+                    me.record_checkout(Instant::now());
+                    me.starved_checkouts += 1;
+                    starved = true;
+                    warn!("PoolNoodle: execution pool starved! Trying again.");
+                }
+
+                if !me.quarantined.is_empty()
+                    && me.quarantined.len() >= me.config.pool_size as usize
+                {
+                    return Err(PoolNoodleError::AllJailsQuarantined);
+                }
+            }
+
+            if Instant::now() >= deadline {
+                return Err(PoolNoodleError::ExecutionPoolStarved);
+            }
+            time::sleep(GET_READY_JAIL_INTERVAL).await;
+        }
+    }
+
+    /// Returns a snapshot of the jails being managed.
+    pub async fn stats(&self) -> PoolNoodleStats {
+        self.0.lock().await.stats()
+    }
+
     /// This readies a jail. This script is place in the correct location during Veritech startup.
     /// todo(scott): This method should be replace with a Rust-native implementation.
     async fn prepare_jail(id: u32) -> Result<()> {
         let command = String::from("/firecracker-data/prepare_jailer.sh");
-        let _status = Command::new("sudo")
+        let status = Command::new("sudo")
             .arg(command)
             .arg(id.to_string())
             .status()
             .await
             .map_err(|_| PoolNoodleError::PrepareJail)?;
 
+        if !status.success() {
+            return Err(PoolNoodleError::PrepareJail);
+        }
+
         Ok(())
     }
 
@@ -239,30 +383,6 @@ impl PoolNoodle {
 }
 
 impl PoolNoodleInner {
-    /// This pops a ready jail from the stack and returns its Id so it can be executed.
-    /// If there are no ready jails, it will retry until it either gets one or retries out.
-    pub async fn get_ready_jail(&mut self) -> Result<u32> {
-        let mut retries = 30;
-        loop {
-            trace!("PoolNoodle: getting a ready jail.");
-            match self.ready.pop() {
-                Some(id) => {
-                    debug!("PoolNoodle: got ready jail: {}", id);
-                    break Ok(id);
-                }
-                None => {
-                    warn!("PoolNoodle: execution pool starved! Trying again.");
-                }
-            }
-
-            if retries < 1 {
-                return Err(PoolNoodleError::ExecutionPoolStarved);
-            }
-            retries -= 1;
-            time::sleep(Duration::from_millis(1000)).await;
-        }
-    }
-
     /// This marks a jail as active
     pub async fn set_as_active(&mut self, id: u32, start_time: Instant) -> Result<Instant> {
         self.active
@@ -272,6 +392,217 @@ impl PoolNoodleInner {
 
     /// This marks a jail as needing to be cleaned
     pub async fn set_as_to_be_cleaned(&mut self, id: u32) {
+        if let Some(start_time) = self.active.get(&id) {
+            update_average(&mut self.execution_latency, start_time.elapsed());
+        }
         self.to_be_cleaned.push_front(id)
     }
+
+    /// Returns a snapshot of the jails being managed.
+    pub fn stats(&self) -> PoolNoodleStats {
+        PoolNoodleStats {
+            target_ready: self.target_ready,
+            ready: self.ready.len(),
+            active: self.active.len(),
+            to_be_cleaned: self.to_be_cleaned.len(),
+            unprepared: self.unprepared.len(),
+            quarantined: self.quarantined.len(),
+            checkouts: self.checkouts,
+            starved_checkouts: self.starved_checkouts,
+            prepare_failures: self.prepare_failures,
+            clean_failures: self.clean_failures,
+        }
+    }
+
+    fn record_checkout(&mut self, now: Instant) {
+        self.checkouts += 1;
+        self.recent_checkouts.push_back(now);
+    }
+
+    /// Works out how many jails we want ready: enough to cover the checkouts expected, at the
+    /// recent checkout rate, over the time it takes for a checked out jail to run, be cleaned and
+    /// be prepared again.
+    fn target_ready(&mut self, now: Instant) -> u32 {
+        while let Some(checkout) = self.recent_checkouts.front() {
+            if now.duration_since(*checkout) <= CHECKOUT_RATE_WINDOW {
+                break;
+            }
+            self.recent_checkouts.pop_front();
+        }
+
+        let checkout_rate = self.recent_checkouts.len() as f64 / CHECKOUT_RATE_WINDOW.as_secs_f64();
+        let turnaround = self.execution_latency.unwrap_or_default()
+            + self.clean_latency.unwrap_or_default()
+            + self.prepare_latency.unwrap_or_default();
+        let demand = (checkout_rate * turnaround.as_secs_f64()).ceil() as u32;
+
+        demand.min(self.config.max_ready).max(self.config.min_ready)
+    }
+
+    /// Picks the jails to prepare and clean on this tick, taking them out of their queues until
+    /// the work is done.
+    fn plan(&mut self, now: Instant) -> (Vec<u32>, Vec<u32>) {
+        let timed_out: Vec<u32> = self
+            .active
+            .iter()
+            .filter(|(_, start_time)| {
+                now.duration_since(**start_time) >= Duration::from_secs(CYCLONE_EXECUTION_TIMEOUT)
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        for id in timed_out {
+            debug!(
+                "PoolNoodle: jail active for more than timeout of {}s: {}",
+                CYCLONE_EXECUTION_TIMEOUT, id
+            );
+            self.active.remove(&id);
+            self.to_be_cleaned.push_front(id);
+        }
+
+        self.target_ready = self.target_ready(now);
+        let shortfall = (self.target_ready as usize).saturating_sub(self.ready.len());
+
+        trace!(
+            "PoolNoodle Stats -- desired ready: {}, ready: {}, active: {}, to be cleaned: {}, unprepared: {}, quarantined: {}",
+            self.target_ready,
+            self.ready.len(),
+            self.active.len(),
+            self.to_be_cleaned.len(),
+            self.unprepared.len(),
+            self.quarantined.len(),
+        );
+
+        let to_prepare = (0..shortfall.min(MAX_CONCURRENT_JAIL_OPERATIONS))
+            .map_while(|_| self.unprepared.pop_back())
+            .collect();
+        let to_clean = (0..MAX_CONCURRENT_JAIL_OPERATIONS)
+            .map_while(|_| self.to_be_cleaned.pop_back())
+            .collect();
+
+        (to_prepare, to_clean)
+    }
+
+    fn prepared(&mut self, id: u32, result: Result<()>, elapsed: Duration) {
+        match result {
+            Ok(_) => {
+                debug!("PoolNoodle: jail readied: {}", id);
+                self.consecutive_prepare_failures.remove(&id);
+                update_average(&mut self.prepare_latency, elapsed);
+                self.ready.push(id);
+            }
+            Err(_) => {
+                self.prepare_failures += 1;
+                let failures = self.consecutive_prepare_failures.entry(id).or_insert(0);
+                *failures += 1;
+
+                // A jail that keeps failing to be prepared would otherwise be retried forever,
+                // holding up the jails behind it
+                if *failures >= self.config.max_prepare_failures {
+                    warn!(
+                        "PoolNoodle: jail failed to be readied {} times in a row, taking it out of rotation: {}",
+                        failures, id
+                    );
+                    self.consecutive_prepare_failures.remove(&id);
+                    self.quarantined.insert(id);
+                } else {
+                    warn!("PoolNoodle: failed to ready jail: {}", id);
+                    self.unprepared.push_front(id);
+                }
+            }
+        }
+    }
+
+    fn cleaned(&mut self, id: u32, result: Result<()>, elapsed: Duration) {
+        // this jail should no longer be active, so let's make sure we remove it
+        self.active.remove(&id);
+
+        match result {
+            Ok(_) => {
+                debug!("PoolNoodle: jail cleaned: {}", id);
+                update_average(&mut self.clean_latency, elapsed);
+                self.unprepared.push_back(id);
+            }
+            // it did not work. This one will be abandoned.
+            Err(_) => {
+                warn!(
+                    "PoolNoodle: failed to clean jail, taking it out of rotation: {}",
+                    id
+                );
+                self.clean_failures += 1;
+                self.quarantined.insert(id);
+            }
+        }
+    }
+}
+
+fn update_average(average: &mut Option<Duration>, sample: Duration) {
+    *average = Some(match *average {
+        Some(average) => {
+            average.mul_f64(1.0 - LATENCY_AVERAGE_WEIGHT) + sample.mul_f64(LATENCY_AVERAGE_WEIGHT)
+        }
+        None => sample,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inner(config: PoolNoodleConfig) -> PoolNoodleInner {
+        Arc::try_unwrap(PoolNoodle::new(config).expect("invalid config").0)
+            .expect("pool noodle is shared")
+            .into_inner()
+    }
+
+    #[test]
+    fn target_ready_follows_demand_within_bounds() {
+        let mut config = PoolNoodleConfig::new(100);
+        config.max_ready = 50;
+        let mut me = inner(config);
+        let now = Instant::now();
+
+        assert_eq!(25, me.target_ready(now));
+
+        // 60 checkouts over the window is 2 per second, each taking 30 seconds to turn around
+        update_average(&mut me.execution_latency, Duration::from_secs(20));
+        update_average(&mut me.prepare_latency, Duration::from_secs(10));
+        for _ in 0..60 {
+            me.record_checkout(now);
+        }
+        assert_eq!(50, me.target_ready(now));
+
+        // Once the burst has passed, the target falls back to the minimum
+        assert_eq!(25, me.target_ready(now + CHECKOUT_RATE_WINDOW * 2));
+    }
+
+    #[test]
+    fn min_ready_above_max_ready_is_rejected() {
+        let mut config = PoolNoodleConfig::new(100);
+        config.min_ready = 60;
+        config.max_ready = 50;
+
+        assert!(matches!(
+            PoolNoodle::new(config),
+            Err(PoolNoodleError::MinReadyAboveMaxReady {
+                min_ready: 60,
+                max_ready: 50,
+            })
+        ));
+    }
+
+    #[test]
+    fn jails_failing_to_prepare_are_quarantined() {
+        let mut me = inner(PoolNoodleConfig::new(1));
+
+        for _ in 0..PoolNoodleConfig::DEFAULT_MAX_PREPARE_FAILURES {
+            let (to_prepare, _) = me.plan(Instant::now());
+            assert_eq!(vec![0], to_prepare);
+            me.prepared(0, Err(PoolNoodleError::PrepareJail), Duration::ZERO);
+        }
+
+        let (to_prepare, _) = me.plan(Instant::now());
+        assert!(to_prepare.is_empty());
+        assert_eq!(1, me.stats().quarantined);
+        assert_eq!(3, me.stats().prepare_failures);
+    }
 }
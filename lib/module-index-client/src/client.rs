@@ -2,7 +2,10 @@ use reqwest::StatusCode;
 use ulid::Ulid;
 use url::Url;
 
-use crate::types::{BuiltinsDetailsResponse, ModulePromotedResponse, ModuleRejectionResponse};
+use crate::types::{
+    BuiltinsDetailsResponse, ModuleListResponse, ModulePromotedResponse, ModuleRejectionResponse,
+    ModuleSearchRequest, ModuleSearchResponse,
+};
 use crate::{IndexClientResult, ModuleDetailsResponse};
 
 #[derive(Debug, Clone)]
@@ -114,6 +117,40 @@ impl IndexClient {
         Ok(bytes.to_vec())
     }
 
+    /// Lists the modules visible to the client whose names contain the given filter.
+    pub async fn list_modules(&self, name: Option<&str>) -> IndexClientResult<ModuleListResponse> {
+        let url = self.base_url.join("modules")?;
+        let mut request = reqwest::Client::new().get(url);
+        if let Some(name) = name {
+            request = request.query(&[("name", name)]);
+        }
+        let resp = request
+            .bearer_auth(&self.auth_token)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(resp.json::<ModuleListResponse>().await?)
+    }
+
+    /// Searches the modules visible to the client, returning a page of results along with the
+    /// facet counts for the whole search.
+    pub async fn search_modules(
+        &self,
+        search: &ModuleSearchRequest,
+    ) -> IndexClientResult<ModuleSearchResponse> {
+        let url = self.base_url.join("modules/")?.join("search")?;
+        let resp = reqwest::Client::new()
+            .get(url)
+            .query(search)
+            .bearer_auth(&self.auth_token)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(resp.json::<ModuleSearchResponse>().await?)
+    }
+
     pub async fn list_builtins(&self) -> IndexClientResult<BuiltinsDetailsResponse> {
         let url = self.base_url.join("builtins")?;
         let resp = reqwest::Client::new()
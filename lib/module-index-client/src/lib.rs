@@ -2,6 +2,10 @@ pub mod client;
 pub mod types;
 
 pub use client::IndexClient;
-pub use types::{FuncMetadata, IndexClientError, IndexClientResult, ModuleDetailsResponse};
+pub use types::{
+    FuncMetadata, IndexClientError, IndexClientResult, ModuleDetailsResponse, ModuleFacetCount,
+    ModuleKind, ModuleListResponse, ModuleOwnerFacetCount, ModuleSearchFacets, ModuleSearchRequest,
+    ModuleSearchResponse, ModuleSearchSort,
+};
 
 pub const DEFAULT_URL: &str = "http://localhost:5157";
@@ -31,6 +31,12 @@ pub struct BuiltinsDetailsResponse {
     pub modules: Vec<ModuleDetailsResponse>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleListResponse {
+    pub modules: Vec<ModuleDetailsResponse>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ModuleDetailsResponse {
@@ -43,6 +49,87 @@ pub struct ModuleDetailsResponse {
     pub latest_hash: String,
     pub latest_hash_created_at: DateTime<Utc>,
     pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub download_count: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ModuleKind {
+    Module,
+    WorkspaceBackup,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ModuleSearchSort {
+    /// Most recently created first
+    #[default]
+    CreatedAt,
+    /// Most downloaded first
+    Downloads,
+}
+
+/// A search of the modules visible to the client. Every field narrows the search, leaving them all
+/// unset returns every module, a page at a time.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleSearchRequest {
+    /// Free text matched against the names and descriptions of modules and the names of their
+    /// schemas and funcs. Every word has to match the start of a word in the module.
+    pub q: Option<String>,
+    /// Only modules with exactly this name
+    pub name: Option<String>,
+    pub owner_user_id: Option<String>,
+    pub kind: Option<ModuleKind>,
+    pub builtin: Option<bool>,
+    pub sort: Option<ModuleSearchSort>,
+    /// The `next_cursor` of the previous page
+    pub cursor: Option<String>,
+    pub limit: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleSearchResponse {
+    pub modules: Vec<ModuleDetailsResponse>,
+    /// Set when there are more results, pass it back as `cursor` to get them
+    pub next_cursor: Option<String>,
+    pub facets: ModuleSearchFacets,
+}
+
+/// How many modules match for each value of a facet. Each facet is counted with every filter
+/// applied except its own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleSearchFacets {
+    pub owners: Vec<ModuleOwnerFacetCount>,
+    pub kinds: Vec<ModuleFacetCount<ModuleKind>>,
+    pub builtin: Vec<ModuleFacetCount<bool>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleFacetCount<T> {
+    pub value: T,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleOwnerFacetCount {
+    pub owner_user_id: String,
+    pub owner_display_name: Option<String>,
+    pub count: i64,
+}
+
+impl ModuleDetailsResponse {
+    /// The version the module was published with, as recorded in its metadata.
+    pub fn version(&self) -> Option<&str> {
+        self.metadata
+            .get("version")
+            .and_then(|version| version.as_str())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
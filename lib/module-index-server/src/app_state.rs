@@ -8,7 +8,7 @@ pub use si_posthog::PosthogClient;
 
 use tokio::sync::{broadcast, mpsc, Mutex};
 
-use crate::{jwt_key::JwtPublicSigningKey, s3::S3Config};
+use crate::{config::ModuleSigningConfig, jwt_key::JwtPublicSigningKey, s3::S3Config};
 
 #[remain::sorted]
 #[derive(Debug, Eq, PartialEq)]
@@ -25,6 +25,7 @@ pub struct AppState {
     posthog_client: PosthogClient,
     aws_creds: AwsCredentials,
     s3_config: S3Config,
+    module_signing: ModuleSigningConfig,
     token_emails: Arc<Mutex<HashMap<String, String>>>,
 
     shutdown_broadcast: ShutdownBroadcast,
@@ -43,6 +44,7 @@ impl AppState {
         posthog_client: PosthogClient,
         aws_creds: AwsCredentials,
         s3_config: S3Config,
+        module_signing: ModuleSigningConfig,
         shutdown_broadcast_tx: broadcast::Sender<()>,
         tmp_shutdown_tx: mpsc::Sender<ShutdownSource>,
     ) -> Self {
@@ -52,6 +54,7 @@ impl AppState {
             posthog_client,
             aws_creds,
             s3_config,
+            module_signing,
             shutdown_broadcast: ShutdownBroadcast(shutdown_broadcast_tx),
             token_emails: Arc::new(Mutex::new(HashMap::new())),
             _tmp_shutdown_tx: Arc::new(tmp_shutdown_tx),
@@ -82,6 +85,11 @@ impl AppState {
         &self.s3_config
     }
 
+    /// Gets a reference to the rules for which module signatures are accepted
+    pub fn module_signing(&self) -> &ModuleSigningConfig {
+        &self.module_signing
+    }
+
     /// Clones the ArcMutex that holds a hashmap between auth tokens and emails
     pub fn token_emails(&self) -> Arc<Mutex<HashMap<String, String>>> {
         self.token_emails.clone()
@@ -4,6 +4,7 @@ use buck2_resources::Buck2Resources;
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 use si_data_pg::PgPoolConfig;
+use si_pkg::PkgVerifyingKey;
 use si_posthog::PosthogConfig;
 use si_std::{CanonicalFile, CanonicalFileError};
 use telemetry::prelude::*;
@@ -56,6 +57,9 @@ pub struct Config {
     posthog: PosthogConfig,
 
     s3: S3Config,
+
+    #[builder(default)]
+    module_signing: ModuleSigningConfig,
 }
 
 impl StandardConfig for Config {
@@ -96,6 +100,25 @@ impl Config {
     pub fn s3(&self) -> &S3Config {
         &self.s3
     }
+
+    /// Gets the config's rules for which module signatures are accepted
+    #[must_use]
+    pub fn module_signing(&self) -> &ModuleSigningConfig {
+        &self.module_signing
+    }
+}
+
+/// Which signatures a module must carry to be uploaded.
+///
+/// Modules carrying a signature that doesn't match their contents are always refused.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ModuleSigningConfig {
+    /// Refuses modules which aren't signed by one of the trusted keys.
+    #[serde(default)]
+    pub require_trusted_signature: bool,
+    /// The base64 encoded ed25519 public keys whose signatures are trusted.
+    #[serde(default)]
+    pub trusted_keys: Vec<PkgVerifyingKey>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -112,6 +135,8 @@ pub struct ConfigFile {
     pub posthog: PosthogConfig,
     #[serde(default)]
     pub s3: S3Config,
+    #[serde(default)]
+    pub module_signing: ModuleSigningConfig,
 }
 
 impl Default for ConfigFile {
@@ -129,6 +154,7 @@ impl Default for ConfigFile {
             jwt_signing_public_key_path: default_jwt_signing_public_key_path(),
             posthog: Default::default(),
             s3: Default::default(),
+            module_signing: Default::default(),
         }
     }
 }
@@ -150,6 +176,7 @@ impl TryFrom<ConfigFile> for Config {
         config.jwt_signing_public_key_path(value.jwt_signing_public_key_path.try_into()?);
         config.posthog(value.posthog);
         config.s3(value.s3);
+        config.module_signing(value.module_signing);
         config.build().map_err(Into::into)
     }
 }
@@ -11,7 +11,7 @@ mod whoami;
 pub use crate::{
     config::{
         detect_and_configure_development, Config, ConfigBuilder, ConfigError, ConfigFile,
-        StandardConfig, StandardConfigFile,
+        ModuleSigningConfig, StandardConfig, StandardConfigFile,
     },
     server::{Server, ServerError},
 };
@@ -0,0 +1,47 @@
+ALTER TABLE modules
+    ADD download_count bigint NOT NULL DEFAULT 0,
+    ADD search_document tsvector;
+
+-- Names (of the module, its schemas and its funcs) are matched as written rather than stemmed, so
+-- everything uses the 'simple' configuration. The module name weighs more than the rest.
+CREATE OR REPLACE FUNCTION modules_search_document_v1(this_name text,
+                                                      this_description text,
+                                                      this_metadata json)
+    RETURNS tsvector
+    LANGUAGE sql
+    IMMUTABLE
+AS
+$$
+SELECT setweight(to_tsvector('simple', coalesce(this_name, '')), 'A')
+           || setweight(to_tsvector('simple', coalesce(this_description, '')), 'B')
+           || setweight(to_tsvector('simple', coalesce(
+                   (SELECT string_agg(schema_name, ' ')
+                    FROM json_array_elements_text(this_metadata -> 'schemas') AS schema_name), '')), 'B')
+           || setweight(to_tsvector('simple', coalesce(
+                   (SELECT string_agg(func ->> 'name', ' ')
+                    FROM json_array_elements(this_metadata -> 'funcs') AS func), '')), 'C')
+$$;
+
+CREATE OR REPLACE FUNCTION modules_set_search_document_v1()
+    RETURNS trigger
+    LANGUAGE plpgsql
+AS
+$$
+BEGIN
+    NEW.search_document = modules_search_document_v1(NEW.name, NEW.description, NEW.metadata);
+    RETURN NEW;
+END;
+$$;
+
+CREATE TRIGGER modules_set_search_document
+    BEFORE INSERT OR UPDATE OF name, description, metadata
+    ON modules
+    FOR EACH ROW
+EXECUTE FUNCTION modules_set_search_document_v1();
+
+UPDATE modules
+SET search_document = modules_search_document_v1(name, description, metadata);
+
+CREATE INDEX modules_search_document_idx ON modules USING gin (search_document);
+CREATE INDEX modules_created_at_idx ON modules (created_at DESC, id DESC);
+CREATE INDEX modules_download_count_idx ON modules (download_count DESC, id DESC);
@@ -93,6 +93,7 @@ pub struct Model {
     pub kind: ModuleKind,
     pub is_builtin_at: Option<DateTimeWithTimeZone>,
     pub is_builtin_at_by_display_name: Option<String>,
+    pub download_count: i64,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -100,6 +101,23 @@ pub enum Relation {}
 
 impl ActiveModelBehavior for ActiveModel {}
 
+/// Counts a download of a module, which is what searches sort by when asked for popular modules.
+pub async fn increment_download_count(
+    db: &impl ConnectionTrait,
+    module_id: ModuleId,
+) -> Result<(), DbErr> {
+    Entity::update_many()
+        .col_expr(
+            Column::DownloadCount,
+            sea_query::Expr::col(Column::DownloadCount).add(1),
+        )
+        .filter(Column::Id.eq(module_id))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
 // custom ulid type
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -19,6 +19,7 @@ mod list_builtins_route;
 mod list_modules_route;
 pub(crate) mod promote_builtin_route;
 pub(crate) mod reject_module_route;
+mod search_modules_route;
 pub(crate) mod upsert_module_route;
 
 use super::{app_state::AppState, server::ServerError};
@@ -32,6 +33,10 @@ pub fn routes(state: AppState) -> Router {
     router = router
         .route("/", get(system_status_route))
         .route("/modules", get(list_modules_route::list_module_route))
+        .route(
+            "/modules/search",
+            get(search_modules_route::search_modules_route),
+        )
         .route("/builtins", get(list_builtins_route::list_builtins_route))
         .route(
             "/builtins/:module_id/promote",
@@ -55,5 +55,8 @@ pub async fn download_module_route(
         .presign_get(format!("{}.sipkg", module.latest_hash), 60 * 5, None)
         .await?;
 
+    si_module::increment_download_count(&txn, module_id).await?;
+    txn.commit().await?;
+
     Ok(Redirect::temporary(&download_url))
 }
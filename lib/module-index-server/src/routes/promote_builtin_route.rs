@@ -91,6 +91,7 @@ pub async fn promote_builtin_route(
             Utc.fix(),
         ))),
         is_builtin_at_by_display_name: Set(Some(data)),
+        download_count: Set(module.download_count),
     };
 
     let updated_module: si_module::Model = dbg!(active_module.update(&txn).await)?;
@@ -91,6 +91,7 @@ pub async fn reject_module(
         kind: Set(module.kind),
         is_builtin_at: Set(module.is_builtin_at),
         is_builtin_at_by_display_name: Set(module.is_builtin_at_by_display_name),
+        download_count: Set(module.download_count),
     };
 
     let updated_module: si_module::Model = dbg!(active_module.update(&txn).await)?;
@@ -0,0 +1,411 @@
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::{engine::general_purpose, Engine};
+use hyper::StatusCode;
+use sea_orm::{
+    sea_query::Expr, ColumnTrait, Condition, DbErr, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect, Select,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    app_state::AppState,
+    extract::{Authorization, DbConnection},
+    models::si_module::{self, ModuleId, ModuleKind},
+    whoami::{is_systeminit_auth_token, WhoamiError},
+};
+
+const DEFAULT_LIMIT: u64 = 50;
+const MAX_LIMIT: u64 = 200;
+const OWNER_FACET_LIMIT: u64 = 20;
+
+#[remain::sorted]
+#[derive(Error, Debug)]
+pub enum SearchModulesError {
+    #[error("db error: {0}")]
+    DbErr(#[from] DbErr),
+    #[error("invalid cursor: {0}")]
+    InvalidCursor(String),
+    #[error("whoami error: {0}")]
+    Whoami(#[from] WhoamiError),
+}
+
+// TODO: figure out how to not keep this serialization logic here
+impl IntoResponse for SearchModulesError {
+    fn into_response(self) -> Response {
+        let (status, error_message) = match self {
+            Self::InvalidCursor(_) => (StatusCode::BAD_REQUEST, self.to_string()),
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
+        };
+
+        let body = Json(
+            serde_json::json!({ "error": { "message": error_message, "code": 42, "statusCode": status.as_u16() } }),
+        );
+
+        (status, body).into_response()
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ModuleSearchSort {
+    /// Most recently created first
+    #[default]
+    CreatedAt,
+    /// Most downloaded first
+    Downloads,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchModulesRequest {
+    /// Free text matched against the names and descriptions of modules and the names of their
+    /// schemas and funcs. Every word has to match the start of a word in the module.
+    pub q: Option<String>,
+    /// Only modules with exactly this name
+    pub name: Option<String>,
+    pub owner_user_id: Option<String>,
+    pub kind: Option<ModuleKind>,
+    pub builtin: Option<bool>,
+    #[serde(default)]
+    pub sort: ModuleSearchSort,
+    /// The `nextCursor` of the previous page
+    pub cursor: Option<String>,
+    pub limit: Option<u64>,
+    pub su: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchModulesResponse {
+    modules: Vec<si_module::Model>,
+    /// Set when there are more results, pass it back as `cursor` to get them
+    next_cursor: Option<String>,
+    facets: SearchModulesFacets,
+}
+
+/// How many modules match for each value of a facet. Each facet is counted with every filter
+/// applied except its own, so the counts tell what selecting another value would return.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchModulesFacets {
+    owners: Vec<OwnerFacetCount>,
+    kinds: Vec<FacetCount<ModuleKind>>,
+    builtin: Vec<FacetCount<bool>>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FacetCount<T> {
+    value: T,
+    count: i64,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct OwnerFacetCount {
+    owner_user_id: String,
+    owner_display_name: Option<String>,
+    count: i64,
+}
+
+/// Where the previous page ended, in the order of the requested sort.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct SearchCursor {
+    sort: ModuleSearchSort,
+    created_at: sea_orm::prelude::DateTimeWithTimeZone,
+    download_count: i64,
+    id: ModuleId,
+}
+
+impl SearchCursor {
+    fn encode(&self) -> String {
+        general_purpose::URL_SAFE_NO_PAD
+            .encode(serde_json::to_vec(self).expect("cursor serializes to json"))
+    }
+
+    /// Decodes a cursor for a search sorted by `sort`, which must be the sort it was created for.
+    fn decode(cursor: &str, sort: ModuleSearchSort) -> Result<Self, SearchModulesError> {
+        let bytes = general_purpose::URL_SAFE_NO_PAD
+            .decode(cursor)
+            .map_err(|err| SearchModulesError::InvalidCursor(err.to_string()))?;
+        let cursor: Self = serde_json::from_slice(&bytes)
+            .map_err(|err| SearchModulesError::InvalidCursor(err.to_string()))?;
+
+        if cursor.sort != sort {
+            return Err(SearchModulesError::InvalidCursor(
+                "cursor was created for a different sort".to_owned(),
+            ));
+        }
+        Ok(cursor)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Facet {
+    Builtin,
+    Kind,
+    Owner,
+}
+
+pub async fn search_modules_route(
+    Authorization {
+        user_claim,
+        auth_token,
+    }: Authorization,
+    DbConnection(txn): DbConnection,
+    Query(request): Query<SearchModulesRequest>,
+    State(state): State<AppState>,
+) -> Result<Json<SearchModulesResponse>, SearchModulesError> {
+    let su = request.su.unwrap_or(false)
+        && is_systeminit_auth_token(&auth_token, state.token_emails()).await?;
+
+    // Like the module list, users only see their own modules, plus the builtins everyone gets
+    let visible = if su {
+        Condition::all()
+    } else {
+        Condition::any()
+            .add(si_module::Column::OwnerUserId.eq(user_claim.user_pk.to_string()))
+            .add(si_module::Column::IsBuiltinAt.is_not_null())
+    };
+
+    let cursor = match &request.cursor {
+        Some(cursor) => Some(SearchCursor::decode(cursor, request.sort)?),
+        None => None,
+    };
+    let limit = request.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let query = search_query(&request, visible.clone(), None);
+    let query = match (&cursor, request.sort) {
+        (Some(cursor), ModuleSearchSort::CreatedAt) => query.filter(
+            Condition::any()
+                .add(si_module::Column::CreatedAt.lt(cursor.created_at))
+                .add(
+                    Condition::all()
+                        .add(si_module::Column::CreatedAt.eq(cursor.created_at))
+                        .add(si_module::Column::Id.lt(cursor.id)),
+                ),
+        ),
+        (Some(cursor), ModuleSearchSort::Downloads) => query.filter(
+            Condition::any()
+                .add(si_module::Column::DownloadCount.lt(cursor.download_count))
+                .add(
+                    Condition::all()
+                        .add(si_module::Column::DownloadCount.eq(cursor.download_count))
+                        .add(si_module::Column::Id.lt(cursor.id)),
+                ),
+        ),
+        (None, _) => query,
+    };
+    // The id breaks ties so that pages never overlap or skip a module
+    let query = match request.sort {
+        ModuleSearchSort::CreatedAt => query.order_by_desc(si_module::Column::CreatedAt),
+        ModuleSearchSort::Downloads => query.order_by_desc(si_module::Column::DownloadCount),
+    }
+    .order_by_desc(si_module::Column::Id);
+
+    // Fetch one extra to know whether there is another page
+    let mut modules: Vec<si_module::Model> = query.limit(limit + 1).all(&txn).await?;
+    let next_cursor = if modules.len() as u64 > limit {
+        modules.truncate(limit as usize);
+        modules.last().map(|module| {
+            SearchCursor {
+                sort: request.sort,
+                created_at: module.created_at,
+                download_count: module.download_count,
+                id: module.id,
+            }
+            .encode()
+        })
+    } else {
+        None
+    };
+
+    let owners: Vec<(String, Option<String>, i64)> =
+        search_query(&request, visible.clone(), Some(Facet::Owner))
+            .select_only()
+            .column(si_module::Column::OwnerUserId)
+            .column_as(
+                Expr::col(si_module::Column::OwnerDisplayName).max(),
+                "owner_display_name",
+            )
+            .column_as(Expr::col(si_module::Column::Id).count(), "module_count")
+            .group_by(si_module::Column::OwnerUserId)
+            .order_by_desc(Expr::cust("module_count"))
+            .limit(OWNER_FACET_LIMIT)
+            .into_tuple()
+            .all(&txn)
+            .await?;
+    let kinds: Vec<(ModuleKind, i64)> = search_query(&request, visible.clone(), Some(Facet::Kind))
+        .select_only()
+        .column(si_module::Column::Kind)
+        .column_as(Expr::col(si_module::Column::Id).count(), "module_count")
+        .group_by(si_module::Column::Kind)
+        .into_tuple()
+        .all(&txn)
+        .await?;
+    let builtin: Vec<(bool, i64)> = search_query(&request, visible, Some(Facet::Builtin))
+        .select_only()
+        .column_as(
+            Expr::col(si_module::Column::IsBuiltinAt).is_not_null(),
+            "is_builtin",
+        )
+        .column_as(Expr::col(si_module::Column::Id).count(), "module_count")
+        .group_by(Expr::col(si_module::Column::IsBuiltinAt).is_not_null())
+        .into_tuple()
+        .all(&txn)
+        .await?;
+
+    Ok(Json(SearchModulesResponse {
+        modules,
+        next_cursor,
+        facets: SearchModulesFacets {
+            owners: owners
+                .into_iter()
+                .map(
+                    |(owner_user_id, owner_display_name, count)| OwnerFacetCount {
+                        owner_user_id,
+                        owner_display_name,
+                        count,
+                    },
+                )
+                .collect(),
+            kinds: kinds
+                .into_iter()
+                .map(|(value, count)| FacetCount { value, count })
+                .collect(),
+            builtin: builtin
+                .into_iter()
+                .map(|(value, count)| FacetCount { value, count })
+                .collect(),
+        },
+    }))
+}
+
+/// The modules matching the request, leaving out the filter for `except` when counting a facet.
+fn search_query(
+    request: &SearchModulesRequest,
+    visible: Condition,
+    except: Option<Facet>,
+) -> Select<si_module::Entity> {
+    let mut query = si_module::Entity::find()
+        .filter(si_module::Column::RejectedAt.is_null())
+        .filter(visible);
+
+    if let Some(tsquery) = request.q.as_deref().and_then(prefix_tsquery) {
+        query = query.filter(Expr::cust_with_values(
+            "search_document @@ to_tsquery('simple', ?)",
+            [tsquery],
+        ));
+    }
+    if let Some(name) = &request.name {
+        query = query.filter(si_module::Column::Name.eq(name.as_str()));
+    }
+
+    if except != Some(Facet::Owner) {
+        if let Some(owner_user_id) = &request.owner_user_id {
+            query = query.filter(si_module::Column::OwnerUserId.eq(owner_user_id.as_str()));
+        }
+    }
+    if except != Some(Facet::Kind) {
+        if let Some(kind) = &request.kind {
+            query = query.filter(si_module::Column::Kind.eq(kind.to_db_kind()));
+        }
+    }
+    if except != Some(Facet::Builtin) {
+        query = match request.builtin {
+            Some(true) => query.filter(si_module::Column::IsBuiltinAt.is_not_null()),
+            Some(false) => query.filter(si_module::Column::IsBuiltinAt.is_null()),
+            None => query,
+        };
+    }
+
+    query
+}
+
+/// Turns free text into a query for modules containing, for every word, a word starting with it.
+/// Anything other than letters and digits only separates words, so user input can't inject
+/// tsquery operators.
+fn prefix_tsquery(q: &str) -> Option<String> {
+    let terms: Vec<String> = q
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("{}:*", term.to_lowercase()))
+        .collect();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" & "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use ulid::Ulid;
+
+    use super::*;
+
+    #[test]
+    fn prefix_tsquery_matches_word_prefixes() {
+        assert_eq!(Some("aws:* & ec2:*".to_owned()), prefix_tsquery("AWS  EC2"));
+        assert_eq!(None, prefix_tsquery("   "));
+    }
+
+    #[test]
+    fn prefix_tsquery_strips_tsquery_operators() {
+        assert_eq!(
+            Some("a:* & b:* & c:* & d:* & e:*".to_owned()),
+            prefix_tsquery("a&b|!c (d):*e")
+        );
+        assert_eq!(None, prefix_tsquery("&|!():*"));
+        assert_eq!(None, prefix_tsquery("'' <-> \\"));
+    }
+
+    fn cursor(sort: ModuleSearchSort) -> SearchCursor {
+        SearchCursor {
+            sort,
+            created_at: DateTime::parse_from_rfc3339("2023-06-01T12:34:56.789+02:00")
+                .expect("valid timestamp"),
+            download_count: 42,
+            id: ModuleId(Ulid::new()),
+        }
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        for sort in [ModuleSearchSort::CreatedAt, ModuleSearchSort::Downloads] {
+            let cursor = cursor(sort);
+            let decoded =
+                SearchCursor::decode(&cursor.encode(), sort).expect("cursor should decode");
+
+            assert_eq!(cursor, decoded);
+        }
+    }
+
+    #[test]
+    fn cursor_for_another_sort_is_rejected() {
+        let encoded = cursor(ModuleSearchSort::CreatedAt).encode();
+
+        assert!(matches!(
+            SearchCursor::decode(&encoded, ModuleSearchSort::Downloads),
+            Err(SearchModulesError::InvalidCursor(_))
+        ));
+    }
+
+    #[test]
+    fn malformed_cursor_is_rejected() {
+        for encoded in ["not a cursor!", "bm90IGpzb24"] {
+            assert!(matches!(
+                SearchCursor::decode(encoded, ModuleSearchSort::CreatedAt),
+                Err(SearchModulesError::InvalidCursor(_))
+            ));
+        }
+    }
+}
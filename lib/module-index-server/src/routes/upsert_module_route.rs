@@ -1,5 +1,5 @@
 use axum::{
-    extract::{multipart::MultipartError, Multipart},
+    extract::{multipart::MultipartError, Multipart, State},
     response::{IntoResponse, Response},
     Json,
 };
@@ -14,6 +14,7 @@ use telemetry::prelude::*;
 use thiserror::Error;
 
 use crate::{
+    app_state::AppState,
     extract::{Authorization, DbConnection, ExtractedS3Bucket},
     models::si_module,
 };
@@ -29,6 +30,8 @@ pub struct UpsertModuleRequest {
 pub enum UpsertModuleError {
     #[error("db error: {0}")]
     DbErr(#[from] DbErr),
+    #[error("module version refused: {0}")]
+    InvalidVersion(#[source] SiPkgError),
     #[error("file upload error: {0}")]
     IoError(#[from] std::io::Error),
     #[error("multipart decode error: {0}")]
@@ -37,6 +40,8 @@ pub enum UpsertModuleError {
     S3Error(#[from] S3Error),
     #[error("JSON serialization/deserialization error: {0}")]
     SerdeJson(#[from] serde_json::Error),
+    #[error("module signature refused: {0}")]
+    Signature(#[source] SiPkgError),
     #[error("module parsing error: {0}")]
     SiPkgError(#[from] SiPkgError),
     #[error("upload is required")]
@@ -46,7 +51,12 @@ pub enum UpsertModuleError {
 // TODO: figure out how to not keep this serialization logic here
 impl IntoResponse for UpsertModuleError {
     fn into_response(self) -> Response {
-        let (status, error_message) = (StatusCode::INTERNAL_SERVER_ERROR, self.to_string());
+        let status = match self {
+            Self::InvalidVersion(_) => StatusCode::BAD_REQUEST,
+            Self::Signature(_) => StatusCode::FORBIDDEN,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        let error_message = self.to_string();
 
         let body = Json(
             serde_json::json!({ "error": { "message": error_message, "code": 42, "statusCode": status.as_u16() } }),
@@ -61,6 +71,7 @@ pub async fn upsert_module_route(
     Authorization { user_claim, .. }: Authorization,
     ExtractedS3Bucket(s3_bucket): ExtractedS3Bucket,
     DbConnection(txn): DbConnection,
+    State(state): State<AppState>,
     mut multipart: Multipart,
 ) -> Result<Json<ModuleDetailsResponse>, UpsertModuleError> {
     info!("Upsert module");
@@ -76,10 +87,29 @@ pub async fn upsert_module_route(
     let loaded_module = dbg!(SiPkg::load_from_bytes(data.to_vec()))?;
     let module_metadata = dbg!(loaded_module.metadata())?;
 
+    let module_signing = state.module_signing();
+    if module_signing.require_trusted_signature {
+        let signature = loaded_module
+            .verify_trusted(&module_signing.trusted_keys)
+            .map_err(UpsertModuleError::Signature)?;
+        info!(signer = signature.signer(), key_id = %signature.key_id(), "module signature trusted");
+    } else {
+        loaded_module
+            .verify()
+            .map_err(UpsertModuleError::Signature)?;
+    }
+
     let version = module_metadata.version().to_owned();
     let module_kind = match module_metadata.kind() {
         SiPkgKind::WorkspaceBackup => si_module::ModuleKind::WorkspaceBackup,
-        SiPkgKind::Module => si_module::ModuleKind::Module,
+        SiPkgKind::Module => {
+            // Modules are resolved as dependencies by version, so they need versions that can be
+            // compared
+            module_metadata
+                .semver()
+                .map_err(UpsertModuleError::InvalidVersion)?;
+            si_module::ModuleKind::Module
+        }
     };
 
     let schemas: Vec<String> = loaded_module
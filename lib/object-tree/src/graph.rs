@@ -183,6 +183,10 @@ impl NodeEntry {
     pub(crate) fn hash(&self) -> Hash {
         self.hash
     }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
 }
 
 impl WriteBytes for NodeEntry {
@@ -574,6 +578,117 @@ impl<T> ObjectTree<T> {
     pub(crate) fn new(graph: Graph<HashedNode<T>, ()>, root_idx: NodeIndex) -> Self {
         Self { graph, root_idx }
     }
+
+    /// Builds a new `ObjectTree` from its root node, looking up every other node by hash with
+    /// `get_node`. A child for which `get_node` returns `None` is left out of the tree.
+    pub(crate) fn from_root_with_entries<E>(
+        root_node: HashedNodeWithEntries<T>,
+        mut get_node: impl FnMut(Hash) -> Result<Option<HashedNodeWithEntries<T>>, E>,
+    ) -> Result<Self, E> {
+        let mut graph = Graph::new();
+
+        let (root, root_entries) = root_node.into();
+        let root_idx = graph.add_node(root);
+
+        let mut stack: Vec<(Hash, NodeIndex)> = root_entries
+            .into_iter()
+            .rev()
+            .map(|entry| (entry.hash(), root_idx))
+            .collect();
+
+        while let Some((hash, parent_idx)) = stack.pop() {
+            if let Some(node_with_entries) = get_node(hash)? {
+                let (node, child_entries) = node_with_entries.into();
+
+                let node_idx = graph.add_node(node);
+                graph.add_edge(parent_idx, node_idx, ());
+
+                for child_entry in child_entries.into_iter().rev() {
+                    stack.push((child_entry.hash(), node_idx));
+                }
+            }
+        }
+
+        Ok(Self::new(graph, root_idx))
+    }
+
+    /// Returns the node at the given index along with the entries for its children, which is the
+    /// form in which a node is hashed and stored.
+    pub(crate) fn hashed_node_with_entries(
+        &self,
+        node_idx: NodeIndex,
+    ) -> Result<HashedNodeWithEntries<T>, GraphError>
+    where
+        T: Clone + NameStr,
+    {
+        let node = self
+            .graph
+            .node_weight(node_idx)
+            .ok_or(GraphError::NodeWeightNotFound(
+                node_idx.index(),
+                "could not find node for index",
+            ))?
+            .clone();
+
+        let mut entries = Vec::new();
+        for child_idx in self.graph.neighbors_directed(node_idx, Outgoing) {
+            let child_node =
+                self.graph
+                    .node_weight(child_idx)
+                    .ok_or(GraphError::NodeWeightNotFound(
+                        child_idx.index(),
+                        "could not find child node for index",
+                    ))?;
+            entries.push(NodeEntry::new(
+                child_node.kind(),
+                child_node.hash(),
+                child_node.name(),
+            ));
+        }
+
+        Ok(HashedNodeWithEntries::new(node, entries))
+    }
+
+    /// Returns the indices of the nodes in post-order (children before their parents), leaving out
+    /// every subtree whose root `is_pruned` says to skip.
+    ///
+    /// As a node's hash covers all of its descendants, this is how to find the nodes that have to
+    /// be sent to, or stored in, a place which already has the subtrees for some hashes.
+    pub(crate) fn pruned_post_order<E>(
+        &self,
+        mut is_pruned: impl FnMut(&Hash) -> Result<bool, E>,
+    ) -> Result<Vec<NodeIndex>, E>
+    where
+        E: From<GraphError>,
+    {
+        let mut order = Vec::new();
+        let mut stack = vec![(self.root_idx, false)];
+
+        while let Some((node_idx, children_visited)) = stack.pop() {
+            if children_visited {
+                order.push(node_idx);
+                continue;
+            }
+
+            let node = self
+                .graph
+                .node_weight(node_idx)
+                .ok_or(GraphError::NodeWeightNotFound(
+                    node_idx.index(),
+                    "pruned post order: could not find node for index",
+                ))?;
+            if is_pruned(&node.hash())? {
+                continue;
+            }
+
+            stack.push((node_idx, true));
+            for child_idx in self.graph.neighbors_directed(node_idx, Outgoing) {
+                stack.push((child_idx, false));
+            }
+        }
+
+        Ok(order)
+    }
 }
 
 /// A hashed node of type `T`.
@@ -667,6 +782,10 @@ impl<T> HashedNodeWithEntries<T> {
         self.hash
     }
 
+    pub(crate) fn entry_hashes(&self) -> impl Iterator<Item = Hash> + '_ {
+        self.entries.iter().map(NodeEntry::hash)
+    }
+
     fn as_node_with_entries_ref(&self) -> NodeWithEntriesRef<'_, T> {
         NodeWithEntriesRef {
             kind: self.kind,
@@ -76,10 +76,12 @@
 )]
 
 mod graph;
+mod store;
 mod tar;
 
 pub use crate::tar::{
     read::TarReadError,
+    stream::{StreamedTar, TarStreamError, TarStreamReader, DEFAULT_MAX_ENTRY_SIZE},
     write::{TarWriter, TarWriterError},
 };
 pub use graph::{
@@ -87,6 +89,7 @@ pub use graph::{
     GraphError, HashedNode, NameStr, NodeChild, NodeKind, NodeWithChildren, ObjectTree, ReadBytes,
     WriteBytes,
 };
+pub use store::{DirectoryObjectStore, ObjectStore, ObjectStoreError, StoredNode};
 // The `Hash` type is faily coupled to the implementation and data structures in this crate,
 // despite being defined in an external crate. Due to this coupling, we'll re-export
 // `si_hash::Hash` to simplfy crates which consume this one, reducing an extra dependency for a
@@ -0,0 +1,378 @@
+//! Content-addressed storage for the nodes of [`ObjectTree`]s.
+//!
+//! Every node is stored once under its [`struct@Hash`], in the same serialized form used in a tar
+//! bundle. As the hash of a node covers all of its descendants, trees which share subtrees (such as
+//! two versions of a package) share the stored nodes for them, and a tree only needs the nodes a
+//! store is missing to be added to it.
+//!
+//! Stores keep the invariant that a node is only stored once all of its descendants are, so that a
+//! node found in a store can be assumed to come with its whole subtree.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use petgraph::prelude::*;
+use si_hash::Hash;
+use thiserror::Error;
+
+use crate::{
+    graph::{HashedNodeWithEntries, NodeEntry, NodeWithEntries},
+    tar::{
+        object_path,
+        read::{get_root_ref, read_tar_entries, take_detached},
+    },
+    GraphError, HashedNode, NameStr, ObjectTree, ReadBytes, TarReadError, WriteBytes,
+};
+
+/// Errors that can occur when storing or loading nodes of an object tree
+#[remain::sorted]
+#[derive(Debug, Error)]
+pub enum ObjectStoreError {
+    /// When the storage backing an [`ObjectStore`] implementation fails
+    #[error("object store backend error: {0}")]
+    Backend(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
+    /// When a node fails to be serialized, parsed or verified
+    #[error("GraphError: {0}")]
+    Graph(#[from] GraphError),
+    /// When an error occurs reading or writing a stored node
+    #[error("IoError: {0}")]
+    Io(#[from] io::Error),
+    /// When a node is neither in a bundle nor in the store
+    #[error("node not found in bundle or store: {0}")]
+    NodeNotFound(Hash),
+    /// When a bundle cannot be read
+    #[error("error reading bundle: {0}")]
+    TarRead(#[from] TarReadError),
+}
+
+impl ObjectStoreError {
+    /// Wraps an error from the storage backing an [`ObjectStore`] implementation.
+    pub fn backend(source: impl std::error::Error + Send + Sync + 'static) -> Self {
+        Self::Backend(Box::new(source))
+    }
+}
+
+/// A content-addressed store of serialized nodes, keyed by their [`struct@Hash`].
+///
+/// Implementations don't have to verify that the bytes match the hash, as the callers in this
+/// crate either serialize the bytes themselves or have already verified them.
+pub trait ObjectStore {
+    /// Returns whether the node with the given hash is stored.
+    fn contains(&self, hash: &Hash) -> Result<bool, ObjectStoreError>;
+
+    /// Returns the serialized node with the given hash, if it is stored.
+    fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>, ObjectStoreError>;
+
+    /// Stores a serialized node under its hash. Storing a node which is already stored has no
+    /// effect.
+    fn put(&self, hash: &Hash, bytes: &[u8]) -> Result<(), ObjectStoreError>;
+
+    /// Returns which of the given hashes are stored, such as to tell the sender of a thin bundle
+    /// what to leave out of it.
+    fn have<'a>(
+        &self,
+        hashes: impl IntoIterator<Item = &'a Hash>,
+    ) -> Result<HashSet<Hash>, ObjectStoreError>
+    where
+        Self: Sized,
+    {
+        let mut have = HashSet::new();
+        for hash in hashes {
+            if self.contains(hash)? {
+                have.insert(*hash);
+            }
+        }
+
+        Ok(have)
+    }
+}
+
+/// An [`ObjectStore`] keeping every node in its own file in a local directory.
+#[derive(Clone, Debug)]
+pub struct DirectoryObjectStore {
+    root: PathBuf,
+}
+
+impl DirectoryObjectStore {
+    /// Creates a store in the given directory, which is created when the first node is stored.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Returns the directory holding the store.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn node_path(&self, hash: &Hash) -> PathBuf {
+        self.root.join(object_path(hash))
+    }
+}
+
+impl ObjectStore for DirectoryObjectStore {
+    fn contains(&self, hash: &Hash) -> Result<bool, ObjectStoreError> {
+        Ok(self.node_path(hash).try_exists()?)
+    }
+
+    fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>, ObjectStoreError> {
+        match fs::read(self.node_path(hash)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn put(&self, hash: &Hash, bytes: &[u8]) -> Result<(), ObjectStoreError> {
+        let path = self.node_path(hash);
+        if path.try_exists()? {
+            return Ok(());
+        }
+        let dir = path
+            .parent()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "node path has no parent"))?;
+        fs::create_dir_all(dir)?;
+
+        // Written to a temporary file first so that a node is never seen half written
+        let mut file = tempfile::NamedTempFile::new_in(dir)?;
+        file.write_all(bytes)?;
+        file.persist(&path).map_err(io::Error::from)?;
+
+        Ok(())
+    }
+}
+
+impl<T> ObjectTree<T> {
+    /// Returns the hashes of every node in the tree.
+    pub fn node_hashes(&self) -> HashSet<Hash> {
+        let (graph, _) = self.as_petgraph();
+        graph.node_weights().map(|node| node.hash()).collect()
+    }
+
+    /// Stores every node of the tree which the store doesn't already have, returning how many
+    /// nodes were stored.
+    pub fn write_to_store<S>(&self, store: &S) -> Result<usize, ObjectStoreError>
+    where
+        S: ObjectStore,
+        T: Clone + NameStr + WriteBytes,
+    {
+        // Identical subtrees can appear more than once in a tree, but only need storing once
+        let mut stored = HashSet::new();
+        for node_idx in self.pruned_post_order(|hash| store.contains(hash))? {
+            let node = self.hashed_node_with_entries(node_idx)?;
+            if stored.insert(node.hash()) {
+                store.put(&node.hash(), &node.to_bytes()?)?;
+            }
+        }
+
+        Ok(stored.len())
+    }
+
+    /// Reads and returns the [`ObjectTree`] with the given root hash from a store.
+    pub fn read_from_store<N, S>(
+        store: &S,
+        root_hash: Hash,
+    ) -> Result<ObjectTree<N>, ObjectStoreError>
+    where
+        N: ReadBytes,
+        S: ObjectStore,
+    {
+        let get_node = |hash: Hash| match store.get(&hash)? {
+            Some(bytes) => parse_node(bytes, hash),
+            None => Err(ObjectStoreError::NodeNotFound(hash)),
+        };
+
+        let root_node = get_node(root_hash)?.ok_or(TarReadError::RootNodeError)?;
+        ObjectTree::from_root_with_entries(root_node, get_node)
+    }
+
+    /// Reads and returns part of the [`ObjectTree`] with the given root hash from a store, only
+    /// reading the subtrees that `keep` asks for.
+    ///
+    /// `keep` is called with the names of the nodes on the path from the root (excluding it) down
+    /// to a child, before the child is read. A child it returns `false` for is left out of the
+    /// tree along with all of its descendants, which are never read.
+    pub fn read_from_store_filtered<N, S>(
+        store: &S,
+        root_hash: Hash,
+        mut keep: impl FnMut(&[&str]) -> bool,
+    ) -> Result<ObjectTree<N>, ObjectStoreError>
+    where
+        N: ReadBytes,
+        S: ObjectStore,
+    {
+        let get_node = |hash: Hash| match store.get(&hash)? {
+            Some(bytes) => parse_node::<N>(bytes, hash),
+            None => Err(ObjectStoreError::NodeNotFound(hash)),
+        };
+
+        let mut graph = Graph::new();
+
+        let root_node = get_node(root_hash)?.ok_or(TarReadError::RootNodeError)?;
+        let (root, root_entries) = root_node.into();
+        let root_idx = graph.add_node(root);
+
+        let mut stack: Vec<(NodeEntry, NodeIndex, Vec<String>)> = root_entries
+            .into_iter()
+            .rev()
+            .map(|entry| (entry, root_idx, vec![]))
+            .collect();
+
+        while let Some((entry, parent_idx, mut path)) = stack.pop() {
+            path.push(entry.name().to_string());
+            if !keep(&path.iter().map(String::as_str).collect::<Vec<_>>()) {
+                continue;
+            }
+
+            if let Some(node_with_entries) = get_node(entry.hash())? {
+                let (node, child_entries) = node_with_entries.into();
+
+                let node_idx = graph.add_node(node);
+                graph.add_edge(parent_idx, node_idx, ());
+
+                for child_entry in child_entries.into_iter().rev() {
+                    stack.push((child_entry, node_idx, path.clone()));
+                }
+            }
+        }
+
+        Ok(ObjectTree::new(graph, root_idx))
+    }
+
+    /// Reads a single node from a store along with the hashes and names of its children, for
+    /// walking a tree lazily without reading it whole. Returns `None` if the node is not stored
+    /// or is not an `N`.
+    pub fn read_node_from_store<N, S>(
+        store: &S,
+        hash: Hash,
+    ) -> Result<Option<StoredNode<N>>, ObjectStoreError>
+    where
+        N: ReadBytes,
+        S: ObjectStore,
+    {
+        let node_with_entries = match store.get(&hash)? {
+            Some(bytes) => parse_node::<N>(bytes, hash)?,
+            None => return Ok(None),
+        };
+
+        Ok(node_with_entries.map(|node_with_entries| {
+            let (node, entries) = node_with_entries.into();
+            StoredNode { node, entries }
+        }))
+    }
+
+    /// Reads and returns an [`ObjectTree`] from a bundle written by [`TarWriter::new_thin`],
+    /// taking the nodes left out of the bundle from the store, and stores the nodes which came
+    /// with the bundle.
+    ///
+    /// Every node in the bundle is verified against its hash before it is stored.
+    ///
+    /// [`TarWriter::new_thin`]: crate::TarWriter::new_thin
+    pub fn import_thin_tar<N, S>(
+        tar_data: Vec<u8>,
+        store: &S,
+    ) -> Result<ObjectTree<N>, ObjectStoreError>
+    where
+        N: ReadBytes,
+        S: ObjectStore,
+    {
+        Self::import_thin_tar_with_detached(tar_data, store).map(|(tree, _)| tree)
+    }
+
+    /// Reads and returns an [`ObjectTree`] like [`ObjectTree::import_thin_tar`], along with any
+    /// detached entries which were stored next to it, keyed by name.
+    #[allow(clippy::type_complexity)]
+    pub fn import_thin_tar_with_detached<N, S>(
+        tar_data: Vec<u8>,
+        store: &S,
+    ) -> Result<(ObjectTree<N>, BTreeMap<String, Vec<u8>>), ObjectStoreError>
+    where
+        N: ReadBytes,
+        S: ObjectStore,
+    {
+        let mut tar_data = read_tar_entries(tar_data)?;
+
+        let detached = take_detached(&mut tar_data);
+        let root_hash = get_root_ref(&mut tar_data)?;
+
+        // The nodes which came with the bundle, in the order they were read, which is parents
+        // before their children
+        let mut received = Vec::new();
+        let mut get_node = |hash: Hash| match tar_data.get(&object_path(&hash)) {
+            Some(bytes) => {
+                let computed = Hash::new(bytes);
+                if computed != hash {
+                    return Err(GraphError::Verify(hash, computed).into());
+                }
+                received.push((hash, bytes.clone()));
+                parse_node(bytes.clone(), hash)
+            }
+            None => match store.get(&hash)? {
+                Some(bytes) => parse_node(bytes, hash),
+                None => Err(ObjectStoreError::NodeNotFound(hash)),
+            },
+        };
+
+        let root_node = get_node(root_hash)?.ok_or(TarReadError::RootNodeError)?;
+        let tree = ObjectTree::from_root_with_entries(root_node, &mut get_node)?;
+
+        // Only stored once the whole tree could be read, and children before their parents
+        for (hash, bytes) in received.into_iter().rev() {
+            store.put(&hash, &bytes)?;
+        }
+
+        Ok((tree, detached))
+    }
+}
+
+/// A node read on its own from an [`ObjectStore`] with
+/// [`ObjectTree::read_node_from_store`], which knows its children only by hash and name.
+#[derive(Clone, Debug)]
+pub struct StoredNode<T> {
+    node: HashedNode<T>,
+    entries: Vec<NodeEntry>,
+}
+
+impl<T> StoredNode<T> {
+    /// Returns the node itself.
+    pub fn node(&self) -> &HashedNode<T> {
+        &self.node
+    }
+
+    /// Returns the node itself, consuming `self`.
+    pub fn into_node(self) -> HashedNode<T> {
+        self.node
+    }
+
+    /// Returns the names and hashes of the node's children, to be read with
+    /// [`ObjectTree::read_node_from_store`] as needed.
+    pub fn children(&self) -> impl Iterator<Item = (&str, Hash)> + '_ {
+        self.entries
+            .iter()
+            .map(|entry| (entry.name(), entry.hash()))
+    }
+
+    /// Returns the hash of the child with the given name, if there is one.
+    pub fn child_hash(&self, name: impl AsRef<str>) -> Option<Hash> {
+        self.entries
+            .iter()
+            .find(|entry| entry.name() == name.as_ref())
+            .map(NodeEntry::hash)
+    }
+}
+
+fn parse_node<N>(
+    bytes: Vec<u8>,
+    hash: Hash,
+) -> Result<Option<HashedNodeWithEntries<N>>, ObjectStoreError>
+where
+    N: ReadBytes,
+{
+    let node_with_entries: Option<NodeWithEntries<N>> = NodeWithEntries::from_bytes(bytes)?;
+
+    Ok(node_with_entries
+        .map(|nwe| HashedNodeWithEntries::from_node_with_entries_and_hash(nwe, hash)))
+}
@@ -3,12 +3,23 @@ use std::path::{Path, PathBuf};
 use si_hash::Hash;
 
 pub mod read;
+pub mod stream;
 pub mod write;
 
-fn object_path(hash: &Hash) -> PathBuf {
-    Path::new("objects").join(hash.to_string())
+const OBJECTS_DIR: &str = "objects";
+
+pub(crate) fn object_path(hash: &Hash) -> PathBuf {
+    Path::new(OBJECTS_DIR).join(hash.to_string())
 }
 
 fn ref_path(name: impl AsRef<Path>) -> PathBuf {
     Path::new("refs").join(name)
 }
+
+/// Entries stored in a tar alongside the tree which are not themselves part of it, and so don't
+/// contribute to any hash.
+const DETACHED_DIR: &str = "detached";
+
+fn detached_path(name: impl AsRef<Path>) -> PathBuf {
+    Path::new(DETACHED_DIR).join(name)
+}
@@ -1,12 +1,17 @@
-use std::{collections::HashMap, io::Read, path::PathBuf, str::FromStr, string::FromUtf8Error};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Read,
+    path::PathBuf,
+    str::FromStr,
+    string::FromUtf8Error,
+};
 
-use petgraph::prelude::*;
 use si_hash::{Hash, HashParseError};
 use thiserror::Error;
 
 use crate::{
     graph::{GraphError, HashedNodeWithEntries, NodeWithEntries, ObjectTree, ReadBytes},
-    tar::{object_path, ref_path},
+    tar::{object_path, ref_path, DETACHED_DIR},
 };
 
 /// Errors that can occur when reading a module bundle from a tar file
@@ -51,56 +56,70 @@ impl<T> ObjectTree<T> {
     where
         N: ReadBytes,
     {
-        let mut graph = Graph::new();
-        let mut root_idx: Option<NodeIndex> = None;
-
-        let mut unpacked_tar = ::tar::Archive::new(tar_data.as_slice());
-        let mut tar_data = HashMap::new();
-        for maybe_tar_entry in unpacked_tar.entries()? {
-            let mut tar_entry = maybe_tar_entry?;
-            let entry_path = tar_entry.path()?.into_owned();
-            let mut entry_data = Vec::new();
-            tar_entry.read_to_end(&mut entry_data)?;
+        Self::read_from_tar_with_detached(tar_data).map(|(tree, _)| tree)
+    }
 
-            tar_data.insert(entry_path, entry_data);
-        }
+    /// Reads and returns an [`ObjectTree`] from the underlying file system, along with any
+    /// detached entries which were stored next to it, keyed by name.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` for the same reasons as [`ObjectTree::read_from_tar`].
+    #[allow(clippy::type_complexity)]
+    pub fn read_from_tar_with_detached<N>(
+        tar_data: Vec<u8>,
+    ) -> Result<(ObjectTree<N>, BTreeMap<String, Vec<u8>>), TarReadError>
+    where
+        N: ReadBytes,
+    {
+        let mut tar_data = read_tar_entries(tar_data)?;
 
+        let detached = take_detached(&mut tar_data);
         let root_hash = get_root_ref(&mut tar_data)?;
         let root_node = get_node(&mut tar_data, root_hash)?.ok_or(TarReadError::RootNodeError)?;
 
-        let mut stack: Vec<(HashedNodeWithEntries<N>, Option<NodeIndex>)> = vec![(root_node, None)];
-
-        while let Some((node_with_entries, parent_idx)) = stack.pop() {
-            let (node, child_entries) = node_with_entries.into();
-
-            let node_idx = graph.add_node(node);
-
-            match parent_idx {
-                Some(parent_idx) => {
-                    graph.add_edge(parent_idx, node_idx, ());
-                }
-                None => match root_idx {
-                    None => {
-                        root_idx = Some(node_idx);
-                    }
-                    Some(_) => return Err(TarReadError::ReadTree(GraphError::MultipleRootNode)),
-                },
-            };
-
-            for child_entry in child_entries.into_iter().rev() {
-                if let Some(child_node) = get_node(&mut tar_data, child_entry.hash())? {
-                    stack.push((child_node, Some(node_idx)));
-                }
-            }
-        }
-
-        match root_idx {
-            Some(root_idx) => Ok(ObjectTree::new(graph, root_idx)),
-            None => Err(TarReadError::ReadTree(GraphError::MissingRootNode)),
-        }
+        let tree =
+            ObjectTree::from_root_with_entries(root_node, |hash| get_node(&mut tar_data, hash))?;
+
+        Ok((tree, detached))
     }
 }
 
+/// Reads every entry of a tar, keyed by path.
+pub(crate) fn read_tar_entries(
+    tar_data: Vec<u8>,
+) -> Result<HashMap<PathBuf, Vec<u8>>, TarReadError> {
+    let mut unpacked_tar = ::tar::Archive::new(tar_data.as_slice());
+    let mut entries = HashMap::new();
+    for maybe_tar_entry in unpacked_tar.entries()? {
+        let mut tar_entry = maybe_tar_entry?;
+        let entry_path = tar_entry.path()?.into_owned();
+        let mut entry_data = Vec::new();
+        tar_entry.read_to_end(&mut entry_data)?;
+
+        entries.insert(entry_path, entry_data);
+    }
+
+    Ok(entries)
+}
+
+pub(crate) fn take_detached(tar_data: &mut HashMap<PathBuf, Vec<u8>>) -> BTreeMap<String, Vec<u8>> {
+    let detached_paths: Vec<PathBuf> = tar_data
+        .keys()
+        .filter(|path| path.starts_with(DETACHED_DIR))
+        .cloned()
+        .collect();
+
+    detached_paths
+        .into_iter()
+        .filter_map(|path| {
+            let entry = tar_data.remove(&path)?;
+            let name = path.strip_prefix(DETACHED_DIR).ok()?.to_str()?.to_string();
+            Some((name, entry))
+        })
+        .collect()
+}
+
 fn get_node<N>(
     tar_data: &mut HashMap<PathBuf, Vec<u8>>,
     hash: Hash,
@@ -113,6 +132,12 @@ where
         .get(&dst_path)
         .ok_or_else(|| TarReadError::NodeNotFound(dst_path))?;
 
+    // The hash comes from the entry's path, so the contents must be checked against it
+    let computed = Hash::new(buf);
+    if computed != hash {
+        return Err(TarReadError::ReadTree(GraphError::Verify(hash, computed)));
+    }
+
     let node_with_entries: Option<NodeWithEntries<N>> =
         NodeWithEntries::from_bytes(buf.clone()).map_err(TarReadError::NodeWithEntriesParse)?;
 
@@ -120,7 +145,7 @@ where
         .map(|nwe| HashedNodeWithEntries::from_node_with_entries_and_hash(nwe, hash)))
 }
 
-fn get_root_ref(tar_data: &mut HashMap<PathBuf, Vec<u8>>) -> Result<Hash, TarReadError> {
+pub(crate) fn get_root_ref(tar_data: &mut HashMap<PathBuf, Vec<u8>>) -> Result<Hash, TarReadError> {
     let dst_path = ref_path("root");
     let buf = String::from_utf8(
         tar_data
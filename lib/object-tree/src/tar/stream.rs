@@ -0,0 +1,527 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    string::FromUtf8Error,
+};
+
+use ::tar::{EntryType, Header};
+use si_hash::{Hash, HashParseError};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    task::JoinError,
+};
+
+use crate::{
+    graph::{HashedNodeWithEntries, NodeWithEntries, VerifyHash},
+    tar::{ref_path, DETACHED_DIR, OBJECTS_DIR},
+    GraphError, ObjectStore, ObjectStoreError, ReadBytes, WriteBytes,
+};
+
+const BLOCK_SIZE: usize = 512;
+
+/// The largest entry read by default, which bounds the memory used while streaming.
+pub const DEFAULT_MAX_ENTRY_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Errors that can occur when streaming a tar bundle into an object store
+#[remain::sorted]
+#[derive(Debug, Error)]
+pub enum TarStreamError {
+    /// When a node fails to be parsed or its hash does not match its contents
+    #[error("GraphError: {0}")]
+    Graph(#[from] GraphError),
+    /// When an error occurs creating a [`struct@Hash`] from an entry path or the root ref
+    #[error("Error parsing hash: {0}")]
+    Hash(#[from] HashParseError),
+    /// When an error occurs while reading from the stream
+    #[error("io error when reading: {0}")]
+    Io(#[from] io::Error),
+    /// When a blocking task accessing the store panics or is cancelled
+    #[error("object store task failed: {0}")]
+    Join(#[from] JoinError),
+    /// When a node refers to a child which neither came before it in the bundle nor is in the
+    /// store
+    #[error(
+        "node {0} refers to child {1} which is neither earlier in the bundle nor in the store"
+    )]
+    MissingChild(Hash, Hash),
+    /// When the bundle has no root ref
+    #[error("bundle has no root ref")]
+    MissingRootRef,
+    /// When storing a node fails
+    #[error("object store error: {0}")]
+    ObjectStore(#[from] ObjectStoreError),
+    /// When the root ref points to a node which is neither in the bundle nor in the store
+    #[error("root node not found: {0}")]
+    RootNotFound(Hash),
+    /// When the given byte sequence is not parsable as a UTF8 [`String`]
+    #[error("Invalid string: {0}")]
+    StringParse(#[from] FromUtf8Error),
+    /// When an entry is larger than the configured maximum
+    #[error("entry {0:?} is {1} bytes, larger than the maximum of {2} bytes")]
+    TooLarge(PathBuf, u64, u64),
+    /// When the stream ends in the middle of an entry
+    #[error("bundle ended in the middle of an entry")]
+    UnexpectedEof,
+}
+
+/// Reads a tar bundle of an [`ObjectTree`](crate::ObjectTree) from an [`AsyncRead`] one entry at
+/// a time, storing each node in an [`ObjectStore`] as soon as it is verified.
+///
+/// Nodes are written to bundles children first, so every node can be verified against its hash
+/// and checked to refer only to children which are already stored, without holding more than one
+/// entry in memory. Once imported, the tree can be walked lazily from the store with
+/// [`ObjectTree::read_node_from_store`](crate::ObjectTree::read_node_from_store) or partly read
+/// with [`ObjectTree::read_from_store_filtered`](crate::ObjectTree::read_from_store_filtered).
+///
+/// Thin bundles written by [`TarWriter::new_thin`](crate::TarWriter::new_thin) can be read too, as
+/// the nodes left out of them are found in the store.
+#[derive(Debug)]
+pub struct TarStreamReader<R> {
+    reader: R,
+    max_entry_size: u64,
+}
+
+impl<R> TarStreamReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    /// Creates a reader over the given stream.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            max_entry_size: DEFAULT_MAX_ENTRY_SIZE,
+        }
+    }
+
+    /// Sets the size of the largest entry which will be read, [`DEFAULT_MAX_ENTRY_SIZE`] if not
+    /// set.
+    #[must_use]
+    pub fn max_entry_size(mut self, max_entry_size: u64) -> Self {
+        self.max_entry_size = max_entry_size;
+        self
+    }
+
+    /// Reads the whole bundle, storing its nodes in `store`, and returns the root hash along with
+    /// any detached entries.
+    ///
+    /// Nodes which fail to parse as `N` are stored after checking their hash only, as their
+    /// children can't be known.
+    ///
+    /// [`ObjectStore`] methods block, so the store is cloned onto a blocking task for each node.
+    pub async fn import<N, S>(mut self, store: &S) -> Result<StreamedTar, TarStreamError>
+    where
+        N: ReadBytes + WriteBytes + 'static,
+        S: ObjectStore + Clone + Send + 'static,
+    {
+        let mut root_hash = None;
+        let mut detached = BTreeMap::new();
+        let mut stored = 0_usize;
+
+        while let Some((path, size)) = self.next_header().await? {
+            if let Ok(name) = path.strip_prefix(OBJECTS_DIR) {
+                let hash = Hash::from_str(&name.to_string_lossy())?;
+                let bytes = self.read_entry(&path, size).await?;
+                let newly_stored = with_store(store, move |store| {
+                    if store.contains(&hash)? {
+                        return Ok(false);
+                    }
+                    verify_node::<N, S>(store, hash, &bytes)?;
+                    store.put(&hash, &bytes)?;
+                    Ok(true)
+                })
+                .await?;
+                if newly_stored {
+                    stored = stored.saturating_add(1);
+                }
+            } else if path == ref_path("root") {
+                let bytes = self.read_entry(&path, size).await?;
+                root_hash = Some(Hash::from_str(String::from_utf8(bytes)?.trim())?);
+            } else if let Ok(name) = path.strip_prefix(DETACHED_DIR) {
+                let name = name.to_string_lossy().to_string();
+                detached.insert(name, self.read_entry(&path, size).await?);
+            } else {
+                self.skip(size).await?;
+            }
+        }
+
+        let root_hash = root_hash.ok_or(TarStreamError::MissingRootRef)?;
+        if !with_store(store, move |store| Ok(store.contains(&root_hash)?)).await? {
+            return Err(TarStreamError::RootNotFound(root_hash));
+        }
+
+        Ok(StreamedTar {
+            root_hash,
+            detached,
+            stored,
+        })
+    }
+
+    /// Reads the next header, returning the path and size of its entry, or `None` at the end of
+    /// the bundle. GNU long names are resolved into the path of the entry they precede.
+    async fn next_header(&mut self) -> Result<Option<(PathBuf, u64)>, TarStreamError> {
+        let mut long_name: Option<Vec<u8>> = None;
+
+        loop {
+            let mut block = [0_u8; BLOCK_SIZE];
+            if !self.read_block(&mut block).await? || block.iter().all(|byte| *byte == 0) {
+                return Ok(None);
+            }
+
+            let header = Header::from_byte_slice(&block);
+            let size = header.entry_size()?;
+            match header.entry_type() {
+                EntryType::GNULongName => {
+                    let mut name = self.read_entry(Path::new("././@LongLink"), size).await?;
+                    while name.last() == Some(&0) {
+                        name.pop();
+                    }
+                    long_name = Some(name);
+                }
+                EntryType::Regular | EntryType::Continuous => {
+                    let path = match long_name.take() {
+                        Some(name) => PathBuf::from(String::from_utf8(name)?),
+                        None => header.path()?.into_owned(),
+                    };
+                    return Ok(Some((path, size)));
+                }
+                _ => self.skip(size).await?,
+            }
+        }
+    }
+
+    /// Fills `block`, returning `false` if the stream ended cleanly before it.
+    async fn read_block(&mut self, block: &mut [u8; BLOCK_SIZE]) -> Result<bool, TarStreamError> {
+        let mut filled = 0;
+        while let Some(rest) = block.get_mut(filled..) {
+            if rest.is_empty() {
+                break;
+            }
+            match self.reader.read(rest).await? {
+                0 if filled == 0 => return Ok(false),
+                0 => return Err(TarStreamError::UnexpectedEof),
+                read => filled = filled.saturating_add(read),
+            }
+        }
+
+        Ok(true)
+    }
+
+    async fn read_entry(&mut self, path: &Path, size: u64) -> Result<Vec<u8>, TarStreamError> {
+        if size > self.max_entry_size {
+            return Err(TarStreamError::TooLarge(
+                path.to_path_buf(),
+                size,
+                self.max_entry_size,
+            ));
+        }
+
+        let mut bytes = Vec::with_capacity(usize::try_from(size).unwrap_or_default());
+        (&mut self.reader)
+            .take(size)
+            .read_to_end(&mut bytes)
+            .await?;
+        if (bytes.len() as u64) < size {
+            return Err(TarStreamError::UnexpectedEof);
+        }
+        self.skip_padding(size).await?;
+
+        Ok(bytes)
+    }
+
+    async fn skip(&mut self, size: u64) -> Result<(), TarStreamError> {
+        let skipped =
+            tokio::io::copy(&mut (&mut self.reader).take(size), &mut tokio::io::sink()).await?;
+        if skipped < size {
+            return Err(TarStreamError::UnexpectedEof);
+        }
+        self.skip_padding(size).await
+    }
+
+    /// Entries are padded with zeros up to a whole number of blocks.
+    async fn skip_padding(&mut self, size: u64) -> Result<(), TarStreamError> {
+        let remainder = size.checked_rem(BLOCK_SIZE as u64).unwrap_or_default() as usize;
+        if remainder == 0 {
+            return Ok(());
+        }
+
+        let mut buf = [0_u8; BLOCK_SIZE];
+        if let Some(padding) = buf.get_mut(remainder..) {
+            self.reader
+                .read_exact(padding)
+                .await
+                .map_err(|_| TarStreamError::UnexpectedEof)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs `f` against a clone of `store` on a blocking task.
+async fn with_store<S, T>(
+    store: &S,
+    f: impl FnOnce(&S) -> Result<T, TarStreamError> + Send + 'static,
+) -> Result<T, TarStreamError>
+where
+    S: ObjectStore + Clone + Send + 'static,
+    T: Send + 'static,
+{
+    let store = store.clone();
+    tokio::task::spawn_blocking(move || f(&store)).await?
+}
+
+/// Checks a node against its hash and that all of its children are already stored.
+fn verify_node<N, S>(store: &S, hash: Hash, bytes: &[u8]) -> Result<(), TarStreamError>
+where
+    N: ReadBytes + WriteBytes,
+    S: ObjectStore,
+{
+    let node_with_entries: Option<NodeWithEntries<N>> =
+        NodeWithEntries::from_bytes(bytes.to_vec())?;
+
+    match node_with_entries {
+        Some(node_with_entries) => {
+            let node =
+                HashedNodeWithEntries::from_node_with_entries_and_hash(node_with_entries, hash);
+            node.verify_hash()?;
+
+            for child_hash in node.entry_hashes() {
+                if !store.contains(&child_hash)? {
+                    return Err(TarStreamError::MissingChild(hash, child_hash));
+                }
+            }
+        }
+        None => {
+            let computed = Hash::new(bytes);
+            if computed != hash {
+                return Err(GraphError::Verify(hash, computed).into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The result of streaming a bundle into a store with [`TarStreamReader::import`].
+#[derive(Clone, Debug)]
+pub struct StreamedTar {
+    root_hash: Hash,
+    detached: BTreeMap<String, Vec<u8>>,
+    stored: usize,
+}
+
+impl StreamedTar {
+    /// Returns the hash of the root node of the tree.
+    pub fn root_hash(&self) -> Hash {
+        self.root_hash
+    }
+
+    /// Returns the entries stored next to the tree, keyed by name.
+    pub fn detached(&self) -> &BTreeMap<String, Vec<u8>> {
+        &self.detached
+    }
+
+    /// Returns the entries stored next to the tree, keyed by name, consuming `self`.
+    pub fn into_detached(self) -> BTreeMap<String, Vec<u8>> {
+        self.detached
+    }
+
+    /// Returns how many nodes from the bundle were not already in the store.
+    pub fn stored(&self) -> usize {
+        self.stored
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        io::{BufRead, Read, Write},
+    };
+
+    use super::*;
+    use crate::{
+        read_key_value_line, write_key_value_line, DirectoryObjectStore, NameStr, NodeChild,
+        NodeKind, NodeWithChildren, ObjectTree, TarWriter,
+    };
+
+    #[derive(Clone, Debug)]
+    struct TestNode(String);
+
+    impl NameStr for TestNode {
+        fn name(&self) -> &str {
+            &self.0
+        }
+    }
+
+    impl WriteBytes for TestNode {
+        fn write_bytes<W: Write>(&self, writer: &mut W) -> Result<(), GraphError> {
+            write_key_value_line(writer, "name", &self.0)
+        }
+    }
+
+    impl ReadBytes for TestNode {
+        fn read_bytes<R: BufRead>(reader: &mut R) -> Result<Option<Self>, GraphError> {
+            Ok(Some(Self(read_key_value_line(reader, "name")?)))
+        }
+    }
+
+    #[derive(Clone)]
+    struct TestSpec {
+        name: &'static str,
+        children: Vec<TestSpec>,
+    }
+
+    impl NodeChild for TestSpec {
+        type NodeType = TestNode;
+
+        fn as_node_with_children(&self) -> NodeWithChildren<Self::NodeType> {
+            let kind = if self.children.is_empty() {
+                NodeKind::Leaf
+            } else {
+                NodeKind::Tree
+            };
+            NodeWithChildren::new(
+                kind,
+                TestNode(self.name.to_string()),
+                self.children
+                    .iter()
+                    .cloned()
+                    .map(|child| Box::new(child) as Box<dyn NodeChild<NodeType = TestNode>>)
+                    .collect(),
+            )
+        }
+    }
+
+    fn tree() -> ObjectTree<TestNode> {
+        let leaf = |name| TestSpec {
+            name,
+            children: vec![],
+        };
+        let root = TestSpec {
+            name: "root",
+            children: vec![leaf("first"), leaf("second")],
+        };
+        ObjectTree::create_from_root(root.as_node_with_children()).expect("failed to hash tree")
+    }
+
+    fn node_hash(tree: &ObjectTree<TestNode>, name: &str) -> Hash {
+        let (graph, _) = tree.as_petgraph();
+        graph
+            .node_weights()
+            .find(|node| node.name() == name)
+            .expect("node not in tree")
+            .hash()
+    }
+
+    fn append(builder: &mut ::tar::Builder<Vec<u8>>, path: impl AsRef<Path>, data: &[u8]) {
+        let mut header = Header::new_gnu();
+        header.set_size(data.len() as u64);
+        builder
+            .append_data(&mut header, path, data)
+            .expect("failed to append entry");
+    }
+
+    async fn import(bytes: &[u8], max_entry_size: u64) -> Result<StreamedTar, TarStreamError> {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        TarStreamReader::new(bytes)
+            .max_entry_size(max_entry_size)
+            .import::<TestNode, _>(&DirectoryObjectStore::new(dir.path()))
+            .await
+    }
+
+    #[tokio::test]
+    async fn imports_every_node() {
+        let tree = tree();
+        let bytes = TarWriter::new(&tree).expect("failed to write tar").bytes();
+
+        let streamed = import(&bytes, DEFAULT_MAX_ENTRY_SIZE)
+            .await
+            .expect("failed to import");
+
+        assert_eq!(node_hash(&tree, "root"), streamed.root_hash());
+        assert_eq!(3, streamed.stored());
+    }
+
+    #[tokio::test]
+    async fn truncated_streams_are_unexpected_eof() {
+        let bytes = TarWriter::new(&tree())
+            .expect("failed to write tar")
+            .bytes();
+
+        // Part way through the first header, then part way through the first entry
+        for len in [BLOCK_SIZE / 2, BLOCK_SIZE + 10] {
+            assert!(matches!(
+                import(
+                    bytes.get(..len).expect("bundle is too short"),
+                    DEFAULT_MAX_ENTRY_SIZE
+                )
+                .await,
+                Err(TarStreamError::UnexpectedEof)
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn entries_over_the_maximum_size_are_too_large() {
+        let bytes = TarWriter::new(&tree())
+            .expect("failed to write tar")
+            .bytes();
+
+        assert!(matches!(
+            import(&bytes, 1).await,
+            Err(TarStreamError::TooLarge(_, _, 1))
+        ));
+    }
+
+    #[tokio::test]
+    async fn gnu_long_names_are_resolved() {
+        // `TarWriter` only writes short paths, so the bundle is copied with an extra entry whose
+        // path needs a GNU long name header
+        let name = "a".repeat(200);
+        let mut builder = ::tar::Builder::new(Vec::new());
+        let bundle = TarWriter::new(&tree())
+            .expect("failed to write tar")
+            .bytes();
+        let mut archive = ::tar::Archive::new(bundle.as_slice());
+        for entry in archive.entries().expect("failed to read tar") {
+            let mut entry = entry.expect("failed to read tar entry");
+            let path = entry.path().expect("invalid entry path").into_owned();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).expect("failed to read entry");
+            append(&mut builder, path, &data);
+        }
+        append(&mut builder, Path::new(DETACHED_DIR).join(&name), b"long");
+        let bytes = builder.into_inner().expect("failed to finish tar");
+
+        let streamed = import(&bytes, DEFAULT_MAX_ENTRY_SIZE)
+            .await
+            .expect("failed to import");
+
+        assert_eq!(
+            Some(&b"long".to_vec()),
+            streamed.detached().get(name.as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn nodes_missing_a_child_are_rejected() {
+        let tree = tree();
+        let first = node_hash(&tree, "first");
+        // A thin bundle leaving out a child the (empty) store doesn't have
+        let bytes = TarWriter::new_thin(&tree, &HashSet::from([first]))
+            .expect("failed to write tar")
+            .bytes();
+
+        let root = node_hash(&tree, "root");
+        let err = import(&bytes, DEFAULT_MAX_ENTRY_SIZE)
+            .await
+            .expect_err("imported a node missing a child");
+        assert!(
+            matches!(err, TarStreamError::MissingChild(parent, child) if parent == root && child == first),
+            "unexpected error: {err:?}"
+        );
+    }
+}
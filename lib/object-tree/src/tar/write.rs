@@ -1,12 +1,13 @@
-use std::{num::TryFromIntError, path::PathBuf};
+use std::{collections::HashSet, num::TryFromIntError, path::PathBuf};
 
 use ::tar::{Builder, Header};
 use petgraph::prelude::*;
+use si_hash::Hash;
 use thiserror::Error;
 
 use crate::{
-    graph::{HashedNodeWithEntries, NodeEntry},
-    tar::{object_path, ref_path},
+    graph::HashedNodeWithEntries,
+    tar::{detached_path, object_path, ref_path},
     GraphError, NameStr, ObjectTree, WriteBytes,
 };
 
@@ -33,6 +34,21 @@ pub struct TarWriter {
 impl TarWriter {
     /// Return a [`TarWriter`] populated from the provided [`ObjectTree`]
     pub fn new<T>(tree: &ObjectTree<T>) -> Result<Self, TarWriterError>
+    where
+        T: Clone + NameStr + WriteBytes + Send + Sync + 'static,
+    {
+        Self::new_with_detached(tree, Vec::<(String, Vec<u8>)>::new())
+    }
+
+    /// Return a [`TarWriter`] populated from the provided [`ObjectTree`], along with entries
+    /// which are stored next to the tree without being part of it.
+    ///
+    /// Detached entries don't contribute to the hash of any node, which makes them suitable for
+    /// data about the tree itself, such as signatures over its root hash.
+    pub fn new_with_detached<T>(
+        tree: &ObjectTree<T>,
+        detached: impl IntoIterator<Item = (impl AsRef<str>, impl AsRef<[u8]>)>,
+    ) -> Result<Self, TarWriterError>
     where
         T: Clone + NameStr + WriteBytes + Send + Sync + 'static,
     {
@@ -42,36 +58,52 @@ impl TarWriter {
         let mut dfspo = DfsPostOrder::new(graph, root_idx);
 
         while let Some(node_idx) = dfspo.next(graph) {
-            let node = graph
-                .node_weight(node_idx)
-                .ok_or(GraphError::NodeWeightNotFound(
-                    node_idx.index(),
-                    "tar writer: could not find next node for index for dfspo",
-                ))?
-                .clone();
-
-            let mut entries = Vec::new();
-            for child_idx in graph.neighbors_directed(node_idx, Outgoing) {
-                let child_node =
-                    graph
-                        .node_weight(child_idx)
-                        .ok_or(GraphError::NodeWeightNotFound(
-                            child_idx.index(),
-                            "tar writer: could not find child node for index",
-                        ))?;
-                entries.push(NodeEntry::new(
-                    child_node.kind(),
-                    child_node.hash(),
-                    child_node.name(),
-                ));
-            }
+            write_node(&mut tar_builder, &tree.hashed_node_with_entries(node_idx)?)?;
+        }
 
-            let tar_entry = HashedNodeWithEntries::new(node, entries);
-            write_tar_entry(
-                &mut tar_builder,
-                object_path(&tar_entry.hash()),
-                &tar_entry.to_bytes()?,
-            )?;
+        let root_node = graph
+            .node_weight(root_idx)
+            .ok_or(GraphError::NodeWeightNotFound(
+                root_idx.index(),
+                "tar writer: could not find root node for index",
+            ))?;
+        Self::finish(tar_builder, root_node.hash(), detached)
+    }
+
+    /// Return a "thin" [`TarWriter`] for the provided [`ObjectTree`], holding only the nodes
+    /// which a receiver that already has the nodes for the `have` hashes is missing.
+    ///
+    /// Since a node's hash covers all of its descendants, a node found in `have` is assumed to
+    /// come with its whole subtree, which is then left out. The receiver fills in the missing
+    /// nodes from its own [`ObjectStore`](crate::ObjectStore) when reading the bundle with
+    /// [`ObjectTree::import_thin_tar`].
+    pub fn new_thin<T>(tree: &ObjectTree<T>, have: &HashSet<Hash>) -> Result<Self, TarWriterError>
+    where
+        T: Clone + NameStr + WriteBytes + Send + Sync + 'static,
+    {
+        Self::new_thin_with_detached(tree, have, Vec::<(String, Vec<u8>)>::new())
+    }
+
+    /// Return a "thin" [`TarWriter`] like [`TarWriter::new_thin`], along with entries which are
+    /// stored next to the tree without being part of it.
+    pub fn new_thin_with_detached<T>(
+        tree: &ObjectTree<T>,
+        have: &HashSet<Hash>,
+        detached: impl IntoIterator<Item = (impl AsRef<str>, impl AsRef<[u8]>)>,
+    ) -> Result<Self, TarWriterError>
+    where
+        T: Clone + NameStr + WriteBytes + Send + Sync + 'static,
+    {
+        let (graph, root_idx) = tree.as_petgraph();
+        let mut tar_builder = Builder::new(Vec::new());
+
+        // Identical subtrees can appear more than once in a tree, but only need sending once
+        let mut written = HashSet::new();
+        for node_idx in tree.pruned_post_order::<TarWriterError>(|hash| Ok(have.contains(hash)))? {
+            let node = tree.hashed_node_with_entries(node_idx)?;
+            if written.insert(node.hash()) {
+                write_node(&mut tar_builder, &node)?;
+            }
         }
 
         let root_node = graph
@@ -80,11 +112,26 @@ impl TarWriter {
                 root_idx.index(),
                 "tar writer: could not find root node for index",
             ))?;
+        Self::finish(tar_builder, root_node.hash(), detached)
+    }
+
+    fn finish(
+        mut tar_builder: Builder<Vec<u8>>,
+        root_hash: Hash,
+        detached: impl IntoIterator<Item = (impl AsRef<str>, impl AsRef<[u8]>)>,
+    ) -> Result<Self, TarWriterError> {
         write_tar_entry(
             &mut tar_builder,
             ref_path("root"),
-            root_node.hash().to_string().as_bytes(),
+            root_hash.to_string().as_bytes(),
         )?;
+        for (name, entry) in detached {
+            write_tar_entry(
+                &mut tar_builder,
+                detached_path(name.as_ref()),
+                entry.as_ref(),
+            )?;
+        }
         tar_builder.finish()?;
 
         Ok(Self {
@@ -98,6 +145,16 @@ impl TarWriter {
     }
 }
 
+fn write_node<T>(
+    tar_builder: &mut Builder<Vec<u8>>,
+    node: &HashedNodeWithEntries<T>,
+) -> Result<(), TarWriterError>
+where
+    T: WriteBytes,
+{
+    write_tar_entry(tar_builder, object_path(&node.hash()), &node.to_bytes()?)
+}
+
 fn write_tar_entry(
     tar_builder: &mut Builder<Vec<u8>>,
     path: PathBuf,
@@ -1,6 +1,7 @@
 use std::{env, path::Path};
 
 use buck2_resources::Buck2Resources;
+use dal::SecretBackendsConfig;
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 use si_crypto::{CryptoConfig, SymmetricCryptoServiceConfig, SymmetricCryptoServiceConfigFile};
@@ -56,6 +57,9 @@ pub struct Config {
 
     #[builder(default = "SymmetricCryptoServiceConfig::default()")]
     symmetric_crypto_service: SymmetricCryptoServiceConfig,
+
+    #[builder(default)]
+    secret_backends: SecretBackendsConfig,
 }
 
 impl StandardConfig for Config {
@@ -90,6 +94,11 @@ impl Config {
         &self.symmetric_crypto_service
     }
 
+    /// Gets a reference to the config's external secret backends.
+    pub fn secret_backends(&self) -> &SecretBackendsConfig {
+        &self.secret_backends
+    }
+
     /// Gets the config's concurrency limit.
     pub fn concurrency(&self) -> usize {
         self.concurrency
@@ -115,6 +124,8 @@ pub struct ConfigFile {
     instance_id: String,
     #[serde(default = "default_symmetric_crypto_config")]
     symmetric_crypto_service: SymmetricCryptoServiceConfigFile,
+    #[serde(default)]
+    secret_backends: SecretBackendsConfig,
 }
 
 impl Default for ConfigFile {
@@ -126,6 +137,7 @@ impl Default for ConfigFile {
             crypto: Default::default(),
             instance_id: random_instance_id(),
             symmetric_crypto_service: default_symmetric_crypto_config(),
+            secret_backends: Default::default(),
         }
     }
 }
@@ -147,6 +159,7 @@ impl TryFrom<ConfigFile> for Config {
         config.concurrency(value.concurrency_limit);
         config.instance_id(value.instance_id);
         config.symmetric_crypto_service(value.symmetric_crypto_service.try_into()?);
+        config.secret_backends(value.secret_backends);
         config.build().map_err(Into::into)
     }
 }
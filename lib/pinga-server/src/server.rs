@@ -6,8 +6,10 @@ use dal::{
         definition::{FixesJob, RefreshJob},
         producer::BlockingJobError,
     },
+    secret::SecretBackendError,
     DalContext, DalContextBuilder, DependentValuesUpdate, InitializationError, JobFailure,
-    JobFailureError, JobQueueProcessor, NatsProcessor, ServicesContext, TransactionsError,
+    JobFailureError, JobQueueProcessor, NatsProcessor, SecretBackends, ServicesContext,
+    TransactionsError,
 };
 use futures::{FutureExt, Stream, StreamExt};
 use nats_subscriber::{Request, SubscriberError};
@@ -48,6 +50,8 @@ pub enum ServerError {
     #[error(transparent)]
     PgPool(#[from] Box<PgPoolError>),
     #[error(transparent)]
+    SecretBackend(#[from] SecretBackendError),
+    #[error(transparent)]
     SerdeJson(#[from] serde_json::Error),
     #[error("failed to setup signal handler")]
     Signal(#[source] io::Error),
@@ -110,6 +114,7 @@ impl Server {
         let job_processor = Self::create_job_processor(nats.clone());
         let symmetric_crypto_service =
             Self::create_symmetric_crypto_service(config.symmetric_crypto_service()).await?;
+        let secret_backends = SecretBackends::from_config(config.secret_backends())?;
 
         let services_context = ServicesContext::new(
             pg_pool,
@@ -120,6 +125,7 @@ impl Server {
             None,
             None,
             symmetric_crypto_service,
+            secret_backends,
         );
 
         Self::from_services(
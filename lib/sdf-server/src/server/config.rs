@@ -1,4 +1,4 @@
-use dal::jwt_key::JwtConfig;
+use dal::{jwt_key::JwtConfig, SecretBackendsConfig};
 use si_crypto::CryptoConfig;
 use std::{
     env,
@@ -69,6 +69,9 @@ pub struct Config {
     #[builder(default = "SymmetricCryptoServiceConfig::default()")]
     symmetric_crypto_service: SymmetricCryptoServiceConfig,
 
+    #[builder(default)]
+    secret_backends: SecretBackendsConfig,
+
     #[builder(default = "MigrationMode::default()")]
     migration_mode: MigrationMode,
 
@@ -145,6 +148,11 @@ impl Config {
         &self.symmetric_crypto_service
     }
 
+    /// The external backends secrets can be stored in
+    pub fn secret_backends(&self) -> &SecretBackendsConfig {
+        &self.secret_backends
+    }
+
     /// URL to the module index service
     #[must_use]
     pub fn module_index_url(&self) -> &str {
@@ -184,6 +192,8 @@ pub struct ConfigFile {
     pub module_index_url: String,
     #[serde(default = "default_symmetric_crypto_config")]
     symmetric_crypto_service: SymmetricCryptoServiceConfigFile,
+    #[serde(default)]
+    secret_backends: SecretBackendsConfig,
 }
 
 impl Default for ConfigFile {
@@ -199,6 +209,7 @@ impl Default for ConfigFile {
             posthog: Default::default(),
             module_index_url: default_module_index_url(),
             symmetric_crypto_service: default_symmetric_crypto_config(),
+            secret_backends: Default::default(),
         }
     }
 }
@@ -224,6 +235,7 @@ impl TryFrom<ConfigFile> for Config {
         config.posthog(value.posthog);
         config.module_index_url(value.module_index_url);
         config.symmetric_crypto_service(value.symmetric_crypto_service.try_into()?);
+        config.secret_backends(value.secret_backends);
         config.build().map_err(Into::into)
     }
 }
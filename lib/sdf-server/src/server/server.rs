@@ -11,9 +11,10 @@ use dal::{
     builtins,
     jwt_key::JwtConfig,
     pkg::{import_pkg_from_pkg, ImportOptions, PkgError},
-    tasks::{ResourceScheduler, StatusReceiver, StatusReceiverError},
-    BuiltinsError, DalContext, JwtPublicSigningKey, ServicesContext, Tenancy, TransactionsError,
-    Workspace, WorkspaceError,
+    secret::SecretBackendError,
+    tasks::{ResourceScheduler, StatusReceiver, StatusReceiverError, SymmetricKeyRotator},
+    BuiltinsError, DalContext, JwtPublicSigningKey, SecretBackends, SecretBackendsConfig,
+    ServicesContext, Tenancy, TransactionsError, Workspace, WorkspaceError,
 };
 use hyper::server::{accept::Accept, conn::AddrIncoming};
 use module_index_client::{types::BuiltinsDetailsResponse, IndexClient, ModuleDetailsResponse};
@@ -85,6 +86,8 @@ pub enum ServerError {
     PkgInstall,
     #[error(transparent)]
     Posthog(#[from] si_posthog::PosthogError),
+    #[error(transparent)]
+    SecretBackend(#[from] SecretBackendError),
     #[error("failed to setup signal handler")]
     Signal(#[source] io::Error),
     #[error(transparent)]
@@ -250,6 +253,14 @@ impl Server<(), ()> {
         ResourceScheduler::new(services_context).start(shutdown_broadcast_rx);
     }
 
+    /// Start re-encrypting data stored with retired symmetric keys under the active key
+    pub async fn start_symmetric_key_rotator(
+        services_context: ServicesContext,
+        shutdown_broadcast_rx: broadcast::Receiver<()>,
+    ) {
+        SymmetricKeyRotator::new(services_context).start(shutdown_broadcast_rx);
+    }
+
     pub async fn start_status_updater(
         services_context: ServicesContext,
         shutdown_broadcast_rx: broadcast::Receiver<()>,
@@ -286,6 +297,11 @@ impl Server<(), ()> {
             .await
             .map_err(Into::into)
     }
+
+    #[instrument(name = "sdf.init.create_secret_backends", skip_all)]
+    pub fn create_secret_backends(config: &SecretBackendsConfig) -> Result<SecretBackends> {
+        SecretBackends::from_config(config).map_err(Into::into)
+    }
 }
 
 impl<I, IO, IE, S> Server<I, S>
@@ -396,6 +412,8 @@ async fn install_builtins(
                         skip_import_funcs: None,
                         no_record: false,
                         is_builtin: true,
+                        trusted_signing_keys: None,
+                        module_index_client: None,
                     }),
                     true,
                 )
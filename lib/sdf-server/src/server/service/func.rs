@@ -251,7 +251,8 @@ impl TryFrom<&Func> for FuncVariant {
             | (FuncBackendKind::Object, _)
             | (FuncBackendKind::String, _)
             | (FuncBackendKind::Unset, _)
-            | (FuncBackendKind::Validation, _) => {
+            | (FuncBackendKind::Validation, _)
+            | (FuncBackendKind::Wasm, _) => {
                 Err(FuncError::FuncCannotBeTurnedIntoVariant(*func.id()))
             }
         }
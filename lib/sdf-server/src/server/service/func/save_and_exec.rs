@@ -162,6 +162,7 @@ pub async fn save_and_exec(
         | FuncBackendKind::String
         | FuncBackendKind::Unset
         | FuncBackendKind::Validation
+        | FuncBackendKind::Wasm
         | FuncBackendKind::JsValidation => Err(FuncError::FuncNotRunnable)?,
     }
 
@@ -15,6 +15,7 @@ use dal::{
     SchemaVariantId, StandardModel, Visibility, WsEvent,
 };
 use dal::{FuncBackendResponseType, PropKind, SchemaVariant};
+use veritech_client::ExecutionLimits;
 
 use crate::server::extract::{AccessBuilder, HandlerContext, PosthogClient};
 use crate::server::tracking::track;
@@ -32,6 +33,14 @@ pub struct SaveFuncRequest {
     pub name: String,
     pub description: Option<String>,
     pub code: Option<String>,
+    /// Whether the func's results may be cached, left unchanged if not given.
+    pub pure: Option<bool>,
+    /// The runtime the func's code is written for, left unchanged if not given. An empty runtime
+    /// resets the func to JavaScript run by the default lang server.
+    pub runtime: Option<String>,
+    /// The resource ceilings cyclone enforces when executing the func, left unchanged if not
+    /// given. Limits with nothing set remove the func's limits.
+    pub limits: Option<ExecutionLimits>,
     pub associations: Option<FuncAssociations>,
     #[serde(flatten)]
     pub visibility: Visibility,
@@ -529,6 +538,16 @@ pub async fn do_save_func(
     func.set_display_name(ctx, request.display_name).await?;
     func.set_name(ctx, request.name).await?;
     func.set_description(ctx, request.description).await?;
+    if let Some(pure) = request.pure {
+        func.set_pure(ctx, pure).await?;
+    }
+    if let Some(runtime) = request.runtime {
+        func.set_runtime(ctx, Some(runtime).filter(|runtime| !runtime.is_empty()))
+            .await?;
+    }
+    if let Some(limits) = request.limits {
+        func.set_limits(ctx, Some(limits)).await?;
+    }
     func.set_code_plaintext(ctx, request.code.as_deref())
         .await?;
 
@@ -645,6 +664,7 @@ pub async fn do_save_func(
         | FuncBackendKind::String
         | FuncBackendKind::Unset
         | FuncBackendKind::Validation
+        | FuncBackendKind::Wasm
         | FuncBackendKind::JsValidation => return Err(FuncError::NotWritable),
     }
 
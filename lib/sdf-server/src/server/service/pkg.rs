@@ -75,6 +75,8 @@ pub enum PkgError {
     PackageNotFound(String),
     #[error("Package version required")]
     PackageVersionEmpty,
+    #[error("Package version must be a semantic version: {0}")]
+    PackageVersionInvalid(#[source] semver::Error),
     #[error(transparent)]
     Pg(#[from] si_data_pg::PgError),
     #[error(transparent)]
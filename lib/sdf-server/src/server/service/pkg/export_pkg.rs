@@ -43,6 +43,8 @@ pub async fn export_pkg(
         return Err(PkgError::PackageVersionEmpty);
     }
 
+    semver::Version::parse(request.version.trim()).map_err(PkgError::PackageVersionInvalid)?;
+
     if request.schema_variants.is_empty() {
         return Err(PkgError::PackageExportEmpty);
     }
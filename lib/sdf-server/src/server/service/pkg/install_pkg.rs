@@ -10,7 +10,10 @@ use crate::{
 use axum::extract::OriginalUri;
 use axum::http::uri::Uri;
 use axum::{response::IntoResponse, Json};
-use dal::{pkg::import_pkg_from_pkg, ChangeSet, Visibility, WsEvent};
+use dal::{
+    pkg::{import_pkg_from_pkg, ImportOptions},
+    ChangeSet, Visibility, WsEvent,
+};
 use dal::{DalContext, HistoryActor, User, WorkspacePk};
 use module_index_client::IndexClient;
 use serde::{Deserialize, Serialize};
@@ -134,7 +137,11 @@ async fn install_pkg_inner(
     let (_, svs, _import_skips) = import_pkg_from_pkg(
         ctx,
         &pkg,
-        None, // TODO: add is_builtin option
+        // TODO: add is_builtin option
+        Some(ImportOptions {
+            module_index_client: Some(module_index_client.clone()),
+            ..Default::default()
+        }),
         request.override_builtin_schema_feature_flag,
     )
     .await?;
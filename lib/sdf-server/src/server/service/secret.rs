@@ -14,6 +14,7 @@ use crate::server::state::AppState;
 
 pub mod create_secret;
 pub mod get_public_key;
+pub mod list_secret_usage;
 pub mod list_secrets;
 pub mod update_secret;
 
@@ -74,5 +75,6 @@ pub fn routes() -> Router<AppState> {
         .route("/get_public_key", get(get_public_key::get_public_key))
         .route("/", post(create_secret::create_secret))
         .route("/", get(list_secrets::list_secrets))
+        .route("/usage", get(list_secret_usage::list_secret_usage))
         .route("/", patch(update_secret::update_secret))
 }
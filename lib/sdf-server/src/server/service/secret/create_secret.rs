@@ -2,8 +2,8 @@ use axum::response::IntoResponse;
 use axum::Json;
 use dal::secret::SecretView;
 use dal::{
-    key_pair::KeyPairPk, ChangeSet, EncryptedSecret, SecretAlgorithm, SecretVersion, Visibility,
-    WsEvent,
+    key_pair::KeyPairPk, ChangeSet, EncryptedSecret, SecretAlgorithm, SecretReference,
+    SecretVersion, Visibility, WsEvent,
 };
 use serde::{Deserialize, Serialize};
 
@@ -17,7 +17,11 @@ pub struct CreateSecretRequest {
     pub name: String,
     pub definition: String,
     pub description: Option<String>,
+    #[serde(default)]
     pub crypted: Vec<u8>,
+    /// Where the secret is stored when it is kept in an external backend, in which case `crypted`
+    /// is ignored
+    pub reference: Option<SecretReference>,
     pub key_pair_pk: KeyPairPk,
     pub version: SecretVersion,
     pub algorithm: SecretAlgorithm,
@@ -36,17 +40,32 @@ pub async fn create_secret(
 
     let force_changeset_pk = ChangeSet::force_new(&mut ctx).await?;
 
-    let secret = EncryptedSecret::new(
-        &ctx,
-        request.name,
-        request.definition,
-        request.description,
-        &request.crypted,
-        request.key_pair_pk,
-        request.version,
-        request.algorithm,
-    )
-    .await?;
+    let secret = match &request.reference {
+        Some(reference) => {
+            EncryptedSecret::new_external(
+                &ctx,
+                request.name,
+                request.definition,
+                request.description,
+                reference,
+                request.key_pair_pk,
+            )
+            .await?
+        }
+        None => {
+            EncryptedSecret::new(
+                &ctx,
+                request.name,
+                request.definition,
+                request.description,
+                &request.crypted,
+                request.key_pair_pk,
+                request.version,
+                request.algorithm,
+            )
+            .await?
+        }
+    };
 
     WsEvent::change_set_written(&ctx)
         .await?
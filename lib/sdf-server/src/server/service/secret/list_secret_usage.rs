@@ -0,0 +1,44 @@
+use axum::extract::Query;
+use axum::Json;
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use dal::secret::{SecretUsage, SecretUsageView};
+use dal::{SecretId, Visibility};
+
+use crate::server::extract::{AccessBuilder, HandlerContext};
+
+use super::SecretResult;
+
+const DEFAULT_DAYS: i64 = 30;
+const MAX_DAYS: i64 = 365;
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ListSecretUsageRequest {
+    pub id: SecretId,
+    /// How many days of history to return, 30 by default and at most a year
+    pub days: Option<i64>,
+    #[serde(flatten)]
+    pub visibility: Visibility,
+}
+
+pub type ListSecretUsageResponse = Vec<SecretUsageView>;
+
+pub async fn list_secret_usage(
+    HandlerContext(builder): HandlerContext,
+    AccessBuilder(request_ctx): AccessBuilder,
+    Query(request): Query<ListSecretUsageRequest>,
+) -> SecretResult<Json<ListSecretUsageResponse>> {
+    let ctx = builder.build(request_ctx.build(request.visibility)).await?;
+
+    let since =
+        Utc::now() - Duration::days(request.days.unwrap_or(DEFAULT_DAYS).clamp(0, MAX_DAYS));
+
+    let mut views = vec![];
+    for usage in SecretUsage::list_for_secret(&ctx, request.id, Some(since)).await? {
+        views.push(SecretUsageView::from_usage(&ctx, usage).await?);
+    }
+
+    Ok(Json(views))
+}
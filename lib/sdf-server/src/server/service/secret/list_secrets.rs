@@ -4,7 +4,7 @@ use axum::extract::Query;
 use axum::Json;
 use serde::{Deserialize, Serialize};
 
-use dal::secret::{SecretDefinitionView, SecretView};
+use dal::secret::{SecretDefinitionView, SecretUsage, SecretView};
 use dal::{Secret, StandardModel, Visibility};
 
 use crate::server::extract::{AccessBuilder, HandlerContext};
@@ -51,12 +51,14 @@ pub async fn list_secrets(
         })
         .collect::<HashMap<_, _>>();
 
+    let last_used_at_by_secret = SecretUsage::last_used_at_by_secret(&ctx).await?;
     for secret in Secret::list(&ctx).await? {
+        let last_used_at = last_used_at_by_secret.get(secret.id()).copied();
         hash_map
             .get_mut(secret.definition())
             .ok_or(SecretError::SecretWithInvalidDefinition(*secret.id()))?
             .secrets
-            .push(SecretView::from_secret(&ctx, secret).await?);
+            .push(SecretView::from_secret_last_used_at(&ctx, secret, last_used_at).await?);
     }
 
     Ok(Json(hash_map))
@@ -2,8 +2,8 @@ use axum::response::IntoResponse;
 use axum::Json;
 use dal::secret::SecretView;
 use dal::{
-    key_pair::KeyPairPk, ChangeSet, EncryptedSecret, SecretAlgorithm, SecretVersion, Visibility,
-    WsEvent,
+    key_pair::KeyPairPk, ChangeSet, EncryptedSecret, SecretAlgorithm, SecretReference,
+    SecretVersion, Visibility, WsEvent,
 };
 use dal::{HistoryActor, SecretError, SecretId, StandardModel};
 use serde::{Deserialize, Serialize};
@@ -28,6 +28,8 @@ pub struct UpdateSecretRequest {
     pub name: String,
     pub description: Option<String>,
     pub new_secret_data: Option<NewSecretData>,
+    /// Moves the secret to an external backend, replacing its contents
+    pub new_secret_reference: Option<SecretReference>,
     #[serde(flatten)]
     pub visibility: Visibility,
 }
@@ -66,6 +68,9 @@ pub async fn update_secret(
         secret.set_version(&ctx, new_data.version).await?;
         secret.set_algorithm(&ctx, new_data.algorithm).await?;
     }
+    if let Some(reference) = request.new_secret_reference {
+        secret.set_reference(&ctx, &reference).await?;
+    }
 
     WsEvent::change_set_written(&ctx)
         .await?
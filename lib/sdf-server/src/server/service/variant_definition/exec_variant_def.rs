@@ -198,6 +198,8 @@ pub async fn exec_variant_def(
             )])),
             no_record: true,
             is_builtin: false,
+            trusted_signing_keys: None,
+            module_index_client: None,
         }),
         request.override_builtin_schema_feature_flag,
     )
@@ -199,6 +199,37 @@ impl SymmetricCryptoService {
         secretbox::open(ciphertext, nonce, &key.0)
             .map_err(|_| SymmetricCryptoError::DecryptionFailed)
     }
+
+    /// Decrypts a ciphertext encrypted with any loaded [`SymmetricKey`] and encrypts the message
+    /// again with the active key, returning the new crypted bytes, nonce, and [`Hash`] of the
+    /// active key.
+    ///
+    /// This is how data is moved off of a key which is being retired.
+    ///
+    /// # Errors
+    ///
+    /// Return `Err` if the ciphertext could not be decrypted, for the same reasons as
+    /// [`decrypt`](Self::decrypt).
+    pub fn reencrypt(
+        &self,
+        ciphertext: &[u8],
+        nonce: &SymmetricNonce,
+        key_hash: &Hash,
+    ) -> SymmetricCryptoResult<(Vec<u8>, SymmetricNonce, &Hash)> {
+        let message = self.decrypt(ciphertext, nonce, key_hash)?;
+
+        Ok(self.encrypt(&message))
+    }
+
+    /// Returns the [`Hash`] of the active key, used for all encryption.
+    pub fn active_key_hash(&self) -> &Hash {
+        self.active_key_hash.as_ref()
+    }
+
+    /// Returns the [`Hash`]es of all loaded keys, including the active key.
+    pub fn key_hashes(&self) -> impl Iterator<Item = &Hash> {
+        self.keys.keys()
+    }
 }
 
 /// A symmetric encryption key (i.e. a key which can encrypt *and* decrypt data).
@@ -333,6 +364,34 @@ mod tests {
         assert_eq!(message.as_slice(), decrypted);
     }
 
+    #[test]
+    fn reencrypt_with_active_key() {
+        let old_key = SymmetricCryptoService::generate_key();
+        let old_service = SymmetricCryptoService::new(old_key.clone(), vec![]);
+
+        let message = b"Keep your friends close, but your enemies closer.";
+
+        let (ciphertext, nonce, old_key_hash) = old_service.encrypt(message);
+
+        let new_key = SymmetricCryptoService::generate_key();
+        let new_service = SymmetricCryptoService::new(new_key.clone(), vec![old_key]);
+
+        let (ciphertext, nonce, key_hash) = new_service
+            .reencrypt(ciphertext.as_ref(), &nonce, old_key_hash)
+            .expect("Should be able to reencrypt");
+
+        assert_eq!(new_service.active_key_hash(), key_hash);
+        assert_ne!(old_key_hash, key_hash);
+
+        // The old key is no longer needed to read the message
+        let retired_service = SymmetricCryptoService::new(new_key, vec![]);
+        let decrypted = retired_service
+            .decrypt(ciphertext.as_ref(), &nonce, key_hash)
+            .expect("Should be able to decrypt");
+
+        assert_eq!(message.as_slice(), decrypted);
+    }
+
     #[test]
     fn missing_key() {
         let old_key = SymmetricCryptoService::generate_key();
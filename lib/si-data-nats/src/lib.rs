@@ -23,7 +23,7 @@ pub mod service;
 mod subscriber;
 
 pub use async_nats::{
-    connection::State, header, header::HeaderMap, rustls, status, subject, Auth, AuthError,
+    connection::State, header, header::HeaderMap, rustls, status, subject, Auth, AuthError, Event,
     HeaderName, HeaderValue, ServerAddr, ServerInfo, Subject,
 };
 pub use connect_options::ConnectOptions;
@@ -106,8 +106,18 @@ pub struct Client {
 impl Client {
     #[instrument(name = "client::new", skip_all, level = "debug")]
     pub async fn new(config: &NatsConfig) -> Result<Self> {
-        let mut options = ConnectOptions::default();
+        Self::new_with_options(config, ConnectOptions::default()).await
+    }
 
+    /// Connects using the given config, layered on top of caller-provided [`ConnectOptions`].
+    ///
+    /// This is useful for callers which need options the config doesn't expose, such as an
+    /// [`event_callback`](ConnectOptions::event_callback) to observe reconnects.
+    #[instrument(name = "client::new_with_options", skip_all, level = "debug")]
+    pub async fn new_with_options(
+        config: &NatsConfig,
+        mut options: ConnectOptions,
+    ) -> Result<Self> {
         if let Some(creds) = &config.creds {
             options = options.credentials(creds)?;
         }
@@ -0,0 +1,20 @@
+use std::env::args;
+use tokio::fs;
+
+use si_pkg::SiPkg;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = args();
+    let input = args.nth(1).expect("usage: program <PKG_DIR> <TARBALL>");
+    let tar_file = args.next().expect("usage: program <PKG_DIR> <TARBALL>");
+
+    println!("--- Reading pkg from directory: {input}");
+    let pkg = SiPkg::load_from_dir(&input).await?;
+
+    println!("--- Writing pkg to: {tar_file}");
+    fs::write(&tar_file, pkg.write_to_bytes()?).await?;
+
+    println!("--- Done.");
+    Ok(())
+}
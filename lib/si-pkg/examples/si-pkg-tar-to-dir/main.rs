@@ -0,0 +1,19 @@
+use std::env::args;
+
+use si_pkg::SiPkg;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = args();
+    let tar_file = args.nth(1).expect("usage: program <TARBALL> <PKG_DIR>");
+    let dst = args.next().expect("usage: program <TARBALL> <PKG_DIR>");
+
+    println!("--- Reading pkg from file: {tar_file}");
+    let pkg = SiPkg::load_from_file(&tar_file).await?;
+
+    println!("--- Writing pkg to directory: {dst}");
+    pkg.write_to_dir(&dst).await?;
+
+    println!("--- Done.");
+    Ok(())
+}
@@ -7,6 +7,7 @@ pub use spec::*;
 
 #[cfg(test)]
 mod tests {
+    use object_tree::{DirectoryObjectStore, ObjectStore};
     use petgraph::dot::Dot;
     use tokio::sync::Mutex;
 
@@ -70,6 +71,115 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn pkg_dir_round_trip() {
+        let spec: PkgSpec = serde_json::from_str(WORKSPACE_JSON).unwrap();
+        let pkg = SiPkg::load_from_spec(spec).expect("failed to load spec");
+        let dir = tempfile::tempdir().expect("failed to create tempdir");
+
+        pkg.write_to_dir(dir.path())
+            .await
+            .expect("failed to write pkg to dir");
+        let read_pkg = SiPkg::load_from_dir(dir.path())
+            .await
+            .expect("failed to load pkg from dir");
+
+        assert_eq!(
+            pkg.hash().expect("get hash"),
+            read_pkg.hash().expect("get read hash")
+        );
+    }
+
+    #[tokio::test]
+    async fn pkg_store_thin_round_trip() {
+        let spec: PkgSpec = serde_json::from_str(PACKAGE_JSON).unwrap();
+        let pkg = SiPkg::load_from_spec(spec.clone()).expect("failed to load spec");
+        let dir = tempfile::tempdir().expect("failed to create tempdir");
+        let store = DirectoryObjectStore::new(dir.path());
+
+        let stored = pkg
+            .write_to_store(&store)
+            .expect("failed to write pkg to store");
+        assert!(stored > 0);
+        assert_eq!(
+            0,
+            pkg.write_to_store(&store)
+                .expect("failed to write pkg to store again")
+        );
+
+        let mut next_spec = spec;
+        next_spec.version = "2.0.0".to_owned();
+        let next_pkg = SiPkg::load_from_spec(next_spec).expect("failed to load next spec");
+
+        let have = store
+            .have(&next_pkg.node_hashes())
+            .expect("failed to check store");
+        let thin = next_pkg
+            .write_thin_to_bytes(&have)
+            .expect("failed to write thin bundle");
+        let full = next_pkg
+            .write_to_bytes()
+            .expect("failed to write full bundle");
+        assert!(thin.len() < full.len());
+
+        let read_pkg =
+            SiPkg::load_from_thin_bytes(thin.clone(), &store).expect("failed to load thin bundle");
+        assert_eq!(
+            next_pkg.hash().expect("get hash"),
+            read_pkg.hash().expect("get read hash")
+        );
+
+        let stored_pkg = SiPkg::load_from_store(&store, read_pkg.hash().expect("get read hash"))
+            .expect("failed to load pkg from store");
+        assert_eq!(
+            "2.0.0",
+            stored_pkg.metadata().expect("get metadata").version()
+        );
+
+        // A receiver without the shared nodes can't complete the package
+        let empty_dir = tempfile::tempdir().expect("failed to create tempdir");
+        let empty_store = DirectoryObjectStore::new(empty_dir.path());
+        assert!(SiPkg::load_from_thin_bytes(thin, &empty_store).is_err());
+    }
+
+    #[tokio::test]
+    async fn pkg_stream_round_trip() {
+        let spec: PkgSpec = serde_json::from_str(PACKAGE_JSON).unwrap();
+        let pkg = SiPkg::load_from_spec(spec).expect("failed to load spec");
+        let pkg_data = pkg.write_to_bytes().expect("failed to serialize pkg");
+        let dir = tempfile::tempdir().expect("failed to create tempdir");
+        let store = DirectoryObjectStore::new(dir.path());
+
+        let metadata = SiPkg::import_from_stream(pkg_data.as_slice(), &store)
+            .await
+            .expect("failed to import pkg from stream");
+        assert_eq!(pkg.hash().expect("get hash"), metadata.hash());
+        assert_eq!("complex", metadata.name());
+
+        let no_schemas = SiPkg::load_from_store_with_schemas(&store, metadata.hash(), &[])
+            .expect("failed to load pkg without schemas");
+        assert!(no_schemas.schemas().expect("get schemas").is_empty());
+        assert_eq!(2, no_schemas.funcs().expect("get funcs").len());
+
+        let one_schema =
+            SiPkg::load_from_store_with_schemas(&store, metadata.hash(), &["k8sDeployment"])
+                .expect("failed to load pkg with schema");
+        assert_eq!(1, one_schema.schemas().expect("get schemas").len());
+
+        // A node which doesn't match its hash is refused
+        let mut tampered = pkg_data;
+        let at = tampered
+            .windows(b"si:truthy".len())
+            .position(|window| window == b"si:truthy")
+            .expect("func name in bundle");
+        tampered[at] = b'S';
+        let other_dir = tempfile::tempdir().expect("failed to create tempdir");
+        let other_store = DirectoryObjectStore::new(other_dir.path());
+        assert!(SiPkg::import_from_stream(tampered.as_slice(), &other_store)
+            .await
+            .is_err());
+    }
+
     #[tokio::test]
     async fn pkg_bytes_round_trip() {
         let spec: PkgSpec = serde_json::from_str(PACKAGE_JSON).unwrap();
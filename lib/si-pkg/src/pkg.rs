@@ -1,15 +1,22 @@
 use core::fmt;
-use std::{collections::HashMap, convert::Infallible, path::Path, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::Infallible,
+    path::Path,
+    sync::Arc,
+};
 
 use chrono::{DateTime, Utc};
 use object_tree::{
-    GraphError, Hash, HashedNode, NameStr, NodeChild, ObjectTree, TarReadError, TarWriter,
-    TarWriterError,
+    GraphError, Hash, HashedNode, NameStr, NodeChild, ObjectStore, ObjectStoreError, ObjectTree,
+    TarReadError, TarStreamError, TarStreamReader, TarWriter, TarWriterError,
 };
 use petgraph::prelude::*;
+use semver::Version;
 use serde::{Deserialize, Serialize};
 use strum::{AsRefStr, Display, EnumIter, EnumString};
 use thiserror::Error;
+use tokio::io::AsyncRead;
 
 mod action_func;
 mod attr_func_input;
@@ -17,6 +24,8 @@ mod attribute_value;
 mod auth_func;
 mod change_set;
 mod component;
+mod dependency;
+mod diff;
 mod edge;
 mod func;
 mod leaf_function;
@@ -26,18 +35,20 @@ mod prop;
 mod root_prop_func;
 mod schema;
 mod si_prop_func;
+mod signature;
 mod socket;
 mod variant;
 
 pub use {
     action_func::*, attr_func_input::*, attribute_value::*, auth_func::*, change_set::*,
-    component::*, edge::*, func::*, leaf_function::*, map_key_func::*, position::*, prop::*,
-    root_prop_func::*, schema::*, si_prop_func::*, socket::*, variant::*,
+    component::*, dependency::*, diff::*, edge::*, func::*, leaf_function::*, map_key_func::*,
+    position::*, prop::*, root_prop_func::*, schema::*, si_prop_func::*, signature::*, socket::*,
+    variant::*,
 };
 
 use crate::{
     node::{CategoryNode, PkgNode},
-    spec::{FuncSpec, PkgSpec, SchemaVariantSpecPropRoot, SpecError},
+    spec::{DependencySpec, FuncSpec, PkgSpec, SchemaVariantSpecPropRoot, SpecError},
 };
 
 #[remain::sorted]
@@ -47,6 +58,10 @@ pub enum SiPkgError {
     ComponentMissingPosition(String),
     #[error(transparent)]
     Graph(#[from] GraphError),
+    #[error("invalid version {0:?}, versions must follow semantic versioning: {1}")]
+    InvalidVersion(String, #[source] semver::Error),
+    #[error("invalid version requirement {0:?}: {1}")]
+    InvalidVersionReq(String, #[source] semver::Error),
     #[error(transparent)]
     Io(#[from] std::io::Error),
     #[error(transparent)]
@@ -55,6 +70,8 @@ pub enum SiPkgError {
     NodeWithHashNotFound(Hash),
     #[error("node not found with name={0}")]
     NodeWithNameNotFound(String),
+    #[error(transparent)]
+    ObjectStore(#[from] ObjectStoreError),
     #[error("found multiple pkg node domain props for variant with hash={0}")]
     PropRootMultipleFound(SchemaVariantSpecPropRoot, Hash),
     #[error("could not find pkg node root prop {0} for variant with hash={1}")]
@@ -65,12 +82,24 @@ pub enum SiPkgError {
     SchemaVariantChildNotFound(&'static str),
     #[error(transparent)]
     SerdeJson(#[from] serde_json::Error),
+    #[error("signature by {0} (key {1}) does not match the package")]
+    SignatureInvalid(String, Hash),
+    #[error("failed to parse package signature: {0}")]
+    SignatureParse(String),
+    #[error("failed to parse package signing key: {0}")]
+    SigningKeyParse(String),
     #[error(transparent)]
     Spec(#[from] SpecError),
     #[error(transparent)]
     TarRead(#[from] TarReadError),
+    #[error(transparent)]
+    TarStream(#[from] TarStreamError),
     #[error("unexpected pkg node type; expected={0}, actual={1}")]
     UnexpectedPkgNodeType(&'static str, &'static str),
+    #[error("package is not signed")]
+    Unsigned,
+    #[error("package is not signed by a trusted key; signed by: {0:?}")]
+    UntrustedSigners(Vec<String>),
     #[error("Validation spec missing required field: {0}")]
     ValidationMissingField(String),
     #[error("error while visiting prop: {0}")]
@@ -100,6 +129,7 @@ impl From<Infallible> for SiPkgError {
 #[derive(Clone, Debug)]
 pub struct SiPkg {
     tree: Arc<ObjectTree<PkgNode>>,
+    signatures: Vec<SiPkgSignature>,
 }
 
 impl SiPkg {
@@ -109,10 +139,81 @@ impl SiPkg {
     }
 
     pub fn load_from_bytes(bytes: Vec<u8>) -> PkgResult<Self> {
-        let tree: ObjectTree<PkgNode> = ObjectTree::<PkgNode>::read_from_tar(bytes)?;
+        let (tree, detached): (ObjectTree<PkgNode>, _) =
+            ObjectTree::<PkgNode>::read_from_tar_with_detached(bytes)?;
+
+        Self::from_tree_with_detached(tree, detached)
+    }
+
+    /// Loads a package from a "thin" bundle written by [`SiPkg::write_thin_to_bytes`], taking the
+    /// nodes left out of it from `store`. The nodes which came with the bundle are added to the
+    /// store.
+    pub fn load_from_thin_bytes(bytes: Vec<u8>, store: &impl ObjectStore) -> PkgResult<Self> {
+        let (tree, detached): (ObjectTree<PkgNode>, _) =
+            ObjectTree::<PkgNode>::import_thin_tar_with_detached(bytes, store)?;
+
+        Self::from_tree_with_detached(tree, detached)
+    }
+
+    /// Loads the package with the given root hash from `store`. Signatures aren't kept in a store,
+    /// so the package is unsigned.
+    pub fn load_from_store(store: &impl ObjectStore, root_hash: Hash) -> PkgResult<Self> {
+        let tree = ObjectTree::<PkgNode>::read_from_store(store, root_hash)?;
+
+        Ok(Self {
+            tree: Arc::new(tree),
+            signatures: vec![],
+        })
+    }
+
+    /// Streams a package bundle into `store` without holding the whole package in memory,
+    /// verifying every node as it is read, and returns the package's metadata.
+    ///
+    /// The package can then be read from the store in full with [`SiPkg::load_from_store`], or
+    /// in part with [`SiPkg::load_from_store_with_schemas`].
+    pub async fn import_from_stream(
+        reader: impl AsyncRead + Unpin,
+        store: &(impl ObjectStore + Clone + Send + 'static),
+    ) -> PkgResult<SiPkgMetadata> {
+        let streamed = TarStreamReader::new(reader)
+            .import::<PkgNode, _>(store)
+            .await?;
+
+        let mut metadata = SiPkgMetadata::load_from_store(store, streamed.root_hash())?;
+        metadata.signatures = signatures_from_detached(streamed.into_detached())?;
+
+        Ok(metadata)
+    }
+
+    /// Loads the package with the given root hash from `store`, leaving out every schema not
+    /// named in `schema_names` without reading it. Signatures aren't kept in a store, so the
+    /// package is unsigned.
+    pub fn load_from_store_with_schemas(
+        store: &impl ObjectStore,
+        root_hash: Hash,
+        schema_names: &[&str],
+    ) -> PkgResult<Self> {
+        let tree =
+            ObjectTree::<PkgNode>::read_from_store_filtered(store, root_hash, |path| match path {
+                [category, schema_name] if *category == CategoryNode::Schemas.name() => {
+                    schema_names.contains(schema_name)
+                }
+                _ => true,
+            })?;
+
+        Ok(Self {
+            tree: Arc::new(tree),
+            signatures: vec![],
+        })
+    }
 
+    fn from_tree_with_detached(
+        tree: ObjectTree<PkgNode>,
+        detached: BTreeMap<String, Vec<u8>>,
+    ) -> PkgResult<Self> {
         Ok(Self {
             tree: Arc::new(tree),
+            signatures: signatures_from_detached(detached)?,
         })
     }
 
@@ -126,17 +227,123 @@ impl SiPkg {
 
         Ok(Self {
             tree: Arc::new(tree),
+            signatures: vec![],
         })
     }
 
+    /// Loads a package from a directory in the layout written by [`SiPkg::write_to_dir`].
+    pub async fn load_from_dir(path: impl AsRef<Path>) -> PkgResult<Self> {
+        Self::load_from_spec(PkgSpec::load_from_dir(path).await?)
+    }
+
+    /// Writes the package out as a human editable directory, see [`PkgSpec::write_to_dir`].
+    /// Signatures are not written, since the package has to be signed again once edited.
+    pub async fn write_to_dir(&self, path: impl AsRef<Path>) -> PkgResult<()> {
+        Ok(self.to_spec().await?.write_to_dir(path).await?)
+    }
+
     pub fn write_to_bytes(&self) -> PkgResult<Vec<u8>> {
-        Ok(TarWriter::new(&self.tree)?.bytes())
+        let mut detached = Vec::with_capacity(self.signatures.len());
+        for signature in &self.signatures {
+            detached.push((signature.entry_name(), signature.to_entry()?));
+        }
+
+        Ok(TarWriter::new_with_detached(&self.tree, detached)?.bytes())
+    }
+
+    /// Writes the package as a "thin" bundle, leaving out every node whose hash is in `have`
+    /// along with its descendants. The receiver answers which of the package's
+    /// [`node_hashes`](SiPkg::node_hashes) it already has, typically with [`ObjectStore::have`].
+    pub fn write_thin_to_bytes(&self, have: &HashSet<Hash>) -> PkgResult<Vec<u8>> {
+        let mut detached = Vec::with_capacity(self.signatures.len());
+        for signature in &self.signatures {
+            detached.push((signature.entry_name(), signature.to_entry()?));
+        }
+
+        Ok(TarWriter::new_thin_with_detached(&self.tree, have, detached)?.bytes())
+    }
+
+    /// Adds the nodes of the package which `store` doesn't already have to it, returning how many
+    /// were added. Signatures aren't kept in a store.
+    pub fn write_to_store(&self, store: &impl ObjectStore) -> PkgResult<usize> {
+        Ok(self.tree.write_to_store(store)?)
+    }
+
+    /// The hashes of every node in the package.
+    pub fn node_hashes(&self) -> HashSet<Hash> {
+        self.tree.node_hashes()
     }
 
     pub fn metadata(&self) -> PkgResult<SiPkgMetadata> {
         let (graph, root_idx) = self.as_petgraph();
 
-        SiPkgMetadata::from_graph(graph, root_idx)
+        let mut metadata = SiPkgMetadata::from_graph(graph, root_idx)?;
+        metadata.signatures = self.signatures.clone();
+
+        Ok(metadata)
+    }
+
+    /// Signs the package's root hash, replacing any earlier signature made with the same key.
+    ///
+    /// Signatures are stored next to the package contents rather than in them, so signing leaves
+    /// the package's hash unchanged.
+    pub fn sign(&mut self, signer: impl Into<String>, key: &PkgSigningKey) -> PkgResult<()> {
+        let signature = SiPkgSignature::new(signer, key, self.hash()?);
+        self.signatures
+            .retain(|existing| existing.key_id() != signature.key_id());
+        self.signatures.push(signature);
+
+        Ok(())
+    }
+
+    pub fn signatures(&self) -> &[SiPkgSignature] {
+        &self.signatures
+    }
+
+    /// Checks that every signature on the package was made over its contents.
+    ///
+    /// An unsigned package passes, as there's nothing to contradict. Use
+    /// [`SiPkg::verify_trusted`] to require a signature.
+    pub fn verify(&self) -> PkgResult<()> {
+        let root_hash = self.hash()?;
+        match self
+            .signatures
+            .iter()
+            .find(|signature| !signature.is_valid_for(root_hash))
+        {
+            Some(invalid) => Err(SiPkgError::SignatureInvalid(
+                invalid.signer().to_string(),
+                invalid.key_id(),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that the package is signed by at least one of the trusted keys and that none of its
+    /// signatures are invalid, returning the first trusted signature.
+    pub fn verify_trusted(&self, trusted_keys: &[PkgVerifyingKey]) -> PkgResult<&SiPkgSignature> {
+        self.verify()?;
+
+        if self.signatures.is_empty() {
+            return Err(SiPkgError::Unsigned);
+        }
+
+        self.signatures
+            .iter()
+            .find(|signature| trusted_keys.contains(&signature.verifying_key()))
+            .ok_or_else(|| {
+                SiPkgError::UntrustedSigners(
+                    self.signatures
+                        .iter()
+                        .map(|signature| signature.signer().to_string())
+                        .collect(),
+                )
+            })
+    }
+
+    /// Returns the changes from this package to a newer version of it.
+    pub fn diff(&self, new: &SiPkg) -> PkgResult<SiPkgDiff> {
+        SiPkgDiff::new(self, new)
     }
 
     pub fn hash(&self) -> PkgResult<Hash> {
@@ -178,6 +385,19 @@ impl SiPkg {
         Ok(schemas)
     }
 
+    /// The other modules this package needs installed before it can be.
+    pub fn dependencies(&self) -> PkgResult<Vec<SiPkgDependency>> {
+        let (graph, root_idx) = self.as_petgraph();
+
+        let node_idxs = category_node_idxs(CategoryNode::Dependencies, graph, root_idx)?;
+        let mut dependencies = Vec::with_capacity(node_idxs.len());
+        for node_idx in node_idxs {
+            dependencies.push(SiPkgDependency::from_graph(graph, node_idx)?);
+        }
+
+        Ok(dependencies)
+    }
+
     pub fn change_sets(&self) -> PkgResult<Vec<SiPkgChangeSet>> {
         let (graph, root_idx) = self.as_petgraph();
 
@@ -241,6 +461,10 @@ impl SiPkg {
             builder.schema(schema.to_spec().await?);
         }
 
+        for dependency in self.dependencies()? {
+            builder.dependency(DependencySpec::try_from(dependency)?);
+        }
+
         if let SiPkgKind::WorkspaceBackup = metadata.kind() {
             if let Some(default_change_set) = metadata.default_change_set() {
                 builder.default_change_set(default_change_set);
@@ -280,6 +504,17 @@ fn idx_for_hash(
     Ok(node_idx)
 }
 
+fn signatures_from_detached(detached: BTreeMap<String, Vec<u8>>) -> PkgResult<Vec<SiPkgSignature>> {
+    let mut signatures = Vec::new();
+    for (name, entry) in detached {
+        if name.starts_with(SIGNATURE_ENTRY_PREFIX) {
+            signatures.push(SiPkgSignature::from_entry(&entry)?);
+        }
+    }
+
+    Ok(signatures)
+}
+
 fn category_node_idxs(
     category_node: CategoryNode,
     graph: &Graph<HashedNode<PkgNode>, ()>,
@@ -365,11 +600,24 @@ pub struct SiPkgMetadata {
     workspace_pk: Option<String>,
     workspace_name: Option<String>,
     hash: Hash,
+    signatures: Vec<SiPkgSignature>,
 }
 
 impl SiPkgMetadata {
+    /// Loads the metadata of the package with the given root hash from `store`, reading only its
+    /// root node. Signatures aren't kept in a store, so none are included.
+    pub fn load_from_store(store: &impl ObjectStore, root_hash: Hash) -> PkgResult<Self> {
+        let root_node = ObjectTree::<PkgNode>::read_node_from_store(store, root_hash)?
+            .ok_or(SiPkgError::NodeWithHashNotFound(root_hash))?;
+
+        Self::from_hashed_node(root_node.node())
+    }
+
     fn from_graph(graph: &Graph<HashedNode<PkgNode>, ()>, node_idx: NodeIndex) -> PkgResult<Self> {
-        let metadata_hashed_node = &graph[node_idx];
+        Self::from_hashed_node(&graph[node_idx])
+    }
+
+    fn from_hashed_node(metadata_hashed_node: &HashedNode<PkgNode>) -> PkgResult<Self> {
         let metadata_node = match metadata_hashed_node.inner() {
             PkgNode::Package(node) => node.clone(),
             unexpected => {
@@ -391,6 +639,7 @@ impl SiPkgMetadata {
             workspace_pk: metadata_node.workspace_pk,
             workspace_name: metadata_node.workspace_name,
             hash: metadata_hashed_node.hash(),
+            signatures: vec![],
         })
     }
 
@@ -406,6 +655,15 @@ impl SiPkgMetadata {
         self.version.as_ref()
     }
 
+    /// Parses the version as a semantic version.
+    ///
+    /// Packages made before versions were validated may have free-form versions, which return an
+    /// error here.
+    pub fn semver(&self) -> PkgResult<Version> {
+        Version::parse(self.version.trim())
+            .map_err(|err| SiPkgError::InvalidVersion(self.version.clone(), err))
+    }
+
     pub fn description(&self) -> &str {
         self.description.as_ref()
     }
@@ -433,4 +691,14 @@ impl SiPkgMetadata {
     pub fn hash(&self) -> Hash {
         self.hash
     }
+
+    /// The signatures on the package, which haven't necessarily been verified.
+    pub fn signatures(&self) -> &[SiPkgSignature] {
+        &self.signatures
+    }
+
+    /// The names of everyone who signed the package, as they gave them.
+    pub fn signers(&self) -> impl Iterator<Item = &str> {
+        self.signatures.iter().map(|signature| signature.signer())
+    }
 }
@@ -0,0 +1,127 @@
+use object_tree::{Hash, HashedNode};
+use petgraph::prelude::*;
+use semver::{Version, VersionReq};
+
+use super::{PkgResult, SiPkgError, Source};
+
+use crate::{node::PkgNode, DependencySpec};
+
+#[derive(Clone, Debug)]
+pub struct SiPkgDependency<'a> {
+    name: String,
+    version_req: VersionReq,
+
+    hash: Hash,
+    source: Source<'a>,
+}
+
+impl<'a> SiPkgDependency<'a> {
+    pub fn from_graph(
+        graph: &'a Graph<HashedNode<PkgNode>, ()>,
+        node_idx: NodeIndex,
+    ) -> PkgResult<Self> {
+        let hashed_node = &graph[node_idx];
+        let node = match hashed_node.inner() {
+            PkgNode::Dependency(node) => node.clone(),
+            unexpected => {
+                return Err(SiPkgError::UnexpectedPkgNodeType(
+                    PkgNode::DEPENDENCY_KIND_STR,
+                    unexpected.node_kind_str(),
+                ))
+            }
+        };
+
+        let version_req = VersionReq::parse(&node.version_req)
+            .map_err(|err| SiPkgError::InvalidVersionReq(node.version_req, err))?;
+
+        Ok(Self {
+            name: node.name,
+            version_req,
+
+            hash: hashed_node.hash(),
+            source: Source::new(graph, node_idx),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn version_req(&self) -> &VersionReq {
+        &self.version_req
+    }
+
+    /// Returns whether the given version of the module satisfies this dependency.
+    pub fn matches(&self, version: &Version) -> bool {
+        self.version_req.matches(version)
+    }
+
+    pub fn hash(&self) -> Hash {
+        self.hash
+    }
+
+    pub fn source(&self) -> &Source<'a> {
+        &self.source
+    }
+}
+
+impl<'a> TryFrom<SiPkgDependency<'a>> for DependencySpec {
+    type Error = SiPkgError;
+
+    fn try_from(value: SiPkgDependency<'a>) -> Result<Self, Self::Error> {
+        Ok(DependencySpec::builder()
+            .name(value.name())
+            .version_req(value.version_req().to_string())
+            .build()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{PkgSpec, SiPkg, SpecError};
+
+    use super::*;
+
+    const PACKAGE_JSON: &str = include_str!("../../pkg-complex.json");
+
+    #[test]
+    fn dependencies_survive_a_round_trip() {
+        let mut spec: PkgSpec = serde_json::from_str(PACKAGE_JSON).expect("failed to parse spec");
+        let hash_without_dependencies = SiPkg::load_from_spec(spec.clone())
+            .expect("failed to load spec")
+            .hash()
+            .expect("failed to hash pkg");
+        spec.dependencies.push(
+            DependencySpec::builder()
+                .name("aws-funcs")
+                .version_req("^1.2")
+                .build()
+                .expect("failed to build dependency"),
+        );
+
+        let pkg = SiPkg::load_from_spec(spec).expect("failed to load spec");
+        assert_ne!(
+            hash_without_dependencies,
+            pkg.hash().expect("failed to hash pkg")
+        );
+
+        let bytes = pkg.write_to_bytes().expect("failed to serialize pkg");
+        let read_pkg = SiPkg::load_from_bytes(bytes).expect("failed to load pkg from bytes");
+        let dependencies = read_pkg.dependencies().expect("failed to get dependencies");
+
+        assert_eq!(1, dependencies.len());
+        assert_eq!("aws-funcs", dependencies[0].name());
+        assert!(dependencies[0].matches(&Version::new(1, 4, 0)));
+        assert!(!dependencies[0].matches(&Version::new(2, 0, 0)));
+    }
+
+    #[test]
+    fn refuses_invalid_version_requirements() {
+        let result = DependencySpec::builder()
+            .name("aws-funcs")
+            .version_req("latest")
+            .build();
+
+        assert!(matches!(result, Err(SpecError::ValidationError(_))));
+    }
+}
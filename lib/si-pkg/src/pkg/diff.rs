@@ -0,0 +1,658 @@
+//! Structural diffs between two versions of a package.
+//!
+//! Both object trees are walked together from their roots, and any pair of subtrees whose hashes
+//! match is skipped without looking inside, so comparing two mostly identical packages only
+//! visits the parts that changed. Changes are reported against the schemas, variants, props,
+//! funcs and sockets they belong to, and changes to any other kind of node (func arguments,
+//! attribute function inputs, leaf functions, etc.) are folded into the nearest of those above
+//! it as field changes.
+
+use std::{fmt, iter};
+
+use base64::{engine::general_purpose, Engine};
+use object_tree::{GraphError, HashedNode, NameStr, WriteBytes};
+use petgraph::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum::{AsRefStr, Display};
+
+use super::{PkgResult, SiPkg};
+use crate::node::PkgNode;
+
+const KEY_NODE_KIND_STR: &str = "node_kind";
+const KEY_CODE_STR: &str = "code_base64";
+const FIELD_CODE_STR: &str = "code";
+
+type PkgGraph = Graph<HashedNode<PkgNode>, ()>;
+
+#[remain::sorted]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, AsRefStr, Display)]
+#[serde(rename_all = "camelCase")]
+#[strum(serialize_all = "camelCase")]
+pub enum SiPkgChangeKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+/// The kinds of package contents that changes are reported against.
+#[remain::sorted]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, AsRefStr, Display)]
+#[serde(rename_all = "camelCase")]
+#[strum(serialize_all = "camelCase")]
+pub enum SiPkgDiffEntity {
+    Func,
+    Package,
+    Prop,
+    Schema,
+    SchemaVariant,
+    Socket,
+}
+
+impl SiPkgDiffEntity {
+    fn for_node(node: &PkgNode) -> Option<Self> {
+        match node {
+            PkgNode::Func(_) => Some(Self::Func),
+            PkgNode::Package(_) => Some(Self::Package),
+            PkgNode::Prop(_) => Some(Self::Prop),
+            PkgNode::Schema(_) => Some(Self::Schema),
+            PkgNode::SchemaVariant(_) => Some(Self::SchemaVariant),
+            PkgNode::Socket(_) => Some(Self::Socket),
+            _ => None,
+        }
+    }
+}
+
+/// A change to a single field of an entity or of one of the nodes folded into it.
+///
+/// Fields of folded nodes are named by the path of nodes below the entity, such as
+/// `func_argument[value].kind`.
+#[remain::sorted]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum SiPkgFieldChange {
+    /// A line based diff of function code, each line prefixed with `-`, `+` or a space.
+    Code { field: String, lines: Vec<String> },
+    /// A field which was added (no `old`), removed (no `new`), or changed.
+    Value {
+        field: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+}
+
+impl SiPkgFieldChange {
+    pub fn field(&self) -> &str {
+        match self {
+            Self::Code { field, .. } | Self::Value { field, .. } => field,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiPkgChange {
+    kind: SiPkgChangeKind,
+    entity: SiPkgDiffEntity,
+    /// The names of the entity and of every entity above it, outermost first. This is empty for
+    /// the package itself.
+    path: Vec<String>,
+    /// Only modifications carry field changes.
+    fields: Vec<SiPkgFieldChange>,
+}
+
+impl SiPkgChange {
+    pub fn kind(&self) -> SiPkgChangeKind {
+        self.kind
+    }
+
+    pub fn entity(&self) -> SiPkgDiffEntity {
+        self.entity
+    }
+
+    pub fn path(&self) -> &[String] {
+        &self.path
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.path.last().map(String::as_str)
+    }
+
+    pub fn fields(&self) -> &[SiPkgFieldChange] {
+        &self.fields
+    }
+}
+
+/// The changes between an older and a newer version of a package.
+///
+/// The [`fmt::Display`] implementation renders a human readable summary, and the serialized form
+/// is meant for UIs.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiPkgDiff {
+    changes: Vec<SiPkgChange>,
+}
+
+impl SiPkgDiff {
+    pub fn new(old: &SiPkg, new: &SiPkg) -> PkgResult<Self> {
+        let (old_graph, old_root_idx) = old.as_petgraph();
+        let (new_graph, new_root_idx) = new.as_petgraph();
+
+        let mut differ = Differ {
+            old: old_graph,
+            new: new_graph,
+            changes: vec![],
+        };
+        differ.diff_entity(SiPkgDiffEntity::Package, vec![], old_root_idx, new_root_idx)?;
+
+        Ok(Self {
+            changes: differ.changes,
+        })
+    }
+
+    pub fn changes(&self) -> &[SiPkgChange] {
+        &self.changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for SiPkgDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.changes.is_empty() {
+            return writeln!(f, "no changes");
+        }
+
+        for change in &self.changes {
+            let sigil = match change.kind {
+                SiPkgChangeKind::Added => '+',
+                SiPkgChangeKind::Modified => '~',
+                SiPkgChangeKind::Removed => '-',
+            };
+            write!(f, "{sigil} {}", change.entity)?;
+            if !change.path.is_empty() {
+                write!(f, " {}", change.path.join(" / "))?;
+            }
+            writeln!(f)?;
+
+            for field in &change.fields {
+                match field {
+                    SiPkgFieldChange::Code { field, lines } => {
+                        writeln!(f, "    {field}:")?;
+                        for line in lines {
+                            writeln!(f, "        {line}")?;
+                        }
+                    }
+                    SiPkgFieldChange::Value { field, old, new } => {
+                        writeln!(f, "    {field}: {} -> {}", Quoted(old), Quoted(new))?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct Quoted<'a>(&'a Option<String>);
+
+impl<'a> fmt::Display for Quoted<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(value) => write!(f, "{value:?}"),
+            None => f.write_str("(none)"),
+        }
+    }
+}
+
+/// The entity currently being compared, which collects the field changes of the nodes folded
+/// into it.
+struct Owner {
+    path: Vec<String>,
+    fields: Vec<SiPkgFieldChange>,
+}
+
+struct Differ<'a> {
+    old: &'a PkgGraph,
+    new: &'a PkgGraph,
+    changes: Vec<SiPkgChange>,
+}
+
+impl<'a> Differ<'a> {
+    fn diff_entity(
+        &mut self,
+        entity: SiPkgDiffEntity,
+        path: Vec<String>,
+        old_idx: NodeIndex,
+        new_idx: NodeIndex,
+    ) -> PkgResult<()> {
+        if self.old[old_idx].hash() == self.new[new_idx].hash() {
+            return Ok(());
+        }
+
+        let mut owner = Owner {
+            path,
+            fields: vec![],
+        };
+        // The entity's own change is reported ahead of the changes found below it
+        let position = self.changes.len();
+
+        diff_fields(
+            &self.old[old_idx],
+            &self.new[new_idx],
+            &[],
+            &mut owner.fields,
+        )?;
+        self.diff_children(old_idx, new_idx, &mut owner, &[])?;
+
+        if !owner.fields.is_empty() {
+            self.changes.insert(
+                position,
+                SiPkgChange {
+                    kind: SiPkgChangeKind::Modified,
+                    entity,
+                    path: owner.path,
+                    fields: owner.fields,
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    fn diff_matched(
+        &mut self,
+        old_idx: NodeIndex,
+        new_idx: NodeIndex,
+        owner: &mut Owner,
+        prefix: &[String],
+    ) -> PkgResult<()> {
+        let new_node = &self.new[new_idx];
+        match SiPkgDiffEntity::for_node(new_node.inner()) {
+            Some(entity) => {
+                let path = child_path(owner, new_node);
+                self.diff_entity(entity, path, old_idx, new_idx)
+            }
+            None => {
+                let prefix = child_prefix(prefix, new_node);
+                diff_fields(&self.old[old_idx], new_node, &prefix, &mut owner.fields)?;
+                self.diff_children(old_idx, new_idx, owner, &prefix)
+            }
+        }
+    }
+
+    /// Pairs up the children of two matched nodes by kind and name, skipping pairs that are
+    /// identical, and compares the rest.
+    fn diff_children(
+        &mut self,
+        old_idx: NodeIndex,
+        new_idx: NodeIndex,
+        owner: &mut Owner,
+        prefix: &[String],
+    ) -> PkgResult<()> {
+        let mut old_remaining: Vec<Option<NodeIndex>> =
+            children(self.old, old_idx).map(Some).collect();
+
+        let mut unmatched_new = vec![];
+        for new_child_idx in children(self.new, new_idx) {
+            let new_hash = self.new[new_child_idx].hash();
+            match old_remaining
+                .iter_mut()
+                .find(|old_child_idx| matches!(old_child_idx, Some(idx) if self.old[*idx].hash() == new_hash))
+            {
+                Some(identical) => *identical = None,
+                None => unmatched_new.push(new_child_idx),
+            }
+        }
+
+        for new_child_idx in unmatched_new {
+            let new_child = &self.new[new_child_idx];
+            let old_child_idx = old_remaining
+                .iter_mut()
+                .find(|old_child_idx| match old_child_idx {
+                    Some(idx) => same_key(&self.old[*idx], new_child),
+                    None => false,
+                })
+                .and_then(Option::take);
+
+            match old_child_idx {
+                Some(old_child_idx) => {
+                    self.diff_matched(old_child_idx, new_child_idx, owner, prefix)?
+                }
+                None => {
+                    self.diff_one_sided(SiPkgChangeKind::Added, new_child_idx, owner, prefix)?
+                }
+            }
+        }
+
+        for old_child_idx in old_remaining.into_iter().flatten() {
+            self.diff_one_sided(SiPkgChangeKind::Removed, old_child_idx, owner, prefix)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reports a subtree which only exists in one of the packages.
+    ///
+    /// Only the outermost entities of the subtree are reported, while the fields of any nodes
+    /// above them are folded into the owner.
+    fn diff_one_sided(
+        &mut self,
+        kind: SiPkgChangeKind,
+        idx: NodeIndex,
+        owner: &mut Owner,
+        prefix: &[String],
+    ) -> PkgResult<()> {
+        let graph = match kind {
+            SiPkgChangeKind::Removed => self.old,
+            _ => self.new,
+        };
+        let node = &graph[idx];
+
+        match SiPkgDiffEntity::for_node(node.inner()) {
+            Some(entity) => self.changes.push(SiPkgChange {
+                kind,
+                entity,
+                path: child_path(owner, node),
+                fields: vec![],
+            }),
+            None => {
+                let prefix = child_prefix(prefix, node);
+                for (key, value) in node_fields(node.inner())? {
+                    let (old, new) = match kind {
+                        SiPkgChangeKind::Removed => (Some(value.as_str()), None),
+                        _ => (None, Some(value.as_str())),
+                    };
+                    owner.fields.push(field_change(&prefix, &key, old, new));
+                }
+                for child_idx in children(graph, idx) {
+                    self.diff_one_sided(kind, child_idx, owner, &prefix)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the children of a node in the order they were added to the tree.
+fn children(graph: &PkgGraph, idx: NodeIndex) -> impl Iterator<Item = NodeIndex> {
+    let mut children: Vec<_> = graph.neighbors_directed(idx, Outgoing).collect();
+    children.reverse();
+    children.into_iter()
+}
+
+fn same_key(old: &HashedNode<PkgNode>, new: &HashedNode<PkgNode>) -> bool {
+    old.inner().node_kind_str() == new.inner().node_kind_str() && old.name() == new.name()
+}
+
+fn child_path(owner: &Owner, node: &HashedNode<PkgNode>) -> Vec<String> {
+    owner
+        .path
+        .iter()
+        .cloned()
+        .chain(iter::once(node.name().to_string()))
+        .collect()
+}
+
+fn child_prefix(prefix: &[String], node: &HashedNode<PkgNode>) -> Vec<String> {
+    let kind = node.inner().node_kind_str();
+    let name = node.name();
+    // Nodes without a name of their own are named after their kind
+    let label = if name == kind {
+        kind.to_string()
+    } else {
+        format!("{kind}[{name}]")
+    };
+
+    prefix.iter().cloned().chain(iter::once(label)).collect()
+}
+
+fn diff_fields(
+    old: &HashedNode<PkgNode>,
+    new: &HashedNode<PkgNode>,
+    prefix: &[String],
+    changes: &mut Vec<SiPkgFieldChange>,
+) -> PkgResult<()> {
+    let old_fields = node_fields(old.inner())?;
+    let new_fields = node_fields(new.inner())?;
+
+    for (key, new_value) in &new_fields {
+        let old_value = old_fields
+            .iter()
+            .find(|(old_key, _)| old_key == key)
+            .map(|(_, value)| value);
+        if old_value != Some(new_value) {
+            changes.push(field_change(
+                prefix,
+                key,
+                old_value.map(String::as_str),
+                Some(new_value),
+            ));
+        }
+    }
+    for (key, old_value) in &old_fields {
+        if !new_fields.iter().any(|(new_key, _)| new_key == key) {
+            changes.push(field_change(prefix, key, Some(old_value), None));
+        }
+    }
+
+    Ok(())
+}
+
+fn field_change(
+    prefix: &[String],
+    key: &str,
+    old: Option<&str>,
+    new: Option<&str>,
+) -> SiPkgFieldChange {
+    if key == KEY_CODE_STR {
+        if let (Some(old_code), Some(new_code)) = (decode_code(old), decode_code(new)) {
+            let lines = diff::lines(&old_code, &new_code)
+                .into_iter()
+                .map(|line| match line {
+                    diff::Result::Left(left) => format!("-{left}"),
+                    diff::Result::Both(unchanged, _) => format!(" {unchanged}"),
+                    diff::Result::Right(right) => format!("+{right}"),
+                })
+                .collect();
+
+            return SiPkgFieldChange::Code {
+                field: field_name(prefix, FIELD_CODE_STR),
+                lines,
+            };
+        }
+    }
+
+    SiPkgFieldChange::Value {
+        field: field_name(prefix, key),
+        old: old.map(ToString::to_string),
+        new: new.map(ToString::to_string),
+    }
+}
+
+fn field_name(prefix: &[String], key: &str) -> String {
+    prefix
+        .iter()
+        .map(String::as_str)
+        .chain(iter::once(key))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Decodes base64 encoded code, treating a missing value as no code at all. Returns `None` if
+/// the value isn't valid code, in which case it's compared as is.
+fn decode_code(value: Option<&str>) -> Option<String> {
+    match value {
+        None => Some(String::new()),
+        Some(encoded) => general_purpose::STANDARD_NO_PAD
+            .decode(encoded.trim_end_matches('='))
+            .ok()
+            .and_then(|buf| String::from_utf8(buf).ok()),
+    }
+}
+
+/// Returns the key/value fields a node writes when it's hashed, in order.
+fn node_fields(node: &PkgNode) -> PkgResult<Vec<(String, String)>> {
+    let mut buf = Vec::new();
+    node.write_bytes(&mut buf)?;
+    let bytes = String::from_utf8(buf).map_err(GraphError::parse)?;
+
+    let mut fields = vec![];
+    let mut rest = bytes.as_str();
+    while !rest.is_empty() {
+        let (key, after_key) = rest
+            .split_once(':')
+            .ok_or_else(|| GraphError::parse_custom("missing key in node line"))?;
+        let (len, after_len) = after_key
+            .split_once('=')
+            .ok_or_else(|| GraphError::parse_custom("missing length in node line"))?;
+        let len: usize = len.parse().map_err(GraphError::parse)?;
+        let value = after_len
+            .get(..len)
+            .ok_or_else(|| GraphError::parse_custom("truncated value in node line"))?;
+        rest = after_len[len..].trim_start_matches('\n');
+
+        if key != KEY_NODE_KIND_STR {
+            fields.push((key.to_string(), value.to_string()));
+        }
+    }
+
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::PkgSpec;
+
+    use super::*;
+
+    const PACKAGE_JSON: &str = include_str!("../../pkg-complex.json");
+
+    fn pkg(json: serde_json::Value) -> SiPkg {
+        let spec: PkgSpec = serde_json::from_value(json).expect("failed to parse spec");
+        SiPkg::load_from_spec(spec).expect("failed to load spec")
+    }
+
+    fn package_json() -> serde_json::Value {
+        let mut json: serde_json::Value =
+            serde_json::from_str(PACKAGE_JSON).expect("failed to parse json");
+        // The fixture predates func data being nested, so move it to where it belongs
+        for func in json["funcs"].as_array_mut().expect("funcs are an array") {
+            let func = func.as_object_mut().expect("func is an object");
+            let mut data = serde_json::Map::new();
+            for key in [
+                "displayName",
+                "description",
+                "handler",
+                "codeBase64",
+                "backendKind",
+                "responseType",
+                "hidden",
+                "link",
+            ] {
+                if let Some(value) = func.remove(key) {
+                    data.insert(key.to_string(), value);
+                }
+            }
+            data.insert("name".to_string(), func["name"].clone());
+            func.insert("data".to_string(), data.into());
+        }
+
+        json
+    }
+
+    #[test]
+    fn identical_pkgs_have_no_changes() {
+        let diff = SiPkgDiff::new(&pkg(package_json()), &pkg(package_json()))
+            .expect("failed to diff pkgs");
+
+        assert!(diff.is_empty());
+        assert_eq!("no changes\n", diff.to_string());
+    }
+
+    #[test]
+    fn reports_changes_against_entities() {
+        let old = package_json();
+        let mut new = package_json();
+        new["version"] = "2".into();
+        new["funcs"][0]["data"]["codeBase64"] = general_purpose::STANDARD
+            .encode("function truth() {\n  return 1 === 1;\n}")
+            .into();
+        new["funcs"][0]["arguments"][0]["kind"] = "string".into();
+        let domain = &mut new["schemas"][0]["variants"][0]["domain"]["entries"];
+        domain[1]["kind"] = "number".into();
+        let domain = domain.as_array_mut().expect("entries are an array");
+        domain.retain(|entry| entry["name"] != "status");
+        domain.push(serde_json::json!({ "name": "replicas", "kind": "number" }));
+
+        let diff = SiPkgDiff::new(&pkg(old), &pkg(new)).expect("failed to diff pkgs");
+
+        let summary: Vec<_> = diff
+            .changes()
+            .iter()
+            .map(|change| (change.kind(), change.entity(), change.path().join("/")))
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    SiPkgChangeKind::Modified,
+                    SiPkgDiffEntity::Package,
+                    "".to_string()
+                ),
+                (
+                    SiPkgChangeKind::Modified,
+                    SiPkgDiffEntity::Prop,
+                    "k8sDeployment/v0/domain/kind".to_string()
+                ),
+                (
+                    SiPkgChangeKind::Added,
+                    SiPkgDiffEntity::Prop,
+                    "k8sDeployment/v0/domain/replicas".to_string()
+                ),
+                (
+                    SiPkgChangeKind::Removed,
+                    SiPkgDiffEntity::Prop,
+                    "k8sDeployment/v0/domain/status".to_string()
+                ),
+                (
+                    SiPkgChangeKind::Modified,
+                    SiPkgDiffEntity::Func,
+                    "si:truthy".to_string()
+                ),
+            ],
+            summary
+        );
+
+        let func_fields = diff.changes()[4].fields();
+        assert_eq!(
+            vec!["code", "func_argument[value].kind"],
+            func_fields
+                .iter()
+                .map(SiPkgFieldChange::field)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            SiPkgFieldChange::Code {
+                field: "code".to_string(),
+                lines: vec![
+                    "-function truth() { return true; }".to_string(),
+                    "+function truth() {".to_string(),
+                    "+  return 1 === 1;".to_string(),
+                    "+}".to_string(),
+                ],
+            },
+            func_fields[0]
+        );
+
+        let rendered = diff.to_string();
+        assert!(rendered.contains("~ func si:truthy\n    code:\n"));
+        assert!(rendered.contains("+ prop k8sDeployment / v0 / domain / replicas\n"));
+
+        let json = serde_json::to_value(&diff).expect("failed to serialize diff");
+        assert_eq!("modified", json["changes"][0]["kind"]);
+        assert_eq!("version", json["changes"][0]["fields"][0]["field"]);
+        assert_eq!("2", json["changes"][0]["fields"][0]["new"]);
+    }
+}
@@ -0,0 +1,322 @@
+use std::{fmt, str::FromStr};
+
+use base64::{engine::general_purpose, Engine};
+use object_tree::Hash;
+use serde::{Deserialize, Serialize};
+use sodiumoxide::crypto::sign::ed25519::{self, PublicKey, SecretKey, Signature};
+
+use super::{PkgResult, SiPkgError};
+
+/// Detached entries holding signatures are named with this prefix, followed by the signing key's
+/// id.
+pub(super) const SIGNATURE_ENTRY_PREFIX: &str = "signatures/";
+
+/// A key that signs packages, which must be kept secret by whoever produces them.
+#[derive(Clone)]
+pub struct PkgSigningKey {
+    public_key: PublicKey,
+    secret_key: SecretKey,
+}
+
+impl PkgSigningKey {
+    /// Generates a new, random signing key.
+    pub fn generate() -> Self {
+        // Initializing makes random number generation thread safe and may be repeated
+        let _ = sodiumoxide::init();
+        let (public_key, secret_key) = ed25519::gen_keypair();
+
+        Self {
+            public_key,
+            secret_key,
+        }
+    }
+
+    /// Decodes a signing key from a base64 encoded ed25519 secret key.
+    pub fn decode(encoded: impl AsRef<[u8]>) -> PkgResult<Self> {
+        let buf = general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|err| SiPkgError::SigningKeyParse(err.to_string()))?;
+        let secret_key = SecretKey::from_slice(&buf)
+            .ok_or_else(|| SiPkgError::SigningKeyParse("wrong key length".to_string()))?;
+
+        Ok(Self {
+            public_key: secret_key.public_key(),
+            secret_key,
+        })
+    }
+
+    /// Encodes the secret key as a base64 string.
+    pub fn encode(&self) -> String {
+        general_purpose::STANDARD.encode(self.secret_key.as_ref())
+    }
+
+    /// Returns the key that verifies signatures made with this one.
+    pub fn verifying_key(&self) -> PkgVerifyingKey {
+        PkgVerifyingKey(self.public_key)
+    }
+
+    fn sign(&self, root_hash: Hash) -> Signature {
+        ed25519::sign_detached(&signed_message(root_hash), &self.secret_key)
+    }
+}
+
+impl fmt::Debug for PkgSigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PkgSigningKey")
+            .field("key_id", &self.verifying_key().key_id())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The public half of a [`PkgSigningKey`], which checks that a package was signed by it.
+///
+/// Verifying keys are exchanged and configured as base64 encoded strings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PkgVerifyingKey(PublicKey);
+
+impl PkgVerifyingKey {
+    /// Returns an identifier for this key which is short enough to name it in entries and logs.
+    pub fn key_id(&self) -> Hash {
+        Hash::new(self.0.as_ref())
+    }
+
+    fn verify(&self, root_hash: Hash, signature: &Signature) -> bool {
+        ed25519::verify_detached(signature, &signed_message(root_hash), &self.0)
+    }
+}
+
+impl fmt::Display for PkgVerifyingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&general_purpose::STANDARD.encode(self.0.as_ref()))
+    }
+}
+
+impl FromStr for PkgVerifyingKey {
+    type Err = SiPkgError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let buf = general_purpose::STANDARD
+            .decode(s)
+            .map_err(|err| SiPkgError::SigningKeyParse(err.to_string()))?;
+        let public_key = PublicKey::from_slice(&buf)
+            .ok_or_else(|| SiPkgError::SigningKeyParse("wrong key length".to_string()))?;
+
+        Ok(Self(public_key))
+    }
+}
+
+impl TryFrom<String> for PkgVerifyingKey {
+    type Error = SiPkgError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<PkgVerifyingKey> for String {
+    fn from(value: PkgVerifyingKey) -> Self {
+        value.to_string()
+    }
+}
+
+/// A detached signature over the root hash of a package.
+///
+/// The signer's name is informational only and is not covered by the signature, so trust must
+/// always be decided by the [`PkgVerifyingKey`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SiPkgSignature {
+    signer: String,
+    verifying_key: PkgVerifyingKey,
+    signature: Signature,
+}
+
+impl SiPkgSignature {
+    pub(super) fn new(signer: impl Into<String>, key: &PkgSigningKey, root_hash: Hash) -> Self {
+        Self {
+            signer: signer.into(),
+            verifying_key: key.verifying_key(),
+            signature: key.sign(root_hash),
+        }
+    }
+
+    pub fn signer(&self) -> &str {
+        self.signer.as_ref()
+    }
+
+    pub fn verifying_key(&self) -> PkgVerifyingKey {
+        self.verifying_key
+    }
+
+    pub fn key_id(&self) -> Hash {
+        self.verifying_key.key_id()
+    }
+
+    /// Returns whether this signature was made over the given root hash.
+    pub fn is_valid_for(&self, root_hash: Hash) -> bool {
+        self.verifying_key.verify(root_hash, &self.signature)
+    }
+
+    pub(super) fn entry_name(&self) -> String {
+        format!("{SIGNATURE_ENTRY_PREFIX}{}", self.key_id())
+    }
+
+    pub(super) fn to_entry(&self) -> PkgResult<Vec<u8>> {
+        let entry = SignatureEntry {
+            signer: self.signer.clone(),
+            public_key: self.verifying_key.to_string(),
+            signature: general_purpose::STANDARD.encode(self.signature.to_bytes()),
+        };
+
+        Ok(serde_json::to_vec(&entry)?)
+    }
+
+    pub(super) fn from_entry(bytes: &[u8]) -> PkgResult<Self> {
+        let entry: SignatureEntry = serde_json::from_slice(bytes)?;
+        let signature_bytes = general_purpose::STANDARD
+            .decode(entry.signature)
+            .map_err(|err| SiPkgError::SignatureParse(err.to_string()))?;
+        let signature = Signature::from_bytes(&signature_bytes)
+            .map_err(|_| SiPkgError::SignatureParse("wrong signature length".to_string()))?;
+
+        Ok(Self {
+            signer: entry.signer,
+            verifying_key: entry.public_key.parse()?,
+            signature,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SignatureEntry {
+    signer: String,
+    public_key: String,
+    signature: String,
+}
+
+/// Signatures are made over the root hash with a prefix, so that they can't be confused with
+/// signatures the same key made over a bare hash for another purpose.
+fn signed_message(root_hash: Hash) -> Vec<u8> {
+    format!("si-pkg-signature-v1:{root_hash}").into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{PkgSpec, SiPkg};
+
+    use super::*;
+
+    const PACKAGE_JSON: &str = include_str!("../../pkg-complex.json");
+    const WORKSPACE_JSON: &str = include_str!("../../pkg-workspace.json");
+
+    fn pkg(json: &str) -> SiPkg {
+        let spec: PkgSpec = serde_json::from_str(json).expect("failed to parse spec");
+        SiPkg::load_from_spec(spec).expect("failed to load spec")
+    }
+
+    #[test]
+    fn signatures_survive_a_round_trip() {
+        let key = PkgSigningKey::generate();
+        let mut pkg = pkg(PACKAGE_JSON);
+        let hash = pkg.hash().expect("failed to hash pkg");
+        pkg.sign("Dr. Peanut Butter", &key)
+            .expect("failed to sign pkg");
+
+        let bytes = pkg.write_to_bytes().expect("failed to serialize pkg");
+        let read_pkg = SiPkg::load_from_bytes(bytes).expect("failed to load pkg from bytes");
+
+        assert_eq!(hash, read_pkg.hash().expect("failed to hash pkg"));
+        let metadata = read_pkg.metadata().expect("failed to get metadata");
+        assert_eq!(
+            vec!["Dr. Peanut Butter"],
+            metadata.signers().collect::<Vec<_>>()
+        );
+        let signature = read_pkg
+            .verify_trusted(&[key.verifying_key()])
+            .expect("signature should be trusted");
+        assert_eq!(key.verifying_key().key_id(), signature.key_id());
+    }
+
+    #[test]
+    fn refuses_unsigned_and_untrusted_pkgs() {
+        let trusted = PkgSigningKey::generate();
+        let mut pkg = pkg(PACKAGE_JSON);
+
+        assert!(pkg.verify().is_ok());
+        assert!(matches!(
+            pkg.verify_trusted(&[trusted.verifying_key()]),
+            Err(SiPkgError::Unsigned)
+        ));
+
+        pkg.sign("stranger", &PkgSigningKey::generate())
+            .expect("failed to sign pkg");
+        assert!(pkg.verify().is_ok());
+        assert!(matches!(
+            pkg.verify_trusted(&[trusted.verifying_key()]),
+            Err(SiPkgError::UntrustedSigners(_))
+        ));
+    }
+
+    #[test]
+    fn detects_signatures_over_other_contents() {
+        let key = PkgSigningKey::generate();
+        let mut signed = pkg(PACKAGE_JSON);
+        signed.sign("signer", &key).expect("failed to sign pkg");
+
+        let tampered = SiPkg {
+            tree: Arc::clone(&pkg(WORKSPACE_JSON).tree),
+            signatures: signed.signatures().to_vec(),
+        };
+
+        assert!(matches!(
+            tampered.verify_trusted(&[key.verifying_key()]),
+            Err(SiPkgError::SignatureInvalid(_, _))
+        ));
+    }
+
+    #[test]
+    fn detects_tampered_objects_in_a_signed_bundle() {
+        let key = PkgSigningKey::generate();
+        let mut signed = pkg(PACKAGE_JSON);
+        signed.sign("signer", &key).expect("failed to sign pkg");
+        let bytes = signed.write_to_bytes().expect("failed to serialize pkg");
+
+        // Rename a func in place, keeping the entry's name (and so its claimed hash) and the
+        // entry's size the same
+        let (original, replacement) = ("si:truthy", "si:trusty");
+        let offset = bytes
+            .windows(original.len())
+            .position(|window| window == original.as_bytes())
+            .expect("func not found in bundle");
+        let mut tampered = bytes.clone();
+        tampered.splice(
+            offset..offset + original.len(),
+            replacement.bytes().collect::<Vec<_>>(),
+        );
+
+        assert!(SiPkg::load_from_bytes(bytes).is_ok());
+        assert!(matches!(
+            SiPkg::load_from_bytes(tampered),
+            Err(SiPkgError::TarRead(object_tree::TarReadError::ReadTree(
+                object_tree::GraphError::Verify(_, _)
+            )))
+        ));
+    }
+
+    #[test]
+    fn encodes_keys_as_base64() {
+        let key = PkgSigningKey::generate();
+        let decoded = PkgSigningKey::decode(key.encode()).expect("failed to decode signing key");
+        assert_eq!(key.verifying_key(), decoded.verifying_key());
+
+        let verifying_key: PkgVerifyingKey = key
+            .verifying_key()
+            .to_string()
+            .parse()
+            .expect("failed to parse verifying key");
+        assert_eq!(key.verifying_key(), verifying_key);
+    }
+}
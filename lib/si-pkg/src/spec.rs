@@ -9,6 +9,8 @@ mod attribute_value;
 mod authentication_func;
 mod change_set;
 mod component;
+mod dependency;
+mod dir;
 mod edge;
 mod func;
 mod leaf_function;
@@ -23,8 +25,8 @@ mod variant;
 
 pub use {
     action_func::*, attr_func_input::*, attribute_value::*, authentication_func::*, change_set::*,
-    component::*, edge::*, func::*, leaf_function::*, map_key_func::*, position::*, prop::*,
-    root_prop_func::*, schema::*, si_prop_func::*, socket::*, variant::*,
+    component::*, dependency::*, dir::*, edge::*, func::*, leaf_function::*, map_key_func::*,
+    position::*, prop::*, root_prop_func::*, schema::*, si_prop_func::*, socket::*, variant::*,
 };
 
 use super::SiPkgKind;
@@ -65,6 +67,10 @@ pub struct PkgSpec {
     #[builder(setter(each(name = "change_set", into)), default)]
     #[serde(default)]
     pub change_sets: Vec<ChangeSetSpec>,
+
+    #[builder(setter(each(name = "dependency", into)), default)]
+    #[serde(default)]
+    pub dependencies: Vec<DependencySpec>,
 }
 
 impl PkgSpec {
@@ -97,6 +103,15 @@ impl PkgSpecBuilder {
         Ok(self.schema(converted))
     }
 
+    #[allow(unused_mut)]
+    pub fn try_dependency<I>(&mut self, item: I) -> Result<&mut Self, I::Error>
+    where
+        I: TryInto<DependencySpec>,
+    {
+        let converted: DependencySpec = item.try_into()?;
+        Ok(self.dependency(converted))
+    }
+
     #[allow(unused_mut)]
     pub fn try_func<I>(&mut self, item: I) -> Result<&mut Self, I::Error>
     where
@@ -118,10 +133,16 @@ impl TryFrom<PkgSpecBuilder> for PkgSpec {
 #[remain::sorted]
 #[derive(Debug, Error)]
 pub enum SpecError {
+    #[error("invalid func code file path: {0}")]
+    InvalidCodeFilePath(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
     #[error("Can't convert {0} to LeafInputLocation")]
     LeafInputLocationConversionError(String),
     #[error(transparent)]
     SerdeJson(#[from] serde_json::Error),
+    #[error(transparent)]
+    SerdeYaml(#[from] serde_yaml::Error),
     /// Uninitialized field
     #[error("{0} must be initialized")]
     UninitializedField(&'static str),
@@ -0,0 +1,37 @@
+use derive_builder::Builder;
+use semver::VersionReq;
+use serde::{Deserialize, Serialize};
+
+use super::SpecError;
+
+/// Another module which must be installed, at a version matching the requirement, before a
+/// package can be installed.
+#[derive(Builder, Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[builder(build_fn(error = "SpecError", validate = "Self::validate"))]
+pub struct DependencySpec {
+    #[builder(setter(into))]
+    pub name: String,
+    /// A semver version requirement, such as `^1.2`.
+    #[builder(setter(into))]
+    pub version_req: String,
+}
+
+impl DependencySpec {
+    #[must_use]
+    pub fn builder() -> DependencySpecBuilder {
+        DependencySpecBuilder::default()
+    }
+}
+
+impl DependencySpecBuilder {
+    fn validate(&self) -> Result<(), String> {
+        if let Some(version_req) = &self.version_req {
+            VersionReq::parse(version_req).map_err(|err| {
+                format!("invalid version requirement {version_req:?} for dependency: {err}")
+            })?;
+        }
+
+        Ok(())
+    }
+}
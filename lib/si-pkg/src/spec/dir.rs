@@ -0,0 +1,295 @@
+//! A human editable layout for a [`PkgSpec`] on disk, suitable for keeping a module in version
+//! control and reviewing changes to it.
+//!
+//! ```text
+//! <dir>/
+//!   pkg.yaml                            the spec, with func code moved out to files
+//!   funcs/<func name>.js                code of the funcs in `funcs`
+//!   change-sets/<name>/funcs/<name>.js  code of the funcs of a change set in a workspace backup
+//! ```
+//!
+//! `pkg.yaml` is the YAML serialization of [`PkgSpec`], using the same field names as its JSON
+//! form, except that the `codeBase64` field of a func's `data` is replaced by a `codeFile` field
+//! holding the path of the plain text code, relative to the directory. Func file names are derived
+//! from the func names and only need to be unique; they can be renamed freely as long as
+//! `codeFile` is updated to match.
+//!
+//! Code which doesn't decode to text, or whose encoding would change when encoded again, is left
+//! inline as `codeBase64` so that loading a written directory always gives back the same spec.
+
+use std::{
+    collections::HashSet,
+    path::{Component, Path},
+};
+
+use base64::{engine::general_purpose, Engine};
+use serde_yaml::{Mapping, Sequence, Value};
+use tokio::fs;
+
+use super::{PkgSpec, SpecError};
+
+/// The name of the file holding the spec in a package directory.
+pub const PKG_SPEC_FILE_NAME: &str = "pkg.yaml";
+
+const CHANGE_SETS_DIR: &str = "change-sets";
+const CODE_BASE64_KEY: &str = "codeBase64";
+const CODE_FILE_KEY: &str = "codeFile";
+const FUNCS_DIR: &str = "funcs";
+
+impl PkgSpec {
+    /// Reads a spec from a package directory, inlining the code of each func from its file.
+    pub async fn load_from_dir(path: impl AsRef<Path>) -> Result<Self, SpecError> {
+        let root = path.as_ref();
+
+        let buf = fs::read_to_string(root.join(PKG_SPEC_FILE_NAME)).await?;
+        let mut value: Value = serde_yaml::from_str(&buf)?;
+
+        if let Some(funcs) = value.get_mut("funcs").and_then(Value::as_sequence_mut) {
+            inline_code(root, funcs).await?;
+        }
+        if let Some(change_sets) = value.get_mut("changeSets").and_then(Value::as_sequence_mut) {
+            for change_set in change_sets {
+                if let Some(funcs) = change_set.get_mut("funcs").and_then(Value::as_sequence_mut) {
+                    inline_code(root, funcs).await?;
+                }
+            }
+        }
+
+        Ok(serde_yaml::from_value(value)?)
+    }
+
+    /// Writes the spec out as a package directory, creating the directory if needed. Existing
+    /// files are overwritten but files which are no longer part of the package are left alone.
+    pub async fn write_to_dir(&self, path: impl AsRef<Path>) -> Result<(), SpecError> {
+        let root = path.as_ref();
+
+        let mut value = serde_yaml::to_value(self)?;
+        let mut code_files = CodeFiles::default();
+
+        if let Some(funcs) = value.get_mut("funcs").and_then(Value::as_sequence_mut) {
+            code_files.extract(FUNCS_DIR, funcs);
+        }
+        if let Some(change_sets) = value.get_mut("changeSets").and_then(Value::as_sequence_mut) {
+            for change_set in change_sets {
+                let name = change_set
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .map(file_stem)
+                    .unwrap_or_default();
+                let dir = format!("{CHANGE_SETS_DIR}/{name}/{FUNCS_DIR}");
+                if let Some(funcs) = change_set.get_mut("funcs").and_then(Value::as_sequence_mut) {
+                    code_files.extract(&dir, funcs);
+                }
+            }
+        }
+
+        fs::create_dir_all(root).await?;
+        for (file, code) in code_files.files {
+            let file_path = root.join(file);
+            if let Some(parent) = file_path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            fs::write(file_path, code).await?;
+        }
+        fs::write(
+            root.join(PKG_SPEC_FILE_NAME),
+            serde_yaml::to_string(&value)?,
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct CodeFiles {
+    used: HashSet<String>,
+    files: Vec<(String, String)>,
+}
+
+impl CodeFiles {
+    fn extract(&mut self, dir: &str, funcs: &mut Sequence) {
+        for func in funcs {
+            let stem = func
+                .get("name")
+                .and_then(Value::as_str)
+                .map(file_stem)
+                .unwrap_or_default();
+            let data = match func.get_mut("data").and_then(Value::as_mapping_mut) {
+                Some(data) => data,
+                None => continue,
+            };
+            let code = match data
+                .get(CODE_BASE64_KEY)
+                .and_then(Value::as_str)
+                .and_then(decode_code)
+            {
+                Some(code) => code,
+                None => continue,
+            };
+            let extension = match data.get("backendKind").and_then(Value::as_str) {
+                Some(backend_kind) if backend_kind.starts_with("js") => "js",
+                _ => "txt",
+            };
+
+            let mut file = format!("{dir}/{stem}.{extension}");
+            let mut suffix = 2;
+            while !self.used.insert(file.clone()) {
+                file = format!("{dir}/{stem}-{suffix}.{extension}");
+                suffix += 1;
+            }
+
+            replace_key(data, CODE_BASE64_KEY, CODE_FILE_KEY, file.clone().into());
+            self.files.push((file, code));
+        }
+    }
+}
+
+async fn inline_code(root: &Path, funcs: &mut Sequence) -> Result<(), SpecError> {
+    for func in funcs {
+        let data = match func.get_mut("data").and_then(Value::as_mapping_mut) {
+            Some(data) => data,
+            None => continue,
+        };
+        let file = match data.get(CODE_FILE_KEY) {
+            Some(file) => file
+                .as_str()
+                .ok_or_else(|| SpecError::InvalidCodeFilePath(format!("{file:?}")))?
+                .to_owned(),
+            None => continue,
+        };
+        // Keep code files inside the package directory
+        if !Path::new(&file)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(SpecError::InvalidCodeFilePath(file));
+        }
+
+        let code = fs::read_to_string(root.join(&file)).await?;
+        let code_base64 = general_purpose::STANDARD_NO_PAD.encode(code);
+        replace_key(data, CODE_FILE_KEY, CODE_BASE64_KEY, code_base64.into());
+    }
+
+    Ok(())
+}
+
+/// Returns the code as text if encoding it again gives back exactly `code_base64`.
+fn decode_code(code_base64: &str) -> Option<String> {
+    if code_base64.is_empty() {
+        return None;
+    }
+
+    let code =
+        String::from_utf8(general_purpose::STANDARD_NO_PAD.decode(code_base64).ok()?).ok()?;
+    (general_purpose::STANDARD_NO_PAD.encode(&code) == code_base64).then_some(code)
+}
+
+fn file_stem(name: &str) -> String {
+    let stem: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    if stem.is_empty() {
+        "unnamed".to_owned()
+    } else {
+        stem
+    }
+}
+
+/// Replaces `old_key` with `new_key` without moving the entry, keeping the file diffable.
+fn replace_key(mapping: &mut Mapping, old_key: &str, new_key: &str, value: Value) {
+    *mapping = std::mem::take(mapping)
+        .into_iter()
+        .map(|(key, existing)| {
+            if key.as_str() == Some(old_key) {
+                (new_key.into(), value.clone())
+            } else {
+                (key, existing)
+            }
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FuncSpec, FuncSpecBackendKind, FuncSpecBackendResponseType, FuncSpecData, SiPkg};
+
+    use super::*;
+
+    fn func(name: &str, code_base64: &str) -> FuncSpec {
+        FuncSpec::builder()
+            .name(name)
+            .unique_id(name)
+            .data(
+                FuncSpecData::builder()
+                    .name(name)
+                    .handler("main")
+                    .code_base64(code_base64)
+                    .backend_kind(FuncSpecBackendKind::JsAttribute)
+                    .response_type(FuncSpecBackendResponseType::Boolean)
+                    .build()
+                    .expect("failed to build func data"),
+            )
+            .build()
+            .expect("failed to build func")
+    }
+
+    #[tokio::test]
+    async fn dir_round_trip() {
+        let code = "function main() {\n  return true;\n}\n";
+        let spec = PkgSpec::builder()
+            .name("round trip")
+            .version("0.1.0")
+            .created_by("sally@systeminit.com")
+            .func(func(
+                "si:truthy",
+                &general_purpose::STANDARD_NO_PAD.encode(code),
+            ))
+            // Padded, so writing it out as text would change it on the way back in
+            .func(func("si:padded", &general_purpose::STANDARD.encode("true")))
+            .build()
+            .expect("failed to build spec");
+        let dir = tempfile::tempdir().expect("failed to create tempdir");
+
+        spec.write_to_dir(dir.path())
+            .await
+            .expect("failed to write spec");
+        let read_spec = PkgSpec::load_from_dir(dir.path())
+            .await
+            .expect("failed to load spec");
+
+        assert_eq!(
+            code,
+            std::fs::read_to_string(dir.path().join("funcs/si_truthy.js"))
+                .expect("failed to read code file")
+        );
+        assert!(!dir.path().join("funcs/si_padded.js").exists());
+        assert_eq!(
+            SiPkg::load_from_spec(spec).unwrap().hash().unwrap(),
+            SiPkg::load_from_spec(read_spec).unwrap().hash().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_code_files_outside_dir() {
+        let dir = tempfile::tempdir().expect("failed to create tempdir");
+        std::fs::write(
+            dir.path().join(PKG_SPEC_FILE_NAME),
+            "kind: module\nname: escape\nversion: 0.1.0\ncreatedAt: 2023-05-23T00:00:00Z\ncreatedBy: sally\nfuncs:\n- name: escape\n  uniqueId: escape\n  data:\n    name: escape\n    handler: main\n    codeFile: ../escape.js\n    backendKind: jsAttribute\n    responseType: boolean\n  arguments: []\n",
+        )
+        .expect("failed to write spec");
+
+        assert!(matches!(
+            PkgSpec::load_from_dir(dir.path()).await,
+            Err(SpecError::InvalidCodeFilePath(path)) if path == "../escape.js"
+        ));
+    }
+}
@@ -76,6 +76,7 @@ pub enum FuncSpecBackendKind {
     String,
     Unset,
     Validation,
+    Wasm,
 }
 
 #[remain::sorted]
@@ -0,0 +1,76 @@
+//! Answers callback requests which functions make to their caller while they execute.
+
+use std::{fmt, sync::Arc};
+
+use futures::{future::BoxFuture, StreamExt};
+use si_data_nats::{NatsClient, Subscriber};
+use telemetry::prelude::*;
+
+use crate::{CallbackRequest, CallbackResponse};
+
+/// Functions asking for data when nobody is there to provide it are refused straight away rather
+/// than left waiting for an answer.
+const NO_CALLBACK_HANDLER: &str = "the caller does not answer callback requests";
+
+/// Provides the data functions ask their caller for with `askCaller`, such as another component's
+/// view.
+///
+/// Handlers are set on a [`Client`](crate::Client) with
+/// [`with_callback_handler`](crate::Client::with_callback_handler). Requests they can't answer
+/// should get a [`CallbackResponse::failure`], which rejects the function's request.
+pub trait CallbackHandler: Send + Sync + 'static {
+    fn handle(&self, request: CallbackRequest) -> BoxFuture<'static, CallbackResponse>;
+}
+
+impl fmt::Debug for dyn CallbackHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CallbackHandler")
+    }
+}
+
+/// Answers the given request with `handler`, or refuses it if there's no handler.
+pub(crate) async fn answer(
+    handler: Option<&Arc<dyn CallbackHandler>>,
+    request: CallbackRequest,
+) -> CallbackResponse {
+    match handler {
+        Some(handler) => handler.handle(request).await,
+        None => CallbackResponse::failure(request.id, NO_CALLBACK_HANDLER),
+    }
+}
+
+/// Answers callback requests relayed by veritech until the subscription ends.
+pub(crate) async fn answer_callbacks_task(
+    nats: NatsClient,
+    mut callback_subscriber: Subscriber,
+    handler: Arc<dyn CallbackHandler>,
+) {
+    while let Some(msg) = callback_subscriber.next().await {
+        let reply = match msg.reply() {
+            Some(reply) => reply.clone(),
+            None => {
+                warn!(subject = %msg.subject(), "callback request has no reply subject, skipping");
+                continue;
+            }
+        };
+        let request: CallbackRequest = match serde_json::from_slice(msg.payload()) {
+            Ok(request) => request,
+            Err(err) => {
+                warn!(error = ?err, "failed to deserialize callback request, skipping");
+                continue;
+            }
+        };
+
+        let response = handler.handle(request).await;
+        let payload = match serde_json::to_vec(&response) {
+            Ok(payload) => payload,
+            Err(err) => {
+                warn!(error = ?err, "failed to serialize callback response");
+                continue;
+            }
+        };
+        if let Err(err) = nats.publish(reply, payload.into()).await {
+            warn!(error = ?err, "failed to publish callback response");
+        }
+    }
+}
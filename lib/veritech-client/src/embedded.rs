@@ -0,0 +1,249 @@
+//! Executes functions on a cyclone pool owned by the calling process rather than by a veritech
+//! server on the other end of NATS.
+//!
+//! This is meant for tests and single-binary development setups, where running separate NATS,
+//! veritech and cyclone processes is more trouble than it's worth. Requests skip veritech's
+//! scheduler entirely, so the size of the pool is the only limit on concurrent executions.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use deadpool_cyclone::{
+    instance::cyclone::LocalUdsInstanceSpec, CycloneClient, ExecutionError, ExecutionStarted,
+    Manager, Pool, ProgressMessage, UnixStream,
+};
+use futures::StreamExt;
+use serde::de::DeserializeOwned;
+use telemetry::prelude::*;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{
+    callback, ActionRunRequest, ActionRunResultSuccess, CallbackHandler, ClientError, ClientResult,
+    FunctionResult, OutputStream, ReconciliationRequest, ReconciliationResultSuccess,
+    ResolverFunctionRequest, ResolverFunctionResultSuccess, SchemaVariantDefinitionRequest,
+    SchemaVariantDefinitionResultSuccess, ValidationRequest, ValidationResultSuccess,
+};
+
+/// Runs function executions directly on a [`Pool`] of cyclone instances.
+#[derive(Clone)]
+pub struct EmbeddedExecutor {
+    cyclone_pool: Pool<LocalUdsInstanceSpec>,
+    cancel_requests: Arc<Mutex<HashMap<String, oneshot::Sender<()>>>>,
+}
+
+impl EmbeddedExecutor {
+    /// Creates an executor which runs executions on the given pool.
+    pub fn new(cyclone_pool: Pool<LocalUdsInstanceSpec>) -> Self {
+        Self {
+            cyclone_pool,
+            cancel_requests: Default::default(),
+        }
+    }
+
+    /// Sets up the host for the given spec and creates an executor with a pool of its instances.
+    pub async fn for_cyclone_uds(spec: LocalUdsInstanceSpec) -> ClientResult<Self> {
+        let mut manager = Manager::new(spec);
+        manager
+            .setup()
+            .await
+            .map_err(|err| ClientError::CycloneSpec(Box::new(err)))?;
+        let cyclone_pool = Pool::builder(manager)
+            .build()
+            .map_err(|err| ClientError::CycloneSpec(Box::new(err)))?;
+
+        Ok(Self::new(cyclone_pool))
+    }
+
+    pub(crate) async fn execute_resolver_function(
+        &self,
+        output_tx: mpsc::Sender<OutputStream>,
+        request: &ResolverFunctionRequest,
+        callback_handler: Option<Arc<dyn CallbackHandler>>,
+    ) -> ClientResult<FunctionResult<ResolverFunctionResultSuccess>> {
+        let mut client = self.get().await?;
+        let progress = client
+            .execute_resolver(request.clone())
+            .await?
+            .start()
+            .await
+            .map_err(execution_error)?;
+
+        self.run(progress, &request.execution_id, output_tx, callback_handler)
+            .await
+    }
+
+    pub(crate) async fn execute_validation(
+        &self,
+        output_tx: mpsc::Sender<OutputStream>,
+        request: &ValidationRequest,
+        callback_handler: Option<Arc<dyn CallbackHandler>>,
+    ) -> ClientResult<FunctionResult<ValidationResultSuccess>> {
+        let mut client = self.get().await?;
+        let progress = client
+            .execute_validation(request.clone())
+            .await?
+            .start()
+            .await
+            .map_err(execution_error)?;
+
+        self.run(progress, &request.execution_id, output_tx, callback_handler)
+            .await
+    }
+
+    pub(crate) async fn execute_action_run(
+        &self,
+        output_tx: mpsc::Sender<OutputStream>,
+        request: &ActionRunRequest,
+        callback_handler: Option<Arc<dyn CallbackHandler>>,
+    ) -> ClientResult<FunctionResult<ActionRunResultSuccess>> {
+        let mut client = self.get().await?;
+        let progress = client
+            .execute_action_run(request.clone())
+            .await?
+            .start()
+            .await
+            .map_err(execution_error)?;
+
+        self.run(progress, &request.execution_id, output_tx, callback_handler)
+            .await
+    }
+
+    pub(crate) async fn execute_reconciliation(
+        &self,
+        output_tx: mpsc::Sender<OutputStream>,
+        request: &ReconciliationRequest,
+        callback_handler: Option<Arc<dyn CallbackHandler>>,
+    ) -> ClientResult<FunctionResult<ReconciliationResultSuccess>> {
+        let mut client = self.get().await?;
+        let progress = client
+            .execute_reconciliation(request.clone())
+            .await?
+            .start()
+            .await
+            .map_err(execution_error)?;
+
+        self.run(progress, &request.execution_id, output_tx, callback_handler)
+            .await
+    }
+
+    pub(crate) async fn execute_schema_variant_definition(
+        &self,
+        output_tx: mpsc::Sender<OutputStream>,
+        request: &SchemaVariantDefinitionRequest,
+        callback_handler: Option<Arc<dyn CallbackHandler>>,
+    ) -> ClientResult<FunctionResult<SchemaVariantDefinitionResultSuccess>> {
+        let mut client = self.get().await?;
+        let progress = client
+            .execute_schema_variant_definition(request.clone())
+            .await?
+            .start()
+            .await
+            .map_err(execution_error)?;
+
+        self.run(progress, &request.execution_id, output_tx, callback_handler)
+            .await
+    }
+
+    /// Cancels the execution with the given id if it's still running here.
+    pub(crate) fn cancel_execution(&self, execution_id: &str) {
+        let cancel_tx = self.lock_cancel_requests().remove(execution_id);
+        match cancel_tx {
+            Some(cancel_tx) => {
+                // The execution may have finished in the meantime, which is fine
+                let _ = cancel_tx.send(());
+            }
+            None => debug!(execution_id, "no running execution to cancel"),
+        }
+    }
+
+    fn lock_cancel_requests(&self) -> MutexGuard<'_, HashMap<String, oneshot::Sender<()>>> {
+        // A panic while holding the lock can't leave a half-registered cancel sender behind, as
+        // every access is a single insert or remove, so a poisoned map is still safe to use
+        self.cancel_requests
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    async fn get(&self) -> ClientResult<deadpool_cyclone::Object<LocalUdsInstanceSpec>> {
+        self.cyclone_pool
+            .get()
+            .await
+            .map_err(|err| ClientError::CyclonePool(Box::new(err)))
+    }
+
+    /// Forwards the execution's output until it finishes, then returns its result.
+    async fn run<Success>(
+        &self,
+        mut progress: ExecutionStarted<UnixStream, Success>,
+        execution_id: &str,
+        output_tx: mpsc::Sender<OutputStream>,
+        callback_handler: Option<Arc<dyn CallbackHandler>>,
+    ) -> ClientResult<FunctionResult<Success>>
+    where
+        Success: DeserializeOwned + Unpin + fmt::Debug + Send + Sync + 'static,
+    {
+        let (cancel_tx, mut cancel_rx) = oneshot::channel();
+        self.lock_cancel_requests()
+            .insert(execution_id.to_string(), cancel_tx);
+        let mut cancelled = false;
+
+        loop {
+            let msg = tokio::select! {
+                msg = progress.next() => msg,
+                _ = &mut cancel_rx, if !cancelled => {
+                    cancelled = true;
+                    info!(execution_id, "cancelling embedded execution");
+                    if let Err(err) = progress.cancel(execution_id).await {
+                        self.lock_cancel_requests().remove(execution_id);
+                        return Err(execution_error(err));
+                    }
+                    continue;
+                }
+            };
+
+            match msg {
+                Some(Ok(ProgressMessage::OutputStream(output))) => {
+                    if let Err(err) = output_tx.send(output).await {
+                        warn!(error = ?err, "failed to send output message on channel");
+                    }
+                }
+                Some(Ok(ProgressMessage::Heartbeat)) => {
+                    trace!("received heartbeat message");
+                }
+                Some(Ok(ProgressMessage::CallbackRequest(request))) => {
+                    let response = callback::answer(callback_handler.as_ref(), request).await;
+                    if let Err(err) = progress.respond(&response).await {
+                        self.lock_cancel_requests().remove(execution_id);
+                        return Err(execution_error(err));
+                    }
+                }
+                Some(Err(err)) => {
+                    warn!(error = ?err, "next progress message was an error, bailing out");
+                    break;
+                }
+                None => break,
+            }
+        }
+
+        self.lock_cancel_requests().remove(execution_id);
+        progress.finish().await.map_err(execution_error)
+    }
+}
+
+impl fmt::Debug for EmbeddedExecutor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EmbeddedExecutor")
+            .field("cyclone_pool", &self.cyclone_pool.status())
+            .finish_non_exhaustive()
+    }
+}
+
+fn execution_error<Success>(err: ExecutionError<Success>) -> ClientError
+where
+    Success: fmt::Debug + Send + Sync + 'static,
+{
+    ClientError::Execution(Box::new(err))
+}
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use futures::{StreamExt, TryStreamExt};
 use nats_subscriber::{Subscriber, SubscriberError};
 use serde::{de::DeserializeOwned, Serialize};
@@ -6,25 +8,40 @@ use telemetry::prelude::*;
 use thiserror::Error;
 use tokio::sync::mpsc;
 use veritech_core::{
-    nats_action_run_subject, nats_reconciliation_subject, nats_resolver_function_subject,
-    nats_schema_variant_definition_subject, nats_subject, nats_validation_subject,
-    reply_mailbox_for_output, reply_mailbox_for_result, FINAL_MESSAGE_HEADER_KEY,
+    nats_action_run_subject, nats_cancel_execution_subject, nats_reconciliation_subject,
+    nats_resolver_function_subject, nats_schema_variant_definition_subject, nats_subject,
+    nats_validation_subject, reply_mailbox_for_callback, reply_mailbox_for_output,
+    reply_mailbox_for_result, FINAL_MESSAGE_HEADER_KEY, WORKSPACE_PK_HEADER_KEY,
 };
 
+pub use callback::CallbackHandler;
 pub use cyclone_core::{
-    encrypt_value_tree, ActionRunRequest, ActionRunResultSuccess, BeforeFunction, ComponentKind,
-    ComponentView, CycloneValueDecryptError, CycloneValueEncryptError, FunctionResult,
-    FunctionResultFailure, OutputStream, ReconciliationRequest, ReconciliationResultSuccess,
-    ResolverFunctionComponent, ResolverFunctionRequest, ResolverFunctionResponseType,
-    ResolverFunctionResultSuccess, ResourceStatus, SchemaVariantDefinitionRequest,
-    SchemaVariantDefinitionResultSuccess, SensitiveContainer, ValidationRequest,
-    ValidationResultSuccess,
+    encrypt_value_tree, ActionRunRequest, ActionRunResultSuccess, BeforeFunction, CallbackRequest,
+    CallbackResponse, ComponentKind, ComponentView, CycloneValueDecryptError,
+    CycloneValueEncryptError, ExceededLimit, ExecutionLimits, FunctionResult,
+    FunctionResultFailure, FunctionResultFailureError, OutputStream, ReconciliationRequest,
+    ReconciliationResultSuccess, ResolverFunctionComponent, ResolverFunctionRequest,
+    ResolverFunctionResponseType, ResolverFunctionResultSuccess, ResourceStatus,
+    SchemaVariantDefinitionRequest, SchemaVariantDefinitionResultSuccess, SensitiveContainer,
+    ValidationRequest, ValidationResultSuccess,
 };
+pub use embedded::EmbeddedExecutor;
 pub use si_crypto::{CycloneEncryptionKey, CycloneEncryptionKeyError};
 
+mod callback;
+mod embedded;
+
 #[remain::sorted]
 #[derive(Error, Debug)]
 pub enum ClientError {
+    #[error("cyclone error: {0}")]
+    Cyclone(#[from] deadpool_cyclone::ClientError),
+    #[error("cyclone pool error: {0}")]
+    CyclonePool(#[source] Box<dyn std::error::Error + Sync + Send + 'static>),
+    #[error("cyclone spec error: {0}")]
+    CycloneSpec(#[source] Box<dyn std::error::Error + Sync + Send + 'static>),
+    #[error("execution error: {0}")]
+    Execution(#[source] Box<dyn std::error::Error + Sync + Send + 'static>),
     #[error("failed to serialize json message")]
     JSONSerialize(#[source] serde_json::Error),
     #[error("nats error")]
@@ -43,16 +60,61 @@ pub type ClientResult<T> = Result<T, ClientError>;
 
 #[derive(Clone, Debug)]
 pub struct Client {
-    nats: NatsClient,
+    transport: Transport,
+    workspace_pk: Option<String>,
+    callback_handler: Option<Arc<dyn CallbackHandler>>,
+}
+
+/// How a [`Client`] gets its requests executed.
+#[derive(Clone, Debug)]
+enum Transport {
+    /// Requests go to a veritech server over NATS.
+    Nats(NatsClient),
+    /// Requests run on a cyclone pool in this process.
+    Embedded(EmbeddedExecutor),
 }
 
 impl Client {
     pub fn new(nats: NatsClient) -> Self {
-        Self { nats }
+        Self {
+            transport: Transport::Nats(nats),
+            workspace_pk: None,
+            callback_handler: None,
+        }
+    }
+
+    /// Creates a client which executes requests in this process rather than through a veritech
+    /// server, so that no NATS server is needed.
+    ///
+    /// Subjects passed to the `*_with_subject` methods are ignored, as there's nothing listening
+    /// on them.
+    pub fn embedded(executor: EmbeddedExecutor) -> Self {
+        Self {
+            transport: Transport::Embedded(executor),
+            workspace_pk: None,
+            callback_handler: None,
+        }
+    }
+
+    /// Returns a client whose requests are made on behalf of the given workspace, so that veritech
+    /// can share its capacity fairly between workspaces.
+    pub fn for_workspace(&self, workspace_pk: impl Into<String>) -> Self {
+        Self {
+            transport: self.transport.clone(),
+            workspace_pk: Some(workspace_pk.into()),
+            callback_handler: self.callback_handler.clone(),
+        }
     }
 
-    fn nats_subject_prefix(&self) -> Option<&str> {
-        self.nats.metadata().subject_prefix()
+    /// Returns a client whose executions have their callback requests answered by `handler`.
+    ///
+    /// Without a handler, functions asking their caller for data have their requests refused.
+    pub fn with_callback_handler(&self, handler: impl CallbackHandler) -> Self {
+        Self {
+            transport: self.transport.clone(),
+            workspace_pk: self.workspace_pk.clone(),
+            callback_handler: Some(Arc::new(handler)),
+        }
     }
 
     #[instrument(name = "client.execute_resolver_function", skip_all)]
@@ -61,12 +123,22 @@ impl Client {
         output_tx: mpsc::Sender<OutputStream>,
         request: &ResolverFunctionRequest,
     ) -> ClientResult<FunctionResult<ResolverFunctionResultSuccess>> {
-        self.execute_request(
-            nats_resolver_function_subject(self.nats_subject_prefix()),
-            output_tx,
-            request,
-        )
-        .await
+        match &self.transport {
+            Transport::Nats(nats) => {
+                self.execute_request(
+                    nats,
+                    nats_resolver_function_subject(nats_subject_prefix(nats)),
+                    output_tx,
+                    request,
+                )
+                .await
+            }
+            Transport::Embedded(embedded) => {
+                embedded
+                    .execute_resolver_function(output_tx, request, self.callback_handler.clone())
+                    .await
+            }
+        }
     }
 
     #[instrument(name = "client.execute_resolver_function_with_subject", skip_all)]
@@ -76,12 +148,22 @@ impl Client {
         request: &ResolverFunctionRequest,
         subject_suffix: impl AsRef<str>,
     ) -> ClientResult<FunctionResult<ResolverFunctionResultSuccess>> {
-        self.execute_request(
-            nats_subject(self.nats_subject_prefix(), subject_suffix),
-            output_tx,
-            request,
-        )
-        .await
+        match &self.transport {
+            Transport::Nats(nats) => {
+                self.execute_request(
+                    nats,
+                    nats_subject(nats_subject_prefix(nats), subject_suffix),
+                    output_tx,
+                    request,
+                )
+                .await
+            }
+            Transport::Embedded(embedded) => {
+                embedded
+                    .execute_resolver_function(output_tx, request, self.callback_handler.clone())
+                    .await
+            }
+        }
     }
 
     #[instrument(name = "client.execute_validation", skip_all)]
@@ -90,27 +172,47 @@ impl Client {
         output_tx: mpsc::Sender<OutputStream>,
         request: &ValidationRequest,
     ) -> ClientResult<FunctionResult<ValidationResultSuccess>> {
-        self.execute_request(
-            nats_validation_subject(self.nats_subject_prefix()),
-            output_tx,
-            request,
-        )
-        .await
+        match &self.transport {
+            Transport::Nats(nats) => {
+                self.execute_request(
+                    nats,
+                    nats_validation_subject(nats_subject_prefix(nats)),
+                    output_tx,
+                    request,
+                )
+                .await
+            }
+            Transport::Embedded(embedded) => {
+                embedded
+                    .execute_validation(output_tx, request, self.callback_handler.clone())
+                    .await
+            }
+        }
     }
 
     #[instrument(name = "client.execute_validation_with_subject", skip_all)]
     pub async fn execute_validation_with_subject(
         &self,
         output_tx: mpsc::Sender<OutputStream>,
-        request: &ValidationResultSuccess,
+        request: &ValidationRequest,
         subject_suffix: impl AsRef<str>,
     ) -> ClientResult<FunctionResult<ValidationResultSuccess>> {
-        self.execute_request(
-            nats_subject(self.nats_subject_prefix(), subject_suffix),
-            output_tx,
-            request,
-        )
-        .await
+        match &self.transport {
+            Transport::Nats(nats) => {
+                self.execute_request(
+                    nats,
+                    nats_subject(nats_subject_prefix(nats), subject_suffix),
+                    output_tx,
+                    request,
+                )
+                .await
+            }
+            Transport::Embedded(embedded) => {
+                embedded
+                    .execute_validation(output_tx, request, self.callback_handler.clone())
+                    .await
+            }
+        }
     }
 
     #[instrument(name = "client.execute_action_run", skip_all)]
@@ -119,12 +221,22 @@ impl Client {
         output_tx: mpsc::Sender<OutputStream>,
         request: &ActionRunRequest,
     ) -> ClientResult<FunctionResult<ActionRunResultSuccess>> {
-        self.execute_request(
-            nats_action_run_subject(self.nats_subject_prefix()),
-            output_tx,
-            request,
-        )
-        .await
+        match &self.transport {
+            Transport::Nats(nats) => {
+                self.execute_request(
+                    nats,
+                    nats_action_run_subject(nats_subject_prefix(nats)),
+                    output_tx,
+                    request,
+                )
+                .await
+            }
+            Transport::Embedded(embedded) => {
+                embedded
+                    .execute_action_run(output_tx, request, self.callback_handler.clone())
+                    .await
+            }
+        }
     }
 
     #[instrument(name = "client.execute_action_run_with_subject", skip_all)]
@@ -134,12 +246,22 @@ impl Client {
         request: &ActionRunRequest,
         subject_suffix: impl AsRef<str>,
     ) -> ClientResult<FunctionResult<ActionRunResultSuccess>> {
-        self.execute_request(
-            nats_subject(self.nats_subject_prefix(), subject_suffix),
-            output_tx,
-            request,
-        )
-        .await
+        match &self.transport {
+            Transport::Nats(nats) => {
+                self.execute_request(
+                    nats,
+                    nats_subject(nats_subject_prefix(nats), subject_suffix),
+                    output_tx,
+                    request,
+                )
+                .await
+            }
+            Transport::Embedded(embedded) => {
+                embedded
+                    .execute_action_run(output_tx, request, self.callback_handler.clone())
+                    .await
+            }
+        }
     }
 
     #[instrument(name = "client.execute_reconciliation", skip_all)]
@@ -148,12 +270,22 @@ impl Client {
         output_tx: mpsc::Sender<OutputStream>,
         request: &ReconciliationRequest,
     ) -> ClientResult<FunctionResult<ReconciliationResultSuccess>> {
-        self.execute_request(
-            nats_reconciliation_subject(self.nats_subject_prefix()),
-            output_tx,
-            request,
-        )
-        .await
+        match &self.transport {
+            Transport::Nats(nats) => {
+                self.execute_request(
+                    nats,
+                    nats_reconciliation_subject(nats_subject_prefix(nats)),
+                    output_tx,
+                    request,
+                )
+                .await
+            }
+            Transport::Embedded(embedded) => {
+                embedded
+                    .execute_reconciliation(output_tx, request, self.callback_handler.clone())
+                    .await
+            }
+        }
     }
 
     #[instrument(name = "client.execute_reconciliation_with_subject", skip_all)]
@@ -163,12 +295,22 @@ impl Client {
         request: &ReconciliationRequest,
         subject_suffix: impl AsRef<str>,
     ) -> ClientResult<FunctionResult<ReconciliationResultSuccess>> {
-        self.execute_request(
-            nats_subject(self.nats_subject_prefix(), subject_suffix),
-            output_tx,
-            request,
-        )
-        .await
+        match &self.transport {
+            Transport::Nats(nats) => {
+                self.execute_request(
+                    nats,
+                    nats_subject(nats_subject_prefix(nats), subject_suffix),
+                    output_tx,
+                    request,
+                )
+                .await
+            }
+            Transport::Embedded(embedded) => {
+                embedded
+                    .execute_reconciliation(output_tx, request, self.callback_handler.clone())
+                    .await
+            }
+        }
     }
 
     #[instrument(name = "client.execute_reconciliation", skip_all)]
@@ -177,12 +319,26 @@ impl Client {
         output_tx: mpsc::Sender<OutputStream>,
         request: &SchemaVariantDefinitionRequest,
     ) -> ClientResult<FunctionResult<SchemaVariantDefinitionResultSuccess>> {
-        self.execute_request(
-            nats_schema_variant_definition_subject(self.nats_subject_prefix()),
-            output_tx,
-            request,
-        )
-        .await
+        match &self.transport {
+            Transport::Nats(nats) => {
+                self.execute_request(
+                    nats,
+                    nats_schema_variant_definition_subject(nats_subject_prefix(nats)),
+                    output_tx,
+                    request,
+                )
+                .await
+            }
+            Transport::Embedded(embedded) => {
+                embedded
+                    .execute_schema_variant_definition(
+                        output_tx,
+                        request,
+                        self.callback_handler.clone(),
+                    )
+                    .await
+            }
+        }
     }
 
     #[instrument(name = "client.execute_reconciliation_with_subject", skip_all)]
@@ -192,16 +348,50 @@ impl Client {
         request: &SchemaVariantDefinitionRequest,
         subject_suffix: impl AsRef<str>,
     ) -> ClientResult<FunctionResult<SchemaVariantDefinitionResultSuccess>> {
-        self.execute_request(
-            nats_subject(self.nats_subject_prefix(), subject_suffix),
-            output_tx,
-            request,
-        )
-        .await
+        match &self.transport {
+            Transport::Nats(nats) => {
+                self.execute_request(
+                    nats,
+                    nats_subject(nats_subject_prefix(nats), subject_suffix),
+                    output_tx,
+                    request,
+                )
+                .await
+            }
+            Transport::Embedded(embedded) => {
+                embedded
+                    .execute_schema_variant_definition(
+                        output_tx,
+                        request,
+                        self.callback_handler.clone(),
+                    )
+                    .await
+            }
+        }
+    }
+
+    /// Asks veritech to cancel the execution with the given id, if it's still running.
+    ///
+    /// The caller waiting on the execution gets a cancelled [`FunctionResultFailure`] as its
+    /// result, unless the function finished first.
+    #[instrument(name = "client.cancel_execution", skip(self))]
+    pub async fn cancel_execution(&self, execution_id: &str) -> ClientResult<()> {
+        match &self.transport {
+            Transport::Nats(nats) => {
+                nats.publish(
+                    nats_cancel_execution_subject(nats_subject_prefix(nats), execution_id),
+                    vec![].into(),
+                )
+                .await?;
+            }
+            Transport::Embedded(embedded) => embedded.cancel_execution(execution_id),
+        }
+        Ok(())
     }
 
     async fn execute_request<R, S>(
         &self,
+        nats: &NatsClient,
         subject: impl Into<String>,
         output_tx: mpsc::Sender<OutputStream>,
         request: &R,
@@ -211,7 +401,7 @@ impl Client {
         S: DeserializeOwned,
     {
         let msg = serde_json::to_vec(request).map_err(ClientError::JSONSerialize)?;
-        let reply_mailbox_root = self.nats.new_inbox();
+        let reply_mailbox_root = nats.new_inbox();
 
         // Construct a subscriber stream for the result
         let result_subscriber_subject = reply_mailbox_for_result(&reply_mailbox_root);
@@ -222,7 +412,7 @@ impl Client {
         let mut result_subscriber: Subscriber<FunctionResult<S>> =
             Subscriber::create(result_subscriber_subject)
                 .final_message_header_key(FINAL_MESSAGE_HEADER_KEY)
-                .start(&self.nats)
+                .start(nats)
                 .await?;
 
         // Construct a subscriber stream for output messages
@@ -233,12 +423,33 @@ impl Client {
         );
         let output_subscriber = Subscriber::create(output_subscriber_subject)
             .final_message_header_key(FINAL_MESSAGE_HEADER_KEY)
-            .start(&self.nats)
+            .start(nats)
             .await?;
 
         // Spawn a task to forward output to the sender provided by the caller
         tokio::spawn(forward_output_task(output_subscriber, output_tx));
 
+        // Spawn a task to answer callback requests, if there's anything to answer them with.
+        // Otherwise nobody is listening, so veritech refuses them when its requests go unanswered.
+        let callback_task = match &self.callback_handler {
+            Some(handler) => {
+                let callback_subscriber_subject = reply_mailbox_for_callback(&reply_mailbox_root);
+                trace!(
+                    messaging.destination = &callback_subscriber_subject.as_str(),
+                    "subscribing for callback requests"
+                );
+                let callback_subscriber = nats.subscribe(callback_subscriber_subject).await?;
+                Some(tokio::spawn(callback::answer_callbacks_task(
+                    nats.clone(),
+                    callback_subscriber,
+                    handler.clone(),
+                )))
+            }
+            None => None,
+        };
+        // Stops answering callback requests once the execution is over, however that happens
+        let _callback_task_guard = AbortOnDrop(callback_task);
+
         // Submit the request message
         let subject = subject.into();
         trace!(
@@ -247,11 +458,25 @@ impl Client {
         );
 
         // Root reply mailbox will receive a reply if nobody is listening to the channel `subject`
-        let mut root_subscriber = self.nats.subscribe(reply_mailbox_root.clone()).await?;
+        let mut root_subscriber = nats.subscribe(reply_mailbox_root.clone()).await?;
 
-        self.nats
-            .publish_with_reply(subject, reply_mailbox_root.clone(), msg.into())
-            .await?;
+        match &self.workspace_pk {
+            Some(workspace_pk) => {
+                let mut headers = si_data_nats::HeaderMap::new();
+                headers.insert(WORKSPACE_PK_HEADER_KEY, workspace_pk.as_str());
+                nats.publish_with_reply_and_headers(
+                    subject,
+                    reply_mailbox_root.clone(),
+                    headers,
+                    msg.into(),
+                )
+                .await?;
+            }
+            None => {
+                nats.publish_with_reply(subject, reply_mailbox_root.clone(), msg.into())
+                    .await?;
+            }
+        }
 
         tokio::select! {
             // Wait for one message on the result reply mailbox
@@ -288,6 +513,21 @@ impl Client {
     }
 }
 
+/// Aborts the task, if any, when dropped.
+struct AbortOnDrop(Option<tokio::task::JoinHandle<()>>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        if let Some(task) = &self.0 {
+            task.abort();
+        }
+    }
+}
+
+fn nats_subject_prefix(nats: &NatsClient) -> Option<&str> {
+    nats.metadata().subject_prefix()
+}
+
 async fn forward_output_task(
     mut output_subscriber: Subscriber<OutputStream>,
     output_tx: mpsc::Sender<OutputStream>,
@@ -1,17 +1,17 @@
-use std::env;
+use std::{env, future::Future, pin::Pin};
 
 use base64::{engine::general_purpose, Engine};
 use cyclone_core::{
-    ComponentKind, ComponentView, FunctionResult, ResolverFunctionComponent,
-    ResolverFunctionRequest, ResolverFunctionResponseType, SchemaVariantDefinitionRequest,
-    ValidationRequest,
+    CallbackRequest, CallbackResponse, ComponentKind, ComponentView, FunctionResult,
+    ResolverFunctionComponent, ResolverFunctionRequest, ResolverFunctionResponseType,
+    SchemaVariantDefinitionRequest, ValidationRequest,
 };
 use si_data_nats::{NatsClient, NatsConfig};
 use test_log::test;
 use tokio::{sync::mpsc, task::JoinHandle};
 use tracing::info;
 use uuid::Uuid;
-use veritech_client::Client;
+use veritech_client::{CallbackHandler, Client, EmbeddedExecutor};
 use veritech_server::{
     Config, CycloneSpec, Instance, LocalUdsInstance, Server, ServerError, StandardConfig,
 };
@@ -36,12 +36,12 @@ fn nats_prefix() -> String {
     Uuid::new_v4().as_simple().to_string()
 }
 
-async fn veritech_server_for_uds_cyclone(subject_prefix: String) -> Server {
+fn cyclone_spec() -> CycloneSpec {
     let mut config_file = veritech_server::ConfigFile::default_local_uds();
     veritech_server::detect_and_configure_development(&mut config_file)
         .expect("failed to determine test configuration");
 
-    let cyclone_spec = CycloneSpec::LocalUds(
+    CycloneSpec::LocalUds(
         LocalUdsInstance::spec()
             .try_cyclone_cmd_path(config_file.cyclone.cyclone_cmd_path())
             .expect("failed to setup cyclone_cmd_path")
@@ -51,10 +51,13 @@ async fn veritech_server_for_uds_cyclone(subject_prefix: String) -> Server {
             .all_endpoints()
             .build()
             .expect("failed to build cyclone spec"),
-    );
+    )
+}
+
+async fn veritech_server_for_uds_cyclone(subject_prefix: String) -> Server {
     let config = Config::builder()
         .nats(nats_config(subject_prefix.clone()))
-        .cyclone_spec(cyclone_spec)
+        .cyclone_spec(cyclone_spec())
         .build()
         .expect("failed to build spec");
     Server::for_cyclone_uds(config)
@@ -66,6 +69,16 @@ async fn client(subject_prefix: String) -> Client {
     Client::new(nats(subject_prefix).await)
 }
 
+async fn embedded_client() -> Client {
+    let executor = match cyclone_spec() {
+        CycloneSpec::LocalUds(spec) => EmbeddedExecutor::for_cyclone_uds(spec)
+            .await
+            .expect("failed to create embedded executor"),
+        CycloneSpec::LocalHttp(_) => unreachable!("spec is always LocalUds"),
+    };
+    Client::embedded(executor)
+}
+
 async fn run_veritech_server_for_uds_cyclone(
     subject_prefix: String,
 ) -> JoinHandle<Result<(), ServerError>> {
@@ -76,6 +89,72 @@ fn base64_encode(input: impl AsRef<[u8]>) -> String {
     general_purpose::STANDARD_NO_PAD.encode(input)
 }
 
+/// Answers `name` callback requests with a fixed name.
+struct NameHandler;
+
+impl CallbackHandler for NameHandler {
+    fn handle(
+        &self,
+        request: CallbackRequest,
+    ) -> Pin<Box<dyn Future<Output = CallbackResponse> + Send + 'static>> {
+        Box::pin(async move {
+            match request.kind.as_str() {
+                "name" => CallbackResponse::success(request.id, serde_json::json!("starfield")),
+                kind => CallbackResponse::failure(request.id, format!("unknown kind: {kind}")),
+            }
+        })
+    }
+}
+
+/// A resolver function which returns what its caller answers when asked for a name, or
+/// `"refused"` if its request is refused.
+fn ask_caller_request(execution_id: &str) -> ResolverFunctionRequest {
+    ResolverFunctionRequest {
+        execution_id: execution_id.to_string(),
+        handler: "askName".to_string(),
+        component: ResolverFunctionComponent {
+            data: ComponentView {
+                properties: serde_json::json!({}),
+                kind: ComponentKind::Standard,
+            },
+            parents: vec![],
+        },
+        response_type: ResolverFunctionResponseType::String,
+        code_base64: base64_encode(
+            "async function askName() {
+                try { return await askCaller(\"name\"); } catch { return \"refused\"; }
+            }",
+        ),
+        before: vec![],
+        limits: None,
+        runtime: None,
+    }
+}
+
+async fn execute_ask_caller(client: &Client, execution_id: &str) -> serde_json::Value {
+    let (tx, mut rx) = mpsc::channel(64);
+    tokio::spawn(async move {
+        while let Some(output) = rx.recv().await {
+            info!("output: {:?}", output)
+        }
+    });
+
+    let result = client
+        .execute_resolver_function(tx, &ask_caller_request(execution_id))
+        .await
+        .expect("failed to execute resolver function");
+
+    match result {
+        FunctionResult::Success(success) => {
+            assert_eq!(success.execution_id, execution_id);
+            success.data
+        }
+        FunctionResult::Failure(failure) => {
+            panic!("function did not succeed and should have: {failure:?}")
+        }
+    }
+}
+
 #[allow(clippy::disallowed_methods)] // `$RUST_LOG` is checked for in macro
 #[test(tokio::test)]
 async fn executes_simple_resolver_function() {
@@ -106,6 +185,8 @@ async fn executes_simple_resolver_function() {
             "function numberOfInputs(input) { return Object.keys(input)?.length ?? 0; }",
         ),
         before: vec![],
+        limits: None,
+        runtime: None,
     };
 
     let result = client
@@ -125,6 +206,53 @@ async fn executes_simple_resolver_function() {
     }
 }
 
+#[allow(clippy::disallowed_methods)] // `$RUST_LOG` is checked for in macro
+#[test(tokio::test)]
+async fn executes_simple_resolver_function_embedded() {
+    let client = embedded_client().await;
+
+    let (tx, mut rx) = mpsc::channel(64);
+    tokio::spawn(async move {
+        while let Some(output) = rx.recv().await {
+            info!("output: {:?}", output)
+        }
+    });
+
+    let request = ResolverFunctionRequest {
+        execution_id: "5678".to_string(),
+        handler: "numberOfInputs".to_string(),
+        component: ResolverFunctionComponent {
+            data: ComponentView {
+                properties: serde_json::json!({ "foo": "bar" }),
+                kind: ComponentKind::Standard,
+            },
+            parents: vec![],
+        },
+        response_type: ResolverFunctionResponseType::Integer,
+        code_base64: base64_encode(
+            "function numberOfInputs(input) { return Object.keys(input)?.length ?? 0; }",
+        ),
+        before: vec![],
+        limits: None,
+        runtime: None,
+    };
+
+    let result = client
+        .execute_resolver_function(tx, &request)
+        .await
+        .expect("failed to execute resolver function");
+
+    match result {
+        FunctionResult::Success(success) => {
+            assert_eq!(success.execution_id, "5678");
+            assert_eq!(success.data, serde_json::json!(1));
+        }
+        FunctionResult::Failure(failure) => {
+            panic!("function did not succeed and should have: {failure:?}")
+        }
+    }
+}
+
 #[allow(clippy::disallowed_methods)] // `$RUST_LOG` is checked for in macro
 #[test(tokio::test)]
 async fn type_checks_resolve_function() {
@@ -173,6 +301,8 @@ async fn type_checks_resolve_function() {
             response_type,
             code_base64: base64_encode("function returnInputValue(input) { return input.value; }"),
             before: vec![],
+            limits: None,
+            runtime: None,
         };
 
         let result = client
@@ -235,6 +365,8 @@ async fn type_checks_resolve_function() {
             response_type: response_type.clone(),
             code_base64: base64_encode("function returnInputValue(input) { return input.value; }"),
             before: vec![],
+            limits: None,
+            runtime: None,
         };
 
         let result = client
@@ -278,6 +410,8 @@ async fn executes_simple_validation() {
             "function isThirtyThree(value) { return { valid: value === 33 }; };",
         ),
         before: vec![],
+        limits: None,
+        runtime: None,
     };
 
     let result = client
@@ -322,6 +456,8 @@ async fn executes_simple_schema_variant_definition() {
                     };
                 }",
         ),
+        limits: None,
+        runtime: None,
     };
 
     let result = client
@@ -349,3 +485,105 @@ async fn executes_simple_schema_variant_definition() {
         }
     }
 }
+
+#[allow(clippy::disallowed_methods)] // `$RUST_LOG` is checked for in macro
+#[test(tokio::test)]
+async fn answers_callback_requests() {
+    let prefix = nats_prefix();
+    run_veritech_server_for_uds_cyclone(prefix.clone()).await;
+    let client = client(prefix).await.with_callback_handler(NameHandler);
+
+    assert_eq!(
+        serde_json::json!("starfield"),
+        execute_ask_caller(&client, "callback-1").await
+    );
+}
+
+#[allow(clippy::disallowed_methods)] // `$RUST_LOG` is checked for in macro
+#[test(tokio::test)]
+async fn refuses_callback_requests_without_handler() {
+    let prefix = nats_prefix();
+    run_veritech_server_for_uds_cyclone(prefix.clone()).await;
+    let client = client(prefix).await;
+
+    assert_eq!(
+        serde_json::json!("refused"),
+        execute_ask_caller(&client, "callback-2").await
+    );
+}
+
+#[allow(clippy::disallowed_methods)] // `$RUST_LOG` is checked for in macro
+#[test(tokio::test)]
+async fn answers_callback_requests_embedded() {
+    let client = embedded_client().await.with_callback_handler(NameHandler);
+
+    assert_eq!(
+        serde_json::json!("starfield"),
+        execute_ask_caller(&client, "callback-3").await
+    );
+}
+
+#[allow(clippy::disallowed_methods)] // `$RUST_LOG` is checked for in macro
+#[test(tokio::test)]
+async fn cancels_an_in_flight_resolver_function() {
+    let prefix = nats_prefix();
+    run_veritech_server_for_uds_cyclone(prefix.clone()).await;
+    let client = client(prefix).await;
+
+    let request = ResolverFunctionRequest {
+        execution_id: "cancel-1".to_string(),
+        handler: "sleepy".to_string(),
+        component: ResolverFunctionComponent {
+            data: ComponentView {
+                properties: serde_json::json!({}),
+                kind: ComponentKind::Standard,
+            },
+            parents: vec![],
+        },
+        response_type: ResolverFunctionResponseType::Object,
+        code_base64: base64_encode(
+            r#"async function sleepy() {
+                console.log("started");
+                await new Promise((resolve) => setTimeout(resolve, 60000));
+                return { a: 'b' };
+            }"#,
+        ),
+        before: vec![],
+        limits: None,
+        runtime: None,
+    };
+
+    let (tx, mut rx) = mpsc::channel(64);
+    let execution = {
+        let client = client.clone();
+        tokio::spawn(async move { client.execute_resolver_function(tx, &request).await })
+    };
+
+    // Output is only relayed once veritech is listening for cancel requests
+    let output = rx.recv().await.expect("execution ended without output");
+    info!("output: {:?}", output);
+    tokio::spawn(async move {
+        while let Some(output) = rx.recv().await {
+            info!("output: {:?}", output)
+        }
+    });
+    client
+        .cancel_execution("cancel-1")
+        .await
+        .expect("failed to cancel execution");
+
+    let result = execution
+        .await
+        .expect("execution task panicked")
+        .expect("failed to execute resolver function");
+
+    match result {
+        FunctionResult::Success(success) => {
+            panic!("function succeeded and should have been cancelled: {success:?}")
+        }
+        FunctionResult::Failure(failure) => {
+            assert!(failure.error.is_cancelled());
+            assert_eq!("cancel-1", failure.execution_id);
+        }
+    }
+}
@@ -12,12 +12,14 @@
 )]
 
 const NATS_ACTION_RUN_DEFAULT_SUBJECT: &str = "veritech.fn.actionrun";
+const NATS_CANCEL_EXECUTION_DEFAULT_SUBJECT: &str = "veritech.cancel";
 const NATS_CONCILIATION_DEFAULT_SUBJECT: &str = "veritech.fn.reconciliation";
 const NATS_RESOLVER_FUNCTION_DEFAULT_SUBJECT: &str = "veritech.fn.resolverfunction";
 const NATS_SCHEMA_VARIANT_DEFINITION_DEFAULT_SUBJECT: &str = "veritech.fn.schemavariantdefinition";
 const NATS_VALIDATION_DEFAULT_SUBJECT: &str = "veritech.fn.validation";
 
 pub const FINAL_MESSAGE_HEADER_KEY: &str = "X-Final-Message";
+pub const WORKSPACE_PK_HEADER_KEY: &str = "X-Workspace-Pk";
 
 pub fn reply_mailbox_for_output(reply_mailbox: &str) -> String {
     format!("{reply_mailbox}.output")
@@ -27,6 +29,12 @@ pub fn reply_mailbox_for_result(reply_mailbox: &str) -> String {
     format!("{reply_mailbox}.result")
 }
 
+/// The subject on which callback requests from the execution replying to `reply_mailbox` are
+/// relayed to its caller.
+pub fn reply_mailbox_for_callback(reply_mailbox: &str) -> String {
+    format!("{reply_mailbox}.callback")
+}
+
 pub fn nats_resolver_function_subject(prefix: Option<&str>) -> String {
     nats_subject(prefix, NATS_RESOLVER_FUNCTION_DEFAULT_SUBJECT)
 }
@@ -47,6 +55,14 @@ pub fn nats_schema_variant_definition_subject(prefix: Option<&str>) -> String {
     nats_subject(prefix, NATS_SCHEMA_VARIANT_DEFINITION_DEFAULT_SUBJECT)
 }
 
+/// The subject on which requests to cancel the execution with the given id are published.
+pub fn nats_cancel_execution_subject(prefix: Option<&str>, execution_id: &str) -> String {
+    nats_subject(
+        prefix,
+        format!("{NATS_CANCEL_EXECUTION_DEFAULT_SUBJECT}.{execution_id}"),
+    )
+}
+
 pub fn nats_subject(prefix: Option<&str>, suffix: impl AsRef<str>) -> String {
     let suffix = suffix.as_ref();
     match prefix {
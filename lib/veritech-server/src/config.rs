@@ -1,5 +1,6 @@
-use deadpool_cyclone::PoolNoodle;
+use deadpool_cyclone::{PoolNoodle, PoolNoodleConfig};
 use std::{
+    collections::{HashMap, HashSet},
     env,
     net::{SocketAddr, ToSocketAddrs},
     path::{Path, PathBuf},
@@ -22,6 +23,8 @@ use thiserror::Error;
 
 pub use si_settings::{StandardConfig, StandardConfigFile};
 
+use crate::SchedulerConfig;
+
 #[remain::sorted]
 #[derive(Debug, Error)]
 pub enum ConfigError {
@@ -51,6 +54,9 @@ pub struct Config {
     nats: NatsConfig,
 
     cyclone_spec: CycloneSpec,
+
+    #[builder(default)]
+    scheduler: SchedulerConfig,
 }
 
 #[remain::sorted]
@@ -68,6 +74,8 @@ impl StandardConfig for Config {
 pub struct ConfigFile {
     pub nats: NatsConfig,
     pub cyclone: CycloneConfig,
+    #[serde(default)]
+    pub scheduler: SchedulerConfig,
 }
 
 impl ConfigFile {
@@ -75,6 +83,7 @@ impl ConfigFile {
         Self {
             nats: Default::default(),
             cyclone: CycloneConfig::default_local_http(),
+            scheduler: Default::default(),
         }
     }
 
@@ -82,6 +91,7 @@ impl ConfigFile {
         Self {
             nats: Default::default(),
             cyclone: CycloneConfig::default_local_uds(),
+            scheduler: Default::default(),
         }
     }
 }
@@ -99,6 +109,7 @@ impl TryFrom<ConfigFile> for Config {
         let mut config = Config::builder();
         config.nats(value.nats);
         config.cyclone_spec(value.cyclone.try_into()?);
+        config.scheduler(value.scheduler);
         config.build().map_err(Into::into)
     }
 }
@@ -115,6 +126,12 @@ impl Config {
         &self.nats
     }
 
+    /// Gets a reference to the config's execution scheduler limits.
+    #[must_use]
+    pub fn scheduler(&self) -> &SchedulerConfig {
+        &self.scheduler
+    }
+
     /// Gets a reference to the config's subject prefix.
     pub fn subject_prefix(&self) -> Option<&str> {
         self.nats.subject_prefix.as_deref()
@@ -165,6 +182,15 @@ pub enum CycloneConfig {
         cyclone_decryption_key_path: String,
         #[serde(default = "default_lang_server_cmd_path")]
         lang_server_cmd_path: String,
+        /// Lang servers for function runtimes, keyed by runtime. Each reads its request as a line
+        /// of JSON on stdin, which is closed after the request unless the runtime is listed in
+        /// `runtime_lang_server_callbacks`.
+        #[serde(default)]
+        runtime_lang_server_cmd_paths: HashMap<String, String>,
+        /// Runtimes whose lang servers answer callback requests, reading a line of JSON on stdin
+        /// per response, so they must not wait for stdin to reach EOF.
+        #[serde(default)]
+        runtime_lang_server_callbacks: HashSet<String>,
         #[serde(default)]
         socket_strategy: LocalHttpSocketStrategy,
         #[serde(default)]
@@ -185,6 +211,15 @@ pub enum CycloneConfig {
         cyclone_decryption_key_path: String,
         #[serde(default = "default_lang_server_cmd_path")]
         lang_server_cmd_path: String,
+        /// Lang servers for function runtimes, keyed by runtime. Each reads its request as a line
+        /// of JSON on stdin, which is closed after the request unless the runtime is listed in
+        /// `runtime_lang_server_callbacks`.
+        #[serde(default)]
+        runtime_lang_server_cmd_paths: HashMap<String, String>,
+        /// Runtimes whose lang servers answer callback requests, reading a line of JSON on stdin
+        /// per response, so they must not wait for stdin to reach EOF.
+        #[serde(default)]
+        runtime_lang_server_callbacks: HashSet<String>,
         #[serde(default)]
         socket_strategy: LocalUdsSocketStrategy,
         #[serde(default)]
@@ -202,6 +237,14 @@ pub enum CycloneConfig {
         #[serde(default)]
         pool_size: u16,
         #[serde(default)]
+        pool_min_ready: Option<u32>,
+        #[serde(default)]
+        pool_max_ready: Option<u32>,
+        #[serde(default)]
+        pool_max_prepare_failures: Option<u32>,
+        #[serde(default)]
+        warm_pool_size: u16,
+        #[serde(default)]
         connect_timeout: u64,
     },
 }
@@ -212,6 +255,8 @@ impl CycloneConfig {
             cyclone_cmd_path: default_cyclone_cmd_path(),
             cyclone_decryption_key_path: default_cyclone_decryption_key_path(),
             lang_server_cmd_path: default_lang_server_cmd_path(),
+            runtime_lang_server_cmd_paths: HashMap::new(),
+            runtime_lang_server_callbacks: HashSet::new(),
             socket_strategy: Default::default(),
             watch_timeout: Default::default(),
             limit_requets: default_limit_requests(),
@@ -226,6 +271,8 @@ impl CycloneConfig {
             cyclone_cmd_path: default_cyclone_cmd_path(),
             cyclone_decryption_key_path: default_cyclone_decryption_key_path(),
             lang_server_cmd_path: default_lang_server_cmd_path(),
+            runtime_lang_server_cmd_paths: HashMap::new(),
+            runtime_lang_server_callbacks: HashSet::new(),
             socket_strategy: Default::default(),
             runtime_strategy: default_runtime_strategy(),
             watch_timeout: Default::default(),
@@ -234,6 +281,10 @@ impl CycloneConfig {
             resolver: default_enable_endpoint(),
             action: default_enable_endpoint(),
             pool_size: default_pool_size(),
+            pool_min_ready: None,
+            pool_max_ready: None,
+            pool_max_prepare_failures: None,
+            warm_pool_size: Default::default(),
             connect_timeout: default_connect_timeout(),
         }
     }
@@ -356,6 +407,8 @@ impl TryFrom<CycloneConfig> for CycloneSpec {
                 cyclone_cmd_path,
                 cyclone_decryption_key_path,
                 lang_server_cmd_path,
+                runtime_lang_server_cmd_paths,
+                runtime_lang_server_callbacks,
                 socket_strategy,
                 runtime_strategy,
                 watch_timeout,
@@ -364,6 +417,10 @@ impl TryFrom<CycloneConfig> for CycloneSpec {
                 resolver,
                 action,
                 pool_size,
+                pool_min_ready,
+                pool_max_ready,
+                pool_max_prepare_failures,
+                warm_pool_size,
                 connect_timeout,
             } => {
                 let mut builder = LocalUdsInstance::spec();
@@ -377,6 +434,14 @@ impl TryFrom<CycloneConfig> for CycloneSpec {
                     builder
                         .try_lang_server_cmd_path(lang_server_cmd_path)
                         .map_err(ConfigError::cyclone_spec_build)?;
+                    for (runtime, path) in runtime_lang_server_cmd_paths {
+                        builder
+                            .try_runtime_lang_server_cmd_path(runtime, path)
+                            .map_err(ConfigError::cyclone_spec_build)?;
+                    }
+                }
+                for runtime in runtime_lang_server_callbacks {
+                    builder.runtime_lang_server_callbacks(runtime);
                 }
                 builder.socket_strategy(socket_strategy);
                 builder.runtime_strategy(runtime_strategy);
@@ -395,7 +460,20 @@ impl TryFrom<CycloneConfig> for CycloneSpec {
                 }
                 builder.pool_size(pool_size);
                 builder.connect_timeout(connect_timeout);
-                builder.pool_noodle(PoolNoodle::new(pool_size.into()));
+                let mut pool_noodle_config = PoolNoodleConfig::new(pool_size.into());
+                if let Some(min_ready) = pool_min_ready {
+                    pool_noodle_config.min_ready = min_ready;
+                }
+                if let Some(max_ready) = pool_max_ready {
+                    pool_noodle_config.max_ready = max_ready;
+                }
+                if let Some(max_prepare_failures) = pool_max_prepare_failures {
+                    pool_noodle_config.max_prepare_failures = max_prepare_failures;
+                }
+                builder.pool_noodle(
+                    PoolNoodle::new(pool_noodle_config).map_err(ConfigError::cyclone_spec_build)?,
+                );
+                builder.warm_pool_size(warm_pool_size);
 
                 Ok(Self::LocalUds(
                     builder.build().map_err(ConfigError::cyclone_spec_build)?,
@@ -405,6 +483,8 @@ impl TryFrom<CycloneConfig> for CycloneSpec {
                 cyclone_cmd_path,
                 cyclone_decryption_key_path,
                 lang_server_cmd_path,
+                runtime_lang_server_cmd_paths,
+                runtime_lang_server_callbacks,
                 socket_strategy,
                 watch_timeout,
                 limit_requets,
@@ -420,6 +500,14 @@ impl TryFrom<CycloneConfig> for CycloneSpec {
                 builder
                     .try_lang_server_cmd_path(lang_server_cmd_path)
                     .map_err(ConfigError::cyclone_spec_build)?;
+                for (runtime, path) in runtime_lang_server_cmd_paths {
+                    builder
+                        .try_runtime_lang_server_cmd_path(runtime, path)
+                        .map_err(ConfigError::cyclone_spec_build)?;
+                }
+                for runtime in runtime_lang_server_callbacks {
+                    builder.runtime_lang_server_callbacks(runtime);
+                }
                 builder.socket_strategy(socket_strategy);
                 if let Some(watch_timeout) = watch_timeout {
                     builder.watch_timeout(watch_timeout);
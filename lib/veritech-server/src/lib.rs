@@ -1,5 +1,6 @@
 mod config;
 mod publisher;
+mod scheduler;
 mod server;
 mod subscriber;
 
@@ -8,6 +9,7 @@ pub use crate::{
         detect_and_configure_development, Config, ConfigBuilder, ConfigError, ConfigFile,
         CycloneSpec, CycloneStream, StandardConfig, StandardConfigFile,
     },
+    scheduler::SchedulerConfig,
     server::{Server, ServerError, VeritechShutdownHandle},
 };
 pub(crate) use crate::{
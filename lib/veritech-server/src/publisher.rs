@@ -1,8 +1,12 @@
-use deadpool_cyclone::{FunctionResult, OutputStream};
+use deadpool_cyclone::{CallbackRequest, CallbackResponse, FunctionResult, OutputStream};
 use serde::Serialize;
 use si_data_nats::{NatsClient, Subject};
+use telemetry::prelude::*;
 use thiserror::Error;
-use veritech_core::{reply_mailbox_for_output, reply_mailbox_for_result, FINAL_MESSAGE_HEADER_KEY};
+use veritech_core::{
+    reply_mailbox_for_callback, reply_mailbox_for_output, reply_mailbox_for_result,
+    FINAL_MESSAGE_HEADER_KEY,
+};
 
 #[remain::sorted]
 #[derive(Error, Debug)]
@@ -18,6 +22,7 @@ type Result<T> = std::result::Result<T, PublisherError>;
 #[derive(Debug)]
 pub struct Publisher<'a> {
     nats: &'a NatsClient,
+    reply_mailbox_callback: Subject,
     reply_mailbox_output: Subject,
     reply_mailbox_result: Subject,
 }
@@ -26,6 +31,7 @@ impl<'a> Publisher<'a> {
     pub fn new(nats: &'a NatsClient, reply_mailbox: &str) -> Self {
         Self {
             nats,
+            reply_mailbox_callback: reply_mailbox_for_callback(reply_mailbox).into(),
             reply_mailbox_output: reply_mailbox_for_output(reply_mailbox).into(),
             reply_mailbox_result: reply_mailbox_for_result(reply_mailbox).into(),
         }
@@ -40,6 +46,50 @@ impl<'a> Publisher<'a> {
             .map_err(|err| PublisherError::NatsPublish(err, self.reply_mailbox_output.to_string()))
     }
 
+    /// Relays a callback request from the function to the caller and returns its answer.
+    ///
+    /// The request is refused if the caller can't be reached or doesn't answer it, which is the
+    /// case when the caller has no callback handler and so isn't listening for requests.
+    pub async fn request_callback(&self, request: CallbackRequest) -> CallbackResponse {
+        let id = request.id;
+        let nats_msg = match serde_json::to_vec(&request) {
+            Ok(nats_msg) => nats_msg,
+            Err(err) => {
+                return CallbackResponse::failure(
+                    id,
+                    format!("failed to serialize callback request: {err}"),
+                )
+            }
+        };
+
+        let reply = match self
+            .nats
+            .request(self.reply_mailbox_callback.clone(), nats_msg.into())
+            .await
+        {
+            Ok(reply) => reply,
+            Err(err) => {
+                debug!(error = ?err, id, "caller did not answer callback request");
+                return CallbackResponse::failure(
+                    id,
+                    format!("caller did not answer callback request: {err}"),
+                );
+            }
+        };
+
+        match serde_json::from_slice::<CallbackResponse>(reply.payload()) {
+            Ok(response) if response.id == id => response,
+            Ok(response) => CallbackResponse::failure(
+                id,
+                format!("caller answered callback request {} instead", response.id),
+            ),
+            Err(err) => CallbackResponse::failure(
+                id,
+                format!("failed to deserialize callback response: {err}"),
+            ),
+        }
+    }
+
     pub async fn finalize_output(&self) -> Result<()> {
         let mut headers = si_data_nats::HeaderMap::new();
         headers.insert(FINAL_MESSAGE_HEADER_KEY, "true");
@@ -0,0 +1,353 @@
+//! Decides which function execution requests get to run on the cyclone pool next.
+//!
+//! Requests wait in one queue per [`Priority`] class and a lower class is only admitted when no
+//! request of a higher class can be. Within a class, workspaces take turns: a workspace with
+//! hundreds of queued requests only delays another workspace's request by a single execution. A
+//! workspace can also be capped at a number of executions in flight, so that it can't hold every
+//! cyclone instance at once even when nobody else is waiting yet.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use serde::{Deserialize, Serialize};
+use si_data_nats::HeaderMap;
+use telemetry::prelude::*;
+use thiserror::Error;
+use tokio::sync::oneshot;
+use veritech_core::WORKSPACE_PK_HEADER_KEY;
+
+#[remain::sorted]
+#[derive(Debug, Error)]
+pub enum SchedulerError {
+    #[error("scheduler dropped the request before admitting it")]
+    Dropped,
+}
+
+type SchedulerResult<T> = Result<T, SchedulerError>;
+
+/// The priority class of a function execution request, from most to least urgent.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Priority {
+    /// Executions a user is actively waiting on, such as attribute resolver functions.
+    Interactive,
+    /// Validation function executions.
+    Validation,
+    /// Executions which run on behalf of a user without them watching, such as actions and
+    /// resource refreshes.
+    Background,
+}
+
+impl Priority {
+    const COUNT: usize = 3;
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Limits on the function executions the [`Scheduler`] lets run at once.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct SchedulerConfig {
+    /// Most executions to run at once. Defaults to the size of the cyclone pool.
+    #[serde(default)]
+    pub concurrency_limit: Option<usize>,
+    /// Most executions to run at once for any one workspace. Defaults to no limit.
+    #[serde(default)]
+    pub workspace_concurrency_limit: Option<usize>,
+}
+
+/// Admits function execution requests in priority order, sharing each priority class fairly
+/// between workspaces.
+#[derive(Clone, Debug)]
+pub struct Scheduler {
+    state: Arc<Mutex<State>>,
+}
+
+impl Scheduler {
+    pub fn new(concurrency_limit: usize, workspace_concurrency_limit: Option<usize>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                concurrency_limit: concurrency_limit.max(1),
+                workspace_concurrency_limit: workspace_concurrency_limit.map(|limit| limit.max(1)),
+                in_flight: 0,
+                workspace_in_flight: HashMap::new(),
+                queues: Default::default(),
+            })),
+        }
+    }
+
+    /// Waits until the request is admitted, returning a permit which must be held for as long as
+    /// the execution runs.
+    ///
+    /// Requests that don't carry a workspace share their turns as though they came from a single
+    /// workspace.
+    pub async fn acquire(
+        &self,
+        priority: Priority,
+        workspace_pk: Option<String>,
+    ) -> SchedulerResult<SchedulerPermit> {
+        self.enqueue(priority, workspace_pk)
+            .await
+            .map_err(|_| SchedulerError::Dropped)
+    }
+
+    fn enqueue(
+        &self,
+        priority: Priority,
+        workspace_pk: Option<String>,
+    ) -> oneshot::Receiver<SchedulerPermit> {
+        let (tx, rx) = oneshot::channel();
+        let mut state = lock(&self.state);
+        state.queues[priority.index()].push(workspace_pk, tx);
+        state.dispatch(&self.state);
+        trace!(
+            in_flight = state.in_flight,
+            ?priority,
+            "enqueued function execution request"
+        );
+        rx
+    }
+}
+
+/// Gets the workspace a function execution request was made on behalf of, if its sender said.
+pub fn workspace_pk_from(headers: Option<&HeaderMap>) -> Option<String> {
+    headers
+        .and_then(|headers| headers.get(WORKSPACE_PK_HEADER_KEY))
+        .map(|value| value.to_string())
+}
+
+/// Allows a function execution to run until dropped, at which point the next request in line is
+/// admitted.
+#[derive(Debug)]
+pub struct SchedulerPermit {
+    state: Arc<Mutex<State>>,
+    workspace_pk: Option<String>,
+    released: bool,
+}
+
+impl Drop for SchedulerPermit {
+    fn drop(&mut self) {
+        if self.released {
+            return;
+        }
+        self.released = true;
+
+        let mut state = lock(&self.state);
+        state.release(&self.workspace_pk);
+        state.dispatch(&self.state);
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    concurrency_limit: usize,
+    workspace_concurrency_limit: Option<usize>,
+    in_flight: usize,
+    workspace_in_flight: HashMap<Option<String>, usize>,
+    queues: [FairQueue; Priority::COUNT],
+}
+
+impl State {
+    /// Admits waiting requests until the concurrency limit is reached or no waiting request can be
+    /// admitted.
+    fn dispatch(&mut self, handle: &Arc<Mutex<State>>) {
+        while self.in_flight < self.concurrency_limit {
+            let (workspace_pk, tx) = match self.next_waiting() {
+                Some(next) => next,
+                None => break,
+            };
+
+            self.in_flight += 1;
+            *self
+                .workspace_in_flight
+                .entry(workspace_pk.clone())
+                .or_default() += 1;
+            let permit = SchedulerPermit {
+                state: handle.clone(),
+                workspace_pk,
+                released: false,
+            };
+            if let Err(mut permit) = tx.send(permit) {
+                // The request stopped waiting, so the permit goes straight back. It's released
+                // here rather than in `Drop` as we're already holding the lock.
+                permit.released = true;
+                self.release(&permit.workspace_pk);
+            }
+        }
+    }
+
+    fn next_waiting(&mut self) -> Option<(Option<String>, oneshot::Sender<SchedulerPermit>)> {
+        let workspace_in_flight = &self.workspace_in_flight;
+        let workspace_concurrency_limit = self.workspace_concurrency_limit;
+        let can_admit = |workspace_pk: &Option<String>| match workspace_concurrency_limit {
+            Some(limit) => workspace_in_flight.get(workspace_pk).copied().unwrap_or(0) < limit,
+            None => true,
+        };
+
+        self.queues
+            .iter_mut()
+            .find_map(|queue| queue.pop(can_admit))
+    }
+
+    fn release(&mut self, workspace_pk: &Option<String>) {
+        self.in_flight = self.in_flight.saturating_sub(1);
+        if let Some(count) = self.workspace_in_flight.get_mut(workspace_pk) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                self.workspace_in_flight.remove(workspace_pk);
+            }
+        }
+    }
+}
+
+/// The requests waiting in one priority class, grouped by workspace.
+#[derive(Debug, Default)]
+struct FairQueue {
+    /// Workspaces with waiting requests, in the order they take turns.
+    turns: VecDeque<Option<String>>,
+    waiting: HashMap<Option<String>, VecDeque<oneshot::Sender<SchedulerPermit>>>,
+}
+
+impl FairQueue {
+    fn push(&mut self, workspace_pk: Option<String>, tx: oneshot::Sender<SchedulerPermit>) {
+        let waiting = self.waiting.entry(workspace_pk.clone()).or_default();
+        if waiting.is_empty() {
+            self.turns.push_back(workspace_pk);
+        }
+        waiting.push_back(tx);
+    }
+
+    /// Takes the next request from the first workspace in turn that `can_admit` allows, sending
+    /// that workspace to the back of the line.
+    fn pop(
+        &mut self,
+        can_admit: impl Fn(&Option<String>) -> bool,
+    ) -> Option<(Option<String>, oneshot::Sender<SchedulerPermit>)> {
+        for _ in 0..self.turns.len() {
+            let workspace_pk = self.turns.pop_front()?;
+            if !can_admit(&workspace_pk) {
+                self.turns.push_back(workspace_pk);
+                continue;
+            }
+
+            let waiting = self.waiting.get_mut(&workspace_pk)?;
+            let tx = waiting.pop_front();
+            if waiting.is_empty() {
+                self.waiting.remove(&workspace_pk);
+            } else {
+                self.turns.push_back(workspace_pk.clone());
+            }
+            if let Some(tx) = tx {
+                return Some((workspace_pk, tx));
+            }
+        }
+
+        None
+    }
+}
+
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    // The state is only ever updated whole under the lock, so it's still consistent even if a
+    // holder panicked
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace(pk: &str) -> Option<String> {
+        Some(pk.to_string())
+    }
+
+    #[test]
+    fn admits_higher_priorities_first() {
+        let scheduler = Scheduler::new(1, None);
+        let running = scheduler
+            .enqueue(Priority::Background, None)
+            .try_recv()
+            .expect("first request should be admitted");
+
+        let mut background = scheduler.enqueue(Priority::Background, None);
+        let mut validation = scheduler.enqueue(Priority::Validation, None);
+        let mut interactive = scheduler.enqueue(Priority::Interactive, None);
+
+        drop(running);
+        let running = interactive.try_recv().expect("interactive goes first");
+        assert!(validation.try_recv().is_err());
+        drop(running);
+        let running = validation.try_recv().expect("validation goes second");
+        assert!(background.try_recv().is_err());
+        drop(running);
+        background.try_recv().expect("background goes last");
+    }
+
+    #[test]
+    fn workspaces_take_turns() {
+        let scheduler = Scheduler::new(1, None);
+        let mut running = scheduler
+            .enqueue(Priority::Interactive, workspace("busy"))
+            .try_recv()
+            .expect("first request should be admitted");
+
+        let mut busy: VecDeque<_> = (0..3)
+            .map(|_| scheduler.enqueue(Priority::Interactive, workspace("busy")))
+            .collect();
+        let mut quiet = scheduler.enqueue(Priority::Interactive, workspace("quiet"));
+
+        drop(running);
+        running = busy
+            .pop_front()
+            .expect("queued")
+            .try_recv()
+            .expect("busy was first in line");
+        assert!(quiet.try_recv().is_err());
+
+        drop(running);
+        running = quiet.try_recv().expect("quiet takes the next turn");
+        for rx in busy.iter_mut() {
+            assert!(rx.try_recv().is_err());
+        }
+        drop(running);
+    }
+
+    #[test]
+    fn limits_executions_per_workspace() {
+        let scheduler = Scheduler::new(4, Some(1));
+        let running = scheduler
+            .enqueue(Priority::Interactive, workspace("a"))
+            .try_recv()
+            .expect("first request should be admitted");
+
+        let mut a = scheduler.enqueue(Priority::Interactive, workspace("a"));
+        let mut b = scheduler.enqueue(Priority::Background, workspace("b"));
+        assert!(a.try_recv().is_err());
+        let _b = b.try_recv().expect("other workspaces aren't held up");
+
+        drop(running);
+        a.try_recv()
+            .expect("admitted once its workspace has capacity");
+    }
+
+    #[test]
+    fn skips_requests_which_stopped_waiting() {
+        let scheduler = Scheduler::new(1, None);
+        let running = scheduler
+            .enqueue(Priority::Interactive, None)
+            .try_recv()
+            .expect("first request should be admitted");
+
+        drop(scheduler.enqueue(Priority::Interactive, workspace("gone")));
+        let mut waiting = scheduler.enqueue(Priority::Interactive, workspace("waiting"));
+
+        drop(running);
+        let _admitted = waiting
+            .try_recv()
+            .expect("next waiting request is admitted");
+        assert_eq!(1, lock(&scheduler.state).in_flight);
+    }
+}
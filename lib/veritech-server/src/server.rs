@@ -1,14 +1,16 @@
 use chrono::Utc;
 use deadpool_cyclone::{
     instance::cyclone::LocalUdsInstanceSpec, ActionRunRequest, ActionRunResultSuccess,
-    CycloneClient, FunctionResult, FunctionResultFailure, FunctionResultFailureError, Manager,
-    Pool, ProgressMessage, ReconciliationRequest, ReconciliationResultSuccess,
-    ResolverFunctionRequest, ResolverFunctionResultSuccess, SchemaVariantDefinitionRequest,
-    SchemaVariantDefinitionResultSuccess, ValidationRequest, ValidationResultSuccess,
+    CycloneClient, ExecutionError, ExecutionStarted, FunctionResult, FunctionResultFailure,
+    FunctionResultFailureError, Manager, Pool, ProgressMessage, ReconciliationRequest,
+    ReconciliationResultSuccess, ResolverFunctionRequest, ResolverFunctionResultSuccess,
+    SchemaVariantDefinitionRequest, SchemaVariantDefinitionResultSuccess, UnixStream,
+    ValidationRequest, ValidationResultSuccess,
 };
-use futures::{channel::oneshot, join, StreamExt};
+use futures::{channel::oneshot, future, join, StreamExt};
 use nats_subscriber::Request;
-use si_data_nats::NatsClient;
+use serde::de::DeserializeOwned;
+use si_data_nats::{NatsClient, Subscriber};
 use std::io;
 use telemetry::prelude::*;
 use thiserror::Error;
@@ -16,8 +18,13 @@ use tokio::{
     signal::unix,
     sync::{broadcast, mpsc},
 };
+use veritech_core::nats_cancel_execution_subject;
 
-use crate::{config::CycloneSpec, Config, FunctionSubscriber, Publisher, PublisherError};
+use crate::{
+    config::CycloneSpec,
+    scheduler::{workspace_pk_from, Priority, Scheduler, SchedulerError},
+    Config, FunctionSubscriber, Publisher, PublisherError,
+};
 
 #[remain::sorted]
 #[derive(Error, Debug)]
@@ -34,6 +41,8 @@ pub enum ServerError {
     CycloneSpec(#[source] Box<dyn std::error::Error + Sync + Send + 'static>),
     #[error("error connecting to nats: {0}")]
     NatsConnect(#[source] si_data_nats::NatsError),
+    #[error("error subscribing to nats: {0}")]
+    NatsSubscribe(#[source] si_data_nats::NatsError),
     #[error("no reply mailbox found")]
     NoReplyMailboxFound,
     #[error(transparent)]
@@ -43,6 +52,8 @@ pub enum ServerError {
     #[error(transparent)]
     ResolverFunction(#[from] deadpool_cyclone::ExecutionError<ResolverFunctionResultSuccess>),
     #[error(transparent)]
+    Scheduler(#[from] SchedulerError),
+    #[error(transparent)]
     SchemaVariantDefinition(
         #[from] deadpool_cyclone::ExecutionError<SchemaVariantDefinitionResultSuccess>,
     ),
@@ -62,6 +73,9 @@ pub struct Server {
     nats: NatsClient,
     subject_prefix: Option<String>,
     cyclone_pool: Pool<LocalUdsInstanceSpec>,
+    // Shares its warm pool of booted instances with the spec in `cyclone_pool`
+    cyclone_spec: LocalUdsInstanceSpec,
+    scheduler: Scheduler,
     shutdown_broadcast_tx: broadcast::Sender<()>,
     shutdown_tx: mpsc::Sender<ShutdownSource>,
     shutdown_rx: oneshot::Receiver<()>,
@@ -116,6 +130,13 @@ impl Server {
                 let cyclone_pool = Pool::builder(manager)
                     .build()
                     .map_err(|err| ServerError::CycloneSpec(Box::new(err)))?;
+                let scheduler = Scheduler::new(
+                    config
+                        .scheduler()
+                        .concurrency_limit
+                        .unwrap_or_else(|| cyclone_pool.status().max_size),
+                    config.scheduler().workspace_concurrency_limit,
+                );
 
                 let graceful_shutdown_rx =
                     prepare_graceful_shutdown(shutdown_rx, shutdown_broadcast_tx.clone())?;
@@ -124,6 +145,8 @@ impl Server {
                     nats,
                     subject_prefix: config.subject_prefix().map(|s| s.to_string()),
                     cyclone_pool,
+                    cyclone_spec: spec.clone(),
+                    scheduler,
                     shutdown_broadcast_tx,
                     shutdown_tx,
                     shutdown_rx: graceful_shutdown_rx,
@@ -151,36 +174,42 @@ impl Server {
                 self.nats.clone(),
                 self.subject_prefix.clone(),
                 self.cyclone_pool.clone(),
+                self.scheduler.clone(),
                 self.shutdown_broadcast_tx.subscribe(),
             ),
             process_validation_requests_task(
                 self.nats.clone(),
                 self.subject_prefix.clone(),
                 self.cyclone_pool.clone(),
+                self.scheduler.clone(),
                 self.shutdown_broadcast_tx.subscribe(),
             ),
             process_action_run_requests_task(
                 self.nats.clone(),
                 self.subject_prefix.clone(),
                 self.cyclone_pool.clone(),
+                self.scheduler.clone(),
                 self.shutdown_broadcast_tx.subscribe(),
             ),
             process_reconciliation_requests_task(
                 self.nats.clone(),
                 self.subject_prefix.clone(),
                 self.cyclone_pool.clone(),
+                self.scheduler.clone(),
                 self.shutdown_broadcast_tx.subscribe(),
             ),
             process_schema_variant_definition_requests_task(
                 self.nats.clone(),
                 self.subject_prefix.clone(),
                 self.cyclone_pool.clone(),
+                self.scheduler.clone(),
                 self.shutdown_broadcast_tx.subscribe(),
             ),
         );
 
         let _ = self.shutdown_rx.await;
         info!("received graceful shutdown, terminating server instance");
+        self.cyclone_spec.shutdown_warm_pool().await;
 
         Ok(())
     }
@@ -207,12 +236,14 @@ async fn process_resolver_function_requests_task(
     nats: NatsClient,
     subject_prefix: Option<String>,
     cyclone_pool: Pool<LocalUdsInstanceSpec>,
+    scheduler: Scheduler,
     shutdown_broadcast_rx: broadcast::Receiver<()>,
 ) {
     if let Err(err) = process_resolver_function_requests(
         nats,
         subject_prefix,
         cyclone_pool,
+        scheduler,
         shutdown_broadcast_rx,
     )
     .await
@@ -225,6 +256,7 @@ async fn process_resolver_function_requests(
     nats: NatsClient,
     subject_prefix: Option<String>,
     cyclone_pool: Pool<LocalUdsInstanceSpec>,
+    scheduler: Scheduler,
     mut shutdown_broadcast_rx: broadcast::Receiver<()>,
 ) -> ServerResult<()> {
     let mut requests =
@@ -245,6 +277,7 @@ async fn process_resolver_function_requests(
                         tokio::spawn(resolver_function_request_task(
                             nats.clone(),
                             cyclone_pool.clone(),
+                            scheduler.clone(),
                             request,
                         ));
                     }
@@ -274,9 +307,11 @@ async fn process_resolver_function_requests(
 async fn resolver_function_request_task(
     nats: NatsClient,
     cyclone_pool: Pool<LocalUdsInstanceSpec>,
+    scheduler: Scheduler,
     request: Request<ResolverFunctionRequest>,
 ) {
     let cyclone_request = request.payload;
+    let workspace_pk = workspace_pk_from(request.headers.as_ref());
 
     let reply_mailbox = match request.reply {
         Some(reply) => reply,
@@ -288,8 +323,15 @@ async fn resolver_function_request_task(
     let execution_id = cyclone_request.execution_id.clone();
     let publisher = Publisher::new(&nats, &reply_mailbox);
 
-    let function_result =
-        resolver_function_request(&publisher, cyclone_pool, cyclone_request).await;
+    let function_result = resolver_function_request(
+        &nats,
+        &publisher,
+        cyclone_pool,
+        scheduler,
+        workspace_pk,
+        cyclone_request,
+    )
+    .await;
 
     if let Err(err) = publisher.finalize_output().await {
         error!(error = ?err, "failed to finalize output by sending final message");
@@ -332,10 +374,18 @@ async fn resolver_function_request_task(
 }
 
 async fn resolver_function_request(
+    nats: &NatsClient,
     publisher: &Publisher<'_>,
     cyclone_pool: Pool<LocalUdsInstanceSpec>,
+    scheduler: Scheduler,
+    workspace_pk: Option<String>,
     cyclone_request: ResolverFunctionRequest,
 ) -> ServerResult<FunctionResult<ResolverFunctionResultSuccess>> {
+    let execution_id = cyclone_request.execution_id.clone();
+    let cancel_requests = subscribe_for_cancel_requests(nats, &execution_id).await?;
+    let _permit = scheduler
+        .acquire(Priority::Interactive, workspace_pk)
+        .await?;
     let mut client = cyclone_pool
         .get()
         .await
@@ -346,20 +396,7 @@ async fn resolver_function_request(
         .start()
         .await?;
 
-    while let Some(msg) = progress.next().await {
-        match msg {
-            Ok(ProgressMessage::OutputStream(output)) => {
-                publisher.publish_output(&output).await?;
-            }
-            Ok(ProgressMessage::Heartbeat) => {
-                trace!("received heartbeat message");
-            }
-            Err(err) => {
-                warn!(error = ?err, "next progress message was an error, bailing out");
-                break;
-            }
-        }
-    }
+    forward_progress(&mut progress, publisher, &execution_id, cancel_requests).await?;
 
     let function_result = progress.finish().await?;
     Ok(function_result)
@@ -369,10 +406,17 @@ async fn process_validation_requests_task(
     nats: NatsClient,
     subject_prefix: Option<String>,
     cyclone_pool: Pool<LocalUdsInstanceSpec>,
+    scheduler: Scheduler,
     shutdown_broadcast_rx: broadcast::Receiver<()>,
 ) {
-    if let Err(err) =
-        process_validation_requests(nats, subject_prefix, cyclone_pool, shutdown_broadcast_rx).await
+    if let Err(err) = process_validation_requests(
+        nats,
+        subject_prefix,
+        cyclone_pool,
+        scheduler,
+        shutdown_broadcast_rx,
+    )
+    .await
     {
         warn!(error = ?err, "processing validation requests failed");
     }
@@ -382,6 +426,7 @@ async fn process_validation_requests(
     nats: NatsClient,
     subject_prefix: Option<String>,
     cyclone_pool: Pool<LocalUdsInstanceSpec>,
+    scheduler: Scheduler,
     mut shutdown_broadcast_rx: broadcast::Receiver<()>,
 ) -> ServerResult<()> {
     let mut requests = FunctionSubscriber::validation(&nats, subject_prefix.as_deref()).await?;
@@ -401,6 +446,7 @@ async fn process_validation_requests(
                         tokio::spawn(validation_request_task(
                             nats.clone(),
                             cyclone_pool.clone(),
+                            scheduler.clone(),
                             request,
                         ));
                     }
@@ -430,9 +476,10 @@ async fn process_validation_requests(
 async fn validation_request_task(
     nats: NatsClient,
     cyclone_pool: Pool<LocalUdsInstanceSpec>,
+    scheduler: Scheduler,
     request: Request<ValidationRequest>,
 ) {
-    if let Err(err) = validation_request(nats, cyclone_pool, request).await {
+    if let Err(err) = validation_request(nats, cyclone_pool, scheduler, request).await {
         warn!(error = ?err, "validation execution failed");
     }
 }
@@ -440,6 +487,7 @@ async fn validation_request_task(
 async fn validation_request(
     nats: NatsClient,
     cyclone_pool: Pool<LocalUdsInstanceSpec>,
+    scheduler: Scheduler,
     request: Request<ValidationRequest>,
 ) -> ServerResult<()> {
     let cyclone_request = request.payload;
@@ -447,6 +495,14 @@ async fn validation_request(
     let reply_mailbox = request.reply.ok_or(ServerError::NoReplyMailboxFound)?;
 
     let publisher = Publisher::new(&nats, &reply_mailbox);
+    let execution_id = cyclone_request.execution_id.clone();
+    let cancel_requests = subscribe_for_cancel_requests(&nats, &execution_id).await?;
+    let _permit = scheduler
+        .acquire(
+            Priority::Validation,
+            workspace_pk_from(request.headers.as_ref()),
+        )
+        .await?;
     let mut client = cyclone_pool
         .get()
         .await
@@ -457,20 +513,7 @@ async fn validation_request(
         .start()
         .await?;
 
-    while let Some(msg) = progress.next().await {
-        match msg {
-            Ok(ProgressMessage::OutputStream(output)) => {
-                publisher.publish_output(&output).await?;
-            }
-            Ok(ProgressMessage::Heartbeat) => {
-                trace!("received heartbeat message");
-            }
-            Err(err) => {
-                warn!(error = ?err, "next progress message was an error, bailing out");
-                break;
-            }
-        }
-    }
+    forward_progress(&mut progress, &publisher, &execution_id, cancel_requests).await?;
     publisher.finalize_output().await?;
 
     let function_result = progress.finish().await?;
@@ -483,12 +526,14 @@ async fn process_schema_variant_definition_requests_task(
     nats: NatsClient,
     subject_prefix: Option<String>,
     cyclone_pool: Pool<LocalUdsInstanceSpec>,
+    scheduler: Scheduler,
     shutdown_broadcast_rx: broadcast::Receiver<()>,
 ) {
     if let Err(err) = process_schema_variant_definition_requests(
         nats,
         subject_prefix,
         cyclone_pool,
+        scheduler,
         shutdown_broadcast_rx,
     )
     .await
@@ -501,6 +546,7 @@ async fn process_schema_variant_definition_requests(
     nats: NatsClient,
     subject_prefix: Option<String>,
     cyclone_pool: Pool<LocalUdsInstanceSpec>,
+    scheduler: Scheduler,
     mut shutdown_broadcast_rx: broadcast::Receiver<()>,
 ) -> ServerResult<()> {
     let mut requests =
@@ -521,6 +567,7 @@ async fn process_schema_variant_definition_requests(
                         tokio::spawn(schema_variant_definition_request_task(
                             nats.clone(),
                             cyclone_pool.clone(),
+                            scheduler.clone(),
                             request,
                         ));
                     }
@@ -550,9 +597,12 @@ async fn process_schema_variant_definition_requests(
 async fn schema_variant_definition_request_task(
     nats: NatsClient,
     cyclone_pool: Pool<LocalUdsInstanceSpec>,
+    scheduler: Scheduler,
     request: Request<SchemaVariantDefinitionRequest>,
 ) {
-    if let Err(err) = schema_variant_definition_request(nats, cyclone_pool, request).await {
+    if let Err(err) =
+        schema_variant_definition_request(nats, cyclone_pool, scheduler, request).await
+    {
         warn!(error = ?err, "schema variant definition execution failed");
     }
 }
@@ -560,12 +610,21 @@ async fn schema_variant_definition_request_task(
 async fn schema_variant_definition_request(
     nats: NatsClient,
     cyclone_pool: Pool<LocalUdsInstanceSpec>,
+    scheduler: Scheduler,
     request: Request<SchemaVariantDefinitionRequest>,
 ) -> ServerResult<()> {
     let cyclone_request = request.payload;
     let reply_mailbox = request.reply.ok_or(ServerError::NoReplyMailboxFound)?;
 
     let publisher = Publisher::new(&nats, &reply_mailbox);
+    let execution_id = cyclone_request.execution_id.clone();
+    let cancel_requests = subscribe_for_cancel_requests(&nats, &execution_id).await?;
+    let _permit = scheduler
+        .acquire(
+            Priority::Interactive,
+            workspace_pk_from(request.headers.as_ref()),
+        )
+        .await?;
     let mut client = cyclone_pool
         .get()
         .await
@@ -577,20 +636,7 @@ async fn schema_variant_definition_request(
         .start()
         .await?;
 
-    while let Some(msg) = progress.next().await {
-        match msg {
-            Ok(ProgressMessage::OutputStream(output)) => {
-                publisher.publish_output(&output).await?;
-            }
-            Ok(ProgressMessage::Heartbeat) => {
-                trace!("received heartbeat message");
-            }
-            Err(err) => {
-                warn!(error = ?err, "next progress message was an error, bailing out");
-                break;
-            }
-        }
-    }
+    forward_progress(&mut progress, &publisher, &execution_id, cancel_requests).await?;
     publisher.finalize_output().await?;
 
     let function_result = progress.finish().await?;
@@ -603,10 +649,17 @@ async fn process_action_run_requests_task(
     nats: NatsClient,
     subject_prefix: Option<String>,
     cyclone_pool: Pool<LocalUdsInstanceSpec>,
+    scheduler: Scheduler,
     shutdown_broadcast_rx: broadcast::Receiver<()>,
 ) {
-    if let Err(err) =
-        process_action_run_requests(nats, subject_prefix, cyclone_pool, shutdown_broadcast_rx).await
+    if let Err(err) = process_action_run_requests(
+        nats,
+        subject_prefix,
+        cyclone_pool,
+        scheduler,
+        shutdown_broadcast_rx,
+    )
+    .await
     {
         warn!(error = ?err, "processing action run requests failed");
     }
@@ -616,6 +669,7 @@ async fn process_action_run_requests(
     nats: NatsClient,
     subject_prefix: Option<String>,
     cyclone_pool: Pool<LocalUdsInstanceSpec>,
+    scheduler: Scheduler,
     mut shutdown_broadcast_rx: broadcast::Receiver<()>,
 ) -> ServerResult<()> {
     let mut requests = FunctionSubscriber::action_run(&nats, subject_prefix.as_deref()).await?;
@@ -635,6 +689,7 @@ async fn process_action_run_requests(
                         tokio::spawn(action_run_request_task(
                             nats.clone(),
                             cyclone_pool.clone(),
+                            scheduler.clone(),
                             request,
                         ));
                     }
@@ -664,9 +719,10 @@ async fn process_action_run_requests(
 async fn action_run_request_task(
     nats: NatsClient,
     cyclone_pool: Pool<LocalUdsInstanceSpec>,
+    scheduler: Scheduler,
     request: Request<ActionRunRequest>,
 ) {
-    if let Err(err) = action_run_request(nats, cyclone_pool, request).await {
+    if let Err(err) = action_run_request(nats, cyclone_pool, scheduler, request).await {
         warn!(error = ?err, "action run execution failed");
     }
 }
@@ -674,12 +730,21 @@ async fn action_run_request_task(
 async fn action_run_request(
     nats: NatsClient,
     cyclone_pool: Pool<LocalUdsInstanceSpec>,
+    scheduler: Scheduler,
     request: Request<ActionRunRequest>,
 ) -> ServerResult<()> {
     let cyclone_request = request.payload;
     let reply_mailbox = request.reply.ok_or(ServerError::NoReplyMailboxFound)?;
 
     let publisher = Publisher::new(&nats, &reply_mailbox);
+    let execution_id = cyclone_request.execution_id.clone();
+    let cancel_requests = subscribe_for_cancel_requests(&nats, &execution_id).await?;
+    let _permit = scheduler
+        .acquire(
+            Priority::Background,
+            workspace_pk_from(request.headers.as_ref()),
+        )
+        .await?;
     let mut client = cyclone_pool
         .get()
         .await
@@ -691,20 +756,7 @@ async fn action_run_request(
         .start()
         .await?;
 
-    while let Some(msg) = progress.next().await {
-        match msg {
-            Ok(ProgressMessage::OutputStream(output)) => {
-                publisher.publish_output(&output).await?;
-            }
-            Ok(ProgressMessage::Heartbeat) => {
-                trace!("received heartbeat message");
-            }
-            Err(err) => {
-                warn!(error = ?err, "next progress message was an error, bailing out");
-                break;
-            }
-        }
-    }
+    forward_progress(&mut progress, &publisher, &execution_id, cancel_requests).await?;
     publisher.finalize_output().await?;
 
     let function_result = progress.finish().await?;
@@ -717,11 +769,17 @@ async fn process_reconciliation_requests_task(
     nats: NatsClient,
     subject_prefix: Option<String>,
     cyclone_pool: Pool<LocalUdsInstanceSpec>,
+    scheduler: Scheduler,
     shutdown_broadcast_rx: broadcast::Receiver<()>,
 ) {
-    if let Err(err) =
-        process_reconciliation_requests(nats, subject_prefix, cyclone_pool, shutdown_broadcast_rx)
-            .await
+    if let Err(err) = process_reconciliation_requests(
+        nats,
+        subject_prefix,
+        cyclone_pool,
+        scheduler,
+        shutdown_broadcast_rx,
+    )
+    .await
     {
         warn!(error = ?err, "processing reconciliation requests failed");
     }
@@ -731,6 +789,7 @@ async fn process_reconciliation_requests(
     nats: NatsClient,
     subject_prefix: Option<String>,
     cyclone_pool: Pool<LocalUdsInstanceSpec>,
+    scheduler: Scheduler,
     mut shutdown_broadcast_rx: broadcast::Receiver<()>,
 ) -> ServerResult<()> {
     let mut requests = FunctionSubscriber::reconciliation(&nats, subject_prefix.as_deref()).await?;
@@ -750,6 +809,7 @@ async fn process_reconciliation_requests(
                         tokio::spawn(reconciliation_request_task(
                             nats.clone(),
                             cyclone_pool.clone(),
+                            scheduler.clone(),
                             request,
                         ));
                     }
@@ -779,9 +839,10 @@ async fn process_reconciliation_requests(
 async fn reconciliation_request_task(
     nats: NatsClient,
     cyclone_pool: Pool<LocalUdsInstanceSpec>,
+    scheduler: Scheduler,
     request: Request<ReconciliationRequest>,
 ) {
-    if let Err(err) = reconciliation_request(nats, cyclone_pool, request).await {
+    if let Err(err) = reconciliation_request(nats, cyclone_pool, scheduler, request).await {
         warn!(error = ?err, "reconciliation execution failed");
     }
 }
@@ -789,12 +850,21 @@ async fn reconciliation_request_task(
 async fn reconciliation_request(
     nats: NatsClient,
     cyclone_pool: Pool<LocalUdsInstanceSpec>,
+    scheduler: Scheduler,
     request: Request<ReconciliationRequest>,
 ) -> ServerResult<()> {
     let cyclone_request = request.payload;
     let reply_mailbox = request.reply.ok_or(ServerError::NoReplyMailboxFound)?;
 
     let publisher = Publisher::new(&nats, &reply_mailbox);
+    let execution_id = cyclone_request.execution_id.clone();
+    let cancel_requests = subscribe_for_cancel_requests(&nats, &execution_id).await?;
+    let _permit = scheduler
+        .acquire(
+            Priority::Background,
+            workspace_pk_from(request.headers.as_ref()),
+        )
+        .await?;
     let mut client = cyclone_pool
         .get()
         .await
@@ -806,24 +876,90 @@ async fn reconciliation_request(
         .start()
         .await?;
 
-    while let Some(msg) = progress.next().await {
+    forward_progress(&mut progress, &publisher, &execution_id, cancel_requests).await?;
+    publisher.finalize_output().await?;
+
+    let function_result = progress.finish().await?;
+    publisher.publish_result(&function_result).await?;
+
+    Ok(())
+}
+
+/// Subscribes for requests to cancel the execution with the given id.
+///
+/// Subscribing before the execution has been admitted means that a request made while it's still
+/// waiting for a cyclone instance is held until it can be forwarded.
+async fn subscribe_for_cancel_requests(
+    nats: &NatsClient,
+    execution_id: &str,
+) -> ServerResult<Subscriber> {
+    let subject = nats_cancel_execution_subject(nats.metadata().subject_prefix(), execution_id);
+    trace!(
+        messaging.destination = &subject.as_str(),
+        "subscribing for cancel requests"
+    );
+    nats.subscribe(subject)
+        .await
+        .map_err(ServerError::NatsSubscribe)
+}
+
+/// Relays the progress of an execution until it is done: output is published to the caller,
+/// callback requests are answered by the caller and the first cancel request to arrive is
+/// forwarded to cyclone.
+async fn forward_progress<Success>(
+    progress: &mut ExecutionStarted<UnixStream, Success>,
+    publisher: &Publisher<'_>,
+    execution_id: &str,
+    cancel_requests: Subscriber,
+) -> ServerResult<()>
+where
+    Success: DeserializeOwned + Unpin + std::fmt::Debug,
+    ServerError: From<ExecutionError<Success>>,
+{
+    let mut cancel_requests = Some(cancel_requests);
+    loop {
+        let cancel_request = async {
+            match cancel_requests.as_mut() {
+                Some(cancel_requests) => cancel_requests.next().await,
+                None => future::pending().await,
+            }
+        };
+
+        let msg = tokio::select! {
+            msg = progress.next() => msg,
+            cancel_request = cancel_request => {
+                // Only the first cancel request needs forwarding
+                cancel_requests = None;
+                if cancel_request.is_some() {
+                    info!(execution_id, "forwarding cancel request to cyclone");
+                    if let Err(err) = progress.cancel(execution_id).await {
+                        warn!(error = ?err, "failed to forward cancel request, bailing out");
+                        break;
+                    }
+                }
+                continue;
+            }
+        };
+
         match msg {
-            Ok(ProgressMessage::OutputStream(output)) => {
+            Some(Ok(ProgressMessage::OutputStream(output))) => {
                 publisher.publish_output(&output).await?;
             }
-            Ok(ProgressMessage::Heartbeat) => {
+            Some(Ok(ProgressMessage::Heartbeat)) => {
                 trace!("received heartbeat message");
             }
-            Err(err) => {
+            Some(Ok(ProgressMessage::CallbackRequest(request))) => {
+                progress
+                    .respond(&publisher.request_callback(request).await)
+                    .await?;
+            }
+            Some(Err(err)) => {
                 warn!(error = ?err, "next progress message was an error, bailing out");
                 break;
             }
+            None => break,
         }
     }
-    publisher.finalize_output().await?;
-
-    let function_result = progress.finish().await?;
-    publisher.publish_result(&function_result).await?;
 
     Ok(())
 }